@@ -1,30 +1,118 @@
 use {
     crate::{
         commands::{
+            closest_match,
             connect::{self, Connection},
-            help, macros, version, Command,
+            help,
+            lint::{lint, LintRules},
+            macros, parse_command_line, parse_gcode_line, sd, settings, stats, status, version,
+            Command, SyntaxError, COMMAND_NAMES,
         },
-        response::Response,
+        config::{config_file, init_file, Config, ConfigError},
+        eta::{format_duration, Eta},
+        gcode::{parse_line, HostStep, MachineState},
+        history::History,
+        profiles::Profiles,
+        response::{Notice, Response, Source},
         tasks::{
-            send_gcodes, start_logging, start_print_file, start_repeat, BackgroundTask, Tasks,
+            send_gcodes, send_verbatim, set_rate, start_extrude, start_heightmap, start_logging,
+            start_print_file, start_repeat, start_sd_print, start_sd_upload, start_upload_print,
+            BackgroundTask, ExtrudeOptions, Overrides, Pauses, PrintOptions, Rate, TaskLog, Tasks,
         },
+        transport::{dryrun, virtual_printer},
+    },
+    print3rs_core::{Capability, Printer, PrinterEvent, PrinterOptions, Recorder, Replay},
+    std::{
+        path::PathBuf,
+        sync::{Arc, Mutex},
+        time::Duration,
+    },
+    tokio::{
+        io::{AsyncRead, AsyncWrite, BufReader},
+        net::TcpStream,
     },
-    print3rs_core::Printer,
-    std::sync::Arc,
-    tokio::{io::BufReader, net::TcpStream},
     tokio_serial::SerialPortBuilderExt,
 };
 
+#[cfg(feature = "duet")]
+use crate::transport::duet;
+#[cfg(feature = "moonraker")]
+use crate::transport::moonraker;
+#[cfg(feature = "mqtt")]
+use crate::transport::mqtt;
+#[cfg(feature = "octoprint")]
+use crate::transport::octoprint;
+#[cfg(feature = "prusalink")]
+use crate::transport::prusalink;
+
+/// Wait before the first attempt to reopen a lost connection, doubled after every failed attempt
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+/// Longest wait between attempts to reopen a lost connection
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+/// Most scripts that can be running inside each other, to catch a script that runs itself
+const MAX_SCRIPT_DEPTH: usize = 8;
+/// Name of the printer selected before any other is connected with `as:<name>`
+pub const DEFAULT_PRINTER: &str = "default";
+
 type CommandReceiver = tokio::sync::mpsc::Receiver<Command<String>>;
 type ResponseSender = tokio::sync::broadcast::Sender<Response>;
 type ResponseReceiver = tokio::sync::broadcast::Receiver<Response>;
 
+/// Everything kept for a printer while another one is selected with `use`
+#[derive(Debug, Default)]
+struct Device {
+    printer: Printer,
+    tasks: Tasks,
+    machine_state: Arc<Mutex<MachineState>>,
+    reconnector: Option<tokio::task::JoinHandle<()>>,
+    upload_prints: bool,
+    connected_via: Option<String>,
+    last_connection: Option<Connection<String>>,
+    gcode_order: Arc<tokio::sync::Mutex<()>>,
+    pauses: Arc<Pauses>,
+    overrides: Arc<Mutex<Overrides>>,
+    dry_run: Option<Printer>,
+}
+
 #[derive(Debug)]
 pub struct Commander {
     printer: Printer,
+    /// Name of the printer commands go to, whose state is kept in the fields here
+    printer_name: String,
+    /// Printers that aren't selected, by name
+    printers: std::collections::BTreeMap<String, Device>,
     pub tasks: Tasks,
     pub macros: macros::Macros,
+    /// Lines run from the console, kept only in memory unless replaced with a loaded `History`
+    pub history: History,
+    /// Connections saved under a name, kept only in memory unless replaced with loaded `Profiles`
+    pub profiles: Profiles,
+    /// Last connection given to `connect`, saved by `profile save` without a connection
+    last_connection: Option<Connection<String>>,
+    config: Config,
+    /// File read by `load_config`, read again by `config reload`
+    config_file: Option<PathBuf>,
     responder: ResponseSender,
+    machine_state: Arc<Mutex<MachineState>>,
+    sparklines: bool,
+    printer_options: PrinterOptions,
+    reconnect: bool,
+    reconnector: Option<tokio::task::JoinHandle<()>>,
+    /// Whether the connection prints files by uploading them, rather than streaming lines
+    upload_prints: bool,
+    /// Protocol of the last `connect`, shown by `status`
+    connected_via: Option<String>,
+    /// Held by each batch of console gcodes while it is sent, so batches go out one after another
+    gcode_order: Arc<tokio::sync::Mutex<()>>,
+    extrude_options: ExtrudeOptions,
+    pauses: Arc<Pauses>,
+    overrides: Arc<Mutex<Overrides>>,
+    /// The real printer, put aside while gcodes go to a simulated one with `dryrun on`
+    dry_run: Option<Printer>,
+    /// File the traffic of serial and tcp connections is recorded to, set with `record`
+    record_to: Option<PathBuf>,
+    /// How many `run` scripts are running inside each other
+    script_depth: usize,
 }
 #[derive(Debug, Clone)]
 pub struct ErrorKindOf(pub String);
@@ -49,9 +137,30 @@ impl Commander {
         let (responder, _) = tokio::sync::broadcast::channel(32);
         Self {
             printer: Default::default(),
+            printer_name: DEFAULT_PRINTER.to_string(),
+            printers: Default::default(),
             responder,
             tasks: Default::default(),
             macros: Default::default(),
+            history: Default::default(),
+            machine_state: Default::default(),
+            sparklines: false,
+            printer_options: Default::default(),
+            reconnect: false,
+            reconnector: None,
+            connected_via: None,
+            profiles: Default::default(),
+            last_connection: None,
+            config: Default::default(),
+            config_file: None,
+            gcode_order: Default::default(),
+            extrude_options: Default::default(),
+            pauses: Default::default(),
+            overrides: Default::default(),
+            upload_prints: false,
+            dry_run: None,
+            record_to: None,
+            script_depth: 0,
         }
     }
 
@@ -59,9 +168,246 @@ impl Commander {
         &self.printer
     }
 
+    /// Name of the printer commands go to, chosen with `use`
+    pub fn printer_name(&self) -> &str {
+        &self.printer_name
+    }
+
+    /// Names of every printer, sorted
+    pub fn printer_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.printers.keys().map(String::as_str).collect();
+        names.push(&self.printer_name);
+        names.sort();
+        names
+    }
+
+    /// Exchange the selected printer's state with `device`
+    fn swap_device(&mut self, device: &mut Device) {
+        use std::mem::swap;
+        swap(&mut self.printer, &mut device.printer);
+        swap(&mut self.tasks, &mut device.tasks);
+        swap(&mut self.machine_state, &mut device.machine_state);
+        swap(&mut self.reconnector, &mut device.reconnector);
+        swap(&mut self.upload_prints, &mut device.upload_prints);
+        swap(&mut self.connected_via, &mut device.connected_via);
+        swap(&mut self.last_connection, &mut device.last_connection);
+        swap(&mut self.gcode_order, &mut device.gcode_order);
+        swap(&mut self.pauses, &mut device.pauses);
+        swap(&mut self.overrides, &mut device.overrides);
+        swap(&mut self.dry_run, &mut device.dry_run);
+    }
+
+    /// Send commands to the printer called `name` from now on, adding it disconnected if `create`.
+    /// Returns the name of the printer selected before.
+    fn select_printer(&mut self, name: &str, create: bool) -> Result<String, ErrorKindOf> {
+        if name == self.printer_name {
+            return Ok(name.to_owned());
+        }
+        let mut device = match self.printers.remove(name) {
+            Some(device) => device,
+            None if create => Device::default(),
+            None => {
+                return Err(format!(
+                    "No printer named {name}, add one with `connect <proto> <args> as:{name}`"
+                )
+                .into())
+            }
+        };
+        self.swap_device(&mut device);
+        let previous = std::mem::replace(&mut self.printer_name, name.to_owned());
+        self.printers.insert(previous.clone(), device);
+        Ok(previous)
+    }
+
+    /// Send commands to the printer called `name` from now on, adding it disconnected if there isn't one,
+    /// like `use` for a printer that doesn't have to be connected yet
+    pub fn use_printer(&mut self, name: &str) {
+        let _ = self.select_printer(name, true);
+    }
+
+    /// Hand a printer found by autoconnecting or reconnecting to the printer called `name`,
+    /// whether it is selected or not
+    pub fn set_printer_of(&mut self, name: &str, printer: Printer) {
+        let Ok(previous) = self.select_printer(name, true) else {
+            return;
+        };
+        self.set_printer(printer);
+        let _ = self.select_printer(&previous, true);
+    }
+
     pub fn set_printer(&mut self, printer: Printer) {
         self.tasks.clear();
+        self.reset_machine_state();
+        *self.overrides.lock().unwrap() = Overrides::default();
+        // a new connection ends any dry run, rather than being put aside by `dryrun off`
+        self.dry_run = None;
         self.printer = printer;
+        if let Err(e) = self.send_startup_gcode() {
+            let _ = self.responder.send(Response::Error(e));
+        }
+    }
+
+    /// Settings read by `load_config`
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Read defaults from a TOML config file and apply them, a missing file is the same as an empty one.
+    ///
+    /// Macros in the file replace any of the same name, and the file is remembered for `config reload`.
+    pub fn load_config(&mut self, file: impl Into<PathBuf>) -> Result<(), ConfigError> {
+        let file = file.into();
+        self.config_file = Some(file.clone());
+        let config = Config::load(&file)?;
+        for (name, steps) in config.macros.iter() {
+            let steps = parse_gcode_line(steps)
+                .map_err(|e| ConfigError::Macro(name.clone(), e.to_string()))?;
+            self.macros
+                .add(name, steps)
+                .map_err(|e| ConfigError::Macro(name.clone(), e.to_string()))?;
+        }
+        if let Some(min_temp) = config.limits.min_extrude_temp {
+            self.extrude_options.min_temp = Some(min_temp);
+        }
+        if let Some(sparklines) = config.ui.sparklines {
+            self.sparklines = sparklines;
+        }
+        self.config = config;
+        Ok(())
+    }
+
+    /// Load the config from the platform's config directory, if there is one
+    pub fn load_default_config(&mut self) -> Result<(), ConfigError> {
+        match config_file() {
+            Some(file) => self.load_config(file),
+            None => Ok(()),
+        }
+    }
+
+    /// Run the console commands in the init file of the platform's config directory, if there is one.
+    ///
+    /// Lines that fail are reported and skipped, so one mistake doesn't stop the rest of the setup.
+    pub fn run_init_script(&mut self) -> Result<(), ErrorKindOf> {
+        match init_file().filter(|file| file.exists()) {
+            Some(file) => self.run_script(&file.display().to_string(), true),
+            None => Ok(()),
+        }
+    }
+
+    /// Limits checked by `lint` and used by `heightmap`, from the config where it sets them
+    fn lint_rules(&self) -> LintRules {
+        let defaults = LintRules::default();
+        LintRules {
+            build_volume: self
+                .config
+                .limits
+                .build_volume
+                .unwrap_or(defaults.build_volume),
+            min_extrude_temp: self
+                .config
+                .limits
+                .min_extrude_temp
+                .unwrap_or(defaults.min_extrude_temp),
+        }
+    }
+
+    /// Send the config's startup gcode to a newly connected printer
+    fn send_startup_gcode(&mut self) -> Result<(), ErrorKindOf> {
+        if self.config.startup_gcode.is_empty() || !self.printer.is_connected() {
+            return Ok(());
+        }
+        let lines = self.config.startup_gcode.clone();
+        let mut codes = vec![];
+        for line in lines.iter() {
+            codes.extend(parse_gcode_line(line)?);
+        }
+        let codes = self.expand_steps(codes)?;
+        self.queue_gcodes(codes)
+    }
+
+    /// State of the machine (position, tools, temperatures...) as tracked from the gcodes sent to it
+    pub fn machine_state(&self) -> MachineState {
+        self.machine_state.lock().unwrap().clone()
+    }
+
+    /// Whether a print is parked, waiting for `resume`
+    pub fn is_paused(&self) -> bool {
+        self.pauses.is_paused()
+    }
+
+    /// Whether recent log task values should be shown as sparklines, set with the `sparklines` command
+    pub fn show_sparklines(&self) -> bool {
+        self.sparklines
+    }
+
+    /// Options used for every printer connected from now on
+    pub fn printer_options(&self) -> &PrinterOptions {
+        &self.printer_options
+    }
+
+    /// Change the options used for printers connected from now on, the current printer is unaffected
+    pub fn set_printer_options(&mut self, options: PrinterOptions) {
+        self.printer_options = options;
+    }
+
+    /// How `extrude` and `retract` move filament
+    pub fn extrude_options(&self) -> ExtrudeOptions {
+        self.extrude_options
+    }
+
+    /// Change the default feedrate and cold extrusion check of `extrude` and `retract`
+    pub fn set_extrude_options(&mut self, options: ExtrudeOptions) {
+        self.extrude_options = options;
+    }
+
+    /// Connection given to the selected printer's last `connect`
+    pub fn last_connection(&self) -> Option<&Connection<String>> {
+        self.last_connection.as_ref()
+    }
+
+    /// Whether lost serial and TCP connections are reopened, set with the `reconnect` command
+    pub fn reconnects(&self) -> bool {
+        self.reconnect
+    }
+
+    /// Reopen serial and TCP connections made from now on when they are lost
+    pub fn set_reconnect(&mut self, reconnect: bool) {
+        self.reconnect = reconnect;
+    }
+
+    fn reset_machine_state(&self) {
+        *self.machine_state.lock().unwrap() = MachineState::default();
+    }
+
+    /// Suggest a command or macro name for input that failed to parse because of an unknown word
+    pub fn suggest<'a>(&'a self, input: &str, error: &SyntaxError) -> Option<&'a str> {
+        if error.label.is_some() {
+            return None;
+        }
+        let word = input.get(error.span.clone())?;
+        if word.is_empty() {
+            return None;
+        }
+        let macro_names = self.macros.iter().map(|(name, _)| name.as_str());
+        closest_match(word, COMMAND_NAMES.iter().copied().chain(macro_names))
+    }
+
+    /// Log for a new background task with the given name
+    fn task_log(&self, name: &str) -> TaskLog {
+        TaskLog::new(name, self.responder.clone())
+    }
+
+    /// Let a printer which is no longer used finish communicating in the background,
+    /// reporting the error it ended with, if any
+    fn shut_down(&self, mut printer: Printer) {
+        let responder = self.responder.clone();
+        tokio::spawn(async move {
+            if let Err(e) = printer.shutdown().await {
+                let _ = responder.send(Response::Error(
+                    format!("Disconnected with error: {e}\n").into(),
+                ));
+            }
+        });
     }
 
     pub fn subscribe_responses(&self) -> ResponseReceiver {
@@ -70,20 +416,293 @@ impl Commander {
 
     fn forward_broadcast(
         mut in_channel: tokio::sync::broadcast::Receiver<Arc<str>>,
+        source: Source,
         out_channel: tokio::sync::broadcast::Sender<Response>,
     ) {
         tokio::spawn(async move {
             while let Ok(in_message) = in_channel.recv().await {
-                out_channel.send(Response::Output(in_message)).unwrap();
+                let _ = out_channel.send(Response::Output(source.clone(), in_message));
+            }
+        });
+    }
+
+    /// Report changes in the printer's connection as responses
+    fn forward_events(
+        mut events: tokio::sync::broadcast::Receiver<PrinterEvent>,
+        name: Arc<str>,
+        out_channel: tokio::sync::broadcast::Sender<Response>,
+    ) {
+        tokio::spawn(async move {
+            while let Ok(event) = events.recv().await {
+                let response = match event {
+                    PrinterEvent::Unresponsive => {
+                        Response::Error("Printer is not responding!\n".into())
+                    }
+                    PrinterEvent::Responsive => "Printer is responding again\n".into(),
+                    PrinterEvent::Disconnected => {
+                        let _ = out_channel.send(Response::Notify(
+                            Source::Printer(name.clone()),
+                            Notice::Disconnected,
+                            format!("Printer {name} disconnected").into(),
+                        ));
+                        Response::Error("Printer disconnected!\n".into())
+                    }
+                    _ => continue,
+                };
+                let _ = out_channel.send(response);
             }
         });
     }
 
+    /// Forward the lines and connection changes of the printer with the given name as responses
+    fn forward_printer(printer: &Printer, name: &Arc<str>, responder: &ResponseSender) {
+        if let Ok(print_messages) = printer.subscribe_lines() {
+            let source = Source::Printer(name.clone());
+            Self::forward_broadcast(print_messages, source, responder.clone());
+        }
+        if let Ok(events) = printer.subscribe_events() {
+            Self::forward_events(events, name.clone(), responder.clone());
+        }
+    }
+
     fn add_printer_output_to_responses(&self) {
-        if let Ok(print_messages) = self.printer.subscribe_lines() {
-            let responder = self.responder.clone();
-            Self::forward_broadcast(print_messages, responder);
+        let name = Arc::from(self.printer_name.as_str());
+        Self::forward_printer(&self.printer, &name, &self.responder);
+    }
+
+    /// Open a serial or TCP connection again after it was lost
+    async fn reopen(
+        connection: &Connection<String>,
+        options: PrinterOptions,
+    ) -> Result<Printer, ErrorKindOf> {
+        match connection {
+            Connection::Serial {
+                port,
+                baud,
+                dialect,
+            } => {
+                let port = tokio_serial::new(port, baud.unwrap_or(115200)).open_native_async()?;
+                Ok(Printer::with_options(
+                    BufReader::new(port),
+                    options.dialect(*dialect),
+                ))
+            }
+            Connection::Tcp { hostname, port } => {
+                let addr = match port {
+                    Some(port) => format!("{hostname}:{port}"),
+                    None => hostname.clone(),
+                };
+                let stream = TcpStream::connect(addr).await?;
+                Ok(Printer::with_options(BufReader::new(stream), options))
+            }
+            _ => Err("only serial and tcp connections can be reopened".into()),
+        }
+    }
+
+    /// Reopen `connection` every time the printer on it disconnects, retrying with backoff.
+    ///
+    /// Reopened printers are handed over the same way as autoconnected ones.
+    async fn reconnect_loop(
+        mut events: tokio::sync::broadcast::Receiver<PrinterEvent>,
+        connection: Connection<String>,
+        name: Arc<str>,
+        options: PrinterOptions,
+        responder: ResponseSender,
+    ) {
+        use tokio::sync::broadcast::error::RecvError;
+        loop {
+            match events.recv().await {
+                Ok(PrinterEvent::Disconnected) => {}
+                Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return,
+            }
+            let mut delay = RECONNECT_DELAY;
+            let printer = loop {
+                let _ = responder.send(format!("Reconnecting in {}s...\n", delay.as_secs()).into());
+                tokio::time::sleep(delay).await;
+                match Self::reopen(&connection, options.clone()).await {
+                    Ok(printer) => break printer,
+                    Err(e) => {
+                        let _ = responder.send(Response::Error(
+                            format!("Reconnect failed: {}\n", e.0).into(),
+                        ));
+                        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+                    }
+                }
+            };
+            let Ok(printer_events) = printer.subscribe_events() else {
+                return;
+            };
+            events = printer_events;
+            Self::forward_printer(&printer, &name, &responder);
+            let _ = responder.send(Response::AutoConnect(
+                name.clone(),
+                Arc::new(Mutex::new(printer)),
+            ));
+            let _ = responder.send(format!("Reconnected {name}\n").into());
+        }
+    }
+
+    /// Keep reopening `connection` when it is lost, if reconnecting is on
+    fn watch_connection(&mut self, connection: Connection<String>) {
+        if !self.reconnect {
+            return;
+        }
+        let Ok(events) = self.printer.subscribe_events() else {
+            return;
+        };
+        self.reconnector = Some(tokio::spawn(Self::reconnect_loop(
+            events,
+            connection,
+            self.printer_name.as_str().into(),
+            self.printer_options.clone(),
+            self.responder.clone(),
+        )));
+    }
+
+    fn stop_reconnecting(&mut self) {
+        if let Some(reconnector) = self.reconnector.take() {
+            reconnector.abort();
+        }
+    }
+
+    /// Connect the selected printer to a serial port or socket, recording its traffic if `record` was used
+    fn connect_transport<T>(
+        &mut self,
+        transport: T,
+        options: PrinterOptions,
+    ) -> Result<(), ErrorKindOf>
+    where
+        T: AsyncRead + AsyncWrite + Unpin + Send + std::fmt::Debug + 'static,
+    {
+        match &self.record_to {
+            Some(file) => {
+                let recorder = Recorder::create(transport, file)
+                    .map_err(|e| format!("Could not record to {}: {e}", file.display()))?;
+                self.printer.connect_with(BufReader::new(recorder), options);
+            }
+            None => self
+                .printer
+                .connect_with(BufReader::new(transport), options),
+        }
+        Ok(())
+    }
+
+    /// Report the error that ended a network transport's bridge task, if it failed
+    fn report_bridge_error<E: std::fmt::Display + Send + 'static>(
+        &self,
+        bridge: tokio::task::JoinHandle<Result<(), E>>,
+    ) {
+        let responder = self.responder.clone();
+        tokio::spawn(async move {
+            if let Ok(Err(e)) = bridge.await {
+                let _ = responder.send(Response::Error(format!("Disconnected: {e}\n").into()));
+            }
+        });
+    }
+
+    /// Expand macros in a sequence, rejecting it if a host step like `@delay` can't be run
+    fn expand_steps(&self, codes: Vec<&str>) -> Result<Vec<String>, ErrorKindOf> {
+        let steps = self.macros.expand(codes)?;
+        for step in steps.iter() {
+            if let Some(Err(e)) = HostStep::parse(step) {
+                return Err(e.into());
+            }
+        }
+        Ok(steps)
+    }
+
+    /// Send gcodes to the printer in order from a background task, tracking what they do to the machine
+    fn queue_gcodes(&mut self, codes: Vec<String>) -> Result<(), ErrorKindOf> {
+        let socket = self.printer().socket()?.clone();
+        {
+            let mut state = self.machine_state.lock().unwrap();
+            for code in codes.iter().filter(|code| HostStep::parse(code).is_none()) {
+                state.apply(&parse_line(code));
+            }
+        }
+        static COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let name = format!(
+            "gcodes_{}",
+            COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+        );
+        let task = send_gcodes(
+            socket,
+            codes,
+            self.gcode_order.clone(),
+            self.task_log(&name),
+        );
+        self.tasks.insert(name, task);
+        Ok(())
+    }
+
+    /// Change the speed or flow of prints from a background task, reporting how it was done
+    fn set_rate(&mut self, rate: Rate, percent: f32) -> Result<(), ErrorKindOf> {
+        let socket = self.printer.socket()?.clone();
+        let overrides = self.overrides.clone();
+        let rate_responder = self.responder.clone();
+        tokio::spawn(async move {
+            let name = match rate {
+                Rate::Speed => "speed",
+                Rate::Flow => "flow",
+            };
+            let response = match set_rate(&socket, &overrides, rate, percent).await {
+                Ok(true) => Response::Output(Source::Commander, format!("Set {name} to {percent}%\n").into()),
+                Ok(false) => Response::Output(
+                    Source::Commander,
+                    format!(
+                        "Set {name} to {percent}% by changing the lines printed, the firmware can't do it itself\n"
+                    )
+                    .into(),
+                ),
+                Err(e) => Response::Error(format!("Could not set {name}: {e}\n").into()),
+            };
+            let _ = rate_responder.send(response);
+        });
+        Ok(())
+    }
+
+    /// Dispatch every command in a script file in order, stopping at the first failure unless `keep_going`
+    fn run_script(&mut self, filename: &str, keep_going: bool) -> Result<(), ErrorKindOf> {
+        if self.script_depth >= MAX_SCRIPT_DEPTH {
+            return Err(format!(
+                "Scripts are nested more than {MAX_SCRIPT_DEPTH} deep, does {filename} run itself?"
+            )
+            .into());
+        }
+        let script = std::fs::read_to_string(filename)
+            .map_err(|e| format!("Could not read {filename}: {e}"))?;
+        self.script_depth += 1;
+        let result = self.run_script_lines(filename, &script, keep_going);
+        self.script_depth -= 1;
+        result
+    }
+
+    fn run_script_lines(
+        &mut self,
+        filename: &str,
+        script: &str,
+        keep_going: bool,
+    ) -> Result<(), ErrorKindOf> {
+        for (number, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let result = match parse_command_line(line) {
+                Ok(command) => self.dispatch(command),
+                Err(error) => Err(error.into()),
+            };
+            if let Err(e) = result {
+                let e = format!("{filename} line {}: {}", number + 1, e.0);
+                if !keep_going {
+                    return Err(e.into());
+                }
+                self.responder
+                    .send(Response::Error(format!("{e}\n").into()))?;
+            }
         }
+        Ok(())
     }
 
     pub fn background(mut self, mut commands: CommandReceiver) -> tokio::task::JoinHandle<()> {
@@ -112,54 +731,486 @@ impl Commander {
                 self.responder.send(Response::Quit)?;
             }
             Gcodes(codes) => {
+                let codes = self.expand_steps(codes)?;
+                self.queue_gcodes(codes)?;
+            }
+            Raw(text) => {
                 let socket = self.printer().socket()?.clone();
-                let codes = self.macros.expand(codes);
-                let task = send_gcodes(socket, codes);
+                self.machine_state.lock().unwrap().apply(&parse_line(text));
                 static COUNTER: std::sync::atomic::AtomicUsize =
                     std::sync::atomic::AtomicUsize::new(0);
-                self.tasks.insert(
-                    format!(
-                        "gcodes_{}",
-                        COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
-                    ),
-                    task,
+                let name = format!(
+                    "raw_{}",
+                    COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
                 );
+                let task = send_verbatim(
+                    socket,
+                    text.to_owned(),
+                    self.gcode_order.clone(),
+                    self.task_log(&name),
+                );
+                self.tasks.insert(name, task);
+            }
+            Home(axes) => {
+                let mut home = "G28".to_string();
+                for (homed, letter) in [(axes.x, 'X'), (axes.y, 'Y'), (axes.z, 'Z')] {
+                    if homed {
+                        home.push(' ');
+                        home.push(letter);
+                    }
+                }
+                self.queue_gcodes(vec![home])?;
             }
-            Print(filename) => {
+            Extrude(length, feedrate) => {
                 let socket = self.printer.socket()?.clone();
-                let print = start_print_file(filename, socket);
+                let feedrate = feedrate.unwrap_or(self.extrude_options.feedrate);
+                let codes = self
+                    .machine_state
+                    .lock()
+                    .unwrap()
+                    .relative_extrude(length, feedrate);
+                let name = if length < 0.0 { "retract" } else { "extrude" };
+                let task = start_extrude(
+                    socket,
+                    codes,
+                    self.extrude_options.min_temp,
+                    self.machine_state.clone(),
+                    self.gcode_order.clone(),
+                    self.responder.clone(),
+                    self.task_log(name),
+                );
+                self.tasks.insert(name.to_string(), task);
+            }
+            Speed(percent) => self.set_rate(Rate::Speed, percent)?,
+            Flow(percent) => self.set_rate(Rate::Flow, percent)?,
+            Babystep(axis, distance) => {
+                let native = self
+                    .printer
+                    .info()
+                    .is_ok_and(|info| info.has_capability(Capability::Babystepping));
+                let code = self
+                    .machine_state
+                    .lock()
+                    .unwrap()
+                    .babystep(axis, distance, native);
+                self.queue_gcodes(vec![code])?;
+            }
+            Move(words) => {
+                let codes = {
+                    let state = self.machine_state.lock().unwrap();
+                    state.relative_move(&words)
+                };
+                self.queue_gcodes(codes)?;
+            }
+            Print(filename, options) => {
+                let socket = self.printer.socket()?.clone();
+                let print = if self.upload_prints {
+                    if options != PrintOptions::default() {
+                        return Err(
+                            "This connection prints by uploading files, so can't start partway or move the print"
+                                .into(),
+                        );
+                    }
+                    start_upload_print(filename, socket, self.task_log(filename))
+                } else {
+                    let arcs = self
+                        .printer
+                        .info()
+                        .is_ok_and(|info| info.has_capability(Capability::Arcs));
+                    start_print_file(
+                        filename,
+                        options,
+                        !arcs,
+                        socket,
+                        self.machine_state.clone(),
+                        self.pauses.clone(),
+                        self.overrides.clone(),
+                        self.responder.clone(),
+                        self.task_log(filename),
+                    )
+                };
                 self.tasks.insert(filename.to_string(), print);
             }
-            Log(name, pattern) => {
-                let log = start_logging(name, pattern, &self.printer)?;
+            Lint(filename) => {
+                let filename = filename.to_owned();
+                let lint_responder = self.responder.clone();
+                // without a connected printer only the file itself is checked
+                let capabilities = self.printer.info().unwrap_or_default();
+                let rules = self.lint_rules();
+                tokio::spawn(async move {
+                    let response = match tokio::fs::read_to_string(&filename).await {
+                        Ok(source) => {
+                            let findings = lint(&source, &capabilities, &rules);
+                            let mut report =
+                                format!("{filename}: {} problems found\n", findings.len());
+                            for finding in findings {
+                                report.push_str(&format!("{finding}\n"));
+                            }
+                            Response::Output(Source::Commander, report.into())
+                        }
+                        Err(e) => {
+                            Response::Error(format!("Could not read {filename}: {e}\n").into())
+                        }
+                    };
+                    let _ = lint_responder.send(response);
+                });
+            }
+            Run(filename, keep_going) => self.run_script(filename, keep_going)?,
+            Heightmap(grid) => {
+                let socket = self.printer.socket()?.clone();
+                let [width, depth, _] = self.lint_rules().build_volume;
+                let heightmap = start_heightmap(
+                    grid,
+                    [width, depth],
+                    socket,
+                    self.responder.clone(),
+                    self.task_log("heightmap"),
+                );
+                self.tasks.insert("heightmap".to_string(), heightmap);
+            }
+            SaveSettings(filename) => {
+                let socket = self.printer.socket()?.clone();
+                let filename = filename.to_owned();
+                let settings_responder = self.responder.clone();
+                tokio::spawn(async move {
+                    let response = match settings::save(&socket, &filename).await {
+                        Ok(count) => Response::Output(
+                            Source::Commander,
+                            format!("Saved {count} settings to {filename}\n").into(),
+                        ),
+                        Err(e) => Response::Error(format!("{e}\n").into()),
+                    };
+                    let _ = settings_responder.send(response);
+                });
+            }
+            DiffSettings(filename) => {
+                let socket = self.printer.socket()?.clone();
+                let filename = filename.to_owned();
+                let settings_responder = self.responder.clone();
+                tokio::spawn(async move {
+                    let response = match settings::diff_saved(&socket, &filename).await {
+                        Ok(changes) if changes.is_empty() => Response::Output(
+                            Source::Commander,
+                            format!("No settings changed since {filename}\n").into(),
+                        ),
+                        Ok(changes) => {
+                            let mut report =
+                                format!("{} settings changed since {filename}\n", changes.len());
+                            for change in changes {
+                                report.push_str(&format!("{change}\n"));
+                            }
+                            Response::Output(Source::Commander, report.into())
+                        }
+                        Err(e) => Response::Error(format!("{e}\n").into()),
+                    };
+                    let _ = settings_responder.send(response);
+                });
+            }
+            SdList => {
+                let socket = self.printer.socket()?.clone();
+                let sd_responder = self.responder.clone();
+                tokio::spawn(async move {
+                    let response = match sd::list(&socket).await {
+                        Ok(files) => {
+                            let mut report = format!("{} files on SD card\n", files.len());
+                            for file in files {
+                                report.push_str(&format!("{file}\n"));
+                            }
+                            Response::Output(Source::Commander, report.into())
+                        }
+                        Err(e) => Response::Error(format!("{e}\n").into()),
+                    };
+                    let _ = sd_responder.send(response);
+                });
+            }
+            SdPrint(filename) => {
+                let socket = self.printer.socket()?.clone();
+                let print = start_sd_print(
+                    filename,
+                    socket,
+                    self.responder.clone(),
+                    self.task_log(filename),
+                );
+                self.tasks.insert(filename.to_string(), print);
+            }
+            SdUpload(local, remote) => {
+                let socket = self.printer.socket()?.clone();
+                let upload = start_sd_upload(
+                    local,
+                    remote,
+                    socket,
+                    self.responder.clone(),
+                    self.task_log(remote),
+                );
+                self.tasks.insert(remote.to_string(), upload);
+            }
+            SdDelete(filename) => {
+                let socket = self.printer.socket()?.clone();
+                let filename = filename.to_owned();
+                let sd_responder = self.responder.clone();
+                tokio::spawn(async move {
+                    let response = match sd::delete(&socket, &filename).await {
+                        Ok(()) => Response::Output(
+                            Source::Commander,
+                            format!("Deleted {filename}\n").into(),
+                        ),
+                        Err(e) => Response::Error(format!("{e}\n").into()),
+                    };
+                    let _ = sd_responder.send(response);
+                });
+            }
+            SdStatus => {
+                let socket = self.printer.socket()?.clone();
+                let sd_responder = self.responder.clone();
+                tokio::spawn(async move {
+                    let response = match sd::status(&socket).await {
+                        Ok(status) => {
+                            Response::Output(Source::Commander, format!("{status}\n").into())
+                        }
+                        Err(e) => Response::Error(format!("{e}\n").into()),
+                    };
+                    let _ = sd_responder.send(response);
+                });
+            }
+            Log(name, sink, pattern) => {
+                let log = start_logging(
+                    pattern,
+                    sink,
+                    self.config.log_dir.as_deref(),
+                    &self.printer,
+                    self.task_log(name),
+                )?;
                 self.tasks.insert(name.to_string(), log);
             }
             Repeat(name, gcodes) => {
                 let socket = self.printer.socket()?.clone();
-                let gcodes = self.macros.expand(gcodes);
-                let repeat = start_repeat(gcodes, socket);
+                let gcodes = self.expand_steps(gcodes)?;
+                let repeat = start_repeat(gcodes, socket, self.task_log(name));
                 self.tasks.insert(name.to_string(), repeat);
             }
             Tasks => {
                 for (
                     name,
                     BackgroundTask {
-                        description,
-                        abort_handle: _,
+                        description, log, ..
                     },
                 ) in self.tasks.iter()
                 {
                     self.responder
-                        .send(format!("{name}\t{description}\n").into())?;
+                        .send(format!("{name}\t{description}\t{}\n", log.verbosity()).into())?;
+                }
+            }
+            Progress(name) => {
+                let mut tasks: Vec<_> = self
+                    .tasks
+                    .iter()
+                    .filter(|(task, _)| name.map_or(true, |name| name == task.as_str()))
+                    .collect();
+                tasks.sort_by_key(|(task, _)| task.as_str());
+                if let Some(name) = name {
+                    match tasks.first() {
+                        None => return Err(format!("No task named {name}").into()),
+                        Some((_, task)) if task.progress.is_none() => {
+                            return Err(format!("{name} does not report progress").into())
+                        }
+                        _ => {}
+                    }
+                }
+                let mut report = String::new();
+                for (task, BackgroundTask { progress, .. }) in tasks {
+                    if let Some(progress) = progress {
+                        report.push_str(&format!("{task}\t{}\n", progress.lock().unwrap()));
+                    }
+                }
+                if report.is_empty() {
+                    report.push_str("No prints or uploads running\n");
+                }
+                self.responder.send(report.into())?;
+            }
+            Eta(Some(name)) if !self.tasks.contains_key(name) => {
+                let filename = name.to_owned();
+                let eta_responder = self.responder.clone();
+                tokio::spawn(async move {
+                    let response = match tokio::fs::read_to_string(&filename).await {
+                        Ok(file) => {
+                            let eta = Eta::for_file(&file);
+                            match eta.total() {
+                                Some(total) => Response::Output(
+                                    Source::Commander,
+                                    format!(
+                                        "{filename}: about {} for {} lines\n",
+                                        format_duration(total),
+                                        eta.total_lines()
+                                    )
+                                    .into(),
+                                ),
+                                None => Response::Output(
+                                    Source::Commander,
+                                    format!("{filename}: nothing to estimate\n").into(),
+                                ),
+                            }
+                        }
+                        Err(e) => Response::Error(
+                            format!(
+                                "No task named {filename}, and could not read it as a file: {e}\n"
+                            )
+                            .into(),
+                        ),
+                    };
+                    let _ = eta_responder.send(response);
+                });
+            }
+            Eta(name) => {
+                let mut tasks: Vec<_> = self
+                    .tasks
+                    .iter()
+                    .filter(|(task, _)| name.map_or(true, |name| name == task.as_str()))
+                    .filter_map(|(task, BackgroundTask { progress, .. })| {
+                        progress.as_ref().map(|progress| (task, progress))
+                    })
+                    .collect();
+                tasks.sort_by_key(|(task, _)| task.as_str());
+                if let (Some(name), true) = (name, tasks.is_empty()) {
+                    return Err(format!("{name} does not report progress").into());
+                }
+                let mut report = String::new();
+                for (task, progress) in tasks {
+                    let remaining = match progress.lock().unwrap().remaining {
+                        Some(remaining) => format!("{} left", format_duration(remaining)),
+                        None => "no estimate yet".to_string(),
+                    };
+                    report.push_str(&format!("{task}\t{remaining}\n"));
                 }
+                if report.is_empty() {
+                    report.push_str("No prints or uploads running\n");
+                }
+                self.responder.send(report.into())?;
+            }
+            Status => {
+                let mut tasks: Vec<String> = self.tasks.keys().cloned().collect();
+                tasks.sort();
+                let status = status::Status::new(&self.printer, self.connected_via.clone(), tasks);
+                match self.printer.socket() {
+                    Ok(socket) => {
+                        let socket = socket.clone();
+                        let status_responder = self.responder.clone();
+                        tokio::spawn(async move {
+                            let status = status.query(&socket).await;
+                            let _ = status_responder.send(status.to_string().into());
+                        });
+                    }
+                    Err(_) => {
+                        self.responder.send(status.to_string().into())?;
+                    }
+                }
+            }
+            Stats => {
+                let counts = self.printer.stats()?;
+                self.responder
+                    .send(stats::Stats(counts).to_string().into())?;
+            }
+            Debug(name, verbosity) => {
+                let Some(task) = self.tasks.get(name) else {
+                    return Err(format!("No task named {name}").into());
+                };
+                task.log.set_verbosity(verbosity);
+                self.responder
+                    .send(format!("{name} verbosity set to {verbosity}\n").into())?;
+            }
+            Sparklines(show) => {
+                self.sparklines = show;
+            }
+            DryRun(true) if self.dry_run.is_some() => {
+                self.responder.send("Already in a dry run\n".into())?;
+            }
+            DryRun(true) => {
+                let name = Arc::from(self.printer_name.as_str());
+                let (transport, _) = dryrun::connect(name, self.responder.clone());
+                let simulated = Printer::with_options(transport, self.printer_options.clone());
+                self.dry_run = Some(std::mem::replace(&mut self.printer, simulated));
+                self.responder.send(
+                    "Dry run on, nothing is sent to the printer until `dryrun off`\n".into(),
+                )?;
+            }
+            DryRun(false) => match self.dry_run.take() {
+                Some(printer) => {
+                    self.printer = printer;
+                    self.responder
+                        .send("Dry run off, sending to the printer again\n".into())?;
+                }
+                None => {
+                    self.responder.send("Not in a dry run\n".into())?;
+                }
+            },
+            HalfDuplex(half_duplex) => {
+                self.printer_options.half_duplex = half_duplex;
+                let mode = if half_duplex { "on" } else { "off" };
+                self.responder.send(
+                    format!("Half-duplex mode {mode}, applies from the next connection\n").into(),
+                )?;
+            }
+            Record(Some(file)) => {
+                self.record_to = Some(file.into());
+                self.responder.send(
+                    format!("Recording serial and tcp connections to {file}, applies from the next connection\n")
+                        .into(),
+                )?;
+            }
+            Record(None) => {
+                self.record_to = None;
+                self.responder
+                    .send("Recording off, applies from the next connection\n".into())?;
+            }
+            Reconnect(reconnect) => {
+                self.reconnect = reconnect;
+                let mode = if reconnect { "on" } else { "off" };
+                self.responder.send(
+                    format!("Reconnecting {mode}, applies from the next connection\n").into(),
+                )?;
+            }
+            Keepalive(seconds) => {
+                self.printer_options.keepalive = seconds.map(|s| Duration::from_secs(s.into()));
+                let message = match seconds {
+                    Some(seconds) => format!(
+                        "Keepalive probe after {seconds}s without traffic, applies from the next connection\n"
+                    ),
+                    None => "Keepalive off, applies from the next connection\n".to_string(),
+                };
+                self.responder.send(message.into())?;
             }
             Stop(name) => {
                 self.tasks.remove(name);
             }
+            PauseAt(layer) => {
+                let message = match layer {
+                    Some(layer) => {
+                        self.pauses.pause_at(layer);
+                        let layers: Vec<_> =
+                            self.pauses.layers().iter().map(usize::to_string).collect();
+                        format!("Prints will pause at layer {}\n", layers.join(", "))
+                    }
+                    None => {
+                        self.pauses.clear();
+                        "Prints will only pause where the file says to\n".to_string()
+                    }
+                };
+                self.responder.send(message.into())?;
+            }
+            PauseNow => {
+                if !self.tasks.values().any(|task| task.description == "print") {
+                    return Err("No print is running".into());
+                }
+                self.pauses.pause_now();
+            }
+            Resume => {
+                if !self.pauses.resume() {
+                    return Err("Nothing is paused".into());
+                }
+            }
             Macro(name, commands) => {
-                if self.macros.add(name, commands).is_err() {
+                if let Err(e) = self.macros.add(name, commands) {
                     self.responder
-                        .send("Infinite macro detected! Macro not added.\n".into())?;
+                        .send(format!("{e}! Macro not added.\n").into())?;
                 }
             }
             Macros => {
@@ -172,35 +1223,87 @@ impl Commander {
             DeleteMacro(name) => {
                 self.macros.remove(name);
             }
+            Command::History(search) => {
+                let mut found = 0;
+                for (number, entry) in self.history.search(search.unwrap_or_default()) {
+                    self.responder
+                        .send(format!("{number:>4}  {entry}\n").into())?;
+                    found += 1;
+                }
+                if found == 0 {
+                    let message = match search {
+                        Some(text) => format!("Nothing in the history contains `{text}`\n"),
+                        None => "The history is empty\n".to_string(),
+                    };
+                    self.responder.send(message.into())?;
+                }
+            }
             Connect(connection) => {
+                // a new connection ends any dry run, putting the real printer back
+                // so it is still there if the new connection can't be made
+                if let Some(real) = self.dry_run.take() {
+                    let simulated = std::mem::replace(&mut self.printer, real);
+                    self.shut_down(simulated);
+                }
                 self.tasks.clear();
+                self.reset_machine_state();
+                self.stop_reconnecting();
+                self.upload_prints = matches!(connection, Connection::PrusaLink { .. });
+                self.connected_via = Some(connection.protocol().to_string());
+                self.last_connection = Some(connection.clone().into_owned());
+                // an autoconnected printer gets its startup gcode once it is found, in `set_printer`
+                let auto = matches!(connection, Connection::Auto(_));
                 match connection {
-                    Connection::Auto => {
+                    Connection::Auto(options) => {
                         self.tasks.clear();
                         self.responder.send("Connecting...\n".into())?;
                         let autoconnect_responder = self.responder.clone();
+                        let printer_options = self.printer_options.clone();
+                        let name: Arc<str> = self.printer_name.as_str().into();
                         tokio::spawn(async move {
-                            let printer = connect::auto_connect().await;
-                            let response = if printer.is_connected() {
-                                Response::Output("Found Printer!\n".into())
-                            } else {
-                                Response::Error("No printer found.\n".into())
-                            };
-                            if let Ok(printer_responses) = printer.subscribe_lines() {
-                                let forward_responder = autoconnect_responder.clone();
-                                Self::forward_broadcast(printer_responses, forward_responder);
-                            }
-                            let _ = autoconnect_responder.send(printer.into());
+                            let (printer, response) =
+                                match connect::auto_detect(&options, printer_options).await {
+                                    Some(found) => (
+                                        found.printer,
+                                        Response::Output(
+                                            Source::Commander,
+                                            format!(
+                                                "Found Printer on {} at {} baud!\n",
+                                                found.port, found.baud
+                                            )
+                                            .into(),
+                                        ),
+                                    ),
+                                    None => (
+                                        Printer::Disconnected,
+                                        Response::Error("No printer found.\n".into()),
+                                    ),
+                                };
+                            Self::forward_printer(&printer, &name, &autoconnect_responder);
+                            let _ = autoconnect_responder
+                                .send(Response::AutoConnect(name, Arc::new(Mutex::new(printer))));
                             let _ = autoconnect_responder.send(response);
                         });
                     }
-                    Connection::Serial { port, baud } => {
+                    Connection::Serial {
+                        port,
+                        baud,
+                        dialect,
+                    } => {
+                        let baud = baud.or(self.config.baud);
                         let connection =
                             tokio_serial::new(port, baud.unwrap_or(115200)).open_native_async()?;
-                        let connection = BufReader::new(connection);
                         self.tasks.clear();
-                        self.printer.connect(connection);
+                        self.connect_transport(
+                            connection,
+                            self.printer_options.clone().dialect(dialect),
+                        )?;
                         self.add_printer_output_to_responses();
+                        self.watch_connection(Connection::Serial {
+                            port: port.to_owned(),
+                            baud,
+                            dialect,
+                        });
                     }
                     Connection::Tcp { hostname, port } => {
                         let addr = if let Some(port) = port {
@@ -209,22 +1312,200 @@ impl Commander {
                             hostname.to_owned()
                         };
                         let connection = std::net::TcpStream::connect(addr)?;
-                        let connection = BufReader::new(TcpStream::from_std(connection)?);
+                        let connection = TcpStream::from_std(connection)?;
                         self.tasks.clear();
-                        self.printer.connect(connection);
+                        self.connect_transport(connection, self.printer_options.clone())?;
                         self.add_printer_output_to_responses();
+                        self.watch_connection(Connection::Tcp {
+                            hostname: hostname.to_owned(),
+                            port,
+                        });
                     }
+                    #[cfg(feature = "mqtt")]
                     Connection::Mqtt {
-                        hostname: _,
-                        port: _,
-                        in_topic: _,
-                        out_topic: _,
-                    } => todo!(),
+                        hostname,
+                        port,
+                        in_topic,
+                        out_topic,
+                    } => {
+                        let (connection, bridge) =
+                            mqtt::connect(hostname, port, in_topic, out_topic);
+                        self.tasks.clear();
+                        self.printer
+                            .connect_with(connection, self.printer_options.clone());
+                        self.add_printer_output_to_responses();
+                        self.report_bridge_error(bridge);
+                    }
+                    #[cfg(feature = "moonraker")]
+                    Connection::Moonraker { hostname, port } => {
+                        let (connection, bridge) = moonraker::connect(hostname, port);
+                        self.tasks.clear();
+                        self.printer
+                            .connect_with(connection, self.printer_options.clone());
+                        self.add_printer_output_to_responses();
+                        self.report_bridge_error(bridge);
+                    }
+                    #[cfg(feature = "octoprint")]
+                    Connection::OctoPrint {
+                        hostname,
+                        port,
+                        api_key,
+                    } => {
+                        let (connection, bridge) = octoprint::connect(hostname, port, api_key);
+                        self.tasks.clear();
+                        self.printer
+                            .connect_with(connection, self.printer_options.clone());
+                        self.add_printer_output_to_responses();
+                        self.report_bridge_error(bridge);
+                    }
+                    #[cfg(feature = "prusalink")]
+                    Connection::PrusaLink {
+                        hostname,
+                        port,
+                        api_key,
+                    } => {
+                        let (connection, bridge) = prusalink::connect(hostname, port, api_key);
+                        self.tasks.clear();
+                        self.printer
+                            .connect_with(connection, self.printer_options.clone());
+                        self.add_printer_output_to_responses();
+                        self.report_bridge_error(bridge);
+                    }
+                    #[cfg(feature = "duet")]
+                    Connection::Duet {
+                        hostname,
+                        port,
+                        password,
+                    } => {
+                        let (connection, bridge) = duet::connect(hostname, port, password);
+                        self.tasks.clear();
+                        self.printer
+                            .connect_with(connection, self.printer_options.clone());
+                        self.add_printer_output_to_responses();
+                        self.report_bridge_error(bridge);
+                    }
+                    Connection::Replay { file } => {
+                        let replay = Replay::open(file)
+                            .map_err(|e| format!("Could not open recording {file}: {e}"))?;
+                        self.tasks.clear();
+                        self.printer
+                            .connect_with(BufReader::new(replay), self.printer_options.clone());
+                        self.add_printer_output_to_responses();
+                    }
+                    Connection::Virtual => {
+                        let (connection, firmware) = virtual_printer::connect();
+                        self.tasks.clear();
+                        self.printer
+                            .connect_with(connection, self.printer_options.clone());
+                        self.add_printer_output_to_responses();
+                        self.report_bridge_error(firmware);
+                    }
+                    #[cfg(not(all(
+                        feature = "mqtt",
+                        feature = "moonraker",
+                        feature = "octoprint",
+                        feature = "prusalink",
+                        feature = "duet"
+                    )))]
+                    unsupported => {
+                        // profiles can still name a backend left out of this build
+                        return Err(format!(
+                            "{} connections aren't supported by this build",
+                            unsupported.protocol()
+                        )
+                        .into());
+                    }
                 };
+                if !auto {
+                    self.send_startup_gcode()?;
+                }
+            }
+            ConnectProfile(name) => {
+                let connection = self.profiles.get(name).cloned().ok_or_else(|| {
+                    format!("No connection profile named {name}, see `profile list`")
+                })?;
+                self.dispatch(Connect(connection.to_borrowed()))?;
+            }
+            SaveProfile(name, connection) => {
+                let connection = match connection {
+                    Some(connection) => connection.into_owned(),
+                    None => self.last_connection.clone().ok_or(
+                        "Nothing has been connected to yet, give the connection to save like `profile save ender serial /dev/ttyUSB0`",
+                    )?,
+                };
+                let saved =
+                    format!("Saved `{connection}` as {name}, connect with `connect @{name}`\n");
+                self.profiles
+                    .insert(name, connection)
+                    .map_err(|e| format!("Could not save profile {name}: {e}"))?;
+                self.responder.send(saved.into())?;
+            }
+            DeleteProfile(name) => {
+                let removed = self
+                    .profiles
+                    .remove(name)
+                    .map_err(|e| format!("Could not save profiles: {e}"))?;
+                if removed.is_none() {
+                    return Err(format!("No connection profile named {name}").into());
+                }
+            }
+            Use(None) => {
+                let mut list = String::new();
+                for name in self.printer_names() {
+                    let (selected, connected_via) = if name == self.printer_name {
+                        ('*', &self.connected_via)
+                    } else {
+                        (' ', &self.printers[name].connected_via)
+                    };
+                    let connected_via = connected_via.as_deref().unwrap_or("not connected");
+                    list.push_str(&format!("{selected} {name:<16} {connected_via}\n"));
+                }
+                self.responder.send(list.into())?;
+            }
+            Use(Some(name)) => {
+                self.select_printer(name, false)?;
+                self.responder
+                    .send(format!("Commands now go to {name}\n").into())?;
+            }
+            On(name, command) => {
+                // only connecting can add a printer, anything else needs one already there
+                let create = matches!(*command, Connect(_) | ConnectProfile(_));
+                let previous = self.select_printer(name, create)?;
+                let result = self.dispatch(*command);
+                self.select_printer(&previous, true)?;
+                result?;
+            }
+            ReloadConfig => {
+                let file = self
+                    .config_file
+                    .clone()
+                    .or_else(config_file)
+                    .ok_or("There is no config directory on this system")?;
+                self.load_config(&file)?;
+                self.responder
+                    .send(format!("Reloaded {}\n", file.display()).into())?;
+            }
+            Command::Profiles => {
+                let mut list = String::new();
+                for (name, connection) in self.profiles.iter() {
+                    list.push_str(&format!("{name:<16} {connection}\n"));
+                }
+                if list.is_empty() {
+                    list.push_str("No connection profiles saved, see `help profile`\n");
+                }
+                self.responder.send(list.into())?;
             }
             Disconnect => {
+                self.connected_via = None;
                 self.tasks.clear();
-                self.printer.disconnect()
+                self.reset_machine_state();
+                self.stop_reconnecting();
+                let mut printer = core::mem::take(&mut self.printer);
+                // disconnect the real printer rather than the simulated one
+                if let Some(real) = self.dry_run.take() {
+                    printer = real;
+                }
+                self.shut_down(printer);
             }
             Help(subcommand) => {
                 self.responder.send(help::help(subcommand).into())?;
@@ -1,30 +1,42 @@
 use {
     self::{
         connect::Connection,
-        log::{parse_logger, Segment},
+        log::{parse_logger, LogSink, Segment},
+    },
+    crate::{
+        commands::connect::{parse_connection, parse_profile_name, parse_protocol},
+        gcode::{Word, X, Y, Z},
+        tasks::{PrintOptions, Verbosity},
     },
-    crate::commands::connect::parse_connection,
     core::borrow::Borrow,
-    std::fmt::Debug,
+    print3rs_core::gcode::Home,
+    std::{fmt::Debug, ops::Range},
     winnow::{
-        ascii::digit1,
-        combinator::terminated,
+        ascii::{dec_uint, digit1, float},
+        combinator::{cut_err, terminated},
+        error::{ContextError, StrContext, StrContextValue},
         stream::{AsChar, Stream},
-        token::take_while,
+        token::{one_of, take_until, take_while},
     },
 };
 
 use winnow::{
     ascii::{alpha1, space0, space1},
-    combinator::{alt, dispatch, empty, fail, opt, preceded, rest, separated},
+    combinator::{alt, dispatch, empty, eof, fail, opt, peek, preceded, repeat, rest, separated},
     prelude::*,
     token::take_till,
 };
 
 pub mod connect;
+pub mod heightmap;
 pub mod help;
+pub mod lint;
 pub mod log;
 pub mod macros;
+pub mod sd;
+pub mod settings;
+pub mod stats;
+pub mod status;
 pub mod version;
 
 pub fn identifier<'a>(input: &mut &'a str) -> PResult<&'a str> {
@@ -43,16 +55,68 @@ pub fn identifier<'a>(input: &mut &'a str) -> PResult<&'a str> {
 #[derive(Debug, Clone, PartialEq)]
 pub enum Command<S> {
     Gcodes(Vec<S>),
-    Print(S),
-    Log(S, Vec<Segment<S>>),
+    /// Text sent to the printer exactly as typed, without uppercasing or expanding macros
+    Raw(S),
+    Print(S, PrintOptions),
+    Lint(S),
+    /// File of console commands to run one after another, and whether to carry on past lines that fail
+    Run(S, bool),
+    Heightmap(Option<u32>),
+    Home(Home),
+    Move(Vec<Word>),
+    /// Length of filament to push through the hotend, negative to retract, and an optional feedrate
+    Extrude(f32, Option<f32>),
+    /// Axis index and distance to nudge it by while moving
+    Babystep(usize, f32),
+    Speed(f32),
+    Flow(f32),
+    SaveSettings(S),
+    DiffSettings(S),
+    SdList,
+    SdPrint(S),
+    SdDelete(S),
+    /// Local file to copy to the SD card, and the name to give it there
+    SdUpload(S, S),
+    SdStatus,
+    Log(S, LogSink<S>, Vec<Segment<S>>),
     Repeat(S, Vec<S>),
     Tasks,
+    Progress(Option<S>),
+    Eta(Option<S>),
+    Status,
+    /// Traffic with the printer since it was connected
+    Stats,
+    Debug(S, Verbosity),
+    Sparklines(bool),
+    /// Whether gcodes go to a simulated printer which shows what would be sent, rather than the real one
+    DryRun(bool),
+    HalfDuplex(bool),
+    Keepalive(Option<u32>),
+    /// File to record the traffic of the next serial or tcp connection to, or `None` to stop recording
+    Record(Option<S>),
+    Reconnect(bool),
     Stop(S),
+    PauseAt(Option<usize>),
+    /// Pause the running print after the line being sent
+    PauseNow,
+    Resume,
     Connect(Connection<S>),
+    /// Connect with the connection saved under a profile name
+    ConnectProfile(S),
+    /// Save the given connection, or the last one used, under a profile name
+    SaveProfile(S, Option<Connection<S>>),
+    DeleteProfile(S),
+    Profiles,
+    /// Select the named printer for the commands after it, or list printers if no name is given
+    Use(Option<S>),
+    /// Run a command on the named printer, without selecting it
+    On(S, Box<Command<S>>),
     Disconnect,
     Macro(S, Vec<S>),
     Macros,
     DeleteMacro(S),
+    History(Option<S>),
+    ReloadConfig,
     Help(S),
     Version,
     Clear,
@@ -65,9 +129,27 @@ impl<'a> Command<&'a str> {
         use Command::*;
         match self {
             Gcodes(codes) => Gcodes(codes.into_iter().map(str::to_owned).collect()),
-            Print(filename) => Print(filename.to_owned()),
-            Log(name, pattern) => Log(
+            Raw(text) => Raw(text.to_owned()),
+            Print(filename, options) => Print(filename.to_owned(), options),
+            Lint(filename) => Lint(filename.to_owned()),
+            Run(filename, keep_going) => Run(filename.to_owned(), keep_going),
+            Heightmap(grid) => Heightmap(grid),
+            Home(axes) => Home(axes),
+            Move(words) => Move(words),
+            Extrude(length, feedrate) => Extrude(length, feedrate),
+            Babystep(axis, distance) => Babystep(axis, distance),
+            Speed(percent) => Speed(percent),
+            Flow(percent) => Flow(percent),
+            SaveSettings(filename) => SaveSettings(filename.to_owned()),
+            DiffSettings(filename) => DiffSettings(filename.to_owned()),
+            SdList => SdList,
+            SdPrint(filename) => SdPrint(filename.to_owned()),
+            SdDelete(filename) => SdDelete(filename.to_owned()),
+            SdUpload(local, remote) => SdUpload(local.to_owned(), remote.to_owned()),
+            SdStatus => SdStatus,
+            Log(name, sink, pattern) => Log(
                 name.to_owned(),
+                sink.into_owned(),
                 pattern.into_iter().map(Segment::into_owned).collect(),
             ),
             Repeat(name, codes) => Repeat(
@@ -75,14 +157,38 @@ impl<'a> Command<&'a str> {
                 codes.into_iter().map(str::to_owned).collect(),
             ),
             Tasks => Tasks,
+            Progress(name) => Progress(name.map(str::to_owned)),
+            Eta(name) => Eta(name.map(str::to_owned)),
+            Status => Status,
+            Stats => Stats,
+            Debug(name, verbosity) => Debug(name.to_owned(), verbosity),
+            Sparklines(show) => Sparklines(show),
+            DryRun(dry_run) => DryRun(dry_run),
+            HalfDuplex(half_duplex) => HalfDuplex(half_duplex),
+            Keepalive(seconds) => Keepalive(seconds),
+            Record(file) => Record(file.map(str::to_owned)),
+            Reconnect(reconnect) => Reconnect(reconnect),
             Stop(s) => Stop(s.to_owned()),
+            PauseAt(layer) => PauseAt(layer),
+            PauseNow => PauseNow,
+            Resume => Resume,
             Connect(connection) => Connect(connection.into_owned()),
+            ConnectProfile(name) => ConnectProfile(name.to_owned()),
+            SaveProfile(name, connection) => {
+                SaveProfile(name.to_owned(), connection.map(Connection::into_owned))
+            }
+            DeleteProfile(name) => DeleteProfile(name.to_owned()),
+            Profiles => Profiles,
+            Use(printer) => Use(printer.map(str::to_owned)),
+            On(printer, command) => On(printer.to_owned(), Box::new(command.into_owned())),
             Disconnect => Disconnect,
             Macro(name, codes) => Macro(
                 name.to_owned(),
                 codes.into_iter().map(str::to_owned).collect(),
             ),
             Macros => Macros,
+            History(search) => History(search.map(str::to_owned)),
+            ReloadConfig => ReloadConfig,
             DeleteMacro(s) => DeleteMacro(s.to_owned()),
             Help(s) => Help(s.to_owned()),
             Version => Version,
@@ -101,20 +207,65 @@ impl Command<String> {
         use Command::*;
         match self {
             Gcodes(codes) => Gcodes(codes.iter().map(|s| s.borrow()).collect()),
-            Print(filename) => Print(filename.borrow()),
-            Log(name, pattern) => Log(
+            Raw(text) => Raw(text.borrow()),
+            Print(filename, options) => Print(filename.borrow(), *options),
+            Lint(filename) => Lint(filename.borrow()),
+            Run(filename, keep_going) => Run(filename.borrow(), *keep_going),
+            Heightmap(grid) => Heightmap(*grid),
+            Home(axes) => Home(*axes),
+            Move(words) => Move(words.clone()),
+            Extrude(length, feedrate) => Extrude(*length, *feedrate),
+            Babystep(axis, distance) => Babystep(*axis, *distance),
+            Speed(percent) => Speed(*percent),
+            Flow(percent) => Flow(*percent),
+            SaveSettings(filename) => SaveSettings(filename.borrow()),
+            DiffSettings(filename) => DiffSettings(filename.borrow()),
+            SdList => SdList,
+            SdPrint(filename) => SdPrint(filename.borrow()),
+            SdDelete(filename) => SdDelete(filename.borrow()),
+            SdUpload(local, remote) => SdUpload(local.borrow(), remote.borrow()),
+            SdStatus => SdStatus,
+            Log(name, sink, pattern) => Log(
                 name.borrow(),
+                sink.to_borrowed(),
                 pattern.iter().map(Segment::to_borrowed).collect(),
             ),
             Repeat(name, codes) => {
                 Repeat(name.borrow(), codes.iter().map(|s| s.borrow()).collect())
             }
             Tasks => Tasks,
+            Progress(name) => Progress(name.as_ref().map(|s| s.borrow())),
+            Eta(name) => Eta(name.as_ref().map(|s| s.borrow())),
+            Status => Status,
+            Stats => Stats,
+            Debug(name, verbosity) => Debug(name.borrow(), *verbosity),
+            Sparklines(show) => Sparklines(*show),
+            DryRun(dry_run) => DryRun(*dry_run),
+            HalfDuplex(half_duplex) => HalfDuplex(*half_duplex),
+            Keepalive(seconds) => Keepalive(*seconds),
+            Record(file) => Record(file.as_ref().map(|s| s.borrow())),
+            Reconnect(reconnect) => Reconnect(*reconnect),
             Stop(s) => Stop(s.borrow()),
+            PauseAt(layer) => PauseAt(*layer),
+            PauseNow => PauseNow,
+            Resume => Resume,
             Connect(connection) => Connect(connection.to_borrowed()),
+            ConnectProfile(name) => ConnectProfile(name.borrow()),
+            SaveProfile(name, connection) => SaveProfile(
+                name.borrow(),
+                connection
+                    .as_ref()
+                    .map(|connection| connection.to_borrowed()),
+            ),
+            DeleteProfile(name) => DeleteProfile(name.borrow()),
+            Profiles => Profiles,
+            Use(printer) => Use(printer.as_ref().map(|s| s.borrow())),
+            On(printer, command) => On(printer.borrow(), Box::new(command.to_borrowed())),
             Disconnect => Disconnect,
             Macro(name, codes) => Macro(name.borrow(), codes.iter().map(|s| s.borrow()).collect()),
             Macros => Macros,
+            History(search) => History(search.as_ref().map(|s| s.borrow())),
+            ReloadConfig => ReloadConfig,
             DeleteMacro(s) => DeleteMacro(s.borrow()),
             Help(s) => Help(s.borrow()),
             Version => Version,
@@ -144,36 +295,538 @@ fn plausible_code<'a>(input: &mut &'a str) -> PResult<&'a str> {
     take_till(2.., ';').parse_next(input)
 }
 
+/// A macro given values for its placeholders, like `heat temp=210 bed=60`
+fn macro_call<'a>(input: &mut &'a str) -> PResult<&'a str> {
+    let value = (identifier, '=', take_till(1.., [' ', '\t', ';']));
+    terminated(
+        preceded(
+            space0,
+            (
+                identifier,
+                repeat::<_, _, (), _, _>(1.., preceded(space1, value)),
+            )
+                .recognize(),
+        ),
+        (space0, peek(alt((";", eof)))),
+    )
+    .parse_next(input)
+}
+
+/// A step run by the host rather than sent, like `@delay 5s`, checked when it is used
+fn host_step<'a>(input: &mut &'a str) -> PResult<&'a str> {
+    preceded(space0, ('@', identifier, take_till(0.., ';')).recognize()).parse_next(input)
+}
+
 fn parse_gcodes<'a>(input: &mut &'a str) -> PResult<Vec<&'a str>> {
-    terminated(separated(0.., plausible_code, ';'), opt(";")).parse_next(input)
+    terminated(
+        separated(0.., alt((plausible_code, macro_call, host_step)), ';'),
+        opt(";"),
+    )
+    .parse_next(input)
+}
+
+/// Gcodes, macro calls and host steps separated by ';', like the steps given to `macro` or `repeat`
+pub fn parse_gcode_line(input: &str) -> Result<Vec<&str>, SyntaxError> {
+    terminated(parse_gcodes, space0)
+        .parse(input)
+        .map_err(|e| SyntaxError::new(input, e))
+}
+
+/// Name of a task or macro, labelled for error reporting
+pub(crate) fn name<'a>(input: &mut &'a str) -> PResult<&'a str> {
+    preceded(space0, identifier)
+        .context(StrContext::Label("name"))
+        .context(StrContext::Expected(StrContextValue::Description(
+            "a name made of letters, numbers, '-', '_' or '.'",
+        )))
+        .parse_next(input)
+}
+
+/// One or more gcodes, labelled for error reporting
+fn required_gcodes<'a>(input: &mut &'a str) -> PResult<Vec<&'a str>> {
+    preceded(space1, parse_gcodes)
+        .verify(|gcodes: &Vec<&str>| !gcodes.is_empty())
+        .context(StrContext::Label("gcodes"))
+        .context(StrContext::Expected(StrContextValue::Description(
+            "gcodes separated by ';'",
+        )))
+        .parse_next(input)
 }
 
 fn parse_repeater<'a>(input: &mut &'a str) -> PResult<Command<&'a str>> {
-    (preceded(space0, identifier), preceded(space1, parse_gcodes))
+    (name, required_gcodes)
         .map(|(name, gcodes)| Command::Repeat(name, gcodes))
         .parse_next(input)
 }
 
 fn parse_macro<'a>(input: &mut &'a str) -> PResult<Command<&'a str>> {
-    let (name, steps) =
-        (preceded(space0, identifier), preceded(space1, parse_gcodes)).parse_next(input)?;
+    let (name, steps) = (name, required_gcodes).parse_next(input)?;
     Ok(Command::Macro(name, steps))
 }
 
+/// Everything left on the line, which must not be empty
+fn required_rest<'a>(label: &'static str) -> impl Parser<&'a str, &'a str, ContextError> {
+    preceded(space0, rest)
+        .verify(|s: &str| !s.trim().is_empty())
+        .context(StrContext::Label(label))
+        .context(StrContext::Expected(StrContextValue::Description(label)))
+}
+
+/// An option after the file name in `print`
+enum PrintOption {
+    From(usize),
+    Offset(Vec<(usize, f32)>),
+    Scale(f32),
+    Mirror(Vec<usize>),
+}
+
+fn parse_print_option(input: &mut &str) -> PResult<PrintOption> {
+    let axis = || {
+        alt((
+            one_of(['x', 'X']).value(X),
+            one_of(['y', 'Y']).value(Y),
+            one_of(['z', 'Z']).value(Z),
+        ))
+    };
+    dispatch! {alpha1;
+        "from" => preceded(space0, dec_uint.verify(|line: &usize| *line > 0))
+            .map(PrintOption::From)
+            .context(StrContext::Label("line number"))
+            .context(StrContext::Expected(StrContextValue::Description(
+                "a line number in the file, counting from 1",
+            ))),
+        "offset" => repeat(1.., preceded(space0, (axis(), preceded(space0, float))))
+            .map(PrintOption::Offset)
+            .context(StrContext::Label("offset"))
+            .context(StrContext::Expected(StrContextValue::Description(
+                "axes and distances in mm, like x10 y5",
+            ))),
+        "scale" => preceded(space0, float.verify(|scale: &f32| *scale > 0.0))
+            .map(PrintOption::Scale)
+            .context(StrContext::Label("scale"))
+            .context(StrContext::Expected(StrContextValue::Description(
+                "a size to scale by, like 1.05",
+            ))),
+        "mirror" => repeat(1.., preceded(space0, axis().verify(|axis: &usize| *axis != Z)))
+            .map(PrintOption::Mirror)
+            .context(StrContext::Label("mirror"))
+            .context(StrContext::Expected(StrContextValue::Description(
+                "x, y or both",
+            ))),
+        _ => fail
+            .context(StrContext::Label("print option"))
+            .context(StrContext::Expected(StrContextValue::Description(
+                "--from, --offset, --scale or --mirror",
+            ))),
+    }
+    .parse_next(input)
+}
+
+/// A file name, optionally followed by options like `--from <line>` or `--offset x10 y5`
+fn parse_print<'a>(input: &mut &'a str) -> PResult<(&'a str, PrintOptions)> {
+    let filename = preceded(space0, alt((take_until(1.., " --"), rest)))
+        .map(str::trim_end)
+        .verify(|filename: &str| !filename.is_empty())
+        .context(StrContext::Label("file name"))
+        .context(StrContext::Expected(StrContextValue::Description(
+            "file name",
+        )))
+        .parse_next(input)?;
+    let options: Vec<PrintOption> = terminated(
+        repeat(0.., preceded((space0, "--"), cut_err(parse_print_option))),
+        space0,
+    )
+    .parse_next(input)?;
+    let mut print = PrintOptions::default();
+    for option in options {
+        match option {
+            PrintOption::From(line) => print.from = Some(line),
+            PrintOption::Offset(offsets) => {
+                for (axis, distance) in offsets {
+                    print.transform.offset[axis] = distance;
+                }
+            }
+            PrintOption::Scale(scale) => print.transform.scale = scale,
+            PrintOption::Mirror(axes) => {
+                for axis in axes {
+                    print.transform.mirror[axis] = true;
+                }
+            }
+        }
+    }
+    Ok((filename, print))
+}
+
+/// A script file name, optionally followed by `--continue` to carry on past lines that fail,
+/// or `--stop` to stop at the first one as is done by default
+fn parse_run<'a>(input: &mut &'a str) -> PResult<(&'a str, bool)> {
+    let filename = preceded(space0, alt((take_until(1.., " --"), rest)))
+        .map(str::trim_end)
+        .verify(|filename: &str| !filename.is_empty())
+        .context(StrContext::Label("file name"))
+        .context(StrContext::Expected(StrContextValue::Description(
+            "file name",
+        )))
+        .parse_next(input)?;
+    let keep_going = terminated(
+        opt(preceded(
+            (space0, "--"),
+            cut_err(alt(("continue".value(true), "stop".value(false))))
+                .context(StrContext::Label("option"))
+                .context(StrContext::Expected(StrContextValue::Description(
+                    "--continue or --stop",
+                ))),
+        )),
+        space0,
+    )
+    .parse_next(input)?;
+    Ok((filename, keep_going.unwrap_or(false)))
+}
+
+/// Verbosity for a task, debug if not given
+fn parse_verbosity(input: &mut &str) -> PResult<Verbosity> {
+    terminated(preceded(space0, opt(alpha1)), space0)
+        .verify_map(|name: Option<&str>| match name {
+            Some(name) => Verbosity::from_name(name),
+            None => Some(Verbosity::Debug),
+        })
+        .context(StrContext::Label("verbosity"))
+        .context(StrContext::Expected(StrContextValue::Description(
+            "off, info, debug or trace",
+        )))
+        .parse_next(input)
+}
+
+/// Optional size of a probing grid, at least 2 points per side
+fn parse_grid(input: &mut &str) -> PResult<Option<u32>> {
+    terminated(preceded(space0, opt(dec_uint)), space0)
+        .verify(|grid: &Option<u32>| grid.map_or(true, |grid| grid >= 2))
+        .context(StrContext::Label("grid size"))
+        .context(StrContext::Expected(StrContextValue::Description(
+            "a number of points per side, 2 or more",
+        )))
+        .parse_next(input)
+}
+
+/// Axes to home, like `xy`, all of them if none are given
+fn parse_home(input: &mut &str) -> PResult<Home> {
+    terminated(
+        repeat(
+            0..,
+            preceded(space0, one_of(['x', 'y', 'z', 'X', 'Y', 'Z'])),
+        ),
+        space0,
+    )
+    .map(|axes: Vec<char>| {
+        let homes = |axis: char| axes.iter().any(|c| c.eq_ignore_ascii_case(&axis));
+        Home {
+            x: homes('x'),
+            y: homes('y'),
+            z: homes('z'),
+        }
+    })
+    .context(StrContext::Label("axes"))
+    .context(StrContext::Expected(StrContextValue::Description(
+        "any of x, y and z, like xy",
+    )))
+    .parse_next(input)
+}
+
+/// Distances to move along each axis and an optional feedrate, like `x10 y-5 f3000`
+fn parse_move(input: &mut &str) -> PResult<Vec<Word>> {
+    let word = (
+        one_of(['x', 'y', 'z', 'e', 'f', 'X', 'Y', 'Z', 'E', 'F']),
+        float::<_, f32, _>,
+    )
+        .map(|(letter, value): (char, f32)| Word {
+            letter: letter.to_ascii_uppercase(),
+            value: Some(value),
+        });
+    terminated(repeat(1.., preceded(space0, word)), space0)
+        .verify(|words: &Vec<Word>| words.iter().any(|word| word.letter != 'F'))
+        .context(StrContext::Label("move"))
+        .context(StrContext::Expected(StrContextValue::Description(
+            "distances along x, y, z or e and an optional feedrate f, like x10 y-5 f3000",
+        )))
+        .parse_next(input)
+}
+
+/// Length of filament in mm and an optional feedrate, like `5 f300`
+fn parse_extrude(input: &mut &str) -> PResult<(f32, Option<f32>)> {
+    terminated(
+        (
+            preceded(space0, float::<_, f32, _>).verify(|length: &f32| *length > 0.0),
+            opt(preceded((space1, one_of(['f', 'F'])), float::<_, f32, _>)),
+        ),
+        space0,
+    )
+    .context(StrContext::Label("length"))
+    .context(StrContext::Expected(StrContextValue::Description(
+        "a length in mm and an optional feedrate f, like 5 f300",
+    )))
+    .parse_next(input)
+}
+
+/// Axis and distance to babystep, like `z 0.02`, defaulting to z if only a distance is given
+fn parse_babystep(input: &mut &str) -> PResult<(usize, f32)> {
+    let axis = alt((
+        one_of(['x', 'X']).value(X),
+        one_of(['y', 'Y']).value(Y),
+        one_of(['z', 'Z']).value(Z),
+    ));
+    terminated(
+        (
+            preceded(space0, opt(axis)).map(|axis| axis.unwrap_or(Z)),
+            preceded(space0, float::<_, f32, _>),
+        ),
+        space0,
+    )
+    .context(StrContext::Label("babystep"))
+    .context(StrContext::Expected(StrContextValue::Description(
+        "an axis and a distance in mm, like z 0.02",
+    )))
+    .parse_next(input)
+}
+
+/// A percentage like `120%`, the `%` being optional
+fn parse_percent(input: &mut &str) -> PResult<f32> {
+    terminated(
+        preceded(space0, float.verify(|percent: &f32| *percent > 0.0)),
+        (opt('%'), space0),
+    )
+    .context(StrContext::Label("percentage"))
+    .context(StrContext::Expected(StrContextValue::Description(
+        "a percentage, like 120%",
+    )))
+    .parse_next(input)
+}
+
+/// `on` or `off`
+fn parse_switch(input: &mut &str) -> PResult<bool> {
+    terminated(
+        preceded(space0, alt(("on".value(true), "off".value(false)))),
+        space0,
+    )
+    .context(StrContext::Label("on or off"))
+    .context(StrContext::Expected(StrContextValue::StringLiteral("on")))
+    .context(StrContext::Expected(StrContextValue::StringLiteral("off")))
+    .parse_next(input)
+}
+
+/// Seconds between keepalive probes, or `off`
+fn parse_keepalive(input: &mut &str) -> PResult<Option<u32>> {
+    terminated(
+        preceded(
+            space0,
+            alt((
+                "off".value(None),
+                dec_uint.verify(|seconds: &u32| *seconds > 0).map(Some),
+            )),
+        ),
+        space0,
+    )
+    .context(StrContext::Label("keepalive interval"))
+    .context(StrContext::Expected(StrContextValue::Description(
+        "a number of seconds, or off",
+    )))
+    .parse_next(input)
+}
+
+/// Layer to pause prints at, counting from 1, or `off`
+fn parse_pause_layer(input: &mut &str) -> PResult<Option<usize>> {
+    terminated(
+        preceded(
+            space0,
+            alt((
+                "off".value(None),
+                dec_uint.verify(|layer: &usize| *layer > 0).map(Some),
+            )),
+        ),
+        space0,
+    )
+    .context(StrContext::Label("layer"))
+    .context(StrContext::Expected(StrContextValue::Description(
+        "a layer number, now, or off",
+    )))
+    .parse_next(input)
+}
+
+fn parse_settings<'a>(input: &mut &'a str) -> PResult<Command<&'a str>> {
+    dispatch! {preceded(space0, alpha1);
+        "save" => required_rest("file name").map(Command::SaveSettings),
+        "diff" => required_rest("file name").map(Command::DiffSettings),
+        _ => fail
+    }
+    .context(StrContext::Label("settings action"))
+    .context(StrContext::Expected(StrContextValue::StringLiteral("save")))
+    .context(StrContext::Expected(StrContextValue::StringLiteral("diff")))
+    .parse_next(input)
+}
+
+fn parse_profile<'a>(input: &mut &'a str) -> PResult<Command<&'a str>> {
+    dispatch! {preceded(space0, alpha1);
+        "save" => (parse_profile_name, alt((eof.value(None), parse_protocol.map(Some))))
+            .map(|(name, connection)| Command::SaveProfile(name, connection)),
+        "delete" => parse_profile_name.map(Command::DeleteProfile),
+        "list" => empty.map(|_| Command::Profiles),
+        _ => fail
+    }
+    .context(StrContext::Label("profile action"))
+    .context(StrContext::Expected(StrContextValue::Description(
+        "save, delete or list",
+    )))
+    .parse_next(input)
+}
+
+fn parse_config<'a>(input: &mut &'a str) -> PResult<Command<&'a str>> {
+    dispatch! {preceded(space0, alpha1);
+        "reload" => empty.map(|_| Command::ReloadConfig),
+        _ => fail
+    }
+    .context(StrContext::Label("config action"))
+    .context(StrContext::Expected(StrContextValue::StringLiteral(
+        "reload",
+    )))
+    .parse_next(input)
+}
+
+fn parse_sd<'a>(input: &mut &'a str) -> PResult<Command<&'a str>> {
+    dispatch! {preceded(space0, alpha1);
+        "list" => empty.map(|_| Command::SdList),
+        "print" => required_rest("file name").map(Command::SdPrint),
+        "delete" => required_rest("file name").map(Command::SdDelete),
+        "status" => empty.map(|_| Command::SdStatus),
+        "upload" => (
+            preceded(space0, take_till(1.., [' ', '\t']))
+                .context(StrContext::Label("file name"))
+                .context(StrContext::Expected(StrContextValue::Description("file name"))),
+            required_rest("remote name"),
+        )
+            .map(|(local, remote)| Command::SdUpload(local, remote)),
+        _ => fail
+    }
+    .context(StrContext::Label("sd action"))
+    .context(StrContext::Expected(StrContextValue::Description(
+        "list, upload, print, delete or status",
+    )))
+    .parse_next(input)
+}
+
+/// Names of every console command understood by `parse_command`
+pub const COMMAND_NAMES: &[&str] = &[
+    "log",
+    "repeat",
+    "print",
+    "lint",
+    "run",
+    "raw",
+    "heightmap",
+    "home",
+    "move",
+    "jog",
+    "extrude",
+    "retract",
+    "babystep",
+    "speed",
+    "flow",
+    "settings",
+    "sd",
+    "tasks",
+    "progress",
+    "eta",
+    "status",
+    "stats",
+    "debug",
+    "sparklines",
+    "dryrun",
+    "halfduplex",
+    "keepalive",
+    "record",
+    "reconnect",
+    "stop",
+    "pause",
+    "resume",
+    "help",
+    "version",
+    "disconnect",
+    "connect",
+    "profile",
+    "use",
+    "on",
+    "macro",
+    "macros",
+    "history",
+    "config",
+    "delmacro",
+    "clear",
+    "quit",
+    "exit",
+];
+
 fn inner_command<'a>(input: &mut &'a str) -> PResult<Command<&'a str>> {
+    // once a command keyword is recognized, errors in its arguments are reported
+    // rather than falling back to parsing the line as gcodes
     dispatch! {preceded(space0, alpha1);
-        "log" => parse_logger,
-        "repeat" => parse_repeater,
-        "print" => preceded(space0, rest).map(Command::Print),
+        "log" => cut_err(parse_logger),
+        "repeat" => cut_err(parse_repeater),
+        "print" => cut_err(parse_print).map(|(filename, options)| Command::Print(filename, options)),
+        "lint" => cut_err(required_rest("file name")).map(Command::Lint),
+        "run" => cut_err(parse_run).map(|(filename, keep_going)| Command::Run(filename, keep_going)),
+        "raw" => cut_err(required_rest("text to send")).map(Command::Raw),
+        // a bare `send` is left for a macro of that name
+        "send" => preceded('!', cut_err(required_rest("text to send"))).map(Command::Raw),
+        "heightmap" => cut_err(parse_grid).map(Command::Heightmap),
+        "home" => cut_err(parse_home).map(Command::Home),
+        "move" | "jog" => cut_err(parse_move).map(Command::Move),
+        "extrude" => cut_err(parse_extrude)
+            .map(|(length, feedrate)| Command::Extrude(length, feedrate)),
+        "retract" => cut_err(parse_extrude)
+            .map(|(length, feedrate)| Command::Extrude(-length, feedrate)),
+        "speed" => cut_err(parse_percent).map(Command::Speed),
+        "flow" => cut_err(parse_percent).map(Command::Flow),
+        "babystep" => cut_err(parse_babystep)
+            .map(|(axis, distance)| Command::Babystep(axis, distance)),
+        "settings" => cut_err(parse_settings),
+        "sd" => cut_err(parse_sd),
         "tasks" => empty.map(|_| Command::Tasks),
-        "stop" => preceded(space0, rest).map(Command::Stop),
+        "progress" => preceded(space0, rest)
+            .map(|name: &str| Command::Progress(Some(name.trim()).filter(|name| !name.is_empty()))),
+        "eta" => preceded(space0, rest)
+            .map(|name: &str| Command::Eta(Some(name.trim()).filter(|name| !name.is_empty()))),
+        "status" => empty.map(|_| Command::Status),
+        "stats" => empty.map(|_| Command::Stats),
+        "debug" => cut_err((name, parse_verbosity))
+            .map(|(name, verbosity)| Command::Debug(name, verbosity)),
+        "sparklines" => cut_err(parse_switch).map(Command::Sparklines),
+        "dryrun" => cut_err(parse_switch).map(Command::DryRun),
+        "halfduplex" => cut_err(parse_switch).map(Command::HalfDuplex),
+        "keepalive" => cut_err(parse_keepalive).map(Command::Keepalive),
+        "record" => cut_err(alt((
+            terminated(preceded(space0, "off"), (space0, eof)).value(None),
+            required_rest("file name").map(|file: &str| Some(file.trim())),
+        )))
+        .map(Command::Record),
+        "reconnect" => cut_err(parse_switch).map(Command::Reconnect),
+        "stop" => cut_err(required_rest("task name")).map(Command::Stop),
+        "pause" => cut_err(alt((
+            (space0, "now", space0).value(Command::PauseNow),
+            parse_pause_layer.map(Command::PauseAt),
+        ))),
+        "resume" => empty.map(|_| Command::Resume),
         "help" => rest.map(Command::Help),
         "version" => empty.map(|_| Command::Version),
         "disconnect" => empty.map(|_| Command::Disconnect),
-        "connect" => parse_connection,
-        "macro" => parse_macro,
+        "connect" => cut_err(parse_connection),
+        "profile" => cut_err(parse_profile),
+        "use" => cut_err(terminated(opt(name), space0)).map(Command::Use),
+        "on" => cut_err((name, preceded((space1, opt(("send", space1))), parse_command)))
+            .map(|(printer, command)| Command::On(printer, Box::new(command))),
+        "macro" => cut_err(parse_macro),
         "macros" => empty.map(|_| Command::Macros),
-        "delmacro" => preceded(space0, rest).map(Command::DeleteMacro),
+        "history" => preceded(space0, rest)
+            .map(|text: &str| Command::History(Some(text.trim()).filter(|text| !text.is_empty()))),
+        "config" => cut_err(parse_config),
+        "delmacro" => cut_err(required_rest("macro name")).map(Command::DeleteMacro),
         "clear" => empty.map(|_| Command::Clear),
         "quit" | "exit" => empty.map(|_| Command::Quit),
         _ => fail
@@ -191,3 +844,571 @@ pub fn parse_command<'a>(input: &mut &'a str) -> PResult<Command<&'a str>> {
     ))
     .parse_next(input)
 }
+
+/// Reason a line of input could not be understood as a command.
+///
+/// Holds the byte range of the offending input so frontends can point at it,
+/// along with whatever the grammar expected to find there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    pub span: Range<usize>,
+    pub label: Option<&'static str>,
+    pub expected: Vec<String>,
+}
+
+impl SyntaxError {
+    fn new(input: &str, error: winnow::error::ParseError<&str, ContextError>) -> Self {
+        let start = error.offset();
+        let len = input[start..]
+            .find(|c: char| c.is_whitespace() || c == ';')
+            .unwrap_or(input.len() - start);
+        let mut label = None;
+        let mut expected = vec![];
+        for context in error.inner().context() {
+            match context {
+                StrContext::Label(l) => {
+                    label.get_or_insert(*l);
+                }
+                StrContext::Expected(e) => expected.push(e.to_string()),
+                _ => {}
+            }
+        }
+        Self {
+            span: start..start + len,
+            label,
+            expected,
+        }
+    }
+}
+
+impl std::fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.label {
+            Some(label) => write!(f, "invalid {label}")?,
+            None if self.span.start == 0 => f.write_str("unrecognized command")?,
+            None => f.write_str("unexpected input")?,
+        }
+        write!(f, " at column {}", self.span.start + 1)?;
+        if let Some((last, rest)) = self.expected.split_last() {
+            f.write_str(", expected ")?;
+            for expected in rest {
+                write!(f, "{expected} or ")?;
+            }
+            f.write_str(last)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+/// Parse a complete line of input into a command,
+/// giving a `SyntaxError` pointing to the problem on failure.
+pub fn parse_command_line(input: &str) -> Result<Command<&str>, SyntaxError> {
+    parse_command
+        .parse(input)
+        .map_err(|e| SyntaxError::new(input, e))
+}
+
+/// Case-insensitive Levenshtein distance between two words
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(!a_char.eq_ignore_ascii_case(b_char));
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        core::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// Find the candidate nearest to `word`, if it is close enough to plausibly be a typo of it
+pub fn closest_match<'a>(
+    word: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let max_distance = (word.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(word, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn gcodes_parse() {
+        let command = parse_command_line("G28;M105").unwrap();
+        assert_eq!(command, Command::Gcodes(vec!["G28", "M105"]));
+    }
+
+    #[test]
+    fn raw_parse() {
+        let command = parse_command_line("raw M117 Hello; World").unwrap();
+        assert_eq!(command, Command::Raw("M117 Hello; World"));
+        let command = parse_command_line("send! M118 \"ok\"").unwrap();
+        assert_eq!(command, Command::Raw("M118 \"ok\""));
+        let command = parse_command_line("on ender send! m117 hi").unwrap();
+        assert_eq!(
+            command,
+            Command::On("ender", Box::new(Command::Raw("m117 hi")))
+        );
+        assert!(parse_command_line("raw").is_err());
+        assert!(parse_command_line("send!").is_err());
+    }
+
+    #[test]
+    fn macro_call_parse() {
+        let command = parse_command_line("heat temp=210 bed=60;G28").unwrap();
+        assert_eq!(
+            command,
+            Command::Gcodes(vec!["heat temp=210 bed=60", "G28"])
+        );
+        let command = parse_command_line("macro warm heat temp={t} bed=60").unwrap();
+        assert_eq!(
+            command,
+            Command::Macro("warm", vec!["heat temp={t} bed=60"])
+        );
+    }
+
+    #[test]
+    fn host_step_parse() {
+        let command = parse_command_line("repeat soak G28;@delay 5s;@waittemp bed 60").unwrap();
+        assert_eq!(
+            command,
+            Command::Repeat("soak", vec!["G28", "@delay 5s", "@waittemp bed 60"])
+        );
+    }
+
+    #[test]
+    fn unknown_command_span() {
+        let error = parse_command_line("conect serial").unwrap_err();
+        assert_eq!(error.span, 0..6);
+        assert_eq!(error.label, None);
+    }
+
+    #[test]
+    fn argument_error_span() {
+        let error = parse_command_line("repeat spam").unwrap_err();
+        assert_eq!(error.label, Some("gcodes"));
+        assert_eq!(error.span.start, 11);
+        assert!(!error.expected.is_empty());
+    }
+
+    #[test]
+    fn missing_argument() {
+        let error = parse_command_line("print ").unwrap_err();
+        assert_eq!(error.label, Some("file name"));
+        assert!(error.to_string().starts_with("invalid file name"));
+    }
+
+    #[test]
+    fn heightmap_grid() {
+        assert_eq!(
+            parse_command_line("heightmap").unwrap(),
+            Command::Heightmap(None)
+        );
+        assert_eq!(
+            parse_command_line("heightmap 5").unwrap(),
+            Command::Heightmap(Some(5))
+        );
+        let error = parse_command_line("heightmap 1").unwrap_err();
+        assert_eq!(error.label, Some("grid size"));
+    }
+
+    #[test]
+    fn home_axes() {
+        assert_eq!(
+            parse_command_line("home").unwrap(),
+            Command::Home(Home::all())
+        );
+        assert_eq!(
+            parse_command_line("home xy").unwrap(),
+            Command::Home(Home {
+                x: true,
+                y: true,
+                z: false
+            })
+        );
+        assert_eq!(
+            parse_command_line("home Z").unwrap(),
+            Command::Home(Home {
+                x: false,
+                y: false,
+                z: true
+            })
+        );
+        assert!(parse_command_line("home q").is_err());
+    }
+
+    #[test]
+    fn relative_move() {
+        let word = |letter, value| Word {
+            letter,
+            value: Some(value),
+        };
+        assert_eq!(
+            parse_command_line("move x10 y-5 f3000").unwrap(),
+            Command::Move(vec![word('X', 10.0), word('Y', -5.0), word('F', 3000.0)])
+        );
+        assert_eq!(
+            parse_command_line("jog Z0.2").unwrap(),
+            Command::Move(vec![word('Z', 0.2)])
+        );
+        let error = parse_command_line("move f3000").unwrap_err();
+        assert_eq!(error.label, Some("move"));
+        assert!(parse_command_line("move q1").is_err());
+    }
+
+    #[test]
+    fn extrude_retract() {
+        assert_eq!(
+            parse_command_line("extrude 5").unwrap(),
+            Command::Extrude(5.0, None)
+        );
+        assert_eq!(
+            parse_command_line("retract 2.5 f1800").unwrap(),
+            Command::Extrude(-2.5, Some(1800.0))
+        );
+        let error = parse_command_line("extrude -5").unwrap_err();
+        assert_eq!(error.label, Some("length"));
+    }
+
+    #[test]
+    fn speed_and_flow() {
+        assert_eq!(
+            parse_command_line("speed 120%").unwrap(),
+            Command::Speed(120.0)
+        );
+        assert_eq!(parse_command_line("flow 95").unwrap(), Command::Flow(95.0));
+        let error = parse_command_line("speed fast").unwrap_err();
+        assert_eq!(error.label, Some("percentage"));
+    }
+
+    #[test]
+    fn babystep_axis() {
+        assert_eq!(
+            parse_command_line("babystep z 0.02").unwrap(),
+            Command::Babystep(Z, 0.02)
+        );
+        assert_eq!(
+            parse_command_line("babystep X -0.1").unwrap(),
+            Command::Babystep(X, -0.1)
+        );
+        assert_eq!(
+            parse_command_line("babystep -0.05").unwrap(),
+            Command::Babystep(Z, -0.05)
+        );
+        let error = parse_command_line("babystep e 1").unwrap_err();
+        assert_eq!(error.label, Some("babystep"));
+    }
+
+    #[test]
+    fn progress_task() {
+        assert_eq!(
+            parse_command_line("progress").unwrap(),
+            Command::Progress(None)
+        );
+        assert_eq!(
+            parse_command_line("progress benchy.gcode").unwrap(),
+            Command::Progress(Some("benchy.gcode"))
+        );
+    }
+
+    #[test]
+    fn run_parse() {
+        assert_eq!(
+            parse_command_line("run setup.txt").unwrap(),
+            Command::Run("setup.txt", false)
+        );
+        assert_eq!(
+            parse_command_line("run my setup.txt --continue").unwrap(),
+            Command::Run("my setup.txt", true)
+        );
+        assert_eq!(
+            parse_command_line("run setup.txt --stop").unwrap(),
+            Command::Run("setup.txt", false)
+        );
+        assert!(parse_command_line("run setup.txt --later").is_err());
+        assert!(parse_command_line("run").is_err());
+    }
+
+    #[test]
+    fn profile_parse() {
+        assert_eq!(
+            parse_command_line("connect @ender").unwrap(),
+            Command::ConnectProfile("ender")
+        );
+        assert_eq!(
+            parse_command_line("profile save ender").unwrap(),
+            Command::SaveProfile("ender", None)
+        );
+        assert_eq!(
+            parse_command_line("profile save voron tcp voron.local:8080").unwrap(),
+            Command::SaveProfile(
+                "voron",
+                Some(Connection::Tcp {
+                    hostname: "voron.local",
+                    port: Some(8080)
+                })
+            )
+        );
+        assert_eq!(
+            parse_command_line("profile delete ender").unwrap(),
+            Command::DeleteProfile("ender")
+        );
+        assert_eq!(
+            parse_command_line("profile list").unwrap(),
+            Command::Profiles
+        );
+        assert!(parse_command_line("profile save").is_err());
+        assert!(parse_command_line("connect @").is_err());
+    }
+
+    #[test]
+    fn config_parse() {
+        assert_eq!(
+            parse_command_line("config reload").unwrap(),
+            Command::ReloadConfig
+        );
+        assert!(parse_command_line("config").is_err());
+        assert!(parse_command_line("config edit").is_err());
+    }
+
+    #[test]
+    fn printer_selection() {
+        assert_eq!(parse_command_line("use").unwrap(), Command::Use(None));
+        assert_eq!(
+            parse_command_line("use ender").unwrap(),
+            Command::Use(Some("ender"))
+        );
+        assert_eq!(
+            parse_command_line("on ender send G28").unwrap(),
+            Command::On("ender", Box::new(Command::Gcodes(vec!["G28"])))
+        );
+        assert_eq!(
+            parse_command_line("on voron home xy").unwrap(),
+            Command::On(
+                "voron",
+                Box::new(Command::Home(Home {
+                    x: true,
+                    y: true,
+                    z: false
+                }))
+            )
+        );
+        assert!(parse_command_line("on ender").is_err());
+    }
+
+    #[test]
+    fn history_parse() {
+        assert_eq!(
+            parse_command_line("history").unwrap(),
+            Command::History(None)
+        );
+        assert_eq!(
+            parse_command_line("history connect tcp").unwrap(),
+            Command::History(Some("connect tcp"))
+        );
+    }
+
+    #[test]
+    fn eta_parse() {
+        assert_eq!(parse_command_line("eta").unwrap(), Command::Eta(None));
+        assert_eq!(
+            parse_command_line("eta parts/benchy.gcode").unwrap(),
+            Command::Eta(Some("parts/benchy.gcode"))
+        );
+    }
+
+    #[test]
+    fn status_parse() {
+        assert_eq!(parse_command_line("status").unwrap(), Command::Status);
+        assert_eq!(parse_command_line(" status").unwrap(), Command::Status);
+    }
+
+    #[test]
+    fn debug_task() {
+        assert_eq!(
+            parse_command_line("debug temps").unwrap(),
+            Command::Debug("temps", Verbosity::Debug)
+        );
+        assert_eq!(
+            parse_command_line("debug temps off").unwrap(),
+            Command::Debug("temps", Verbosity::Quiet)
+        );
+        let error = parse_command_line("debug temps loud").unwrap_err();
+        assert_eq!(error.label, Some("verbosity"));
+    }
+
+    #[test]
+    fn sparklines_switch() {
+        assert_eq!(
+            parse_command_line("sparklines on").unwrap(),
+            Command::Sparklines(true)
+        );
+        assert_eq!(
+            parse_command_line("sparklines off").unwrap(),
+            Command::Sparklines(false)
+        );
+        let error = parse_command_line("sparklines").unwrap_err();
+        assert_eq!(error.label, Some("on or off"));
+        assert_eq!(
+            parse_command_line("halfduplex on").unwrap(),
+            Command::HalfDuplex(true)
+        );
+    }
+
+    #[test]
+    fn record_file() {
+        assert_eq!(
+            parse_command_line("record captures/ender.rec ").unwrap(),
+            Command::Record(Some("captures/ender.rec"))
+        );
+        assert_eq!(
+            parse_command_line("record off").unwrap(),
+            Command::Record(None)
+        );
+        assert!(parse_command_line("record").is_err());
+    }
+
+    #[test]
+    fn keepalive_interval() {
+        assert_eq!(
+            parse_command_line("keepalive 30").unwrap(),
+            Command::Keepalive(Some(30))
+        );
+        assert_eq!(
+            parse_command_line("keepalive off").unwrap(),
+            Command::Keepalive(None)
+        );
+        let error = parse_command_line("keepalive 0").unwrap_err();
+        assert_eq!(error.label, Some("keepalive interval"));
+    }
+
+    #[test]
+    fn print_from_line() {
+        assert_eq!(
+            parse_command_line("print benchy.gcode").unwrap(),
+            Command::Print("benchy.gcode", PrintOptions::default())
+        );
+        assert_eq!(
+            parse_command_line("print my parts/benchy.gcode --from 1200").unwrap(),
+            Command::Print(
+                "my parts/benchy.gcode",
+                PrintOptions {
+                    from: Some(1200),
+                    ..Default::default()
+                }
+            )
+        );
+        let error = parse_command_line("print benchy.gcode --from here").unwrap_err();
+        assert_eq!(error.label, Some("line number"));
+    }
+
+    #[test]
+    fn print_transformed() {
+        let Command::Print(filename, options) =
+            parse_command_line("print benchy.gcode --offset x10 y-5.5 --mirror x --scale 1.05")
+                .unwrap()
+        else {
+            panic!("not a print")
+        };
+        assert_eq!(filename, "benchy.gcode");
+        assert_eq!(options.transform.offset, [10.0, -5.5, 0.0]);
+        assert_eq!(options.transform.mirror, [true, false]);
+        assert_eq!(options.transform.scale, 1.05);
+        let error = parse_command_line("print benchy.gcode --mirror z").unwrap_err();
+        assert_eq!(error.label, Some("mirror"));
+        let error = parse_command_line("print benchy.gcode --rotate 90").unwrap_err();
+        assert_eq!(error.label, Some("print option"));
+    }
+
+    #[test]
+    fn pause_and_resume() {
+        assert_eq!(
+            parse_command_line("pause 12").unwrap(),
+            Command::PauseAt(Some(12))
+        );
+        assert_eq!(
+            parse_command_line("pause off").unwrap(),
+            Command::PauseAt(None)
+        );
+        assert_eq!(parse_command_line("pause now").unwrap(), Command::PauseNow);
+        assert_eq!(parse_command_line("resume").unwrap(), Command::Resume);
+        let error = parse_command_line("pause").unwrap_err();
+        assert_eq!(error.label, Some("layer"));
+    }
+
+    #[test]
+    fn reconnect_switch() {
+        assert_eq!(
+            parse_command_line("reconnect on").unwrap(),
+            Command::Reconnect(true)
+        );
+        let error = parse_command_line("reconnect maybe").unwrap_err();
+        assert_eq!(error.label, Some("on or off"));
+    }
+
+    #[test]
+    fn settings_actions() {
+        assert_eq!(
+            parse_command_line("settings diff tuned.txt").unwrap(),
+            Command::DiffSettings("tuned.txt")
+        );
+        let error = parse_command_line("settings load tuned.txt").unwrap_err();
+        assert_eq!(error.label, Some("settings action"));
+    }
+
+    #[test]
+    fn sd_actions() {
+        assert_eq!(parse_command_line("sd list").unwrap(), Command::SdList);
+        assert_eq!(parse_command_line("sd status").unwrap(), Command::SdStatus);
+        assert_eq!(
+            parse_command_line("sd print BENCHY.GCO").unwrap(),
+            Command::SdPrint("BENCHY.GCO")
+        );
+        assert_eq!(
+            parse_command_line("sd delete CALI~1.GCO").unwrap(),
+            Command::SdDelete("CALI~1.GCO")
+        );
+        let error = parse_command_line("sd print").unwrap_err();
+        assert_eq!(error.label, Some("file name"));
+        assert_eq!(
+            parse_command_line("sd upload ./benchy.gcode BENCHY.GCO").unwrap(),
+            Command::SdUpload("./benchy.gcode", "BENCHY.GCO")
+        );
+        let error = parse_command_line("sd upload benchy.gcode").unwrap_err();
+        assert_eq!(error.label, Some("remote name"));
+        let error = parse_command_line("sd format").unwrap_err();
+        assert_eq!(error.label, Some("sd action"));
+    }
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("conect", "connect"), 1);
+        assert_eq!(edit_distance("PRINT", "print"), 0);
+        assert_eq!(edit_distance("", "log"), 3);
+    }
+
+    #[test]
+    fn typo_suggestion() {
+        assert_eq!(
+            closest_match("conect", COMMAND_NAMES.iter().copied()),
+            Some("connect")
+        );
+        assert_eq!(
+            closest_match("mcros", COMMAND_NAMES.iter().copied()),
+            Some("macros")
+        );
+        assert_eq!(closest_match("xyzzy", COMMAND_NAMES.iter().copied()), None);
+    }
+}
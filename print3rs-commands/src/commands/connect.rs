@@ -1,6 +1,6 @@
 use {
-    super::Command,
-    print3rs_core::Printer,
+    super::{name, Command},
+    print3rs_core::{Dialect, Printer, PrinterOptions},
     std::{borrow::Borrow, str::FromStr, time::Duration},
     tokio::{
         io::BufReader,
@@ -8,32 +8,175 @@ use {
     },
     tokio_serial::{available_ports, SerialPort, SerialPortBuilderExt, SerialPortInfo},
     winnow::{
-        ascii::{alpha0, dec_uint, space0},
-        combinator::{alt, dispatch, empty, opt, preceded, terminated},
+        ascii::{alpha0, alpha1, dec_uint, space0},
+        combinator::{
+            alt, cut_err, dispatch, eof, fail, opt, preceded, repeat, rest, separated, terminated,
+        },
+        error::{StrContext, StrContextValue},
         prelude::*,
-        token::take_till,
+        token::{take_till, take_until},
     },
 };
 
+/// How `auto_connect_with` looks for a device
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct AutoConnect {
+    /// Command sent to check that a device is listening, e.g. `M115`, `M105`, `$I` for GRBL or `version` for Smoothie
+    pub probe: String,
+    /// Glob an answer to the probe must match, e.g. `*Grbl*`; any `ok` or GRBL status report is accepted if unset
+    pub accept: Option<String>,
+    /// How long to wait for a device to answer the probe on each port and baud rate
+    pub timeout: Duration,
+    /// Baud rates to try on each port, in order, keeping the first one the device answers on
+    pub bauds: Vec<u32>,
+    /// Only ports matching one of these globs are tried, all ports are tried if empty
+    pub include: Vec<String>,
+    /// Ports matching any of these globs are never tried
+    pub exclude: Vec<String>,
+}
+
+impl Default for AutoConnect {
+    fn default() -> Self {
+        Self {
+            probe: "M115".to_string(),
+            accept: None,
+            timeout: Duration::from_secs(5),
+            bauds: vec![250000, 115200, 57600],
+            include: vec![],
+            exclude: vec![],
+        }
+    }
+}
+
+/// Match a name against a glob where `*` matches any run of characters and `?` any one character
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // index in the pattern after the last `*`, and the position in name it was tried from
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_n)) => {
+                    p = star_p;
+                    n = star_n + 1;
+                    backtrack = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+impl AutoConnect {
+    /// Check if a port should be tried according to the include and exclude globs
+    pub fn allows_port(&self, port_name: &str) -> bool {
+        let included = self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|pattern| glob_match(pattern, port_name));
+        included
+            && !self
+                .exclude
+                .iter()
+                .any(|pattern| glob_match(pattern, port_name))
+    }
+
+    /// Check if a line sent back after the probe shows a device is there
+    pub fn accepts(&self, line: &str) -> bool {
+        match &self.accept {
+            Some(pattern) => glob_match(pattern, line.trim()),
+            None => is_valid_answer(line),
+        }
+    }
+}
+
+/// Check that a line sent back after the probe is a real answer and not noise from a mismatched baud rate,
+/// which accepts `ok` acknowledgements and GRBL `<...>` status reports
+fn is_valid_answer(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with("ok") || (line.starts_with('<') && line.ends_with('>'))
+}
+
+/// A printer found by `auto_detect`, along with where it was found
+#[derive(Debug)]
+pub struct FoundPrinter {
+    pub printer: Printer,
+    pub port: String,
+    pub baud: u32,
+}
+
 /// Attempt to enumerate and establish a connection to a device,
 /// connecting and returning to said device if any were successful.
 ///
 /// If no valid device is found, return a disconnected device.
 pub async fn auto_connect() -> Printer {
-    async fn check_port(port: SerialPortInfo) -> Option<Printer> {
-        tracing::debug!("checking port {}...", port.port_name);
-        let mut printer_port = tokio_serial::new(port.port_name, 115200)
+    auto_connect_with(&AutoConnect::default()).await
+}
+
+/// Like `auto_connect`, but with control over which ports and baud rates are tried
+/// and how a device is detected.
+///
+/// A device is found once it answers the probe command with a line matching `options.accept`,
+/// or by default with an `ok` or a GRBL status report.
+pub async fn auto_connect_with(options: &AutoConnect) -> Printer {
+    auto_connect_using(options, PrinterOptions::default()).await
+}
+
+/// Like `auto_connect_with`, with the found printer communicating according to `printer_options`
+pub async fn auto_connect_using(options: &AutoConnect, printer_options: PrinterOptions) -> Printer {
+    auto_detect(options, printer_options)
+        .await
+        .map_or(Printer::Disconnected, |found| found.printer)
+}
+
+/// Like `auto_connect_using`, also returning the port and baud rate the printer answered on
+pub async fn auto_detect(
+    options: &AutoConnect,
+    printer_options: PrinterOptions,
+) -> Option<FoundPrinter> {
+    async fn check_port(
+        port_name: &str,
+        baud: u32,
+        options: &AutoConnect,
+        printer_options: &PrinterOptions,
+    ) -> Option<Printer> {
+        tracing::debug!("checking port {port_name} at {baud} baud...");
+        let mut printer_port = tokio_serial::new(port_name, baud)
             .timeout(Duration::from_secs(10))
             .open_native_async()
             .ok()?;
         printer_port.write_data_terminal_ready(true).ok()?;
-        let printer = Printer::new(BufReader::new(printer_port));
+        let printer = Printer::with_options(BufReader::new(printer_port), printer_options.clone());
 
         sleep(Duration::from_secs(1)).await;
 
-        let look_for_ok = printer.send_unsequenced(b"M115\n").await.ok()?;
+        let mut responses = printer.subscribe_lines().ok()?;
+        printer
+            .send_bytes(format!("{}\n", options.probe))
+            .await
+            .ok()?;
+        let answer = async {
+            while let Ok(line) = responses.recv().await {
+                if options.accepts(&line) {
+                    return true;
+                }
+            }
+            false
+        };
 
-        if timeout(Duration::from_secs(5), look_for_ok).await.is_ok() {
+        if let Ok(true) = timeout(options.timeout, answer).await {
             Some(printer)
         } else {
             None
@@ -41,13 +184,25 @@ pub async fn auto_connect() -> Printer {
     }
     if let Ok(ports) = available_ports() {
         tracing::info!("found available ports: {ports:?}");
-        for port in ports {
-            if let Some(printer) = check_port(port).await {
-                return printer;
+        for SerialPortInfo { port_name, .. } in ports {
+            if !options.allows_port(&port_name) {
+                tracing::debug!("skipping port {port_name}");
+                continue;
+            }
+            for baud in options.bauds.iter().copied() {
+                if let Some(printer) = check_port(&port_name, baud, options, &printer_options).await
+                {
+                    tracing::info!("found printer on {port_name} at {baud} baud");
+                    return Some(FoundPrinter {
+                        printer,
+                        port: port_name,
+                        baud,
+                    });
+                }
             }
         }
     }
-    Printer::Disconnected
+    None
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -64,13 +219,14 @@ impl FromStr for HostPort {
 
 /// Underlying protocol used to establish communication to device.
 #[non_exhaustive]
-#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Connection<S> {
-    #[default]
-    Auto,
+    Auto(AutoConnect),
     Serial {
         port: S,
         baud: Option<u32>,
+        /// Protocol the device speaks, from `--dialect`
+        dialect: Dialect,
     },
     Tcp {
         hostname: S,
@@ -82,16 +238,161 @@ pub enum Connection<S> {
         in_topic: Option<S>,
         out_topic: Option<S>,
     },
+    /// Klipper through the Moonraker API
+    Moonraker {
+        hostname: S,
+        port: Option<u16>,
+    },
+    /// The printer attached to an OctoPrint server
+    OctoPrint {
+        hostname: S,
+        port: Option<u16>,
+        api_key: S,
+    },
+    /// A Prusa printer through its PrusaLink HTTP API
+    PrusaLink {
+        hostname: S,
+        port: Option<u16>,
+        api_key: S,
+    },
+    /// A Duet board running RepRapFirmware, through its HTTP API
+    Duet {
+        hostname: S,
+        port: Option<u16>,
+        password: Option<S>,
+    },
+    /// A fake printer running in this process, for trying things out without hardware
+    Virtual,
+    /// A session recorded with `record`, played back as if the printer was there
+    Replay {
+        file: S,
+    },
+}
+
+impl<S> Default for Connection<S> {
+    fn default() -> Self {
+        Connection::Auto(AutoConnect::default())
+    }
 }
 
 impl<T> Connection<T> {
     /// Name of the protocol being used
     pub fn protocol(&self) -> &str {
         match self {
-            Connection::Auto => "Auto",
+            Connection::Auto(_) => "Auto",
             Connection::Serial { .. } => "Serial",
             Connection::Tcp { .. } => "TCP/IP",
             Connection::Mqtt { .. } => "Mqtt",
+            Connection::Moonraker { .. } => "Moonraker",
+            Connection::OctoPrint { .. } => "OctoPrint",
+            Connection::PrusaLink { .. } => "PrusaLink",
+            Connection::Duet { .. } => "Duet",
+            Connection::Virtual => "Virtual",
+            Connection::Replay { .. } => "Replay",
+        }
+    }
+}
+
+/// Written as the arguments `connect` would be given to make the same connection, e.g. `serial /dev/ttyUSB0 115200`
+impl<S: std::fmt::Display> std::fmt::Display for Connection<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn host(
+            f: &mut std::fmt::Formatter<'_>,
+            hostname: impl std::fmt::Display,
+            port: &Option<u16>,
+        ) -> std::fmt::Result {
+            write!(f, " {hostname}")?;
+            match port {
+                Some(port) => write!(f, ":{port}"),
+                None => Ok(()),
+            }
+        }
+        match self {
+            Connection::Auto(options) => {
+                write!(f, "auto probe={}", options.probe)?;
+                if let Some(accept) = &options.accept {
+                    write!(f, " accept={accept}")?;
+                }
+                write!(f, " timeout={}", options.timeout.as_secs())?;
+                let bauds: Vec<String> = options.bauds.iter().map(u32::to_string).collect();
+                if !bauds.is_empty() {
+                    write!(f, " baud={}", bauds.join(","))?;
+                }
+                for pattern in options.include.iter() {
+                    write!(f, " include={pattern}")?;
+                }
+                for pattern in options.exclude.iter() {
+                    write!(f, " exclude={pattern}")?;
+                }
+                Ok(())
+            }
+            Connection::Serial {
+                port,
+                baud,
+                dialect,
+            } => {
+                write!(f, "serial {port}")?;
+                if let Some(baud) = baud {
+                    write!(f, " {baud}")?;
+                }
+                match dialect {
+                    Dialect::Grbl => write!(f, " --dialect grbl"),
+                    _ => Ok(()),
+                }
+            }
+            Connection::Tcp { hostname, port } => {
+                f.write_str("tcp")?;
+                host(f, hostname, port)
+            }
+            Connection::Mqtt {
+                hostname,
+                port,
+                in_topic,
+                out_topic,
+            } => {
+                f.write_str("mqtt")?;
+                host(f, hostname, port)?;
+                for topic in [in_topic, out_topic].into_iter().flatten() {
+                    write!(f, " {topic}")?;
+                }
+                Ok(())
+            }
+            Connection::Moonraker { hostname, port } => {
+                f.write_str("moonraker")?;
+                host(f, hostname, port)
+            }
+            Connection::OctoPrint {
+                hostname,
+                port,
+                api_key,
+            } => {
+                f.write_str("octoprint")?;
+                host(f, hostname, port)?;
+                write!(f, " {api_key}")
+            }
+            Connection::PrusaLink {
+                hostname,
+                port,
+                api_key,
+            } => {
+                f.write_str("prusalink")?;
+                host(f, hostname, port)?;
+                write!(f, " {api_key}")
+            }
+            Connection::Duet {
+                hostname,
+                port,
+                password,
+            } => {
+                f.write_str("duet")?;
+                host(f, hostname, port)?;
+                match password {
+                    Some(password) => write!(f, " {password}"),
+                    None => Ok(()),
+                }
+            }
+            Connection::Virtual => f.write_str("virtual"),
+            Connection::Replay { file } => write!(f, "replay {file}"),
         }
     }
 }
@@ -100,10 +401,15 @@ impl<'a> Connection<&'a str> {
     /// convert any inner borrowed data into owned
     pub fn into_owned(self) -> Connection<String> {
         match self {
-            Connection::Auto => Connection::Auto,
-            Connection::Serial { port, baud } => Connection::Serial {
+            Connection::Auto(options) => Connection::Auto(options),
+            Connection::Serial {
+                port,
+                baud,
+                dialect,
+            } => Connection::Serial {
                 port: port.to_owned(),
                 baud,
+                dialect,
             },
             Connection::Tcp { hostname, port } => Connection::Tcp {
                 hostname: hostname.to_owned(),
@@ -120,6 +426,41 @@ impl<'a> Connection<&'a str> {
                 in_topic: in_topic.map(|s| s.to_owned()),
                 out_topic: out_topic.map(|s| s.to_owned()),
             },
+            Connection::Moonraker { hostname, port } => Connection::Moonraker {
+                hostname: hostname.to_owned(),
+                port,
+            },
+            Connection::OctoPrint {
+                hostname,
+                port,
+                api_key,
+            } => Connection::OctoPrint {
+                hostname: hostname.to_owned(),
+                port,
+                api_key: api_key.to_owned(),
+            },
+            Connection::PrusaLink {
+                hostname,
+                port,
+                api_key,
+            } => Connection::PrusaLink {
+                hostname: hostname.to_owned(),
+                port,
+                api_key: api_key.to_owned(),
+            },
+            Connection::Duet {
+                hostname,
+                port,
+                password,
+            } => Connection::Duet {
+                hostname: hostname.to_owned(),
+                port,
+                password: password.map(|s| s.to_owned()),
+            },
+            Connection::Virtual => Connection::Virtual,
+            Connection::Replay { file } => Connection::Replay {
+                file: file.to_owned(),
+            },
         }
     }
 }
@@ -130,10 +471,15 @@ impl Connection<String> {
         String: Borrow<Borrowed>,
     {
         match self {
-            Connection::Auto => Connection::Auto,
-            Connection::Serial { port, baud } => Connection::Serial {
+            Connection::Auto(options) => Connection::Auto(options.clone()),
+            Connection::Serial {
+                port,
+                baud,
+                dialect,
+            } => Connection::Serial {
                 port: port.borrow(),
                 baud: *baud,
+                dialect: *dialect,
             },
             Connection::Tcp { hostname, port } => Connection::Tcp {
                 hostname: hostname.borrow(),
@@ -150,22 +496,84 @@ impl Connection<String> {
                 in_topic: in_topic.as_ref().map(|s| s.borrow()),
                 out_topic: out_topic.as_ref().map(|s| s.borrow()),
             },
+            Connection::Moonraker { hostname, port } => Connection::Moonraker {
+                hostname: hostname.borrow(),
+                port: *port,
+            },
+            Connection::OctoPrint {
+                hostname,
+                port,
+                api_key,
+            } => Connection::OctoPrint {
+                hostname: hostname.borrow(),
+                port: *port,
+                api_key: api_key.borrow(),
+            },
+            Connection::PrusaLink {
+                hostname,
+                port,
+                api_key,
+            } => Connection::PrusaLink {
+                hostname: hostname.borrow(),
+                port: *port,
+                api_key: api_key.borrow(),
+            },
+            Connection::Duet {
+                hostname,
+                port,
+                password,
+            } => Connection::Duet {
+                hostname: hostname.borrow(),
+                port: *port,
+                password: password.as_ref().map(|s| s.borrow()),
+            },
+            Connection::Virtual => Connection::Virtual,
+            Connection::Replay { file } => Connection::Replay {
+                file: file.borrow(),
+            },
         }
     }
 }
 
 fn parse_serial_connection<'a>(input: &mut &'a str) -> PResult<Connection<&'a str>> {
     let (port, baud) = (
-        preceded(space0, take_till(1.., ' ')),
+        preceded(space0, take_till(1.., ' '))
+            .context(StrContext::Label("serial port"))
+            .context(StrContext::Expected(StrContextValue::Description(
+                "a port like COM3 or /dev/ttyACM0",
+            ))),
         preceded(space0, opt(dec_uint)),
     )
         .parse_next(input)?;
-    Ok(Connection::Serial { port, baud })
+    let dialect = terminated(
+        opt(preceded((space0, "--dialect", space0), parse_dialect)),
+        space0,
+    )
+    .parse_next(input)?
+    .unwrap_or_default();
+    Ok(Connection::Serial {
+        port,
+        baud,
+        dialect,
+    })
+}
+
+fn parse_dialect(input: &mut &str) -> PResult<Dialect> {
+    alt(("marlin".value(Dialect::Marlin), "grbl".value(Dialect::Grbl)))
+        .context(StrContext::Label("dialect"))
+        .context(StrContext::Expected(StrContextValue::Description(
+            "marlin or grbl",
+        )))
+        .parse_next(input)
 }
 
 fn parse_hostname_port<'a>(input: &mut &'a str) -> PResult<(&'a str, Option<u16>)> {
     (
-        preceded(space0, take_till(1.., [' ', ':'])),
+        preceded(space0, take_till(1.., [' ', ':']))
+            .context(StrContext::Label("hostname"))
+            .context(StrContext::Expected(StrContextValue::Description(
+                "a hostname or address",
+            ))),
         preceded(alt((":", space0)), opt(dec_uint)),
     )
         .parse_next(input)
@@ -194,16 +602,170 @@ fn parse_mqtt_connection<'a>(input: &mut &'a str) -> PResult<Connection<&'a str>
     })
 }
 
+fn parse_moonraker_connection<'a>(input: &mut &'a str) -> PResult<Connection<&'a str>> {
+    let (hostname, port) = terminated(parse_hostname_port, space0).parse_next(input)?;
+    Ok(Connection::Moonraker { hostname, port })
+}
+
+/// Hostname, optional port and API key of a web service
+fn parse_api_key_connection<'a>(input: &mut &'a str) -> PResult<(&'a str, Option<u16>, &'a str)> {
+    // the port has to follow a `:`, as API keys can start with digits
+    let (hostname, port) = (
+        preceded(space0, take_till(1.., [' ', ':']))
+            .context(StrContext::Label("hostname"))
+            .context(StrContext::Expected(StrContextValue::Description(
+                "a hostname or address",
+            ))),
+        opt(preceded(':', dec_uint)),
+    )
+        .parse_next(input)?;
+    let api_key = terminated(
+        preceded(space0, take_till(1.., ' '))
+            .context(StrContext::Label("api key"))
+            .context(StrContext::Expected(StrContextValue::Description(
+                "the API key from the service's settings",
+            ))),
+        space0,
+    )
+    .parse_next(input)?;
+    Ok((hostname, port, api_key))
+}
+
+fn parse_octoprint_connection<'a>(input: &mut &'a str) -> PResult<Connection<&'a str>> {
+    let (hostname, port, api_key) = parse_api_key_connection.parse_next(input)?;
+    Ok(Connection::OctoPrint {
+        hostname,
+        port,
+        api_key,
+    })
+}
+
+fn parse_prusalink_connection<'a>(input: &mut &'a str) -> PResult<Connection<&'a str>> {
+    let (hostname, port, api_key) = parse_api_key_connection.parse_next(input)?;
+    Ok(Connection::PrusaLink {
+        hostname,
+        port,
+        api_key,
+    })
+}
+
+fn parse_duet_connection<'a>(input: &mut &'a str) -> PResult<Connection<&'a str>> {
+    let (hostname, port) = parse_hostname_port.parse_next(input)?;
+    let password =
+        terminated(preceded(space0, opt(take_till(1.., ' '))), space0).parse_next(input)?;
+    Ok(Connection::Duet {
+        hostname,
+        port,
+        password,
+    })
+}
+
+fn parse_replay_connection<'a>(input: &mut &'a str) -> PResult<Connection<&'a str>> {
+    let file = terminated(
+        preceded(space0, take_till(1.., ' '))
+            .context(StrContext::Label("recording"))
+            .context(StrContext::Expected(StrContextValue::Description(
+                "a file made with `record`",
+            ))),
+        space0,
+    )
+    .parse_next(input)?;
+    Ok(Connection::Replay { file })
+}
+
+enum AutoOption<'a> {
+    Probe(&'a str),
+    Accept(&'a str),
+    Timeout(u64),
+    Bauds(Vec<u32>),
+    Include(&'a str),
+    Exclude(&'a str),
+}
+
+fn parse_auto_option<'a>(input: &mut &'a str) -> PResult<AutoOption<'a>> {
+    dispatch! { terminated(alpha1, '=');
+        "probe" => take_till(1.., ' ').map(AutoOption::Probe),
+        "accept" => take_till(1.., ' ').map(AutoOption::Accept),
+        "timeout" => dec_uint.map(AutoOption::Timeout),
+        "baud" => separated(1.., dec_uint::<_, u32, _>, ',').map(AutoOption::Bauds),
+        "include" => take_till(1.., ' ').map(AutoOption::Include),
+        "exclude" => take_till(1.., ' ').map(AutoOption::Exclude),
+        _ => fail,
+    }
+    .context(StrContext::Label("auto-connect option"))
+    .context(StrContext::Expected(StrContextValue::Description(
+        "probe=, accept=, timeout=, baud=, include= or exclude=",
+    )))
+    .parse_next(input)
+}
+
+fn parse_auto_connection<'a>(input: &mut &'a str) -> PResult<Connection<&'a str>> {
+    let options: Vec<AutoOption> =
+        terminated(repeat(0.., preceded(space0, parse_auto_option)), space0).parse_next(input)?;
+    let mut auto = AutoConnect::default();
+    for option in options {
+        match option {
+            AutoOption::Probe(probe) => auto.probe = probe.to_owned(),
+            AutoOption::Accept(pattern) => auto.accept = Some(pattern.to_owned()),
+            AutoOption::Timeout(secs) => auto.timeout = Duration::from_secs(secs),
+            AutoOption::Bauds(bauds) => auto.bauds = bauds,
+            AutoOption::Include(pattern) => auto.include.push(pattern.to_owned()),
+            AutoOption::Exclude(pattern) => auto.exclude.push(pattern.to_owned()),
+        }
+    }
+    Ok(Connection::Auto(auto))
+}
+
 /// Parse connection details from a string, for any known protocol
-pub fn parse_connection<'a>(input: &mut &'a str) -> PResult<Command<&'a str>> {
-    let connection = dispatch! { preceded(space0, alpha0);
+pub fn parse_protocol<'a>(input: &mut &'a str) -> PResult<Connection<&'a str>> {
+    dispatch! { preceded(space0, alpha0);
         "serial" => parse_serial_connection,
         "tcp" | "ip" => parse_tcp_connection,
         "mqtt" => parse_mqtt_connection,
-        _ => empty.map(|_| Connection::Auto),
+        "moonraker" | "klipper" => parse_moonraker_connection,
+        "octoprint" => parse_octoprint_connection,
+        "prusalink" => parse_prusalink_connection,
+        "duet" | "reprapfirmware" => parse_duet_connection,
+        "virtual" => space0.value(Connection::Virtual),
+        "replay" => parse_replay_connection,
+        "auto" | "" => parse_auto_connection,
+        _ => fail,
     }
-    .parse_next(input)?;
-    Ok(Command::Connect(connection))
+    .context(StrContext::Label("protocol"))
+    .context(StrContext::Expected(StrContextValue::Description(
+        "auto, serial, tcp, mqtt, moonraker, octoprint, prusalink, duet, virtual or replay",
+    )))
+    .parse_next(input)
+}
+
+/// Name of a connection profile, without spaces
+pub fn parse_profile_name<'a>(input: &mut &'a str) -> PResult<&'a str> {
+    terminated(preceded(space0, take_till(1.., [' ', '\t'])), space0)
+        .context(StrContext::Label("profile name"))
+        .context(StrContext::Expected(StrContextValue::Description(
+            "profile name",
+        )))
+        .parse_next(input)
+}
+
+/// Parse the arguments of `connect`: a saved profile as `@<name>`, or connection details for any known protocol,
+/// optionally followed by `as:<name>` to connect a printer other than the selected one
+pub fn parse_connection<'a>(input: &mut &'a str) -> PResult<Command<&'a str>> {
+    let connect = alt((take_until(0.., " as:"), rest))
+        .and_then(terminated(
+            alt((
+                preceded((space0, '@'), cut_err(parse_profile_name)).map(Command::ConnectProfile),
+                parse_protocol.map(Command::Connect),
+            )),
+            eof,
+        ))
+        .parse_next(input)?;
+    let printer =
+        terminated(opt(preceded((space0, "as:"), cut_err(name))), space0).parse_next(input)?;
+    Ok(match printer {
+        Some(printer) => Command::On(printer, Box::new(connect)),
+        None => connect,
+    })
 }
 
 #[cfg(test)]
@@ -217,7 +779,8 @@ mod test {
             serial,
             Connection::Serial {
                 port: "/dev/ttyS0",
-                baud: Some(9600)
+                baud: Some(9600),
+                dialect: Dialect::Marlin
             }
         );
     }
@@ -229,7 +792,8 @@ mod test {
             serial,
             Connection::Serial {
                 port: "COM1",
-                baud: None
+                baud: None,
+                dialect: Dialect::Marlin
             }
         );
     }
@@ -330,6 +894,156 @@ mod test {
         assert_eq!(borrowed, owned.to_borrowed());
     }
 
+    #[test]
+    fn auto_default_parsing() {
+        let command = parse_connection.parse("").unwrap();
+        assert_eq!(command, Command::Connect(Connection::default()));
+    }
+
+    #[test]
+    fn auto_options_parsing() {
+        let auto = parse_auto_connection
+            .parse(" probe=? timeout=2 baud=115200,250000 include=/dev/ttyUSB* exclude=*1")
+            .unwrap();
+        assert_eq!(
+            auto,
+            Connection::Auto(AutoConnect {
+                probe: "?".to_string(),
+                accept: None,
+                timeout: Duration::from_secs(2),
+                bauds: vec![115200, 250000],
+                include: vec!["/dev/ttyUSB*".to_string()],
+                exclude: vec!["*1".to_string()],
+            })
+        );
+    }
+
+    #[test]
+    fn port_globs() {
+        assert!(glob_match("/dev/ttyUSB*", "/dev/ttyUSB0"));
+        assert!(glob_match("COM?", "COM3"));
+        assert!(!glob_match("COM?", "COM10"));
+        assert!(glob_match("*ACM*", "/dev/ttyACM1"));
+        assert!(!glob_match("/dev/ttyS*", "/dev/ttyUSB0"));
+
+        let options = AutoConnect {
+            include: vec!["/dev/tty*".to_string()],
+            exclude: vec!["/dev/ttyS*".to_string()],
+            ..Default::default()
+        };
+        assert!(options.allows_port("/dev/ttyACM0"));
+        assert!(!options.allows_port("/dev/ttyS0"));
+        assert!(!options.allows_port("COM3"));
+    }
+
+    #[test]
+    fn probe_answers() {
+        assert!(is_valid_answer("ok\n"));
+        assert!(is_valid_answer("ok T:21.0 /0.0 B:20.5 /0.0\r\n"));
+        assert!(is_valid_answer("<Idle|MPos:0.000,0.000,0.000|FS:0,0>\r\n"));
+        assert!(!is_valid_answer("\u{fffd}\u{fffd}x\u{fffd}\n"));
+        assert!(!is_valid_answer("start\n"));
+        assert!(!is_valid_answer("  \n"));
+
+        let Connection::Auto(grbl) = parse_auto_connection
+            .parse(" probe=$I accept=[VER:*")
+            .unwrap()
+        else {
+            panic!("expected auto connection");
+        };
+        assert_eq!(grbl.probe, "$I");
+        assert!(grbl.accepts("[VER:1.1h.20190825:]\r\n"));
+        assert!(!grbl.accepts("ok\n"));
+
+        let smoothie = AutoConnect {
+            probe: "version".to_string(),
+            accept: Some("*Smoothie*".to_string()),
+            ..Default::default()
+        };
+        assert!(smoothie.accepts("Build version: edge-3332442, Build date: Sep 1 2020, MCU: LPC1769, System Clock: 120MHz, Smoothieware\n"));
+        assert!(!smoothie.accepts("echo: unknown command\n"));
+    }
+
+    #[test]
+    fn moonraker_parsing() {
+        let command = parse_connection
+            .parse("moonraker voron.local:7125")
+            .unwrap();
+        assert_eq!(
+            command,
+            Command::Connect(Connection::Moonraker {
+                hostname: "voron.local",
+                port: Some(7125)
+            })
+        );
+    }
+
+    #[test]
+    fn octoprint_parsing() {
+        let command = parse_connection
+            .parse("octoprint octopi.local 0123ABCD")
+            .unwrap();
+        assert_eq!(
+            command,
+            Command::Connect(Connection::OctoPrint {
+                hostname: "octopi.local",
+                port: None,
+                api_key: "0123ABCD"
+            })
+        );
+        let command = parse_connection
+            .parse("octoprint 10.0.0.5:5000 ABCD")
+            .unwrap();
+        assert!(matches!(
+            command,
+            Command::Connect(Connection::OctoPrint {
+                port: Some(5000),
+                ..
+            })
+        ));
+        assert!(parse_connection.parse("octoprint octopi.local").is_err());
+    }
+
+    #[test]
+    fn prusalink_parsing() {
+        let command = parse_connection
+            .parse("prusalink mk4.local:80 9zQzGm")
+            .unwrap();
+        assert_eq!(
+            command,
+            Command::Connect(Connection::PrusaLink {
+                hostname: "mk4.local",
+                port: Some(80),
+                api_key: "9zQzGm"
+            })
+        );
+        assert!(parse_connection.parse("prusalink mk4.local").is_err());
+    }
+
+    #[test]
+    fn duet_parsing() {
+        let command = parse_connection.parse("duet duet3.local").unwrap();
+        assert_eq!(
+            command,
+            Command::Connect(Connection::Duet {
+                hostname: "duet3.local",
+                port: None,
+                password: None
+            })
+        );
+        let command = parse_connection
+            .parse("duet 192.168.1.20:8080 secret")
+            .unwrap();
+        assert_eq!(
+            command,
+            Command::Connect(Connection::Duet {
+                hostname: "192.168.1.20",
+                port: Some(8080),
+                password: Some("secret")
+            })
+        );
+    }
+
     #[test]
     fn command_parse() {
         let input = "serial COM1 9600";
@@ -338,8 +1052,71 @@ mod test {
             command,
             Command::Connect(Connection::Serial {
                 port: "COM1",
-                baud: Some(9600)
+                baud: Some(9600),
+                dialect: Dialect::Marlin
+            })
+        );
+        let command = parse_connection
+            .parse("serial /dev/ttyUSB0 115200 --dialect grbl")
+            .unwrap();
+        assert!(matches!(
+            command,
+            Command::Connect(Connection::Serial {
+                dialect: Dialect::Grbl,
+                ..
             })
+        ));
+        assert!(parse_connection
+            .parse("serial /dev/ttyUSB0 --dialect smoothie")
+            .is_err());
+    }
+
+    #[test]
+    fn connect_as() {
+        assert_eq!(
+            parse_connection
+                .parse("serial COM3 115200 as:ender")
+                .unwrap(),
+            Command::On(
+                "ender",
+                Box::new(Command::Connect(Connection::Serial {
+                    port: "COM3",
+                    baud: Some(115200),
+                    dialect: Dialect::Marlin
+                }))
+            )
         );
+        assert_eq!(
+            parse_connection.parse(" @voron as:voron").unwrap(),
+            Command::On("voron", Box::new(Command::ConnectProfile("voron")))
+        );
+        assert_eq!(
+            parse_connection.parse(" as:prusa").unwrap(),
+            Command::On(
+                "prusa",
+                Box::new(Command::Connect(Connection::Auto(AutoConnect::default())))
+            )
+        );
+        assert!(parse_connection.parse(" tcp printer.local as:").is_err());
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for input in [
+            "auto probe=M105 accept=*Grbl* timeout=2 baud=115200 include=/dev/ttyUSB* exclude=COM1",
+            "serial /dev/ttyUSB0 115200 --dialect grbl",
+            "serial COM3",
+            "tcp 192.168.1.20:8080",
+            "mqtt broker.local:1883 printer/in printer/out",
+            "moonraker voron.local",
+            "octoprint octopi.local:5000 0123ABCD",
+            "prusalink mk4.local 0123ABCD",
+            "duet duet3.local secret",
+            "virtual",
+            "replay ender.rec",
+        ] {
+            let connection = parse_protocol.parse(input).unwrap();
+            assert_eq!(connection.to_string(), input);
+        }
     }
 }
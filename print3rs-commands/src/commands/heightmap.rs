@@ -0,0 +1,226 @@
+use {
+    print3rs_core::Socket,
+    std::{fmt::Write, sync::Arc, time::Duration},
+    tokio::time::timeout,
+};
+
+/// Distance kept from the edges of the bed when choosing probe points
+const PROBE_MARGIN: f32 = 30.0;
+
+/// Longest a single probe or mesh report is waited on
+const PROBE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Bed height measurements on a regular grid.
+///
+/// Rows go from front to back (increasing Y), and each row from left to right (increasing X).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heightmap {
+    pub rows: Vec<Vec<f32>>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum HeightmapError {
+    #[error("{0}")]
+    Printer(#[from] print3rs_core::Error),
+    #[error("printer took too long to respond")]
+    Timeout,
+    #[error("no probe result for X{0} Y{1}")]
+    NoProbeResult(f32, f32),
+    #[error("printer did not report a mesh, run G29 first")]
+    NoMesh,
+}
+
+impl Heightmap {
+    /// Read the mesh out of the topology report given by `G29 T`
+    pub fn from_mesh_report<S: AsRef<str>>(lines: &[S]) -> Option<Self> {
+        let rows: Vec<Vec<f32>> = lines
+            .iter()
+            .filter_map(|line| mesh_row(line.as_ref()))
+            .collect();
+        if rows.is_empty() {
+            None
+        } else {
+            Some(Self { rows })
+        }
+    }
+
+    fn points(&self) -> impl Iterator<Item = (usize, usize, f32)> + '_ {
+        self.rows.iter().enumerate().flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .map(move |(x, z)| (x, y, *z))
+                .filter(|(_, _, z)| z.is_finite())
+        })
+    }
+
+    pub fn min(&self) -> f32 {
+        self.points()
+            .map(|(_, _, z)| z)
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    pub fn max(&self) -> f32 {
+        self.points()
+            .map(|(_, _, z)| z)
+            .fold(f32::NEG_INFINITY, f32::max)
+    }
+
+    /// Difference between the highest and lowest point
+    pub fn range(&self) -> f32 {
+        self.max() - self.min()
+    }
+
+    /// Change in height from one side of the grid to the other in X and Y,
+    /// taken from the plane best fitting all points
+    pub fn tilt(&self) -> [f32; 2] {
+        let count = self.points().count() as f32;
+        let mean_z = self.points().map(|(_, _, z)| z).sum::<f32>() / count;
+        let slope = |axis: fn(&(usize, usize, f32)) -> usize| {
+            let mean = self.points().map(|p| axis(&p) as f32).sum::<f32>() / count;
+            let (covariance, variance) = self.points().fold((0.0, 0.0), |(cov, var), p| {
+                let d = axis(&p) as f32 - mean;
+                (cov + d * (p.2 - mean_z), var + d * d)
+            });
+            let span = self.points().map(|p| axis(&p)).max().unwrap_or_default() as f32;
+            if variance > 0.0 {
+                covariance / variance * span
+            } else {
+                0.0
+            }
+        };
+        [slope(|p| p.0), slope(|p| p.1)]
+    }
+
+    /// Matrix of heights with one row per line
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        for row in &self.rows {
+            let row: Vec<String> = row.iter().map(|z| format!("{z:.3}")).collect();
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// One line summary of the measurements
+    pub fn summary(&self) -> String {
+        let [tilt_x, tilt_y] = self.tilt();
+        let mut summary = String::new();
+        let _ = write!(
+            summary,
+            "min {:.3} max {:.3} range {:.3}, tilt X {tilt_x:+.3} Y {tilt_y:+.3}",
+            self.min(),
+            self.max(),
+            self.range()
+        );
+        summary
+    }
+}
+
+/// A row of the mesh report, like ` 1 +0.050 -0.012 +0.100`.
+///
+/// The column header row is all whole numbers and is not a mesh row.
+fn mesh_row(line: &str) -> Option<Vec<f32>> {
+    let mut tokens = line.split_whitespace();
+    tokens.next()?.parse::<u32>().ok()?;
+    let tokens: Vec<&str> = tokens.collect();
+    if tokens.is_empty() || !tokens.iter().any(|token| token.contains('.')) {
+        return None;
+    }
+    tokens.into_iter().map(|token| token.parse().ok()).collect()
+}
+
+/// Height from the output of a single G30 probe, like `Bed X: 30.00 Y: 30.00 Z: 0.12`
+fn probe_result(line: &str) -> Option<f32> {
+    let line = line.trim();
+    if !line.starts_with("Bed") {
+        return None;
+    }
+    let (_, z) = line.rsplit_once("Z:")?;
+    z.split_whitespace().next()?.parse().ok()
+}
+
+/// Evenly spaced positions along an axis of the given length, kept away from the edges
+fn probe_positions(grid: u32, length: f32) -> Vec<f32> {
+    let margin = PROBE_MARGIN.min(length / 4.0);
+    let step = (length - 2.0 * margin) / (grid.max(2) - 1) as f32;
+    (0..grid.max(2)).map(|i| margin + step * i as f32).collect()
+}
+
+async fn captured(socket: &Socket, gcode: String) -> Result<Vec<Arc<str>>, HeightmapError> {
+    timeout(PROBE_TIMEOUT, socket.send_captured(gcode))
+        .await
+        .map_err(|_| HeightmapError::Timeout)?
+        .map_err(HeightmapError::from)
+}
+
+/// Measure the bed of the printer.
+///
+/// With a `grid` size the bed is homed and probed with G30 at `grid` x `grid` points,
+/// otherwise the mesh already stored by the printer is read with G29 T.
+pub async fn measure(
+    grid: Option<u32>,
+    bed_size: [f32; 2],
+    socket: &Socket,
+) -> Result<Heightmap, HeightmapError> {
+    let Some(grid) = grid else {
+        let report = captured(socket, "G29 T".to_owned()).await?;
+        return Heightmap::from_mesh_report(&report).ok_or(HeightmapError::NoMesh);
+    };
+    captured(socket, "G28".to_owned()).await?;
+    let mut rows = vec![];
+    for y in probe_positions(grid, bed_size[1]) {
+        let mut row = vec![];
+        for x in probe_positions(grid, bed_size[0]) {
+            let output = captured(socket, format!("G30 X{x:.1} Y{y:.1}")).await?;
+            let z = output
+                .iter()
+                .find_map(|line| probe_result(line))
+                .ok_or(HeightmapError::NoProbeResult(x, y))?;
+            row.push(z);
+        }
+        rows.push(row);
+    }
+    Ok(Heightmap { rows })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mesh_report() {
+        let report = [
+            "Bilinear Leveling Grid:",
+            "      0      1      2",
+            " 0 +0.100 +0.050 +0.000",
+            " 1 +0.100 +0.050 +0.000",
+            " 2 +0.100 +0.050 +0.000",
+            "ok",
+        ];
+        let heightmap = Heightmap::from_mesh_report(&report).unwrap();
+        assert_eq!(heightmap.rows.len(), 3);
+        assert_eq!(heightmap.rows[0], vec![0.1, 0.05, 0.0]);
+        assert!((heightmap.range() - 0.1).abs() < 1e-6);
+        let [tilt_x, tilt_y] = heightmap.tilt();
+        assert!((tilt_x + 0.1).abs() < 1e-6);
+        assert!(tilt_y.abs() < 1e-6);
+        assert_eq!(heightmap.to_csv().lines().next(), Some("0.100,0.050,0.000"));
+    }
+
+    #[test]
+    fn no_mesh() {
+        assert!(Heightmap::from_mesh_report(&["Mesh not active", "ok"]).is_none());
+    }
+
+    #[test]
+    fn probe_output() {
+        assert_eq!(probe_result("Bed X: 30.00 Y: 30.00 Z: 0.12\n"), Some(0.12));
+        assert_eq!(probe_result("echo:busy: processing"), None);
+    }
+
+    #[test]
+    fn positions() {
+        assert_eq!(probe_positions(3, 220.0), vec![30.0, 110.0, 190.0]);
+    }
+}
@@ -13,25 +13,85 @@ help         <command?>       display this message or details for specified comm
 version                       display version
 clear                         clear all text on the screen
 printerinfo                   display any information found about the connected printer
-print        <file>           send gcodes from file to printer
-log          <name> <pattern> begin logging parsed output from printer
+progress     <name?>          show how far along prints and uploads are
+eta          <name?>          estimate the time left for a print, or for a file before printing it
+status                        summarize the connection, temperatures, position and tasks
+stats                         show how much has been sent and received, resends and how fast oks come back
+print        <file> <opts?>   send gcodes from file to printer
+lint         <file>           check a gcode file for problems before printing it
+run          <file> <opts?>   run the console commands in a file, one per line
+raw          <text>           send text exactly as typed, without uppercasing or macros, also `send!`
+heightmap    <grid?>          measure the bed and save the heights to a csv file
+home         <axes?>          home the given axes, like `home xy`, or all of them
+move         <distances>      move the toolhead relative to where it is, also `jog`
+extrude      <mm> <feed?>     push filament through the hotend, `retract` pulls it back
+babystep     <axis?> <mm>     nudge an axis mid-print, like `babystep z 0.02`
+speed        <percent>        change how fast prints go, like `speed 120%`
+flow         <percent>        change how much filament prints push out, like `flow 95%`
+sd           <action> <file?> list, upload, print, delete or check prints of files on the SD card
+settings     <action> <file>  save printer settings to a file, or diff them against one
+log          <name> <pattern> begin logging parsed output from printer to a file, database or server
 repeat       <name> <gcodes>  run the given gcodes in a loop until stop
 stop         <name>           stop an active print, log, or repeat
+pause        <layer|now|off>  pause prints when they reach a layer, or right away
+resume                        carry on with a paused print
+debug        <name> <level?>  show what a task is doing in the console
+sparklines   <on|off>         show recent values of log tasks in the prompt
+dryrun       <on|off>         show what would be sent instead of sending it to the printer
 macro        <name> <gcodes>  make an alias for a set of gcodes
 delmacro     <name>           remove an existing alias for set of gcodes
 macros                        list existing command aliases and contents           
+history      <text?>          list commands entered before, or those containing some text
+config       reload           read the config file again
 connect      <proto?> <args?> connect to a device using protocol and args, or attempt to autoconnect
+profile      <action> <name?> save connections under a name to connect with `connect @<name>`
+use          <printer?>       send commands to another connected printer, or list printers
+on           <printer> <cmd>  run one command on another printer, like `on ender send G28`
+halfduplex   <on|off>         wait for ok after every line sent, for printers that can't keep up
+keepalive    <secs|off>       check that the printer is still there when nothing has been sent
+record       <file|off>       save everything sent to and received from the printer, see `connect replay`
+reconnect    <on|off>         reopen serial and tcp connections when they are lost
 disconnect                    disconnect from printer
 quit                          exit program
 \n";
 
-static PRINT_HELP: &str = "print: execute every line of G-code sequentially from the given file. The print job is added as a task which runs in the background with the filename as the task name. Other commands can be sent while a print is running, and a print can be stopped at any time with `stop`\n";
-static LOG_HELP: &str = "log: begin logging the specified pattern from the printer into a csv with the `name` given. This operation runs in the background and is added as a task which can be stopped with `stop`. The pattern given will be used to parse the logs, with values wrapped in `{}` being given a column of whatever is between the `{}`, and pulling a number in its place. If your pattern needs to include a literal `{` or `}`, double them up like `{{` or `}}` to have the parser read it as just a `{` or `}` in the output.\n";
-static REPEAT_HELP: &str = "repeat: repeat the given Gcodes (separated by gcode comment character `;`) in a loop until stopped. \n";
+static PRINT_HELP: &str = "print: execute every line of G-code sequentially from the given file. The print job is added as a task which runs in the background with the filename as the task name. Other commands can be sent while a print is running, and a print can be stopped at any time with `stop`. To carry on with a print that failed partway, add `--from` and the line of the file to start at, like `print benchy.gcode --from 52310`: the lines before it are read but not sent, then the bed and hotend are heated to the temperatures set by then, the nozzle is lifted 10mm, moved over to where it was, lowered, and the print carries on with the positioning modes, extruder position and feedrate it had. The printer needs to be homed first. To print somewhere else on the bed, like around a damaged patch, add `--offset` with distances in mm for any of x, y and z, e.g. `print benchy.gcode --offset x10 y-5`. `--scale 1.05` makes the print bigger or smaller around its middle, putting out more or less filament to match, and `--mirror x`, `--mirror y` or `--mirror x y` flip it over around its middle. Options can be combined, and every move is changed as it is sent. Starting partway isn't possible when the connection prints files by uploading them. Printers that don't report arc support in M115 are sent G2 and G3 arcs as short straight G1 moves instead\n";
+static RAW_HELP: &str = "raw: send the rest of the line to the printer exactly as typed, as a single line, e.g. `raw M117 Hello World` keeps the message's case. Nothing is uppercased, `;` is sent as part of the text rather than separating gcodes, and macros aren't expanded. `send! <text>` does the same.\n";
+static LINT_HELP: &str = "lint: read the given gcode file and report anything that looks like it would cause problems when printed: extruding before a hotend temperature is set, extruding below the minimum extrusion temperature, moves outside the build volume, and commands the connected printer does not report support for. Nothing is sent to the printer.\n";
+static RUN_HELP: &str = "run: run the console commands in the given file one after another, as if each line was typed in, so a setup like connecting, defining macros and starting logs can be reused, e.g. `run setup.txt`. Empty lines and lines starting with `#` are skipped. Running stops at the first line that can't be parsed or fails, reporting its line number; add `--continue` to report the failure and carry on with the next line instead. Scripts can `run` other scripts, up to 8 deep. A script named `init` in the print3rs config directory, e.g. `~/.config/print3rs/init` on Linux, is run every time the console starts, carrying on past lines that fail, to define macros, `connect @<profile>` or start logs straight away.\n";
+static HEIGHTMAP_HELP: &str = "heightmap: measure the height of the bed and save it as a matrix in a csv file named heightmap_<timestamp>, one row per line from front to back. Given a grid size like `heightmap 5`, the printer is homed and the bed is probed with G30 at 5x5 points spread across it. Without a grid size the mesh the printer already has stored is read with G29 T. The lowest and highest point, their range, and how much the bed tilts in X and Y are reported when done. Runs in the background as a task named heightmap, which can be stopped with `stop`.\n";
+static HOME_HELP: &str = "home: home the printer with G28. `home` homes every axis, and naming axes homes only those, e.g. `home xy` or `home z`. Gcodes entered after a `home` are held back until the printer reports homing is done.\n";
+static MOVE_HELP: &str = "move: move the toolhead by the given distances from where it is, like the jog buttons of a graphical frontend. `move x10 y-5 f3000` moves 10mm right and 5mm towards the front at 3000mm/min. Distances can be given for x, y, z and e, and f sets the feedrate. Relative positioning is switched on with G91 for the move, and absolute positioning is restored afterwards if it was in use, keeping relative extrusion set with M83. `jog` does the same.\n";
+static EXTRUDE_HELP: &str = "extrude: `extrude 5` pushes 5mm of filament through the hotend and `retract 5` pulls 5mm back, at 300mm/min unless a feedrate is given like `extrude 5 f120`. Relative extrusion is switched on with M83 for the move if needed. The hotend temperature is checked with M105 first, and nothing is moved if it is below 170°C, to avoid grinding filament against a cold nozzle.\n";
+static SPEED_HELP: &str = "speed: change how fast the printer moves compared to what the file says, e.g. `speed 120%` to go a fifth faster or `speed 100%` to go back to normal. It can be changed while printing. The printer is sent M220; if its firmware doesn't know M220, the feedrates of lines sent by `print` are changed instead, which only works for prints streamed from this console.\n";
+static FLOW_HELP: &str = "flow: change how much filament is pushed out compared to what the file says, e.g. `flow 95%` to under-extrude a little or `flow 100%` to go back to normal. It can be changed while printing. The printer is sent M221; if its firmware doesn't know M221, the extrusion of lines sent by `print` is changed instead, which only works for prints streamed from this console.\n";
+static BABYSTEP_HELP: &str = "babystep: nudge an axis by a small distance while printing, to tune the first layer squish without stopping, e.g. `babystep z 0.02` raises the nozzle by 0.02mm and `babystep z -0.02` lowers it. The axis is z if none is given. Printers that report babystepping support in M115 are sent M290; on other printers the coordinates are shifted with G92 instead, so later moves end up nudged by the same distance.\n";
+static SD_HELP: &str = "sd: manage the printer's SD card. `sd list` lists the files on the card with their size, using M20. `sd print <file>` starts printing a file from the card with M23 and M24, and follows it as a background task named after the file, reporting progress from M27 until it is done; stopping the task does not stop the print. `sd upload <local file> <name>` copies a file to the card with M28 and M29, waiting for each line to be acknowledged, as a background task named after the file on the card; stopping it closes the file with what was written so far. `sd delete <file>` removes a file with M30, and `sd status` shows how far along a print from the card is. Files are named as listed by `sd list`, usually in short 8.3 form like CALI~1.GCO.\n";
+static SETTINGS_HELP: &str = "settings: `settings save <file>` asks the printer for its settings with M503 and saves the report in the given file. `settings diff <file>` asks for the settings again and lists every value that changed compared to the saved file, along with settings that were added or removed. Useful to check what a tuning session actually changed before storing it with M500.\n";
+static LOG_HELP: &str = "log: begin logging the specified pattern from the printer into a csv with the `name` given. This operation runs in the background and is added as a task which can be stopped with `stop`. The pattern given will be used to parse the logs, with values wrapped in `{}` being given a column of whatever is between the `{}`, and pulling a number in its place. Write `{name:str}` to pull text instead, which runs up to whatever follows it in the pattern, or the end of the line, e.g. `log sd SD printing byte {done}/{total} of {file:str}`; text with commas or quotes is quoted in the csv. If your pattern needs to include a literal `{` or `}`, double them up like `{{` or `}}` to have the parser read it as just a `{` or `}` in the output. Put `--format jsonl` before the pattern to write a JSON object per line instead, with the names between the `{}` as keys, e.g. `log temps --format jsonl T:{hotend} /{target}`. Put `--timestamps wall` before the pattern to start every record with a `timestamp` field of milliseconds since the unix epoch, or `--timestamps monotonic` for milliseconds since the log started, which can't jump if the system clock is changed. For captures lasting days, `--max-size 50MB` (B, KB, MB or GB) and `--max-duration 6h` (s, m, h or d) before the pattern start a new timestamped file whenever the current one gets that big or has been written to for that long. Long captures can go into an sqlite database instead by putting `--to sqlite://file.db` after the pattern, e.g. `log temps T:{hotend} B:{bed} --to sqlite://temps.db`, which adds rows to a table named after the log with a column for each name between `{}`, creating the database and table if they aren't there yet. Records can also be sent live to other programs, like Telegraf feeding InfluxDB or Grafana, as a JSON object each: `--to tcp://host:port` writes one per line over a connection that is made again if it breaks, `--to udp://host:port` sends one per datagram, and `--to mqtt://host:port/topic` publishes one per message, with the port optional.\n";
+static REPEAT_HELP: &str = "repeat: repeat the given Gcodes (separated by gcode comment character `;`) in a loop until stopped. Steps starting with `@` are run here instead of being sent: `@delay 5s` waits before the next step (`ms`, `s`, `m` or `h`), and `@waittemp bed 60` waits until the hotend, bed or chamber is within 2 degrees of the temperature, e.g. `repeat soak M140 S60;@waittemp bed 60;@delay 10m`.\n";
+static PAUSE_HELP: &str = "pause: `pause 12` makes the print pause when it starts layer 12, counting the first layer as 1, using the layer change comments written by the slicer. It applies to a print already running or the next one to reach that layer, once. Use it more than once to pause at several layers, and `pause off` to forget them all. `pause now` pauses the running print after the line being sent. Prints also pause by themselves at `;PAUSE` comments, M0, M1 and M600 in the file, which are not sent to the printer. When paused, the filament is pulled back a little, the nozzle is lifted 10mm and moved to X0 Y0, and a message says why. Other commands like `extrude` or `move` can be used while paused, then `resume` moves back to where the print was and carries on.\n";
+static RESUME_HELP: &str = "resume: carry on with a print that paused at a layer chosen with `pause`, or at a pause in the file. The nozzle goes back to where it was before continuing.\n";
 static STOP_HELP: &str = "stop: stops a task running in the background. All background tasks are required to have a name, thus this command can be used to stop them. Tasks can also stop themselves if they fail or can complete, after which running this will do nothing.\n";
-static CONNECT_HELP: &str = "connect: Manually connect to a printer by specifying a protocol and some arguments. Arguments depend on protocol. For serial connection specify its path and optionally its baudrate. On windows this looks like `connect serial COM3 115200`, on linux more like `connect serial /dev/tty/ACM0 250000`. This does not test if the printer is capable of responding to messages, it will only open the port. Specifying no arguments will attempt autoconnection using serial.\n";
+static ETA_HELP: &str = "eta: show the time left for every running print and upload. `eta benchy.gcode` shows it for only the task of that name, or if there is no such task, reads the file and estimates how long printing it would take. The estimate comes from the times the slicer wrote into the file when there are any, otherwise it is worked out from the length and feedrate of every move, which doesn't account for acceleration and so tends to come out short. While printing, the estimate is corrected by how fast the printer has actually been going compared to what was expected, so it gets more accurate as the print goes on.\n";
+static PROGRESS_HELP: &str = "progress: show how far along every running print and upload is, with the percentage done, how long it has been running and an estimate of the time left when the file has slicer estimates, e.g. `benchy.gcode 42.0% (421 of 1000), 12m30s elapsed, 17m05s left`. Give a task name like `progress benchy.gcode` to only show that one. Prints from the SD card count bytes of the file rather than lines.\n";
+static STATS_HELP: &str = "stats: show the traffic with the printer since it was connected, to find out why a link is slow or flaky: commands sent, lines and oks received, bytes each way and per second, lines sent again because the printer asked for them or never acknowledged them, and how long oks take to come back, on average, at most, and counted in steps from under 10ms to over 1s. Lines lost because the console fell behind are shown too.\n";
+static STATUS_HELP: &str = "status: show a summary of the connection type, the firmware name from M115, what the printer is doing, its temperatures from M105 and position from M114, how many lines are queued to be sent, and the running tasks. The printer is given 5 seconds to answer each query, and anything it doesn't answer is shown as `no answer`. While disconnected only the tasks are shown.\n";
+static DEBUG_HELP: &str = "debug: change how much a single background task reports about what it is doing, without changing anything else. Levels are `off`, `info`, `debug` and `trace`, with `debug` used if none is given, e.g. `debug temps trace` to see every line a `temps` log task checks. Messages are prefixed with the task name, and `tasks` lists the level of every task. Every task starts at `off`. Task messages are also emitted as tracing events with the `print3rs::task` target.\n";
+static DRYRUN_HELP: &str = "dryrun: `dryrun on` puts the printer aside and sends everything to a simulated one instead, which shows each line it is sent, like `dry run: G28`, and answers `ok` straight away, so macros, repeats and prints can be checked without moving anything. Nothing reaches the printer until `dryrun off`, which goes back to it. Prints and repeats started during a dry run stop when it ends.\n";
+static SPARKLINES_HELP: &str = "sparklines: `sparklines on` shows the most recent values of every field of every running log task as a small graph in the console prompt, along with the latest value, e.g. `temps hotend ▃▄▅▆▇ 208.2`. Each graph is scaled between the lowest and highest of its last 24 values. `sparklines off` hides them again. Consoles without a prompt may ignore this.\n";
+static HALFDUPLEX_HELP: &str = "halfduplex: `halfduplex on` makes the next connection strictly half-duplex: only one line is ever sent before the printer answers it with `ok`, including gcodes typed in the console, instead of keeping several commands queued up in the printer. Slower, but needed for some TFT screen bridges and old firmwares which corrupt commands sent back to back. `halfduplex off` goes back to the default. Takes effect the next time `connect` is used.\n";
+static KEEPALIVE_HELP: &str = "keepalive: `keepalive 30` makes the next connection send M105 whenever 30 seconds go by without anything sent to or received from the printer. If the printer still hasn't said anything 30 seconds after that, an error is shown, so a USB cable that came loose or a printer that locked up is noticed straight away rather than the next time a command is sent. A message is shown when the printer starts answering again. `keepalive off` turns it off, which is the default. Takes effect the next time `connect` is used.\n";
+static RECONNECT_HELP: &str = "reconnect: `reconnect on` makes the next serial or tcp connection reopen itself whenever it is lost, like when a USB cable is unplugged or the printer is power cycled. After the first try a second later, the wait between attempts doubles up to 30 seconds, and it keeps trying until `connect` or `disconnect` is used. Running tasks are stopped when the connection is lost. `reconnect off` goes back to the default, where a lost connection stays lost. Takes effect the next time `connect` is used.\n";
+static CONNECT_HELP: &str = "connect: Manually connect to a printer by specifying a protocol and some arguments. Arguments depend on protocol. For serial connection specify its path and optionally its baudrate. On windows this looks like `connect serial COM3 115200`, on linux more like `connect serial /dev/tty/ACM0 250000`. This does not test if the printer is capable of responding to messages, it will only open the port. Add `--dialect grbl` for boards running GRBL, e.g. `connect serial /dev/ttyUSB0 115200 --dialect grbl`: lines are sent without line numbers or checksums, `error:` replies are reported as rejected commands, and status reports fill in state and position. To reach a printer through an MQTT broker use `connect mqtt <host> <port?> <in topic?> <out topic?>`, e.g. `connect mqtt broker.local 1883 printer/in printer/out`: gcode is published to the in topic and printer output is read from the out topic, which default to `print3rs/in` and `print3rs/out`. Klipper printers can be reached through Moonraker with `connect moonraker <host>:<port?>`, e.g. `connect moonraker voron.local`, using port 7125 if none is given. A printer attached to OctoPrint is reached with `connect octoprint <host>:<port?> <api key>`, using an API key from OctoPrint's settings. Duet boards running RepRapFirmware are reached over the network with `connect duet <host>:<port?> <password?>`, e.g. `connect duet duet3.local`, giving the password set with M551 if there is one. Prusa printers on PrusaLink are reached with `connect prusalink <host>:<port?> <api key>`; PrusaLink can't run gcode typed in the console, but `print` uploads the file and starts it, temperatures and position are reported every few seconds, and M24, M25 and M524 resume, pause and stop the job. Specifying no arguments, or `auto`, will attempt autoconnection using serial by sending a probe command to each port and waiting for an `ok`, trying 250000, 115200 and 57600 baud on each port and reporting the rate the printer answered on. Autoconnection can be tuned with options after `auto`: `probe=M105` changes the probe command (use `probe=$I` for GRBL or `probe=version` for Smoothie), `accept=*Grbl*` only accepts an answer matching the pattern instead of any `ok`, `timeout=2` waits 2 seconds for an answer, `baud=115200,250000` tries each baud rate in turn, and `include=/dev/ttyUSB*` or `exclude=COM1` limit which ports are tried, and can be repeated. For example `connect auto probe=M105 baud=250000 exclude=/dev/ttyS*`. `connect replay <file>` plays back a session saved with `record`, sending what the printer sent with the timing it was recorded with, see `help record`. `connect virtual` connects to a fake printer running inside print3rs, which answers temperature, position and firmware queries with made up values and now and then asks for a line to be sent again, to try things out without a printer. `connect @<name>` connects with a profile saved with `profile save`, see `help profile`. Add `as:<name>` at the end to connect another printer alongside the selected one, see `help use`.\n";
+static USE_HELP: &str = "use: several printers can be connected at once by naming them when connecting, e.g. `connect serial COM3 as:ender` or `connect @voron as:voron`, which adds the printer without changing where commands go. `use ender` then sends every command after it to ender, with its own tasks, position and state, while the other printers carry on with theirs. `use` on its own lists every printer and how it is connected, marking the selected one with `*`. The printer used before any other is named `default`. To run a single command on another printer without switching, see `help on`.\n";
+static ON_HELP: &str = "on: run one command on the named printer without selecting it, e.g. `on ender send G28`, `on voron home` or `on prusa print benchy.gcode`. `send` is optional before gcodes. Tasks started this way belong to that printer, and `on ender tasks` lists them. Connecting with `on` adds the printer if it isn't there yet, the same as `connect ... as:<name>`.\n";
+static PROFILE_HELP: &str = "profile: `profile save <name>` saves the last connection made with `connect` under a name, and `profile save <name> <proto> <args>` saves the connection given with the same arguments as `connect`, without connecting, e.g. `profile save ender serial /dev/ttyUSB0 115200`. `connect @ender` then connects the same way. `profile list` shows every saved profile and `profile delete <name>` forgets one. Names can't contain spaces. Profiles are kept in the print3rs config directory, e.g. `~/.config/print3rs/profiles.txt` on Linux, including any API keys or passwords they use, and are offered by the graphical connector too.\n";
+static RECORD_HELP: &str = "record: `record ender.rec` saves every byte sent to and received from the printer to the file, with the time it was sent or received, so a problem seen with a real printer can be reproduced later with `connect replay ender.rec`. Applies to serial and tcp connections, from the next time `connect` is used. The file is started again on every connection. `record off` stops recording new connections.\n";
 static DISCONNECT_HELP: &str = "disconnect: disconnect from the currently connected printer. All active tasks will be stopped\n";
-static MACRO_HELP: &str = "create a case-insensitve alias to some set of gcodes, even containing other macros recursively to build up complex sets of builds with a single word. Macro names cannot be a single uppercase letter followed by a number, e.g. H105, to avoid conflict with Gcodes. Names can have any mix of alphanumeric, -, ., and _ characters. Commands in a macro are separated by ';', and macros can be used anywhere Gcodes are passed, including repeat commands and sends.\n";
+static HISTORY_HELP: &str = "history: list the commands entered before, numbered from the oldest, or with `history connect` only those containing `connect`, ignoring case. The last 1000 commands are kept in the print3rs data directory and loaded again each time the console starts, e.g. `~/.local/share/print3rs/history.txt` on Linux.\n";
+static CONFIG_HELP: &str = "config: `config reload` reads the config file again and applies it, after it has been edited. The file is TOML, kept in the print3rs config directory, e.g. `~/.config/print3rs/config.toml` on Linux, and is read when the console starts. Every key is optional: `baud = 250000` is used by `connect serial` when no baud rate is given, `startup_gcode = [\"M155 S2\"]` is sent every time a printer is connected, and `log_dir = \"logs\"` is where `log` writes its files. Macros go in a `[macros]` table like `heat = \"M104 S{temp}; M140 S{bed}\"`, defined in order so each can use the ones before it. A `[limits]` table can set `build_volume = [235, 235, 250]`, used by `lint` and `heightmap`, and `min_extrude_temp = 180`, checked by `lint` and `extrude`. A `[ui]` table can set `sparklines = true`. Macros removed from the file stay defined until restarting.\n";
+static MACRO_HELP: &str = "create a case-insensitve alias to some set of gcodes, even containing other macros recursively to build up complex sets of builds with a single word. Macro names cannot be a single uppercase letter followed by a number, e.g. H105, to avoid conflict with Gcodes. Names can have any mix of alphanumeric, -, ., and _ characters. Commands in a macro are separated by ';', and macros can be used anywhere Gcodes are passed, including repeat commands and sends. Put `{name}` in the steps for values to be given each time the macro is used, e.g. `macro heat M104 S{temp}; M140 S{bed}` is used like `heat temp=210 bed=60`, and every placeholder needs a value. Macros used in another macro can be given some values and leave the rest as placeholders of the new macro, e.g. `macro pla heat bed=60; G28` is used like `pla temp=205`. Macros can also use the `@delay` and `@waittemp` steps described in `help repeat`, e.g. `macro soak M140 S{bed}; @waittemp bed {bed}; @delay 10m`.\n";
 
 /// Gives additional information about commands available or details for a specific command
 pub fn help(command: &str) -> &'static str {
@@ -39,12 +99,42 @@ pub fn help(command: &str) -> &'static str {
 
     match command {
         "print" => PRINT_HELP,
+        "lint" => LINT_HELP,
+        "run" => RUN_HELP,
+        "heightmap" => HEIGHTMAP_HELP,
+        "home" => HOME_HELP,
+        "move" | "jog" => MOVE_HELP,
+        "extrude" | "retract" => EXTRUDE_HELP,
+        "babystep" => BABYSTEP_HELP,
+        "speed" => SPEED_HELP,
+        "flow" => FLOW_HELP,
+        "sd" => SD_HELP,
+        "settings" => SETTINGS_HELP,
         "log" => LOG_HELP,
         "repeat" => REPEAT_HELP,
         "stop" => STOP_HELP,
+        "pause" => PAUSE_HELP,
+        "resume" => RESUME_HELP,
+        "progress" => PROGRESS_HELP,
+        "eta" => ETA_HELP,
+        "status" => STATUS_HELP,
+        "stats" => STATS_HELP,
+        "debug" => DEBUG_HELP,
+        "sparklines" => SPARKLINES_HELP,
+        "dryrun" => DRYRUN_HELP,
         "connect" => CONNECT_HELP,
+        "profile" => PROFILE_HELP,
+        "use" => USE_HELP,
+        "on" => ON_HELP,
+        "raw" | "send!" => RAW_HELP,
+        "halfduplex" => HALFDUPLEX_HELP,
+        "keepalive" => KEEPALIVE_HELP,
+        "reconnect" => RECONNECT_HELP,
+        "record" => RECORD_HELP,
         "disconnect" => DISCONNECT_HELP,
         "macro" => MACRO_HELP,
+        "history" => HISTORY_HELP,
+        "config" => CONFIG_HELP,
         _ => FULL_HELP,
     }
 }
@@ -54,10 +144,42 @@ pub fn help(command: &str) -> &'static str {
 fn test_help() {
     assert_eq!(help(""), FULL_HELP);
     assert_eq!(help("print"), PRINT_HELP);
+    assert_eq!(help("lint"), LINT_HELP);
+    assert_eq!(help("run"), RUN_HELP);
+    assert_eq!(help("raw"), RAW_HELP);
+    assert_eq!(help("heightmap"), HEIGHTMAP_HELP);
+    assert_eq!(help("home"), HOME_HELP);
+    assert_eq!(help("move"), MOVE_HELP);
+    assert_eq!(help("extrude"), EXTRUDE_HELP);
+    assert_eq!(help("retract"), EXTRUDE_HELP);
+    assert_eq!(help("babystep"), BABYSTEP_HELP);
+    assert_eq!(help("speed"), SPEED_HELP);
+    assert_eq!(help("flow"), FLOW_HELP);
+    assert_eq!(help("jog"), MOVE_HELP);
+    assert_eq!(help("sd"), SD_HELP);
+    assert_eq!(help("settings"), SETTINGS_HELP);
     assert_eq!(help("log"), LOG_HELP);
     assert_eq!(help("repeat"), REPEAT_HELP);
     assert_eq!(help("stop"), STOP_HELP);
+    assert_eq!(help("pause"), PAUSE_HELP);
+    assert_eq!(help("resume"), RESUME_HELP);
+    assert_eq!(help("progress"), PROGRESS_HELP);
+    assert_eq!(help("eta"), ETA_HELP);
+    assert_eq!(help("status"), STATUS_HELP);
+    assert_eq!(help("stats"), STATS_HELP);
+    assert_eq!(help("debug"), DEBUG_HELP);
+    assert_eq!(help("sparklines"), SPARKLINES_HELP);
+    assert_eq!(help("dryrun"), DRYRUN_HELP);
+    assert_eq!(help("halfduplex"), HALFDUPLEX_HELP);
+    assert_eq!(help("keepalive"), KEEPALIVE_HELP);
+    assert_eq!(help("reconnect"), RECONNECT_HELP);
+    assert_eq!(help("record"), RECORD_HELP);
     assert_eq!(help("connect"), CONNECT_HELP);
+    assert_eq!(help("profile"), PROFILE_HELP);
+    assert_eq!(help("use"), USE_HELP);
+    assert_eq!(help("on"), ON_HELP);
     assert_eq!(help("disconnect"), DISCONNECT_HELP);
     assert_eq!(help("macro"), MACRO_HELP);
+    assert_eq!(help("history"), HISTORY_HELP);
+    assert_eq!(help("config"), CONFIG_HELP);
 }
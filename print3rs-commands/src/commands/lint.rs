@@ -0,0 +1,162 @@
+use {
+    crate::gcode::{parse_line, MachineState, X, Y, Z},
+    print3rs_core::{Capability, InfoMap},
+    std::fmt::Display,
+};
+
+/// Limits a gcode file is checked against
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintRules {
+    /// Size of the build volume in X, Y and Z, starting from 0
+    pub build_volume: [f32; 3],
+    /// Lowest hotend temperature that extrusion is allowed at
+    pub min_extrude_temp: f32,
+}
+
+impl Default for LintRules {
+    fn default() -> Self {
+        Self {
+            build_volume: [220.0, 220.0, 250.0],
+            min_extrude_temp: 170.0,
+        }
+    }
+}
+
+/// Problem found on a line of a gcode file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    /// Line number, starting from 1
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Commands that only work if the device reports the matching capability
+const CAPABILITY_CODES: &[((char, u32), Capability)] = &[
+    (('G', 2), Capability::Arcs),
+    (('G', 3), Capability::Arcs),
+    (('M', 155), Capability::AutoreportTemp),
+    (('M', 154), Capability::AutoreportPos),
+    (('M', 73), Capability::Progress),
+];
+
+/// Check gcode for problems before printing it.
+///
+/// Unsupported commands are only reported if the device's capabilities are known,
+/// pass an empty `InfoMap` to skip those checks.
+pub fn lint(source: &str, capabilities: &InfoMap, rules: &LintRules) -> Vec<Finding> {
+    let mut findings = vec![];
+    let mut state = MachineState::default();
+    let mut reported_missing_temp = false;
+    let mut reported_cold_temp = None;
+    let mut reported_codes = vec![];
+    for (index, text) in source.lines().enumerate() {
+        let line = parse_line(text);
+        let mut report = |message: String| {
+            findings.push(Finding {
+                line: index + 1,
+                message,
+            })
+        };
+        if let Some(command) = line.command() {
+            if !capabilities.is_empty() && !reported_codes.contains(&command) {
+                for (code, capability) in CAPABILITY_CODES {
+                    if *code == command && !capabilities.has_capability(*capability) {
+                        report(format!(
+                            "{}{} is used but the printer does not report {}",
+                            command.0,
+                            command.1,
+                            capability.as_ref()
+                        ));
+                        reported_codes.push(command);
+                    }
+                }
+            }
+        }
+        let Some(moved) = state.apply(&line) else {
+            continue;
+        };
+        if moved.is_extruding() {
+            match state.hotend_target() {
+                None if !reported_missing_temp => {
+                    report("extrusion before any hotend temperature is set (M104/M109)".into());
+                    reported_missing_temp = true;
+                }
+                Some(temp) if temp < rules.min_extrude_temp && reported_cold_temp != Some(temp) => {
+                    report(format!(
+                        "cold extrusion, hotend target {temp} is below {}",
+                        rules.min_extrude_temp
+                    ));
+                    reported_cold_temp = Some(temp);
+                }
+                _ => {}
+            }
+        }
+        for (axis, name) in [(X, 'X'), (Y, 'Y'), (Z, 'Z')] {
+            let position = moved.to[axis];
+            if position != moved.from[axis] && !(0.0..=rules.build_volume[axis]).contains(&position)
+            {
+                report(format!(
+                    "move to {name}{position} is outside the build volume (0 to {})",
+                    rules.build_volume[axis]
+                ));
+            }
+        }
+    }
+    findings
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn messages(source: &str, capabilities: &InfoMap) -> Vec<Finding> {
+        lint(source, capabilities, &LintRules::default())
+    }
+
+    #[test]
+    fn clean_file() {
+        let source = "M104 S210\nG28\nG1 X10 Y10 Z0.2 F3000\nG1 X20 E1\n";
+        assert!(messages(source, &InfoMap::default()).is_empty());
+    }
+
+    #[test]
+    fn missing_temperature() {
+        let findings = messages("G28\nG1 X10 E1\nG1 X20 E2\n", &InfoMap::default());
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].line, 2);
+    }
+
+    #[test]
+    fn cold_extrusion() {
+        let findings = messages("M104 S150\nG1 X10 E1\nG1 X20 E2\n", &InfoMap::default());
+        assert_eq!(findings.len(), 1);
+        assert!(findings[0].message.starts_with("cold extrusion"));
+    }
+
+    #[test]
+    fn outside_bed() {
+        let findings = messages("G1 X300 Y10\n", &InfoMap::default());
+        assert_eq!(findings.len(), 1);
+        assert!(findings[0].to_string().starts_with("line 1: move to X300"));
+    }
+
+    #[test]
+    fn unsupported_codes() {
+        let source = "G2 X10 Y10 I5 J5\nG3 X0 Y0 I-5 J-5\n";
+        assert!(messages(source, &InfoMap::default()).is_empty());
+
+        let mut capabilities = InfoMap::default();
+        capabilities.add_capability(Capability::AutoreportTemp);
+        let findings = messages(source, &capabilities);
+        assert_eq!(findings.len(), 2);
+
+        capabilities.add_capability(Capability::Arcs);
+        assert!(messages(source, &capabilities).is_empty());
+    }
+}
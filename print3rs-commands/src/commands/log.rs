@@ -1,21 +1,397 @@
 use winnow::{
-    ascii::{float, space1},
-    combinator::{alt, delimited, dispatch, empty, fail, preceded, repeat, rest},
+    ascii::{alpha1, dec_uint, float, space1, Caseless},
+    combinator::{
+        alt, cut_err, delimited, dispatch, empty, eof, fail, opt, preceded, repeat, rest,
+        terminated,
+    },
     prelude::*,
     stream::AsChar,
-    token::{take, take_till, take_until},
+    token::{take, take_till, take_until, take_while},
 };
 use {
-    crate::commands::{identifier, Command},
+    crate::commands::{identifier, name, Command},
     core::borrow::Borrow,
-    winnow::ascii::space0,
+    std::{
+        collections::VecDeque,
+        time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    },
+    winnow::error::{StrContext, StrContextValue},
 };
 
+/// A value captured by a pattern, a number for `{name}` or text for `{name:str}`
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogValue {
+    Number(f32),
+    Text(String),
+    /// Milliseconds from a `--timestamps` clock
+    Millis(u64),
+}
+
+impl std::fmt::Display for LogValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogValue::Number(number) => write!(f, "{number}"),
+            LogValue::Text(text) => f.write_str(text),
+            LogValue::Millis(millis) => write!(f, "{millis}"),
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl rusqlite::ToSql for LogValue {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        match self {
+            LogValue::Number(number) => number.to_sql(),
+            LogValue::Text(text) => text.to_sql(),
+            LogValue::Millis(millis) => Ok(i64::try_from(*millis).unwrap_or(i64::MAX).into()),
+        }
+    }
+}
+
+/// Quote a CSV field if it has anything that would break up the row
+fn csv_field(field: &str) -> std::borrow::Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\"")).into()
+    } else {
+        field.into()
+    }
+}
+
+/// How a log writes each parsed line into its file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// A header of the field names, then a row of values per line
+    #[default]
+    Csv,
+    /// A JSON object per line, keyed by the field names
+    Jsonl,
+}
+
+impl LogFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            LogFormat::Csv => "csv",
+            LogFormat::Jsonl => "jsonl",
+        }
+    }
+
+    /// What the file starts with, before any records
+    pub fn header(self, segments: &[Segment<impl AsRef<str>>]) -> String {
+        match self {
+            LogFormat::Csv => get_headers(segments),
+            LogFormat::Jsonl => String::new(),
+        }
+    }
+
+    /// One parsed line, ending with a newline
+    pub fn record(self, fields: &[String], values: &[LogValue]) -> String {
+        let mut record = String::new();
+        match self {
+            LogFormat::Csv => {
+                for value in values {
+                    record.push_str(&csv_field(&value.to_string()));
+                    record.push(',');
+                }
+                record.pop(); // remove trailing ','
+            }
+            LogFormat::Jsonl => {
+                record.push('{');
+                for (index, (field, value)) in fields.iter().zip(values).enumerate() {
+                    if index > 0 {
+                        record.push(',');
+                    }
+                    record.push_str(&serde_json::Value::from(field.as_str()).to_string());
+                    record.push(':');
+                    match value {
+                        // nan and inf can be parsed but aren't JSON numbers
+                        LogValue::Number(number) if !number.is_finite() => record.push_str("null"),
+                        LogValue::Number(number) => record.push_str(&number.to_string()),
+                        LogValue::Text(text) => {
+                            record.push_str(&serde_json::Value::from(text.as_str()).to_string())
+                        }
+                        LogValue::Millis(millis) => record.push_str(&millis.to_string()),
+                    }
+                }
+                record.push('}');
+            }
+        }
+        record.push('\n');
+        record
+    }
+}
+
+/// Where a log puts what it parses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogSink<S> {
+    /// New files named after the task
+    File(LogFormat, Rotation),
+    /// A table named after the task in an sqlite database, `--to sqlite://file.db`
+    #[cfg(feature = "sqlite")]
+    Sqlite(S),
+    /// A JSON object per line streamed to a server, `--to tcp://host:port`
+    Tcp { hostname: S, port: u16 },
+    /// A JSON object per datagram, `--to udp://host:port`
+    Udp { hostname: S, port: u16 },
+    /// A JSON object per message published to a topic, `--to mqtt://host:port/topic`
+    #[cfg(feature = "mqtt")]
+    Mqtt {
+        hostname: S,
+        port: Option<u16>,
+        topic: S,
+    },
+}
+
+impl LogSink<String> {
+    pub fn to_borrowed<Borrowed: ?Sized>(&self) -> LogSink<&Borrowed>
+    where
+        String: Borrow<Borrowed>,
+    {
+        match self {
+            LogSink::File(format, rotation) => LogSink::File(*format, *rotation),
+            #[cfg(feature = "sqlite")]
+            LogSink::Sqlite(path) => LogSink::Sqlite(path.borrow()),
+            LogSink::Tcp { hostname, port } => LogSink::Tcp {
+                hostname: hostname.borrow(),
+                port: *port,
+            },
+            LogSink::Udp { hostname, port } => LogSink::Udp {
+                hostname: hostname.borrow(),
+                port: *port,
+            },
+            #[cfg(feature = "mqtt")]
+            LogSink::Mqtt {
+                hostname,
+                port,
+                topic,
+            } => LogSink::Mqtt {
+                hostname: hostname.borrow(),
+                port: *port,
+                topic: topic.borrow(),
+            },
+        }
+    }
+}
+
+impl<'a> LogSink<&'a str> {
+    pub fn into_owned(self) -> LogSink<String> {
+        match self {
+            LogSink::File(format, rotation) => LogSink::File(format, rotation),
+            #[cfg(feature = "sqlite")]
+            LogSink::Sqlite(path) => LogSink::Sqlite(path.to_owned()),
+            LogSink::Tcp { hostname, port } => LogSink::Tcp {
+                hostname: hostname.to_owned(),
+                port,
+            },
+            LogSink::Udp { hostname, port } => LogSink::Udp {
+                hostname: hostname.to_owned(),
+                port,
+            },
+            #[cfg(feature = "mqtt")]
+            LogSink::Mqtt {
+                hostname,
+                port,
+                topic,
+            } => LogSink::Mqtt {
+                hostname: hostname.to_owned(),
+                port,
+                topic: topic.to_owned(),
+            },
+        }
+    }
+}
+
+impl<S: std::fmt::Display> std::fmt::Display for LogSink<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogSink::File(format, _) => write!(f, "a {} file", format.extension()),
+            #[cfg(feature = "sqlite")]
+            LogSink::Sqlite(path) => write!(f, "sqlite://{path}"),
+            LogSink::Tcp { hostname, port } => write!(f, "tcp://{hostname}:{port}"),
+            LogSink::Udp { hostname, port } => write!(f, "udp://{hostname}:{port}"),
+            #[cfg(feature = "mqtt")]
+            LogSink::Mqtt {
+                hostname,
+                port: Some(port),
+                topic,
+            } => write!(f, "mqtt://{hostname}:{port}/{topic}"),
+            #[cfg(feature = "mqtt")]
+            LogSink::Mqtt {
+                hostname,
+                port: None,
+                topic,
+            } => write!(f, "mqtt://{hostname}/{topic}"),
+        }
+    }
+}
+
+/// Quote a name so it can be used as a table or column name
+#[cfg(feature = "sqlite")]
+fn sql_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// A table of logged values in an sqlite database, with a `REAL` column per number and `TEXT` per text
+#[cfg(feature = "sqlite")]
+#[derive(Debug)]
+pub struct SqliteLog {
+    connection: rusqlite::Connection,
+    insert: String,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteLog {
+    /// Open or create the database, and the table if it isn't there already
+    pub fn open(
+        path: &str,
+        table: &str,
+        segments: &[Segment<impl AsRef<str>>],
+    ) -> rusqlite::Result<Self> {
+        let connection = rusqlite::Connection::open(path)?;
+        let table = sql_identifier(table);
+        let (columns, definitions): (Vec<_>, Vec<_>) = segments
+            .iter()
+            .filter_map(|segment| match segment {
+                Segment::Value(field) => Some((sql_identifier(field.as_ref()), "REAL")),
+                Segment::Text(field) => Some((sql_identifier(field.as_ref()), "TEXT")),
+                Segment::Timestamp(_) => Some((sql_identifier(TIMESTAMP_FIELD), "INTEGER")),
+                _ => None,
+            })
+            .map(|(column, kind)| (column.clone(), format!("{column} {kind}")))
+            .unzip();
+        connection.execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS {table} ({})",
+                definitions.join(", ")
+            ),
+            (),
+        )?;
+        let placeholders: Vec<_> = (1..=columns.len()).map(|i| format!("?{i}")).collect();
+        let insert = format!(
+            "INSERT INTO {table} ({}) VALUES ({})",
+            columns.join(", "),
+            placeholders.join(", ")
+        );
+        Ok(Self { connection, insert })
+    }
+
+    /// Add a row of values, in the same order as the fields
+    pub fn insert(&self, values: &[LogValue]) -> rusqlite::Result<()> {
+        self.connection
+            .prepare_cached(&self.insert)?
+            .execute(rusqlite::params_from_iter(values))?;
+        Ok(())
+    }
+}
+
+/// When a log file is finished and a new one started, so long captures don't make one huge file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rotation {
+    /// `--max-size 50MB`, bytes a file can grow to
+    pub max_size: Option<u64>,
+    /// `--max-duration 6h`, how long a file is written to
+    pub max_duration: Option<Duration>,
+}
+
+impl Rotation {
+    /// Whether a file this big and this old should be swapped for a new one
+    pub fn due(&self, size: u64, age: Duration) -> bool {
+        self.max_size.is_some_and(|max| size >= max)
+            || self.max_duration.is_some_and(|max| age >= max)
+    }
+}
+
+/// What the `timestamp` field of a log counts, chosen with `--timestamps wall` or `--timestamps monotonic`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clock {
+    /// Milliseconds since the unix epoch, to line up with other records of the same time
+    Wall,
+    /// Milliseconds since the log started, which the system clock changing can't disturb
+    Monotonic,
+}
+
+/// An option given before the pattern, all but `--timestamps` only for logs written to files
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LogOption {
+    Format(LogFormat),
+    MaxSize(u64),
+    MaxDuration(Duration),
+    Timestamps(Clock),
+}
+
+fn parse_size(input: &mut &str) -> PResult<u64> {
+    (
+        dec_uint::<_, u64, _>,
+        alt((
+            Caseless("GB").value(1_000_000_000),
+            Caseless("MB").value(1_000_000),
+            Caseless("KB").value(1_000),
+            opt(Caseless("B")).value(1),
+        )),
+    )
+        .map(|(amount, unit)| amount * unit)
+        .parse_next(input)
+}
+
+fn parse_duration(input: &mut &str) -> PResult<Duration> {
+    (
+        dec_uint::<_, u64, _>,
+        alt((
+            Caseless("s").value(1),
+            Caseless("m").value(60),
+            Caseless("h").value(3600),
+            Caseless("d").value(86400),
+        )),
+    )
+        .map(|(amount, unit)| Duration::from_secs(amount * unit))
+        .parse_next(input)
+}
+
+fn parse_log_option(input: &mut &str) -> PResult<LogOption> {
+    dispatch! {terminated(take_while(1.., ('-', AsChar::is_alpha)), space1);
+        "--format" => cut_err(alt((
+            "csv".value(LogFormat::Csv),
+            "jsonl".value(LogFormat::Jsonl),
+        )))
+        .context(StrContext::Label("log format"))
+        .context(StrContext::Expected(StrContextValue::Description(
+            "csv or jsonl",
+        )))
+        .map(LogOption::Format),
+        "--max-size" => cut_err(parse_size)
+        .context(StrContext::Label("size"))
+        .context(StrContext::Expected(StrContextValue::Description(
+            "a size like 50MB",
+        )))
+        .map(LogOption::MaxSize),
+        "--max-duration" => cut_err(parse_duration)
+        .context(StrContext::Label("duration"))
+        .context(StrContext::Expected(StrContextValue::Description(
+            "a duration like 6h",
+        )))
+        .map(LogOption::MaxDuration),
+        "--timestamps" => cut_err(alt((
+            "wall".value(Clock::Wall),
+            "monotonic".value(Clock::Monotonic),
+        )))
+        .context(StrContext::Label("clock"))
+        .context(StrContext::Expected(StrContextValue::Description(
+            "wall or monotonic",
+        )))
+        .map(LogOption::Timestamps),
+        _ => fail,
+    }
+    .parse_next(input)
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Segment<S> {
     Tag(S),
     Escaped(char),
     Value(S),
+    /// `{name:str}`, text up to whatever comes next in the pattern
+    Text(S),
+    /// A `timestamp` field read from the clock instead of the line, put first by `--timestamps`
+    Timestamp(Clock),
 }
 
 impl Segment<String> {
@@ -27,6 +403,8 @@ impl Segment<String> {
             Segment::Tag(s) => Segment::Tag(s.borrow()),
             Segment::Escaped(c) => Segment::Escaped(*c),
             Segment::Value(s) => Segment::Value(s.borrow()),
+            Segment::Text(s) => Segment::Text(s.borrow()),
+            Segment::Timestamp(clock) => Segment::Timestamp(*clock),
         }
     }
 }
@@ -37,6 +415,8 @@ impl<'a> Segment<&'a str> {
             Segment::Tag(s) => Segment::Tag(s.to_owned()),
             Segment::Escaped(c) => Segment::Escaped(c),
             Segment::Value(s) => Segment::Value(s.to_owned()),
+            Segment::Text(s) => Segment::Text(s.to_owned()),
+            Segment::Timestamp(clock) => Segment::Timestamp(clock),
         }
     }
 }
@@ -55,9 +435,11 @@ fn parse_escape<'a>(input: &mut &'a str) -> PResult<Segment<&'a str>> {
 }
 
 fn parse_value<'a>(input: &mut &'a str) -> PResult<Segment<&'a str>> {
-    Ok(Segment::Value(
-        delimited("{", identifier, "}").parse_next(input)?,
-    ))
+    let (name, text) = delimited("{", (identifier, opt(":str")), "}").parse_next(input)?;
+    Ok(match text {
+        Some(_) => Segment::Text(name),
+        None => Segment::Value(name),
+    })
 }
 
 fn parse_segment<'a>(input: &mut &'a str) -> PResult<Segment<&'a str>> {
@@ -68,26 +450,140 @@ pub fn parse_segments<'a>(input: &mut &'a str) -> PResult<Vec<Segment<&'a str>>>
     repeat(1.., parse_segment).parse_next(input)
 }
 
+fn parse_hostname<'a>(input: &mut &'a str) -> PResult<&'a str> {
+    take_till(1.., [' ', '\t', ':', '/']).parse_next(input)
+}
+
+#[cfg(feature = "sqlite")]
+fn parse_sqlite_destination<'a>(input: &mut &'a str) -> PResult<LogSink<&'a str>> {
+    take_till(1.., [' ', '\t'])
+        .map(LogSink::Sqlite)
+        .parse_next(input)
+}
+
+#[cfg(feature = "mqtt")]
+fn parse_mqtt_destination<'a>(input: &mut &'a str) -> PResult<LogSink<&'a str>> {
+    (
+        parse_hostname,
+        opt(preceded(':', dec_uint)),
+        preceded('/', take_till(1.., [' ', '\t'])),
+    )
+        .map(|(hostname, port, topic)| LogSink::Mqtt {
+            hostname,
+            port,
+            topic,
+        })
+        .parse_next(input)
+}
+
+/// A destination whose cargo feature was left out of this build
+#[cfg(not(all(feature = "sqlite", feature = "mqtt")))]
+fn parse_unsupported_destination<'a>(input: &mut &'a str) -> PResult<LogSink<&'a str>> {
+    fail.context(StrContext::Label("destination left out of this build"))
+        .parse_next(input)
+}
+
+#[cfg(not(feature = "sqlite"))]
+use parse_unsupported_destination as parse_sqlite_destination;
+
+#[cfg(not(feature = "mqtt"))]
+use parse_unsupported_destination as parse_mqtt_destination;
+
+fn parse_log_destination<'a>(input: &mut &'a str) -> PResult<LogSink<&'a str>> {
+    let destination = dispatch! {terminated(alpha1, "://");
+        "sqlite" => parse_sqlite_destination,
+        "tcp" => (parse_hostname, preceded(':', dec_uint))
+            .map(|(hostname, port)| LogSink::Tcp { hostname, port }),
+        "udp" => (parse_hostname, preceded(':', dec_uint))
+            .map(|(hostname, port)| LogSink::Udp { hostname, port }),
+        "mqtt" => parse_mqtt_destination,
+        _ => fail,
+    };
+    preceded(
+        ("--to", space1),
+        cut_err(destination)
+            .context(StrContext::Label("log destination"))
+            .context(StrContext::Expected(StrContextValue::Description(
+                "a destination like `sqlite://temps.db`, `tcp://host:port`, `udp://host:port` or `mqtt://host/topic`",
+            ))),
+    )
+    .parse_next(input)
+}
+
 pub fn parse_logger<'a>(input: &mut &'a str) -> PResult<Command<&'a str>> {
     (
-        preceded(space0, identifier),
-        preceded(space1, parse_segments),
+        name,
+        repeat(0.., preceded(space1, parse_log_option)),
+        preceded(
+            space1,
+            alt((take_until(1.., " --to "), rest)).and_then(terminated(parse_segments, eof)),
+        )
+        .context(StrContext::Label("pattern"))
+        .context(StrContext::Expected(StrContextValue::Description(
+            "a pattern like `T:{temp}`",
+        ))),
+        opt(preceded(space1, parse_log_destination)),
     )
-        .map(|(name, segments)| Command::Log(name, segments))
+        .verify(|(_, options, _, destination): &(_, Vec<LogOption>, _, _)| {
+            let file_options = options
+                .iter()
+                .any(|option| !matches!(option, LogOption::Timestamps(_)));
+            !file_options || destination.is_none()
+        })
+        .context(StrContext::Label("log destination"))
+        .context(StrContext::Expected(StrContextValue::Description(
+            "either file options like `--format` or `--to`, not both",
+        )))
+        .map(|(name, options, mut segments, destination)| {
+            let mut format = LogFormat::default();
+            let mut rotation = Rotation::default();
+            for option in options {
+                match option {
+                    LogOption::Format(chosen) => format = chosen,
+                    LogOption::MaxSize(size) => rotation.max_size = Some(size),
+                    LogOption::MaxDuration(age) => rotation.max_duration = Some(age),
+                    LogOption::Timestamps(clock) => segments.insert(0, Segment::Timestamp(clock)),
+                }
+            }
+            let sink = destination.unwrap_or(LogSink::File(format, rotation));
+            Command::Log(name, sink, segments)
+        })
         .parse_next(input)
 }
 
-pub fn make_parser(segments: Vec<Segment<&str>>) -> impl FnMut(&mut &[u8]) -> PResult<Vec<f32>> {
+/// Captures text for a `{name:str}` segment, up to where the segment after it would start,
+/// or the end of the line if it is last
+fn parse_text(next: Option<&Segment<String>>, input: &mut &[u8]) -> PResult<String> {
+    let text = match next {
+        Some(Segment::Tag(tag)) => take_until(0.., tag.as_bytes()).parse_next(input)?,
+        Some(Segment::Escaped(c)) => take_till(0.., |i| (*c as u8) == i).parse_next(input)?,
+        Some(Segment::Value(_)) => {
+            take_till(0.., |i: u8| i.is_dec_digit() || [b'.', b'-'].contains(&i))
+                .parse_next(input)?
+        }
+        Some(Segment::Text(_)) => take_till(0.., AsChar::is_space).parse_next(input)?,
+        Some(Segment::Timestamp(_)) | None => take_till(0.., [b'\r', b'\n']).parse_next(input)?,
+    };
+    Ok(String::from_utf8_lossy(text).into_owned())
+}
+
+pub fn make_parser(
+    segments: Vec<Segment<&str>>,
+) -> impl FnMut(&mut &[u8]) -> PResult<Vec<LogValue>> {
     let mut owned_segments = Vec::new();
     for segment in segments {
         owned_segments.push(segment.into_owned());
     }
     let segments = owned_segments;
-    move |input: &mut &[u8]| -> PResult<Vec<f32>> {
+    let started = Instant::now();
+    move |input: &mut &[u8]| -> PResult<Vec<LogValue>> {
         let mut values = vec![];
 
         // skips up to pattern start
-        if let Some(first) = segments.first() {
+        let first = segments
+            .iter()
+            .find(|segment| !matches!(segment, Segment::Timestamp(_)));
+        if let Some(first) = first {
             match first {
                 Segment::Tag(tag) => {
                     take_until(0.., tag.as_bytes()).void().parse_next(input)?;
@@ -102,9 +598,12 @@ pub fn make_parser(segments: Vec<Segment<&str>>) -> impl FnMut(&mut &[u8]) -> PR
                         .void()
                         .parse_next(input)?;
                 }
+                // text starts with the line
+                Segment::Text(_) => {}
+                Segment::Timestamp(_) => unreachable!("skipped above"),
             };
         }
-        for segment in segments.iter() {
+        for (index, segment) in segments.iter().enumerate() {
             match segment {
                 Segment::Tag(ref s) => {
                     s.as_bytes().parse_next(input)?;
@@ -113,7 +612,19 @@ pub fn make_parser(segments: Vec<Segment<&str>>) -> impl FnMut(&mut &[u8]) -> PR
                     c.parse_next(input)?;
                 }
                 Segment::Value(_) => {
-                    values.push(float.parse_next(input)?);
+                    values.push(LogValue::Number(float.parse_next(input)?));
+                }
+                Segment::Text(_) => {
+                    values.push(LogValue::Text(parse_text(segments.get(index + 1), input)?));
+                }
+                Segment::Timestamp(Clock::Wall) => {
+                    let now = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default();
+                    values.push(LogValue::Millis(now.as_millis() as u64));
+                }
+                Segment::Timestamp(Clock::Monotonic) => {
+                    values.push(LogValue::Millis(started.elapsed().as_millis() as u64));
                 }
             };
         }
@@ -123,13 +634,68 @@ pub fn make_parser(segments: Vec<Segment<&str>>) -> impl FnMut(&mut &[u8]) -> PR
     }
 }
 
+/// Name of the field `--timestamps` adds
+pub const TIMESTAMP_FIELD: &str = "timestamp";
+
+/// Names of every value captured by a pattern, in order
+pub fn get_fields(segments: &[Segment<impl AsRef<str>>]) -> Vec<String> {
+    segments
+        .iter()
+        .filter_map(|segment| match segment {
+            Segment::Value(label) | Segment::Text(label) => Some(label.as_ref().to_owned()),
+            Segment::Timestamp(_) => Some(TIMESTAMP_FIELD.to_owned()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Number of values kept per field by `RecentValues`
+pub const RECENT_VALUES: usize = 24;
+
+/// The latest values logged for each field of a pattern, oldest first
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecentValues {
+    fields: Vec<String>,
+    values: Vec<VecDeque<f32>>,
+}
+
+impl RecentValues {
+    pub fn new(fields: Vec<String>) -> Self {
+        let values = vec![VecDeque::with_capacity(RECENT_VALUES); fields.len()];
+        Self { fields, values }
+    }
+
+    /// Add one parsed record, forgetting the oldest values once full; text isn't kept
+    pub fn push(&mut self, record: &[LogValue]) {
+        for (values, value) in self.values.iter_mut().zip(record) {
+            let LogValue::Number(value) = value else {
+                continue;
+            };
+            if values.len() == RECENT_VALUES {
+                values.pop_front();
+            }
+            values.push_back(*value);
+        }
+    }
+
+    /// Each field name with its recent values
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &VecDeque<f32>)> {
+        self.fields
+            .iter()
+            .map(String::as_str)
+            .zip(self.values.iter())
+    }
+}
+
 pub fn get_headers(segments: &[Segment<impl AsRef<str>>]) -> String {
     let mut s = String::new();
     for segment in segments {
-        if let Segment::Value(label) = segment {
-            s.push_str(label.as_ref());
-            s.push(',');
+        match segment {
+            Segment::Value(label) | Segment::Text(label) => s.push_str(&csv_field(label.as_ref())),
+            Segment::Timestamp(_) => s.push_str(TIMESTAMP_FIELD),
+            _ => continue,
         }
+        s.push(',');
     }
     // strip trailing
     if s.ends_with(',') {
@@ -142,8 +708,23 @@ pub fn get_headers(segments: &[Segment<impl AsRef<str>>]) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use LogValue::*;
     use Segment::*;
 
+    #[test]
+    fn recent_values() {
+        let fields = get_fields(&[Tag("T:"), Value("hotend"), Tag(" B:"), Value("bed")]);
+        assert_eq!(fields, ["hotend", "bed"]);
+        let mut recent = RecentValues::new(fields);
+        for i in 0..=RECENT_VALUES {
+            recent.push(&[Number(i as f32), Number(60.0)]);
+        }
+        let (field, values) = recent.iter().next().unwrap();
+        assert_eq!(field, "hotend");
+        assert_eq!(values.len(), RECENT_VALUES);
+        assert_eq!(values.front(), Some(&1.0));
+    }
+
     #[test]
     fn test_parse_segments() {
         let input = " this {is}so12.?me{segm_2-ents}";
@@ -172,7 +753,7 @@ mod tests {
         let final_out = parser
             .parse(b"millis: 1234.5,pos:-4.0,current:100")
             .unwrap();
-        assert_eq!(final_out, vec![1234.5, -4.0, 100.0]);
+        assert_eq!(final_out, [Number(1234.5), Number(-4.0), Number(100.0)]);
     }
 
     #[test]
@@ -199,7 +780,7 @@ mod tests {
         let final_out = parser
             .parse(b"a bunch of stuff{}{}{{}}.028millis: 1234.5,pos:-4.0,current:100,and a bunch of other stuff{}{}{{}}.028")
             .unwrap();
-        assert_eq!(final_out, vec![1234.5, -4.0, 100.0]);
+        assert_eq!(final_out, [Number(1234.5), Number(-4.0), Number(100.0)]);
     }
 
     #[test]
@@ -208,6 +789,231 @@ mod tests {
         let _cmd = parse_logger.parse(log_cmd).unwrap();
     }
 
+    #[test]
+    fn format_flag() {
+        let command = parse_logger.parse("temps --format jsonl T:{temp}").unwrap();
+        assert_eq!(
+            command,
+            Command::Log(
+                "temps",
+                LogSink::File(LogFormat::Jsonl, Rotation::default()),
+                vec![Tag("T:"), Value("temp")]
+            )
+        );
+        let command = parse_logger.parse("temps T:{temp}").unwrap();
+        assert!(matches!(
+            command,
+            Command::Log(_, LogSink::File(LogFormat::Csv, _), _)
+        ));
+        assert!(parse_logger.parse("temps --format xml T:{temp}").is_err());
+    }
+
+    #[test]
+    fn rotation_options() {
+        let command = parse_logger
+            .parse("temps --max-size 50MB --format jsonl --max-duration 6h T:{temp}")
+            .unwrap();
+        let rotation = Rotation {
+            max_size: Some(50_000_000),
+            max_duration: Some(Duration::from_secs(6 * 3600)),
+        };
+        assert_eq!(
+            command,
+            Command::Log(
+                "temps",
+                LogSink::File(LogFormat::Jsonl, rotation),
+                vec![Tag("T:"), Value("temp")]
+            )
+        );
+        assert!(parse_logger
+            .parse("temps --max-size lots T:{temp}")
+            .is_err());
+        assert!(parse_logger
+            .parse("temps --max-size 1GB T:{temp} --to sqlite://temps.db")
+            .is_err());
+
+        assert!(!rotation.due(49_999_999, Duration::from_secs(60)));
+        assert!(rotation.due(50_000_000, Duration::from_secs(60)));
+        assert!(rotation.due(10, Duration::from_secs(6 * 3600)));
+        assert!(!Rotation::default().due(u64::MAX, Duration::MAX));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_destination() {
+        let command = parse_logger
+            .parse("temps T:{hotend} B:{bed} --to sqlite://temps.db")
+            .unwrap();
+        assert_eq!(
+            command,
+            Command::Log(
+                "temps",
+                LogSink::Sqlite("temps.db"),
+                vec![Tag("T:"), Value("hotend"), Tag(" B:"), Value("bed")]
+            )
+        );
+        assert!(parse_logger
+            .parse("temps --format jsonl T:{hotend} --to sqlite://temps.db")
+            .is_err());
+        assert!(parse_logger
+            .parse("temps T:{hotend} --to temps.db")
+            .is_err());
+    }
+
+    #[test]
+    fn timestamps() {
+        let command = parse_logger
+            .parse("temps --timestamps monotonic T:{temp} --to udp://10.0.0.2:8089")
+            .unwrap();
+        let Command::Log(_, _, segments) = command else {
+            unreachable!()
+        };
+        assert_eq!(
+            segments,
+            [Timestamp(Clock::Monotonic), Tag("T:"), Value("temp")]
+        );
+        assert_eq!(get_headers(&segments), "timestamp,temp\n");
+        let mut parser = make_parser(segments);
+        let values = parser.parse(b"ok T:210.5 /210.0").unwrap();
+        assert!(
+            matches!(values[..], [Millis(millis), Number(temp)] if millis < 1000 && temp == 210.5)
+        );
+
+        let mut parser = make_parser(vec![Timestamp(Clock::Wall), Text("line")]);
+        let values = parser.parse(b"echo:busy").unwrap();
+        assert!(matches!(values[0], Millis(millis) if millis > 1_600_000_000_000));
+        assert_eq!(values[1], Text("echo:busy".into()));
+        assert!(parse_logger
+            .parse("temps --timestamps soon T:{temp}")
+            .is_err());
+    }
+
+    #[test]
+    fn network_destinations() {
+        let destination = |input| match parse_logger.parse(input).unwrap() {
+            Command::Log(_, sink, _) => sink,
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            destination("temps T:{temp} --to tcp://influx.local:8094"),
+            LogSink::Tcp {
+                hostname: "influx.local",
+                port: 8094
+            }
+        );
+        assert_eq!(
+            destination("temps T:{temp} --to udp://10.0.0.2:8089"),
+            LogSink::Udp {
+                hostname: "10.0.0.2",
+                port: 8089
+            }
+        );
+        assert!(parse_logger
+            .parse("temps T:{temp} --to tcp://influx.local")
+            .is_err());
+        assert!(parse_logger
+            .parse("temps T:{temp} --to http://influx.local")
+            .is_err());
+    }
+
+    #[cfg(feature = "mqtt")]
+    #[test]
+    fn mqtt_destination() {
+        let destination = |input| match parse_logger.parse(input).unwrap() {
+            Command::Log(_, sink, _) => sink,
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            destination("temps T:{temp} --to mqtt://broker/printers/temps"),
+            LogSink::Mqtt {
+                hostname: "broker",
+                port: None,
+                topic: "printers/temps"
+            }
+        );
+        assert_eq!(
+            destination("temps T:{temp} --to mqtt://broker:1884/temps"),
+            LogSink::Mqtt {
+                hostname: "broker",
+                port: Some(1884),
+                topic: "temps"
+            }
+        );
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_rows() {
+        let segments = [Value("hotend"), Value("bed \"top\""), Text("state")];
+        let log = SqliteLog::open(":memory:", "temps", &segments).unwrap();
+        log.insert(&[Number(210.5), Number(60.0), Text("heating".into())])
+            .unwrap();
+        log.insert(&[Number(211.0), Number(60.5), Text("printing".into())])
+            .unwrap();
+        let (count, hotend): (i64, f64) = log
+            .connection
+            .query_row(
+                "SELECT COUNT(*), MAX(\"hotend\") FROM \"temps\"",
+                (),
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((count, hotend), (2, 211.0));
+    }
+
+    #[test]
+    fn jsonl_records() {
+        let fields = get_fields(&[Tag("T:"), Value("hotend"), Tag(" B:"), Value("bed")]);
+        assert_eq!(LogFormat::Jsonl.header(&[Value("hotend")]), "");
+        assert_eq!(
+            LogFormat::Jsonl.record(&fields, &[Number(210.5), Number(60.0)]),
+            "{\"hotend\":210.5,\"bed\":60}\n"
+        );
+        assert_eq!(
+            LogFormat::Csv.record(&fields, &[Number(210.5), Number(60.0)]),
+            "210.5,60\n"
+        );
+    }
+
+    #[test]
+    fn text_captures() {
+        let segments = parse_segments
+            .parse("echo:SD printing file {file:str}, state {state:str} at {percent}%")
+            .unwrap();
+        assert_eq!(segments[1], Text("file"));
+        assert_eq!(get_headers(&segments), "file,state,percent\n");
+        let mut parser = make_parser(segments);
+        let values = parser
+            .parse(b"echo:SD printing file benchy, v2.gcode, state \"busy\" at 42.5%\n")
+            .unwrap();
+        assert_eq!(
+            values,
+            [
+                Text("benchy, v2.gcode".into()),
+                Text("\"busy\"".into()),
+                Number(42.5)
+            ]
+        );
+        let fields = [
+            "file".to_string(),
+            "state".to_string(),
+            "percent".to_string(),
+        ];
+        assert_eq!(
+            LogFormat::Csv.record(&fields, &values),
+            "\"benchy, v2.gcode\",\"\"\"busy\"\"\",42.5\n"
+        );
+        assert_eq!(
+            LogFormat::Jsonl.record(&fields, &values),
+            "{\"file\":\"benchy, v2.gcode\",\"state\":\"\\\"busy\\\"\",\"percent\":42.5}\n"
+        );
+        let mut parser = make_parser(vec![Tag("state:"), Text("state")]);
+        assert_eq!(
+            parser.parse(b"state: idle\r\n").unwrap(),
+            [Text(" idle".into())]
+        );
+    }
+
     #[test]
     fn conversion() {
         let input = ",millis:{millis},PBT:{PBT} {{PBT0:{PBT0},PBT1:{PBT1}}}";
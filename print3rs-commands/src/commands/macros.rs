@@ -1,7 +1,50 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
-#[derive(Debug)]
-pub struct InfiniteRecursion;
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum MacroError {
+    #[error("Infinite macro detected")]
+    InfiniteRecursion,
+    #[error("expected a value like name=value, found {0}")]
+    BadArgument(String),
+    #[error("{name} has no placeholder {placeholder}")]
+    UnknownPlaceholder { name: String, placeholder: String },
+    #[error("{name} needs a value for {placeholder}")]
+    MissingValue { name: String, placeholder: String },
+}
+
+/// Names of the `{placeholder}`s in a macro's steps
+fn placeholders(steps: &[String]) -> BTreeSet<&str> {
+    let mut found = BTreeSet::new();
+    for step in steps {
+        let mut rest = step.as_str();
+        while let Some((_, after)) = rest.split_once('{') {
+            let Some((placeholder, after)) = after.split_once('}') else {
+                break;
+            };
+            if !placeholder.is_empty() {
+                found.insert(placeholder);
+            }
+            rest = after;
+        }
+    }
+    found
+}
+
+/// Split a call like `heat temp=210 bed=60` into the macro name and the values given,
+/// with the names of the values uppercased to match the stored steps
+fn parse_call(code: &str) -> Result<(&str, Vec<(String, &str)>), MacroError> {
+    let mut words = code.split_whitespace();
+    let name = words.next().unwrap_or_default();
+    let values = words
+        .map(|word| match word.split_once('=') {
+            Some((placeholder, value)) if !placeholder.is_empty() => {
+                Ok((placeholder.to_ascii_uppercase(), value))
+            }
+            _ => Err(MacroError::BadArgument(word.to_string())),
+        })
+        .collect::<Result<_, _>>()?;
+    Ok((name, values))
+}
 
 type MacrosInner = HashMap<String, Vec<String>>;
 
@@ -17,17 +60,28 @@ impl Macros {
     }
 
     /// Add a new macro with case insensitive name, stores the expansion.
+    /// Steps can have `{placeholders}` given values when the macro is used,
+    /// and use other macros, leaving any placeholders they aren't given values for to this one.
     /// Errors if the expansion would infinitely recurse
     /// Returns existing expansion if one with the same name existed
     pub fn add<'a>(
         &mut self,
         name: &str,
         steps: impl IntoIterator<Item = &'a str>,
-    ) -> Result<Option<Vec<String>>, InfiniteRecursion> {
+    ) -> Result<Option<Vec<String>>, MacroError> {
         let commands = self.expand_for_insertion(steps)?;
         Ok(self.0.insert(name.to_ascii_uppercase(), commands))
     }
 
+    /// The expansion `add` would store for these steps, without adding anything,
+    /// to check a macro before it is saved
+    pub fn check<'a>(
+        &self,
+        steps: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Vec<String>, MacroError> {
+        self.expand_for_insertion(steps)
+    }
+
     /// Lookup a macro by case insensitive name, return its expansion if defined
     pub fn get(&self, name: &str) -> Option<&Vec<String>> {
         self.0.get(&name.to_ascii_uppercase())
@@ -44,26 +98,82 @@ impl Macros {
         self.0.iter()
     }
 
+    /// The macro a step uses and the values given to it, `None` if the step isn't a defined macro
+    fn call<'c>(
+        &self,
+        code: &'c str,
+    ) -> Result<Option<(&'c str, Vec<(String, &'c str)>)>, MacroError> {
+        let name = code.split_whitespace().next().unwrap_or_default();
+        if self.get(name).is_none() {
+            return Ok(None);
+        }
+        parse_call(code).map(Some)
+    }
+
+    /// Steps of a macro with values put in for its placeholders, checking every value is for one of them.
+    ///
+    /// With `partial`, placeholders without values are left in for an outer macro to fill in.
+    fn substitute(
+        &self,
+        name: &str,
+        steps: &[String],
+        values: &[(String, &str)],
+        partial: bool,
+    ) -> Result<Vec<String>, MacroError> {
+        let placeholders = placeholders(steps);
+        for (placeholder, _) in values {
+            if !placeholders.contains(placeholder.as_str()) {
+                return Err(MacroError::UnknownPlaceholder {
+                    name: name.to_string(),
+                    placeholder: placeholder.to_ascii_lowercase(),
+                });
+            }
+        }
+        if !partial {
+            if let Some(missing) = placeholders
+                .iter()
+                .find(|placeholder| !values.iter().any(|(given, _)| given == *placeholder))
+            {
+                return Err(MacroError::MissingValue {
+                    name: name.to_string(),
+                    placeholder: missing.to_ascii_lowercase(),
+                });
+            }
+        }
+        Ok(steps
+            .iter()
+            .map(|step| {
+                values
+                    .iter()
+                    .fold(step.clone(), |step, (placeholder, value)| {
+                        step.replace(&format!("{{{placeholder}}}"), value)
+                    })
+            })
+            .collect())
+    }
+
     fn expand_recursive(
         &self,
         expanded: &mut Vec<String>,
         code: &str,
-        already_expanded: Option<Vec<&str>>,
-    ) -> Result<(), InfiniteRecursion> {
+        already_expanded: Option<Vec<String>>,
+    ) -> Result<(), MacroError> {
         // track expressions already expanded to prevent infinite recursion
         let mut already_expanded = already_expanded.unwrap_or_default();
-        if already_expanded.contains(&code) {
-            return Err(InfiniteRecursion);
-        }
-        match self.get(code) {
-            Some(expansion) => {
-                already_expanded.push(code);
-                for extra in expansion {
-                    self.expand_recursive(expanded, extra, Some(already_expanded.clone()))?
-                }
-            }
-            None => expanded.push(code.to_ascii_uppercase()),
+        let Some((name, values)) = self.call(code)? else {
+            expanded.push(code.to_ascii_uppercase());
+            return Ok(());
         };
+        let key = name.to_ascii_uppercase();
+        if already_expanded.contains(&key) {
+            return Err(MacroError::InfiniteRecursion);
+        }
+        let expansion = self.get(name).map(Vec::as_slice).unwrap_or_default();
+        let steps = self.substitute(name, expansion, &values, true)?;
+        already_expanded.push(key);
+        for step in steps {
+            self.expand_recursive(expanded, &step, Some(already_expanded.clone()))?
+        }
         Ok(())
     }
 
@@ -72,7 +182,7 @@ impl Macros {
     fn expand_for_insertion(
         &self,
         codes: impl IntoIterator<Item = impl AsRef<str>>,
-    ) -> Result<Vec<String>, InfiniteRecursion> {
+    ) -> Result<Vec<String>, MacroError> {
         let mut expanded = vec![];
 
         for code in codes {
@@ -82,15 +192,35 @@ impl Macros {
     }
 
     /// Given a list of Gcodes and/or macros, replace any defined macros in the sequence with its expansion.
-    pub fn expand<'a>(&self, codes: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    ///
+    /// Macros with placeholders are given values like `heat temp=210 bed=60`,
+    /// and every placeholder needs one.
+    pub fn expand<'a>(
+        &self,
+        codes: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Vec<String>, MacroError> {
         let mut expanded = vec![];
         for code in codes {
-            match self.get(code) {
-                Some(expansion) => expanded.extend(expansion.iter().cloned()),
-                None => expanded.push(code.to_ascii_uppercase()),
+            match self.call(code)? {
+                Some((name, values)) => {
+                    let steps = self.get(name).map(Vec::as_slice).unwrap_or_default();
+                    expanded.extend(self.substitute(name, steps, &values, false)?);
+                }
+                None => {
+                    let code = code.to_ascii_uppercase();
+                    // steps of a macro with placeholders, used without going through the macro
+                    let unfilled = placeholders(std::slice::from_ref(&code)).first().copied();
+                    if let Some(placeholder) = unfilled {
+                        return Err(MacroError::MissingValue {
+                            name: code.clone(),
+                            placeholder: placeholder.to_ascii_lowercase(),
+                        });
+                    }
+                    expanded.push(code)
+                }
             }
         }
-        expanded
+        Ok(expanded)
     }
 }
 
@@ -109,11 +239,30 @@ mod test {
         );
     }
 
+    #[test]
+    fn check_without_adding() {
+        let mut macros = Macros::new();
+        macros.add("home", ["G28"]).unwrap();
+        assert_eq!(
+            macros.check(["home", "G1 Z5"]).unwrap(),
+            vec!["G28", "G1 Z5"]
+        );
+        assert_eq!(
+            macros.check(["home two=2"]),
+            Err(MacroError::UnknownPlaceholder {
+                name: "home".to_string(),
+                placeholder: "two".to_string()
+            })
+        );
+        assert!(macros.get("G1").is_none());
+        assert_eq!(macros.iter().count(), 1);
+    }
+
     #[test]
     fn macro_expansion_empty() {
         let macros = Macros::new();
         let input = vec!["G0", "ONE", "G1"];
-        let output = macros.expand(input.clone());
+        let output = macros.expand(input.clone()).unwrap();
         assert_eq!(input, output)
     }
 
@@ -121,7 +270,7 @@ mod test {
     fn macro_expansion() {
         let mut macros = Macros::new();
         macros.add("one", ["step1", "step2"]).unwrap();
-        let output = macros.expand(["G0", "one", "G1"]);
+        let output = macros.expand(["G0", "one", "G1"]).unwrap();
         assert_eq!(output, vec!["G0", "STEP1", "STEP2", "G1"]);
     }
 
@@ -160,4 +309,49 @@ mod test {
         macros.add("zero", ["one", "two", "three"]).unwrap();
         macros.add("one", ["zero", "one", "two"]).unwrap();
     }
+
+    #[test]
+    fn placeholders_filled_in() {
+        let mut macros = Macros::new();
+        macros.add("heat", ["M104 S{temp}", "M140 S{bed}"]).unwrap();
+        assert_eq!(
+            macros.expand(["heat temp=210 bed=60", "G28"]).unwrap(),
+            vec!["M104 S210", "M140 S60", "G28"]
+        );
+        assert_eq!(
+            macros.expand(["heat temp=210"]),
+            Err(MacroError::MissingValue {
+                name: "heat".to_string(),
+                placeholder: "bed".to_string()
+            })
+        );
+        assert_eq!(
+            macros.expand(["heat temp=210 bed=60 fan=255"]),
+            Err(MacroError::UnknownPlaceholder {
+                name: "heat".to_string(),
+                placeholder: "fan".to_string()
+            })
+        );
+        assert_eq!(
+            macros.expand(["heat 210"]),
+            Err(MacroError::BadArgument("210".to_string()))
+        );
+        assert!(macros.expand(["M104 S{temp}"]).is_err());
+    }
+
+    #[test]
+    fn placeholders_passed_on() {
+        let mut macros = Macros::new();
+        macros.add("heat", ["M104 S{temp}", "M140 S{bed}"]).unwrap();
+        macros.add("pla", ["heat bed=60", "G28"]).unwrap();
+        assert_eq!(
+            macros.get("pla").unwrap(),
+            &vec!["M104 S{TEMP}", "M140 S60", "G28"]
+        );
+        assert_eq!(
+            macros.expand(["pla temp=205"]).unwrap(),
+            vec!["M104 S205", "M140 S60", "G28"]
+        );
+        macros.add("bad", ["heat nozzle=200"]).unwrap_err();
+    }
 }
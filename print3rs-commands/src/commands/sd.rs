@@ -0,0 +1,244 @@
+use {
+    print3rs_core::Socket,
+    std::{fmt::Display, sync::Arc, time::Duration},
+    tokio::time::timeout,
+};
+
+/// Longest the printer is given to answer an SD card command
+const SD_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, thiserror::Error)]
+pub enum SdError {
+    #[error("{0}")]
+    Printer(#[from] print3rs_core::Error),
+    #[error("printer took too long to answer")]
+    Timeout,
+    #[error("no SD card is inserted")]
+    NoCard,
+    #[error("could not open {0}")]
+    OpenFailed(String),
+    #[error("could not delete {0}")]
+    DeleteFailed(String),
+}
+
+/// A file on the printer's SD card, as listed by M20
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SdFile {
+    /// Short 8.3 name, which is what M23 and M30 expect
+    pub name: String,
+    /// Size in bytes, if the firmware reports it
+    pub size: Option<u64>,
+    /// Long file name, only reported by firmwares listing with M20 L
+    pub long_name: Option<String>,
+}
+
+impl Display for SdFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)?;
+        if let Some(size) = self.size {
+            write!(f, "\t{size} bytes")?;
+        }
+        if let Some(long_name) = &self.long_name {
+            write!(f, "\t{long_name}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Read the files between `Begin file list` and `End file list` in the reply to M20.
+///
+/// Entries look like `FILENAME.GCO 12345`, followed by the long name when listed with M20 L.
+pub fn parse_file_list<'a>(lines: impl IntoIterator<Item = &'a str>) -> Vec<SdFile> {
+    let mut files = vec![];
+    let mut listing = false;
+    for line in lines {
+        let line = line.trim();
+        if line.eq_ignore_ascii_case("Begin file list") {
+            listing = true;
+        } else if line.eq_ignore_ascii_case("End file list") {
+            break;
+        } else if listing && !line.is_empty() {
+            let mut words = line.splitn(3, ' ');
+            let Some(name) = words.next() else {
+                continue;
+            };
+            let size = words.next().and_then(|size| size.parse().ok());
+            let long_name = words
+                .next()
+                .map(str::trim)
+                .filter(|long_name| !long_name.is_empty())
+                .map(str::to_string);
+            files.push(SdFile {
+                name: name.to_string(),
+                size,
+                long_name,
+            });
+        }
+    }
+    files
+}
+
+/// Progress of a print from the SD card, as reported by M27
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SdStatus {
+    NotPrinting,
+    Printing { printed: u64, total: u64 },
+}
+
+impl SdStatus {
+    /// Read a line of the reply to M27, like `SD printing byte 1234/56789` or `Not SD printing`
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.starts_with("Not SD printing") || line.starts_with("Done printing file") {
+            return Some(SdStatus::NotPrinting);
+        }
+        let (printed, total) = line.strip_prefix("SD printing byte ")?.split_once('/')?;
+        Some(SdStatus::Printing {
+            printed: printed.trim().parse().ok()?,
+            total: total.trim().parse().ok()?,
+        })
+    }
+
+    /// How much of the file has been printed, from 0 to 100
+    pub fn percent(&self) -> Option<f32> {
+        match self {
+            SdStatus::Printing { printed, total } if *total > 0 => {
+                Some(*printed as f32 * 100.0 / *total as f32)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Display for SdStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SdStatus::NotPrinting => f.write_str("not printing from SD"),
+            SdStatus::Printing { printed, total } => {
+                write!(f, "printing from SD, byte {printed} of {total}")?;
+                if let Some(percent) = self.percent() {
+                    write!(f, " ({percent:.1}%)")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Send a command and collect its reply, failing if the printer doesn't answer in time
+async fn captured(socket: &Socket, command: String) -> Result<Vec<Arc<str>>, SdError> {
+    timeout(SD_TIMEOUT, socket.send_captured(command))
+        .await
+        .map_err(|_| SdError::Timeout)?
+        .map_err(SdError::from)
+}
+
+/// Whether the printer complained about the card itself rather than the command
+fn no_card(reply: &[Arc<str>]) -> bool {
+    reply.iter().any(|line| {
+        let line = line.to_ascii_lowercase();
+        line.contains("no sd card") || line.contains("no media") || line.contains("sd init fail")
+    })
+}
+
+/// List the files on the SD card with M20
+pub async fn list(socket: &Socket) -> Result<Vec<SdFile>, SdError> {
+    let reply = captured(socket, "M20".to_string()).await?;
+    if no_card(&reply) {
+        return Err(SdError::NoCard);
+    }
+    Ok(parse_file_list(reply.iter().map(|line| &**line)))
+}
+
+/// Ask how far along a print from the SD card is with M27
+pub async fn status(socket: &Socket) -> Result<SdStatus, SdError> {
+    let reply = captured(socket, "M27".to_string()).await?;
+    if no_card(&reply) {
+        return Err(SdError::NoCard);
+    }
+    Ok(reply
+        .iter()
+        .find_map(|line| SdStatus::parse(line))
+        .unwrap_or(SdStatus::NotPrinting))
+}
+
+/// Select a file with M23 and start printing it with M24
+pub async fn start_print(socket: &Socket, filename: &str) -> Result<(), SdError> {
+    let reply = captured(socket, format!("M23 {filename}")).await?;
+    if no_card(&reply) {
+        return Err(SdError::NoCard);
+    }
+    if reply.iter().any(|line| line.starts_with("open failed")) {
+        return Err(SdError::OpenFailed(filename.to_owned()));
+    }
+    let _ = socket.send_unsequenced("M24").await?.await;
+    Ok(())
+}
+
+/// Delete a file from the SD card with M30
+pub async fn delete(socket: &Socket, filename: &str) -> Result<(), SdError> {
+    let reply = captured(socket, format!("M30 {filename}")).await?;
+    if no_card(&reply) {
+        return Err(SdError::NoCard);
+    }
+    if reply.iter().any(|line| line.starts_with("Deletion failed")) {
+        return Err(SdError::DeleteFailed(filename.to_owned()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn file_list() {
+        let reply = [
+            "echo:busy: processing",
+            "Begin file list",
+            "BENCHY.GCO 1253421",
+            "CALI~1.GCO 20480 calibration cube.gcode",
+            "/MODELS/PART.GCO 900",
+            "End file list",
+            "ok",
+        ];
+        let files = parse_file_list(reply);
+        assert_eq!(files.len(), 3);
+        assert_eq!(
+            files[0],
+            SdFile {
+                name: "BENCHY.GCO".to_string(),
+                size: Some(1253421),
+                long_name: None,
+            }
+        );
+        assert_eq!(
+            files[1].long_name.as_deref(),
+            Some("calibration cube.gcode")
+        );
+        assert_eq!(files[2].name, "/MODELS/PART.GCO");
+        assert!(parse_file_list(["Begin file list", "End file list"]).is_empty());
+    }
+
+    #[test]
+    fn print_status() {
+        let printing = SdStatus::parse("SD printing byte 250/1000\n").unwrap();
+        assert_eq!(
+            printing,
+            SdStatus::Printing {
+                printed: 250,
+                total: 1000
+            }
+        );
+        assert_eq!(printing.percent(), Some(25.0));
+        assert_eq!(
+            printing.to_string(),
+            "printing from SD, byte 250 of 1000 (25.0%)"
+        );
+        assert_eq!(
+            SdStatus::parse("Not SD printing"),
+            Some(SdStatus::NotPrinting)
+        );
+        assert_eq!(SdStatus::parse("ok"), None);
+    }
+}
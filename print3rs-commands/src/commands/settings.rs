@@ -0,0 +1,226 @@
+use {
+    crate::gcode::{parse_line, Word},
+    print3rs_core::Socket,
+    std::{collections::BTreeMap, fmt::Display, sync::Arc, time::Duration},
+    tokio::time::timeout,
+};
+
+/// Longest the printer is given to report its settings
+const REPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, thiserror::Error)]
+pub enum SettingsError {
+    #[error("{0}")]
+    Printer(#[from] print3rs_core::Error),
+    #[error("printer took too long to report its settings")]
+    Timeout,
+    #[error("could not access {0}: {1}")]
+    File(String, std::io::Error),
+}
+
+/// Parameters which pick out one of several settings sharing a command, rather than being a value
+fn index_params(command: (char, u32)) -> &'static [char] {
+    match command {
+        // material presets are numbered with S
+        ('M', 145) => &['S'],
+        _ => &['T'],
+    }
+}
+
+/// Settings reported by the printer with M503, keyed by command (and tool or preset index)
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Settings(BTreeMap<String, Vec<Word>>);
+
+impl Settings {
+    /// Read settings from the lines of an M503 report, or a saved copy of one.
+    ///
+    /// Lines which aren't commands, like `ok` and comments, are ignored.
+    pub fn parse<'a>(report: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut settings = BTreeMap::new();
+        for line in report {
+            let line = line.trim_start();
+            let line = line.strip_prefix("echo:").unwrap_or(line);
+            let line = parse_line(line);
+            let Some(command) = line.command() else {
+                continue;
+            };
+            let mut key = format!("{}{}", command.0, command.1);
+            let mut values = vec![];
+            for word in line.params() {
+                if index_params(command).contains(&word.letter) {
+                    key.push_str(&format!(
+                        " {}{}",
+                        word.letter,
+                        word.value.unwrap_or_default()
+                    ));
+                } else {
+                    values.push(*word);
+                }
+            }
+            settings.insert(key, values);
+        }
+        Self(settings)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Everything that differs in `self` compared to the `saved` settings
+    pub fn diff(&self, saved: &Settings) -> Vec<Change> {
+        let mut changes = vec![];
+        for (setting, old_values) in &saved.0 {
+            let Some(new_values) = self.0.get(setting) else {
+                changes.push(Change::Removed(setting.clone()));
+                continue;
+            };
+            let value = |values: &[Word], letter| {
+                values
+                    .iter()
+                    .find(|word| word.letter == letter)
+                    .and_then(|word| word.value)
+            };
+            let mut letters = vec![];
+            for word in old_values.iter().chain(new_values) {
+                if !letters.contains(&word.letter) {
+                    letters.push(word.letter);
+                }
+            }
+            for letter in letters {
+                let (old, new) = (value(old_values, letter), value(new_values, letter));
+                if old != new {
+                    changes.push(Change::Changed {
+                        setting: setting.clone(),
+                        letter,
+                        old,
+                        new,
+                    });
+                }
+            }
+        }
+        for setting in self.0.keys() {
+            if !saved.0.contains_key(setting) {
+                changes.push(Change::Added(setting.clone()));
+            }
+        }
+        changes
+    }
+}
+
+/// Ask the printer to report its current settings with M503
+async fn report(socket: &Socket) -> Result<Vec<Arc<str>>, SettingsError> {
+    timeout(REPORT_TIMEOUT, socket.send_captured("M503"))
+        .await
+        .map_err(|_| SettingsError::Timeout)?
+        .map_err(SettingsError::from)
+}
+
+/// Save the printer's current settings report to a file, returning how many settings were in it
+pub async fn save(socket: &Socket, filename: &str) -> Result<usize, SettingsError> {
+    let report = report(socket).await?;
+    let count = Settings::parse(report.iter().map(|line| &**line)).len();
+    tokio::fs::write(filename, report.concat())
+        .await
+        .map_err(|e| SettingsError::File(filename.to_owned(), e))?;
+    Ok(count)
+}
+
+/// Compare the printer's current settings to a report previously saved in a file
+pub async fn diff_saved(socket: &Socket, filename: &str) -> Result<Vec<Change>, SettingsError> {
+    let saved = tokio::fs::read_to_string(filename)
+        .await
+        .map_err(|e| SettingsError::File(filename.to_owned(), e))?;
+    let saved = Settings::parse(saved.lines());
+    let report = report(socket).await?;
+    let current = Settings::parse(report.iter().map(|line| &**line));
+    Ok(current.diff(&saved))
+}
+
+/// Difference in a single setting between two reports
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Changed {
+        setting: String,
+        letter: char,
+        old: Option<f32>,
+        new: Option<f32>,
+    },
+    Added(String),
+    Removed(String),
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = |value: &Option<f32>| match value {
+            Some(value) => value.to_string(),
+            None => "unset".to_string(),
+        };
+        match self {
+            Change::Changed {
+                setting,
+                letter,
+                old,
+                new,
+            } => write!(f, "{setting} {letter}: {} -> {}", value(old), value(new)),
+            Change::Added(setting) => write!(f, "{setting}: added"),
+            Change::Removed(setting) => write!(f, "{setting}: removed"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SAVED: &str = "echo:; Steps per unit:
+echo:  M92 X80.00 Y80.00 Z400.00 E93.00
+echo:; Hotend PID:
+echo:  M301 P22.20 I1.08 D114.00
+echo:  M145 S0 H180.00 B70.00 F0
+echo:  M145 S1 H240.00 B110.00 F0
+ok
+";
+
+    #[test]
+    fn parse_report() {
+        let settings = Settings::parse(SAVED.lines());
+        assert_eq!(settings.0.len(), 4);
+        assert_eq!(settings.0["M92"].len(), 4);
+        assert!(settings.0.contains_key("M145 S1"));
+    }
+
+    #[test]
+    fn no_changes() {
+        let saved = Settings::parse(SAVED.lines());
+        assert!(saved.diff(&saved).is_empty());
+    }
+
+    #[test]
+    fn changed_values() {
+        let saved = Settings::parse(SAVED.lines());
+        let current = SAVED
+            .replace("E93.00", "E95.50")
+            .replace("M145 S1 H240.00", "M145 S1 H250.00")
+            .replace("echo:  M301 P22.20 I1.08 D114.00\n", "")
+            + "echo:  M851 X-40.00 Y-10.00 Z-1.20\n";
+        let current = Settings::parse(current.lines());
+        let changes: Vec<String> = current
+            .diff(&saved)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                "M145 S1 H: 240 -> 250",
+                "M301: removed",
+                "M92 E: 93 -> 95.5",
+                "M851: added"
+            ]
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use {
+    print3rs_core::{ChannelStats, ACK_LATENCY_BUCKETS},
+    std::{fmt::Display, time::Duration},
+};
+
+/// Traffic with the printer since it was connected, shown by `stats`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats(pub ChannelStats);
+
+/// Shortest readable form of a duration, like `850us`, `12.3ms` or `1.2s`
+fn short_duration(duration: Duration) -> String {
+    if duration < Duration::from_millis(1) {
+        format!("{}us", duration.as_micros())
+    } else if duration < Duration::from_secs(1) {
+        format!("{:.1}ms", duration.as_secs_f64() * 1000.0)
+    } else {
+        format!("{:.1}s", duration.as_secs_f64())
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let stats = &self.0;
+        writeln!(f, "connected:   {}s", stats.connected_for.as_secs())?;
+        writeln!(
+            f,
+            "sent:        {} commands, {} bytes, {:.1} bytes/s",
+            stats.commands_sent,
+            stats.bytes_sent,
+            stats.bytes_sent_per_second()
+        )?;
+        writeln!(
+            f,
+            "received:    {} lines, {} oks, {} bytes, {:.1} bytes/s",
+            stats.lines_received,
+            stats.oks,
+            stats.bytes_received,
+            stats.bytes_received_per_second()
+        )?;
+        writeln!(
+            f,
+            "resent:      {} lines, {} given up",
+            stats.resends, stats.timeouts
+        )?;
+        match stats.average_ack_latency() {
+            Some(average) => {
+                writeln!(
+                    f,
+                    "ack time:    {} on average, {} at most, over {} commands",
+                    short_duration(average),
+                    short_duration(stats.ack_latency_max),
+                    stats.acknowledged
+                )?;
+                f.write_str("            ")?;
+                for (bound, count) in ACK_LATENCY_BUCKETS.iter().zip(stats.ack_latency_histogram) {
+                    write!(f, " <{} {count}", short_duration(*bound))?;
+                }
+                let slowest = stats.ack_latency_histogram[ACK_LATENCY_BUCKETS.len()];
+                writeln!(
+                    f,
+                    " >={} {slowest}",
+                    short_duration(ACK_LATENCY_BUCKETS[ACK_LATENCY_BUCKETS.len() - 1])
+                )?;
+            }
+            None => writeln!(f, "ack time:    nothing acknowledged yet")?,
+        }
+        writeln!(
+            f,
+            "lost:        {} lines dropped, {} lagged, {} sends refused",
+            stats.dropped_lines, stats.lagged_lines, stats.full_sends
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        assert_eq!(short_duration(Duration::from_micros(850)), "850us");
+        assert_eq!(short_duration(Duration::from_micros(12_340)), "12.3ms");
+        assert_eq!(short_duration(Duration::from_millis(1250)), "1.2s");
+    }
+}
@@ -0,0 +1,171 @@
+use {
+    print3rs_core::{
+        Error, Info, Position, Printer, PrinterState, Socket, Temperature, TemperatureReport,
+    },
+    std::{fmt::Display, time::Duration},
+    tokio::time::timeout,
+};
+
+/// Longest the printer is given to answer each query
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// One-shot summary of the printer and what is running on it, shown by `status`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Status {
+    /// Protocol of the current connection, `None` when disconnected
+    pub connection: Option<String>,
+    /// `FIRMWARE_NAME` from the M115 report
+    pub firmware: Option<String>,
+    pub state: Option<PrinterState>,
+    pub temperatures: Option<TemperatureReport>,
+    pub position: Option<Position>,
+    /// Names of the running background tasks
+    pub tasks: Vec<String>,
+    /// Lines waiting to be written to the printer
+    pub queued: usize,
+}
+
+/// Ask the printer for its temperatures with M105
+pub(crate) async fn query_temperatures(socket: &Socket) -> Result<TemperatureReport, Error> {
+    // Marlin puts the report on the `ok` line, so it can't be captured with `send_captured`
+    let mut temperatures = socket.temperatures()?;
+    let _ = socket.send_unsequenced("M105").await?;
+    temperatures.recv().await
+}
+
+impl Status {
+    /// What can be told without asking the printer anything.
+    ///
+    /// The firmware name comes from the M115 report read when connecting.
+    pub fn new(printer: &Printer, connection: Option<String>, tasks: Vec<String>) -> Self {
+        let mut status = Self {
+            tasks,
+            ..Default::default()
+        };
+        let Ok(socket) = printer.socket() else {
+            return status;
+        };
+        status.connection = connection;
+        status.firmware = printer
+            .info()
+            .ok()
+            .and_then(|info| match info.get("FIRMWARE_NAME") {
+                Some(Info::Str(name)) => Some(name.clone()),
+                _ => None,
+            });
+        status.state = Some(socket.state());
+        status.queued = socket.queued();
+        status
+    }
+
+    /// Fill in the temperatures and position by asking the printer with M105 and M114.
+    ///
+    /// Anything the printer doesn't answer in time is left out.
+    pub async fn query(mut self, socket: &Socket) -> Self {
+        self.temperatures = timeout(QUERY_TIMEOUT, query_temperatures(socket))
+            .await
+            .ok()
+            .and_then(Result::ok);
+        self.position = timeout(QUERY_TIMEOUT, socket.request_position())
+            .await
+            .ok()
+            .and_then(Result::ok);
+        self
+    }
+}
+
+fn write_temperature(
+    f: &mut std::fmt::Formatter<'_>,
+    name: &str,
+    temperature: &Temperature,
+) -> std::fmt::Result {
+    write!(f, " {name} {:.1}", temperature.current)?;
+    if let Some(target) = temperature.target {
+        write!(f, "/{target:.1}")?;
+    }
+    Ok(())
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Some(connection) = &self.connection else {
+            writeln!(f, "connection:  none")?;
+            return writeln!(f, "tasks:       {}", self.tasks.len());
+        };
+        writeln!(f, "connection:  {connection}")?;
+        let firmware = self.firmware.as_deref().unwrap_or("unknown");
+        writeln!(f, "firmware:    {firmware}")?;
+        if let Some(state) = self.state {
+            writeln!(f, "state:       {state:?}")?;
+        }
+        f.write_str("temperature:")?;
+        match &self.temperatures {
+            Some(report) => {
+                if let Some(hotend) = &report.hotend {
+                    write_temperature(f, "hotend", hotend)?;
+                }
+                if let Some(bed) = &report.bed {
+                    write_temperature(f, "bed", bed)?;
+                }
+                if let Some(chamber) = &report.chamber {
+                    write_temperature(f, "chamber", chamber)?;
+                }
+                writeln!(f)?;
+            }
+            None => writeln!(f, " no answer")?,
+        }
+        match &self.position {
+            Some(Position { x, y, z, e }) => {
+                write!(f, "position:    X{x:.2} Y{y:.2} Z{z:.2}")?;
+                if let Some(e) = e {
+                    write!(f, " E{e:.2}")?;
+                }
+                writeln!(f)?;
+            }
+            None => writeln!(f, "position:    no answer")?,
+        }
+        writeln!(f, "queued:      {} lines", self.queued)?;
+        write!(f, "tasks:       {}", self.tasks.len())?;
+        if !self.tasks.is_empty() {
+            write!(f, " ({})", self.tasks.join(", "))?;
+        }
+        writeln!(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn summary() {
+        let disconnected = Status {
+            tasks: vec!["log".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            disconnected.to_string(),
+            "connection:  none\ntasks:       1\n"
+        );
+
+        let status = Status {
+            connection: Some("Serial".to_string()),
+            firmware: Some("Marlin 2.1.2".to_string()),
+            state: Some(PrinterState::Printing),
+            temperatures: TemperatureReport::parse("ok T:200.1 /200.0 B:60.0 /60.0"),
+            position: Position::parse("X:10.00 Y:0.00 Z:0.20 E:1.50 Count X:800 Y:0 Z:80"),
+            tasks: vec!["benchy.gcode".to_string(), "temps".to_string()],
+            queued: 3,
+        };
+        assert_eq!(
+            status.to_string(),
+            "connection:  Serial\n\
+             firmware:    Marlin 2.1.2\n\
+             state:       Printing\n\
+             temperature: hotend 200.1/200.0 bed 60.0/60.0\n\
+             position:    X10.00 Y0.00 Z0.20 E1.50\n\
+             queued:      3 lines\n\
+             tasks:       2 (benchy.gcode, temps)\n"
+        );
+    }
+}
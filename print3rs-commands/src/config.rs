@@ -0,0 +1,203 @@
+use {
+    crate::response::Notice,
+    serde::{
+        de::{MapAccess, Visitor},
+        Deserialize, Deserializer,
+    },
+    std::path::{Path, PathBuf},
+};
+
+/// Where the config is read from by default, in the platform's config directory
+pub fn config_file() -> Option<PathBuf> {
+    directories_next::ProjectDirs::from("dev", "arades", "print3rs")
+        .map(|dirs| dirs.config_dir().join("config.toml"))
+}
+
+/// Console commands run at startup, like `run` does, in the platform's config directory
+pub fn init_file() -> Option<PathBuf> {
+    directories_next::ProjectDirs::from("dev", "arades", "print3rs")
+        .map(|dirs| dirs.config_dir().join("init"))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("could not read {}: {1}", .0.display())]
+    Read(PathBuf, std::io::Error),
+    #[error("could not parse {}: {1}", .0.display())]
+    Parse(PathBuf, toml::de::Error),
+    #[error("macro {0} in the config: {1}")]
+    Macro(String, String),
+}
+
+/// Defaults for the commander, read from a TOML file where every key is optional, e.g.
+///
+/// ```toml
+/// baud = 250000
+/// startup_gcode = ["M155 S2"]
+/// log_dir = "logs"
+///
+/// [macros]
+/// heat = "M104 S{temp}; M140 S{bed}"
+///
+/// [limits]
+/// build_volume = [235, 235, 250]
+/// min_extrude_temp = 180
+///
+/// [ui]
+/// sparklines = true
+///
+/// [notifications]
+/// print_finished = true
+/// log_error = false
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Baud rate used by `connect serial` when none is given
+    pub baud: Option<u32>,
+    /// Gcodes sent every time a printer is connected
+    pub startup_gcode: Vec<String>,
+    /// Macro names and their steps separated by `;`, defined in the order they are written
+    #[serde(deserialize_with = "ordered_macros")]
+    pub macros: Vec<(String, String)>,
+    pub limits: Limits,
+    /// Directory log files are written to, the working directory if unset
+    pub log_dir: Option<PathBuf>,
+    pub ui: UiPreferences,
+    pub notifications: Notifications,
+}
+
+/// What the printer can safely be asked to do
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    /// Size of the build volume in X, Y and Z, checked by `lint` and probed by `heightmap`
+    pub build_volume: Option<[f32; 3]>,
+    /// Lowest hotend temperature `extrude` and `lint` allow extruding at
+    pub min_extrude_temp: Option<f32>,
+}
+
+/// How frontends show things, each left as the frontend's default if unset
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UiPreferences {
+    /// Show recent log values as sparklines, like the `sparklines` command
+    pub sparklines: Option<bool>,
+}
+
+/// Which notices frontends show as desktop notifications, all of them unless turned off
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Notifications {
+    /// A print finished
+    pub print_finished: Option<bool>,
+    /// A log task stopped because it couldn't write
+    pub log_error: Option<bool>,
+    /// The printer disconnected
+    pub disconnected: Option<bool>,
+}
+
+impl Notifications {
+    /// Check if a notice should be shown as a desktop notification
+    pub fn enabled(&self, notice: Notice) -> bool {
+        match notice {
+            Notice::PrintFinished => self.print_finished,
+            Notice::LogError => self.log_error,
+            Notice::Disconnected => self.disconnected,
+        }
+        .unwrap_or(true)
+    }
+}
+
+impl Config {
+    /// Read the config in `file`, a missing file is the same as an empty one
+    pub fn load(file: &Path) -> Result<Self, ConfigError> {
+        match std::fs::read_to_string(file) {
+            Ok(text) => toml::from_str(&text).map_err(|e| ConfigError::Parse(file.into(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(ConfigError::Read(file.into(), e)),
+        }
+    }
+}
+
+/// Macros in the order they are written, so each can use the ones before it
+fn ordered_macros<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<(String, String)>, D::Error> {
+    struct Macros;
+
+    impl<'de> Visitor<'de> for Macros {
+        type Value = Vec<(String, String)>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a table of macro names and their steps")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut macros = vec![];
+            while let Some(entry) = map.next_entry()? {
+                macros.push(entry);
+            }
+            Ok(macros)
+        }
+    }
+
+    deserializer.deserialize_map(Macros)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn full_config() {
+        let config: Config = toml::from_str(
+            r#"
+            baud = 250000
+            startup_gcode = ["M155 S2", "G28"]
+            log_dir = "logs"
+
+            [macros]
+            heat = "M104 S{temp}; M140 S{bed}"
+            pla = "heat temp=205 bed=60"
+
+            [limits]
+            build_volume = [235, 235, 250]
+            min_extrude_temp = 180
+
+            [ui]
+            sparklines = true
+
+            [notifications]
+            log_error = false
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.baud, Some(250000));
+        assert_eq!(config.startup_gcode, ["M155 S2", "G28"]);
+        assert_eq!(config.log_dir, Some(PathBuf::from("logs")));
+        assert_eq!(
+            config.macros,
+            [
+                ("heat".to_string(), "M104 S{temp}; M140 S{bed}".to_string()),
+                ("pla".to_string(), "heat temp=205 bed=60".to_string())
+            ]
+        );
+        assert_eq!(config.limits.build_volume, Some([235.0, 235.0, 250.0]));
+        assert_eq!(config.limits.min_extrude_temp, Some(180.0));
+        assert_eq!(config.ui.sparklines, Some(true));
+        assert!(config.notifications.enabled(Notice::PrintFinished));
+        assert!(!config.notifications.enabled(Notice::LogError));
+    }
+
+    #[test]
+    fn empty_and_missing() {
+        assert_eq!(toml::from_str::<Config>("").unwrap(), Config::default());
+        assert_eq!(
+            Config::load(Path::new("/nonexistent/print3rs/config.toml")).unwrap(),
+            Config::default()
+        );
+        assert!(toml::from_str::<Config>("baud = \"fast\"").is_err());
+        assert!(toml::from_str::<Config>("bauds = 9600").is_err());
+    }
+}
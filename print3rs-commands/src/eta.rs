@@ -0,0 +1,353 @@
+use {
+    crate::gcode::{parse_line, sendable, MachineState, E},
+    std::time::Duration,
+};
+
+/// Feedrate assumed for moves before a file sets one, in mm/min
+const DEFAULT_FEEDRATE: f32 = 1500.0;
+/// How many points along a file the time worked out from its moves is kept for
+const MOTION_CHECKPOINTS: usize = 100;
+
+/// Remaining print time according to the slicer, after a number of lines have been sent
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Checkpoint {
+    line: usize,
+    remaining: f64,
+}
+
+/// Time estimates left in a gcode file by the slicer that made it.
+///
+/// Understands the PrusaSlicer `; estimated printing time (normal mode) = 1h 2m 3s` summary
+/// and its `M73 P<percent> R<minutes>` progress lines, and Cura's `;TIME:<seconds>` header
+/// with a `;TIME_ELAPSED:<seconds>` comment after every layer.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SlicerEstimate {
+    /// Whole print time in seconds
+    total: Option<f64>,
+    checkpoints: Vec<Checkpoint>,
+}
+
+/// Length of a time like `1d 2h 3m 4s`
+fn parse_duration(text: &str) -> Option<f64> {
+    let mut seconds = 0.0;
+    for part in text.split_whitespace() {
+        let split = part.find(|c: char| c.is_ascii_alphabetic())?;
+        let (value, unit) = part.split_at(split);
+        let value: f64 = value.parse().ok()?;
+        seconds += value
+            * match unit {
+                "d" => 86400.0,
+                "h" => 3600.0,
+                "m" => 60.0,
+                "s" => 1.0,
+                _ => return None,
+            };
+    }
+    Some(seconds)
+}
+
+impl SlicerEstimate {
+    /// Find the slicer's estimates in the lines of a gcode file.
+    ///
+    /// Lines are counted the same way the print task counts lines sent,
+    /// only including those which are `sendable`.
+    pub fn parse<'a>(lines: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut estimate = Self::default();
+        let mut sent = 0;
+        for line in lines {
+            if let Some(code) = sendable(line) {
+                sent += 1;
+                let code = parse_line(code);
+                // M73 Q and S are the estimates for silent mode
+                if let (true, true, Some(minutes)) =
+                    (code.is('M', 73), code.has('P'), code.get('R'))
+                {
+                    estimate.push(sent, minutes as f64 * 60.0);
+                }
+                continue;
+            }
+            let comment = line.trim_start().trim_start_matches(';').trim();
+            if let Some(time) = comment.strip_prefix("estimated printing time (normal mode) =") {
+                estimate.total = estimate.total.or(parse_duration(time));
+            } else if let Some(time) = comment.strip_prefix("TIME:") {
+                estimate.total = estimate.total.or(time.trim().parse().ok());
+            } else if let Some(elapsed) = comment.strip_prefix("TIME_ELAPSED:") {
+                if let (Some(total), Ok(elapsed)) = (estimate.total, elapsed.trim().parse::<f64>())
+                {
+                    estimate.push(sent, total - elapsed);
+                }
+            }
+        }
+        estimate
+    }
+
+    /// Work out how long a gcode file takes from the length and feedrate of its moves and its dwells,
+    /// for files without estimates from the slicer.
+    ///
+    /// Acceleration is not taken into account, so this usually comes out short,
+    /// which `Eta::remaining` makes up for as it measures how fast the print really goes.
+    pub fn from_moves<'a>(lines: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut state = MachineState::default();
+        let mut elapsed = 0.0;
+        let mut times = vec![];
+        for line in lines.into_iter().filter_map(sendable) {
+            let code = parse_line(line);
+            if code.is('G', 4) {
+                elapsed += code
+                    .get('S')
+                    .map(f64::from)
+                    .or(code.get('P').map(|ms| f64::from(ms) / 1000.0))
+                    .unwrap_or_default();
+            }
+            if let Some(moved) = state.apply(&code) {
+                let distance = match moved.distance() {
+                    // extrusion or retraction only
+                    distance if distance == 0.0 => (moved.to[E] - moved.from[E]).abs(),
+                    distance => distance,
+                };
+                let feedrate = moved.feedrate.unwrap_or(DEFAULT_FEEDRATE);
+                if feedrate > 0.0 {
+                    elapsed += f64::from(distance) * 60.0 / f64::from(feedrate);
+                }
+            }
+            times.push(elapsed);
+        }
+        let mut estimate = Self::default();
+        if elapsed <= 0.0 {
+            return estimate;
+        }
+        estimate.total = Some(elapsed);
+        let step = (times.len() / MOTION_CHECKPOINTS).max(1);
+        for (index, time) in times.iter().enumerate().skip(step - 1).step_by(step) {
+            estimate.push(index + 1, elapsed - time);
+        }
+        estimate
+    }
+
+    fn push(&mut self, line: usize, remaining: f64) {
+        self.checkpoints.push(Checkpoint {
+            line,
+            remaining: remaining.max(0.0),
+        });
+    }
+
+    /// Check if the file had any estimates in it
+    pub fn is_empty(&self) -> bool {
+        self.total.is_none() && self.checkpoints.is_empty()
+    }
+
+    /// Whole print time according to the slicer
+    pub fn total(&self) -> Option<Duration> {
+        self.total_seconds().map(Duration::from_secs_f64)
+    }
+
+    fn total_seconds(&self) -> Option<f64> {
+        self.total.or(self
+            .checkpoints
+            .first()
+            .map(|checkpoint| checkpoint.remaining))
+    }
+
+    /// Seconds the slicer expects are left after `lines_sent` lines of `total_lines`,
+    /// interpolated between the nearest estimates either side
+    fn remaining_seconds(&self, lines_sent: usize, total_lines: usize) -> Option<f64> {
+        let total = self.total_seconds()?;
+        let next = self
+            .checkpoints
+            .partition_point(|checkpoint| checkpoint.line <= lines_sent);
+        let before = match next {
+            0 => Checkpoint {
+                line: 0,
+                remaining: total,
+            },
+            next => self.checkpoints[next - 1],
+        };
+        let after = self.checkpoints.get(next).copied().unwrap_or(Checkpoint {
+            line: total_lines.max(before.line),
+            remaining: 0.0,
+        });
+        let span = after.line.saturating_sub(before.line);
+        if span == 0 {
+            return Some(before.remaining);
+        }
+        let through = (lines_sent - before.line) as f64 / span as f64;
+        Some(before.remaining + (after.remaining - before.remaining) * through.min(1.0))
+    }
+}
+
+/// Compact time like `2h05m`, `12m30s` or `45s`
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}h{minutes:02}m")
+    } else if minutes > 0 {
+        format!("{minutes}m{seconds:02}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+/// Estimate of how long is left of a print while it is being streamed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Eta {
+    total_lines: usize,
+    slicer: SlicerEstimate,
+    /// Lines skipped over when starting partway through the file
+    skipped: usize,
+}
+
+impl Eta {
+    pub fn new(total_lines: usize, slicer: SlicerEstimate) -> Self {
+        Self {
+            total_lines,
+            slicer,
+            skipped: 0,
+        }
+    }
+
+    /// Estimate for the lines of a gcode file, using any slicer estimates found in it,
+    /// or working one out from its moves if there are none
+    pub fn for_file(file: &str) -> Self {
+        let total_lines = file.lines().filter(|line| sendable(line).is_some()).count();
+        let mut estimate = SlicerEstimate::parse(file.lines());
+        if estimate.is_empty() {
+            estimate = SlicerEstimate::from_moves(file.lines());
+        }
+        Self::new(total_lines, estimate)
+    }
+
+    /// Whole print time expected before it starts
+    pub fn total(&self) -> Option<Duration> {
+        self.slicer.total()
+    }
+
+    /// Estimate for a print starting after `skipped` lines of the file,
+    /// so only the time spent on lines actually sent is used to measure the pace
+    pub fn starting_at(mut self, skipped: usize) -> Self {
+        self.skipped = skipped;
+        self
+    }
+
+    pub fn total_lines(&self) -> usize {
+        self.total_lines
+    }
+
+    /// Time left after sending `lines_sent` lines, `elapsed` since the print started.
+    ///
+    /// Without slicer estimates this extrapolates from the lines sent so far.
+    /// With them, the slicer's remaining time is scaled by how much faster or slower
+    /// the printer has been than the slicer expected, trusting the measured pace more
+    /// the further the print gets.
+    pub fn remaining(&self, lines_sent: usize, elapsed: Duration) -> Option<Duration> {
+        let elapsed = elapsed.as_secs_f64();
+        let Some(slicer_remaining) = self.slicer.remaining_seconds(lines_sent, self.total_lines)
+        else {
+            let done = lines_sent.saturating_sub(self.skipped);
+            if done == 0 {
+                return None;
+            }
+            let left = self.total_lines.saturating_sub(lines_sent) as f64;
+            return Some(Duration::from_secs_f64(elapsed * left / done as f64));
+        };
+        let total = self
+            .slicer
+            .remaining_seconds(self.skipped, self.total_lines)
+            .unwrap_or_default();
+        let slicer_elapsed = total - slicer_remaining;
+        if slicer_elapsed <= 0.0 || total <= 0.0 {
+            return Some(Duration::from_secs_f64(slicer_remaining));
+        }
+        let pace = elapsed / slicer_elapsed;
+        let trust = (slicer_elapsed / total).clamp(0.0, 1.0);
+        let scale = 1.0 + trust * (pace - 1.0);
+        Some(Duration::from_secs_f64(slicer_remaining * scale))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CURA: &str = ";FLAVOR:Marlin
+;TIME:100
+;LAYER:0
+G1 X1
+G1 X2
+;TIME_ELAPSED:40
+;LAYER:1
+G1 X3
+G1 X4
+;TIME_ELAPSED:100
+";
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("1h 2m 3s"), Some(3723.0));
+        assert_eq!(parse_duration("1d 0h 5m 0s"), Some(86700.0));
+        assert_eq!(parse_duration("soon"), None);
+    }
+
+    #[test]
+    fn formatted_durations() {
+        assert_eq!(format_duration(Duration::from_secs(3900)), "1h05m");
+        assert_eq!(format_duration(Duration::from_secs(750)), "12m30s");
+        assert_eq!(format_duration(Duration::from_secs(45)), "45s");
+    }
+
+    #[test]
+    fn cura_estimates() {
+        let eta = Eta::for_file(CURA);
+        assert_eq!(eta.total_lines(), 4);
+        assert_eq!(eta.slicer.total(), Some(Duration::from_secs(100)));
+        // on pace with the slicer
+        let remaining = eta.remaining(2, Duration::from_secs(40)).unwrap();
+        assert_eq!(remaining.as_secs(), 60);
+        // twice as slow, and halfway through the first layer
+        let remaining = eta.remaining(1, Duration::from_secs(40)).unwrap();
+        assert_eq!(remaining.as_secs(), 96);
+    }
+
+    #[test]
+    fn prusaslicer_estimates() {
+        let file = "M73 P0 R10\nM73 Q0 S12\nG1 X1\nM73 P50 R5\nG1 X2\n; estimated printing time (normal mode) = 10m 0s\n";
+        let slicer = SlicerEstimate::parse(file.lines());
+        assert_eq!(slicer.checkpoints.len(), 2);
+        assert_eq!(slicer.total(), Some(Duration::from_secs(600)));
+        assert_eq!(slicer.remaining_seconds(4, 5), Some(300.0));
+    }
+
+    #[test]
+    fn estimates_from_moves() {
+        let file = "G28\nG1 X60 F1200\nG4 S2\nG1 E5 F300\nG1 X0 F6000\n";
+        let estimate = SlicerEstimate::from_moves(file.lines());
+        // homing is instant, 60mm at 20mm/s, 2s dwell, 5mm at 5mm/s, 60mm at 100mm/s
+        assert_eq!(
+            estimate.total(),
+            Some(Duration::from_secs_f64(3.0 + 2.0 + 1.0 + 0.6))
+        );
+        assert_eq!(estimate.remaining_seconds(2, 5).map(f64::round), Some(4.0));
+
+        let eta = Eta::for_file(file);
+        assert_eq!(eta.total(), estimate.total());
+        // going at half the calculated speed, with half the moves done by time
+        let remaining = eta.remaining(3, Duration::from_secs(10)).unwrap();
+        assert!(remaining > Duration::from_secs_f64(1.6));
+        assert!(SlicerEstimate::from_moves(["M104 S200", "M84"]).is_empty());
+    }
+
+    #[test]
+    fn extrapolate_without_estimates() {
+        let eta = Eta::for_file("M104 S200\nM140 S60\nM106\nM107\n");
+        assert!(eta.slicer.is_empty());
+        assert_eq!(eta.remaining(0, Duration::ZERO), None);
+        let remaining = eta.remaining(1, Duration::from_secs(10)).unwrap();
+        assert_eq!(remaining.as_secs(), 30);
+        // starting from the third line, only the time since then counts
+        let remaining = eta
+            .starting_at(2)
+            .remaining(3, Duration::from_secs(10))
+            .unwrap();
+        assert_eq!(remaining.as_secs(), 10);
+    }
+}
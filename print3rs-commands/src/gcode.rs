@@ -0,0 +1,1311 @@
+use std::{fmt::Display, time::Duration};
+use winnow::{
+    ascii::space0,
+    combinator::{opt, preceded, repeat},
+    prelude::*,
+    token::{any, take_while},
+};
+
+/// A letter and its optional number, e.g. `G1` or `X-10.5`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Word {
+    pub letter: char,
+    pub value: Option<f32>,
+}
+
+/// A G-code line split into words, without line number, checksum or comment.
+///
+/// The first word is the command (`G1`, `M104`, `T0`...) and the rest are its parameters.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Line {
+    pub words: Vec<Word>,
+}
+
+impl Display for Word {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.letter)?;
+        if let Some(value) = self.value {
+            write!(f, "{value}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Line {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, word) in self.words.iter().enumerate() {
+            if index > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{word}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Commands whose arguments are free text rather than words
+const TEXT_COMMANDS: &[(char, u32)] = &[
+    ('M', 23),
+    ('M', 28),
+    ('M', 30),
+    ('M', 32),
+    ('M', 117),
+    ('M', 118),
+];
+
+impl Line {
+    /// The command letter and number of this line, if it has one
+    pub fn command(&self) -> Option<(char, u32)> {
+        let first = self.words.first()?;
+        match (first.letter, first.value) {
+            ('G' | 'M' | 'T', Some(value)) if value >= 0.0 => Some((first.letter, value as u32)),
+            _ => None,
+        }
+    }
+
+    /// Check if this line is the given command, e.g. `line.is('G', 1)`
+    pub fn is(&self, letter: char, number: u32) -> bool {
+        self.command() == Some((letter, number))
+    }
+
+    /// Parameters following the command
+    pub fn params(&self) -> &[Word] {
+        match self.command() {
+            Some(command) if TEXT_COMMANDS.contains(&command) => &[],
+            Some(_) => &self.words[1..],
+            None => &self.words,
+        }
+    }
+
+    /// Value of the given parameter, if present with a value
+    pub fn get(&self, letter: char) -> Option<f32> {
+        self.params()
+            .iter()
+            .find(|word| word.letter == letter)
+            .and_then(|word| word.value)
+    }
+
+    /// Check if the given parameter is present, with or without a value
+    pub fn has(&self, letter: char) -> bool {
+        self.params().iter().any(|word| word.letter == letter)
+    }
+}
+
+/// Remove a trailing `;` comment from a line
+pub fn strip_comment(line: &str) -> &str {
+    match line.split_once(';') {
+        Some((code, _)) => code,
+        None => line,
+    }
+}
+
+/// Remove a leading `N` line number and trailing `*` checksum from a line
+pub fn strip_framing(line: &str) -> &str {
+    let mut code = line.trim();
+    if let Some(rest) = code.strip_prefix(['N', 'n']) {
+        let number = rest.trim_start_matches(|c: char| c.is_ascii_digit());
+        if number.len() < rest.len() {
+            code = number.trim_start();
+        }
+    }
+    if let Some((body, checksum)) = code.rsplit_once('*') {
+        let checksum = checksum.trim();
+        if !checksum.is_empty() && checksum.bytes().all(|b| b.is_ascii_digit()) {
+            code = body.trim_end();
+        }
+    }
+    code
+}
+
+/// The part of a line from a gcode file that should be sent to the printer, if any.
+///
+/// Comments are removed, along with any line number and checksum already in the file,
+/// since lines are numbered again as they are sent.
+/// `M110` is dropped as it would reset the line numbers the serializer is counting.
+pub fn sendable(line: &str) -> Option<&str> {
+    let code = strip_framing(strip_comment(line));
+    if code.is_empty() || parse_line(code).is('M', 110) {
+        None
+    } else {
+        Some(code)
+    }
+}
+
+fn number(input: &mut &str) -> PResult<f32> {
+    take_while(1.., ('0'..='9', ['.', '-', '+']))
+        .parse_to()
+        .parse_next(input)
+}
+
+fn word(input: &mut &str) -> PResult<Word> {
+    (
+        preceded(space0, any.verify(char::is_ascii_alphabetic)),
+        opt(preceded(space0, number)),
+    )
+        .map(|(letter, value): (char, _)| Word {
+            letter: letter.to_ascii_uppercase(),
+            value,
+        })
+        .parse_next(input)
+}
+
+/// Split a line of G-code into its words.
+///
+/// Comments, a leading `N` line number and a trailing `*` checksum are ignored.
+/// Parsing stops at anything that doesn't look like a word.
+pub fn parse_line(line: &str) -> Line {
+    let code = strip_comment(line);
+    let mut code = code.split('*').next().unwrap_or_default();
+    let mut words: Vec<Word> = repeat(0.., word).parse_next(&mut code).unwrap_or_default();
+    if words.first().is_some_and(|word| word.letter == 'N') {
+        words.remove(0);
+    }
+    Line { words }
+}
+
+/// Feedrates in mm/min for parking the head while a print is paused
+const PARK_XY_FEEDRATE: f32 = 6000.0;
+const PARK_Z_FEEDRATE: f32 = 600.0;
+const PARK_EXTRUDE_FEEDRATE: f32 = 1800.0;
+
+/// Longest straight move arcs are split into for firmware without arc support, in mm
+pub const ARC_SEGMENT_LENGTH: f32 = 1.0;
+
+/// Index of each axis in positions
+pub const X: usize = 0;
+pub const Y: usize = 1;
+pub const Z: usize = 2;
+pub const E: usize = 3;
+const AXES: [char; 4] = ['X', 'Y', 'Z', 'E'];
+
+/// A change in position caused by a single line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Move {
+    pub from: [f32; 4],
+    pub to: [f32; 4],
+    pub feedrate: Option<f32>,
+}
+
+impl Move {
+    /// Check if this move pushes filament out
+    pub fn is_extruding(&self) -> bool {
+        self.to[E] > self.from[E]
+    }
+
+    /// Straight line distance travelled by the head
+    pub fn distance(&self) -> f32 {
+        (X..=Z)
+            .map(|axis| (self.to[axis] - self.from[axis]).powi(2))
+            .sum::<f32>()
+            .sqrt()
+    }
+}
+
+/// State of a single extruder/hotend on the machine
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tool {
+    pub hotend_target: Option<f32>,
+    /// Offset of this tool in X, Y and Z from the first tool
+    pub offset: [f32; 3],
+}
+
+/// Machine state as understood by following a stream of G-code lines
+#[derive(Debug, Clone, PartialEq)]
+pub struct MachineState {
+    pub position: [f32; 4],
+    pub absolute: bool,
+    pub absolute_extrusion: bool,
+    pub feedrate: Option<f32>,
+    pub bed_target: Option<f32>,
+    /// Index of the tool selected by the last `Tn`
+    pub active_tool: usize,
+    /// Every tool seen so far, indexed by tool number
+    pub tools: Vec<Tool>,
+}
+
+impl Default for MachineState {
+    fn default() -> Self {
+        Self {
+            position: [0.0; 4],
+            absolute: true,
+            absolute_extrusion: true,
+            feedrate: None,
+            bed_target: None,
+            active_tool: 0,
+            tools: vec![Tool::default()],
+        }
+    }
+}
+
+impl MachineState {
+    /// State of the currently selected tool
+    pub fn tool(&self) -> &Tool {
+        &self.tools[self.active_tool]
+    }
+
+    /// Hotend target temperature of the currently selected tool
+    pub fn hotend_target(&self) -> Option<f32> {
+        self.tool().hotend_target
+    }
+
+    fn tool_mut(&mut self, index: usize) -> &mut Tool {
+        if index >= self.tools.len() {
+            self.tools.resize_with(index + 1, Default::default);
+        }
+        &mut self.tools[index]
+    }
+
+    /// Tool addressed by a `T` parameter, or the active tool without one
+    fn addressed_tool(&self, line: &Line) -> usize {
+        line.get('T')
+            .map(|tool| tool as usize)
+            .unwrap_or(self.active_tool)
+    }
+
+    fn set_offset(&mut self, tool: usize, line: &Line) {
+        for (axis, letter) in AXES[..E].iter().enumerate() {
+            if let Some(offset) = line.get(*letter) {
+                self.tool_mut(tool).offset[axis] = offset;
+            }
+        }
+    }
+
+    /// Gcodes moving by the given distances from the current position: switch to relative positioning,
+    /// move, then restore the positioning modes the machine was in
+    pub fn relative_move(&self, words: &[Word]) -> Vec<String> {
+        let mut movement = "G0".to_string();
+        for Word { letter, value } in words {
+            movement.push_str(&format!(" {letter}{}", value.unwrap_or_default()));
+        }
+        let mut codes = vec!["G91".to_string(), movement];
+        if self.absolute {
+            codes.push("G90".to_string());
+        }
+        // G90 and G91 set the extruder mode too, which M82 and M83 may have changed on their own
+        match (self.absolute, self.absolute_extrusion) {
+            (true, false) => codes.push("M83".to_string()),
+            (false, true) => codes.push("M82".to_string()),
+            _ => {}
+        }
+        codes
+    }
+
+    /// Gcodes pushing `length` mm of filament through the hotend, or pulling it back if negative,
+    /// switching to relative extrusion for the move if needed
+    pub fn relative_extrude(&self, length: f32, feedrate: f32) -> Vec<String> {
+        let extrude = format!("G1 E{length} F{feedrate}");
+        if self.absolute_extrusion {
+            vec!["M83".to_string(), extrude, "M82".to_string()]
+        } else {
+            vec![extrude]
+        }
+    }
+
+    /// Gcode nudging an axis by `distance` while the machine may be moving, to tune the first layer mid-print.
+    ///
+    /// Uses M290 if the firmware has babystepping, otherwise G92 shifts the coordinates
+    /// so later moves end up `distance` further along the axis.
+    pub fn babystep(&self, axis: usize, distance: f32, native: bool) -> String {
+        let letter = AXES[axis];
+        if native {
+            format!("M290 {letter}{distance}")
+        } else {
+            format!("G92 {letter}{:.3}", self.position[axis] - distance)
+        }
+    }
+
+    /// Where a move on this line would end up, without changing the state
+    fn target(&self, line: &Line) -> [f32; 4] {
+        let mut target = self.position;
+        for (axis, letter) in AXES.into_iter().enumerate() {
+            let Some(value) = line.get(letter) else {
+                continue;
+            };
+            let absolute = if axis == E {
+                self.absolute_extrusion
+            } else {
+                self.absolute
+            };
+            target[axis] = if absolute {
+                value
+            } else {
+                target[axis] + value
+            };
+        }
+        target
+    }
+
+    /// G1 moves of at most `segment_length` mm following a G2 or G3 arc from the current position,
+    /// for firmware that can't do arcs itself.
+    ///
+    /// Arcs in the XY plane are given by the offset of their centre with I and J,
+    /// or by their radius with R, negative for the long way round.
+    /// Z and E change evenly along the arc, so helixes and extruding arcs work too.
+    /// Returns `None` for lines that aren't arcs, or arcs that can't be worked out.
+    pub fn flatten_arc(&self, line: &Line, segment_length: f32) -> Option<Vec<String>> {
+        use std::f32::consts::TAU;
+        let clockwise = match line.command()? {
+            ('G', 2) => true,
+            ('G', 3) => false,
+            _ => return None,
+        };
+        let start = self.position;
+        let end = self.target(line);
+        let (dx, dy) = (end[X] - start[X], end[Y] - start[Y]);
+        let (cx, cy) = match (line.get('I'), line.get('J'), line.get('R')) {
+            (None, None, Some(radius)) => {
+                let chord = dx.hypot(dy);
+                if chord == 0.0 {
+                    return None;
+                }
+                let height = (radius * radius - chord * chord / 4.0).max(0.0).sqrt();
+                let side = if clockwise == (radius < 0.0) {
+                    1.0
+                } else {
+                    -1.0
+                };
+                (
+                    start[X] + dx / 2.0 - side * height * dy / chord,
+                    start[Y] + dy / 2.0 + side * height * dx / chord,
+                )
+            }
+            (None, None, None) => return None,
+            (i, j, _) => (
+                start[X] + i.unwrap_or_default(),
+                start[Y] + j.unwrap_or_default(),
+            ),
+        };
+        let radius = (start[X] - cx).hypot(start[Y] - cy);
+        let from = (start[Y] - cy).atan2(start[X] - cx);
+        let mut sweep = (end[Y] - cy).atan2(end[X] - cx) - from;
+        if clockwise && sweep >= 0.0 {
+            sweep -= TAU;
+        } else if !clockwise && sweep <= 0.0 {
+            sweep += TAU;
+        }
+        if !radius.is_normal() || segment_length <= 0.0 {
+            return None;
+        }
+        let segments = ((sweep.abs() * radius / segment_length).ceil() as usize).max(1);
+        let mut emitted = start;
+        let mut codes = Vec::with_capacity(segments);
+        for segment in 1..=segments {
+            let through = segment as f32 / segments as f32;
+            let mut point = end;
+            if segment < segments {
+                let angle = from + sweep * through;
+                point[X] = cx + radius * angle.cos();
+                point[Y] = cy + radius * angle.sin();
+                point[Z] = start[Z] + (end[Z] - start[Z]) * through;
+                point[E] = start[E] + (end[E] - start[E]) * through;
+            }
+            let mut code = "G1".to_string();
+            for (axis, letter) in AXES.into_iter().enumerate() {
+                if axis > Y && point[axis] == start[axis] {
+                    continue;
+                }
+                let absolute = if axis == E {
+                    self.absolute_extrusion
+                } else {
+                    self.absolute
+                };
+                let places = if axis == E { 5 } else { 3 };
+                let value = if absolute {
+                    point[axis]
+                } else {
+                    // rounded steps add up to the same place the absolute values would
+                    let step = point[axis] - emitted[axis];
+                    let scale = 10f32.powi(places);
+                    (step * scale).round() / scale
+                };
+                emitted[axis] = if absolute {
+                    value
+                } else {
+                    emitted[axis] + value
+                };
+                code.push_str(&format!(" {letter}{value:.*}", places as usize));
+            }
+            if segment == 1 {
+                if let Some(feedrate) = line.get('F') {
+                    code.push_str(&format!(" F{feedrate}"));
+                }
+            }
+            codes.push(code);
+        }
+        Some(codes)
+    }
+
+    /// Put back the positioning and extrusion modes after gcodes that changed them
+    fn restore_modes(&self, codes: &mut Vec<String>) {
+        codes.push(if self.absolute { "G90" } else { "G91" }.to_string());
+        codes.push(
+            if self.absolute_extrusion {
+                "M82"
+            } else {
+                "M83"
+            }
+            .to_string(),
+        );
+    }
+
+    /// Gcodes pulling the filament back by `retract` mm and lifting the nozzle by `lift` mm,
+    /// then moving it to X0 Y0 out of the way of the print, for the print to wait there
+    pub fn park(&self, lift: f32, retract: f32) -> Vec<String> {
+        let mut codes = vec![
+            "M83".to_string(),
+            format!("G1 E-{retract} F{PARK_EXTRUDE_FEEDRATE}"),
+            "G91".to_string(),
+            format!("G0 Z{lift} F{PARK_Z_FEEDRATE}"),
+            "G90".to_string(),
+            format!("G0 X0 Y0 F{PARK_XY_FEEDRATE}"),
+        ];
+        self.restore_modes(&mut codes);
+        codes
+    }
+
+    /// Gcodes bringing a machine that was stopped partway through a print back to this state,
+    /// to carry on from there.
+    ///
+    /// Heats everything to its target, lifts the nozzle by `lift` mm clear of what was printed,
+    /// moves over to the position and lowers back down onto it,
+    /// then sets the extruder position, feedrate and modes.
+    pub fn restore(&self, lift: f32) -> Vec<String> {
+        let targets: Vec<_> = self
+            .tools
+            .iter()
+            .enumerate()
+            .filter_map(|(tool, Tool { hotend_target, .. })| {
+                let target = (*hotend_target)?;
+                Some(if self.tools.len() > 1 {
+                    format!("T{tool} S{target}")
+                } else {
+                    format!("S{target}")
+                })
+            })
+            .collect();
+        let mut codes = vec![];
+        if let Some(bed) = self.bed_target {
+            codes.push(format!("M140 S{bed}"));
+        }
+        codes.extend(targets.iter().map(|target| format!("M104 {target}")));
+        codes.extend([
+            "G91".to_string(),
+            format!("G0 Z{lift} F{PARK_Z_FEEDRATE}"),
+            "G90".to_string(),
+        ]);
+        if let Some(bed) = self.bed_target {
+            codes.push(format!("M190 S{bed}"));
+        }
+        codes.extend(targets.iter().map(|target| format!("M109 {target}")));
+        if self.tools.len() > 1 {
+            codes.push(format!("T{}", self.active_tool));
+        }
+        let [x, y, z, e] = self.position;
+        codes.extend([
+            format!("G0 X{x} Y{y} F{PARK_XY_FEEDRATE}"),
+            format!("G0 Z{z} F{PARK_Z_FEEDRATE}"),
+            format!("G92 E{e}"),
+        ]);
+        if let Some(feedrate) = self.feedrate {
+            codes.push(format!("G0 F{feedrate}"));
+        }
+        self.restore_modes(&mut codes);
+        codes
+    }
+
+    /// Gcodes going back from where `park` left the machine to this state and carrying on from there.
+    ///
+    /// The extruder position is set back too, so filament pushed through while parked,
+    /// like purging after a filament change, doesn't throw off later absolute extrusion.
+    pub fn unpark(&self, retract: f32) -> Vec<String> {
+        let [x, y, z, e] = self.position;
+        let mut codes = vec![
+            "G90".to_string(),
+            format!("G0 X{x} Y{y} F{PARK_XY_FEEDRATE}"),
+            format!("G0 Z{z} F{PARK_Z_FEEDRATE}"),
+            "M83".to_string(),
+            format!("G1 E{retract} F{PARK_EXTRUDE_FEEDRATE}"),
+            format!("G92 E{e}"),
+        ];
+        if let Some(feedrate) = self.feedrate {
+            codes.push(format!("G0 F{feedrate}"));
+        }
+        self.restore_modes(&mut codes);
+        codes
+    }
+
+    /// Update the state from a line, returning the move it made if any
+    pub fn apply(&mut self, line: &Line) -> Option<Move> {
+        let (letter, number) = line.command()?;
+        match (letter, number) {
+            ('G', 0..=3) => {
+                if let Some(feedrate) = line.get('F') {
+                    self.feedrate = Some(feedrate);
+                }
+                let from = self.position;
+                for (axis, letter) in AXES.into_iter().enumerate() {
+                    let Some(value) = line.get(letter) else {
+                        continue;
+                    };
+                    let absolute = if axis == E {
+                        self.absolute_extrusion
+                    } else {
+                        self.absolute
+                    };
+                    if absolute {
+                        self.position[axis] = value;
+                    } else {
+                        self.position[axis] += value;
+                    }
+                }
+                return Some(Move {
+                    from,
+                    to: self.position,
+                    feedrate: self.feedrate,
+                });
+            }
+            ('G', 28) => {
+                let all = !AXES[..E].iter().any(|axis| line.has(*axis));
+                for (axis, letter) in AXES[..E].iter().enumerate() {
+                    if all || line.has(*letter) {
+                        self.position[axis] = 0.0;
+                    }
+                }
+            }
+            ('G', 90) => {
+                self.absolute = true;
+                self.absolute_extrusion = true;
+            }
+            ('G', 91) => {
+                self.absolute = false;
+                self.absolute_extrusion = false;
+            }
+            ('G', 92) => {
+                let all = !AXES.iter().any(|axis| line.has(*axis));
+                for (axis, letter) in AXES.into_iter().enumerate() {
+                    if all {
+                        self.position[axis] = 0.0;
+                    } else if let Some(value) = line.get(letter) {
+                        self.position[axis] = value;
+                    }
+                }
+            }
+            ('M', 82) => self.absolute_extrusion = true,
+            ('M', 83) => self.absolute_extrusion = false,
+            ('G', 10) if line.has('P') && !line.has('L') => {
+                // RepRapFirmware style tool offset
+                let tool = line.get('P').unwrap_or_default() as usize;
+                self.set_offset(tool, line);
+            }
+            ('M', 104 | 109) => {
+                if let Some(temp) = line.get('S').or(line.get('R')) {
+                    let tool = self.addressed_tool(line);
+                    self.tool_mut(tool).hotend_target = Some(temp);
+                }
+            }
+            ('M', 218) => {
+                let tool = self.addressed_tool(line);
+                self.set_offset(tool, line);
+            }
+            ('T', tool) => {
+                let tool = tool as usize;
+                self.tool_mut(tool);
+                self.active_tool = tool;
+            }
+            ('M', 140 | 190) => {
+                if let Some(temp) = line.get('S').or(line.get('R')) {
+                    self.bed_target = Some(temp);
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+/// Changes to where a file is printed on the bed, applied to every line as it is sent
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    /// Distance in mm added to X, Y and Z, after scaling and mirroring
+    pub offset: [f32; 3],
+    /// Size of the print, around `centre` in X and Y and up from the bed in Z.
+    /// Extrusion is scaled to match, keeping lines as wide as they were
+    pub scale: f32,
+    /// Whether to flip the print over in X and in Y, around `centre`
+    pub mirror: [bool; 2],
+    /// X and Y the print is scaled and mirrored around
+    pub centre: [f32; 2],
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            offset: [0.0; 3],
+            scale: 1.0,
+            mirror: [false; 2],
+            centre: [0.0; 2],
+        }
+    }
+}
+
+/// Round a position to what is worth sending, so scaling doesn't leave long fractions
+fn round_to(value: f32, places: i32) -> f32 {
+    let scale = 10f32.powi(places);
+    (value * scale).round() / scale
+}
+
+impl Transform {
+    /// Check if this leaves every line as it is
+    pub fn is_identity(&self) -> bool {
+        self.offset == [0.0; 3] && self.scale == 1.0 && self.mirror == [false; 2]
+    }
+
+    /// Scale and mirror around the middle of the extruding moves in a file, so the print stays in place
+    pub fn around_print<'a>(mut self, lines: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut state = MachineState::default();
+        let mut bounds: Option<[f32; 4]> = None;
+        for line in lines.into_iter().filter_map(sendable) {
+            let Some(moved) = state.apply(&parse_line(line)) else {
+                continue;
+            };
+            if !moved.is_extruding() {
+                continue;
+            }
+            for point in [moved.from, moved.to] {
+                let [min_x, min_y, max_x, max_y] =
+                    bounds.get_or_insert([point[X], point[Y], point[X], point[Y]]);
+                *min_x = min_x.min(point[X]);
+                *min_y = min_y.min(point[Y]);
+                *max_x = max_x.max(point[X]);
+                *max_y = max_y.max(point[Y]);
+            }
+        }
+        if let Some([min_x, min_y, max_x, max_y]) = bounds {
+            self.centre = [(min_x + max_x) / 2.0, (min_y + max_y) / 2.0];
+        }
+        self
+    }
+
+    /// Where an X or Y position ends up
+    fn planar(&self, axis: usize, value: f32, absolute: bool) -> f32 {
+        let scale = if self.mirror[axis] {
+            -self.scale
+        } else {
+            self.scale
+        };
+        if absolute {
+            self.centre[axis] + (value - self.centre[axis]) * scale + self.offset[axis]
+        } else {
+            value * scale
+        }
+    }
+
+    /// A line with its positions changed, following the modes in `state`.
+    ///
+    /// Moves, arcs and G92 are changed, anything else is left as it is.
+    /// Mirroring in only one of X and Y turns arcs the other way.
+    pub fn apply(&self, line: &Line, state: &MachineState) -> Line {
+        let mut line = line.clone();
+        let absolute = match line.command() {
+            Some(('G', 0..=3)) => state.absolute,
+            Some(('G', 92)) => {
+                if line.params().is_empty() {
+                    line.words.extend(AXES.map(|letter| Word {
+                        letter,
+                        value: Some(0.0),
+                    }));
+                }
+                true
+            }
+            _ => return line,
+        };
+        if self.mirror[X] != self.mirror[Y] {
+            match line.command() {
+                Some(('G', 2)) => line.words[0].value = Some(3.0),
+                Some(('G', 3)) => line.words[0].value = Some(2.0),
+                _ => {}
+            }
+        }
+        for word in line.words.iter_mut().skip(1) {
+            let Some(value) = word.value.as_mut() else {
+                continue;
+            };
+            *value = match word.letter {
+                'X' => round_to(self.planar(X, *value, absolute), 3),
+                'Y' => round_to(self.planar(Y, *value, absolute), 3),
+                'Z' if absolute => round_to(*value * self.scale + self.offset[Z], 3),
+                'Z' | 'R' => round_to(*value * self.scale, 3),
+                'I' => round_to(self.planar(X, *value, false), 3),
+                'J' => round_to(self.planar(Y, *value, false), 3),
+                // lines get longer and layers taller, both needing more filament
+                'E' => round_to(*value * self.scale * self.scale, 5),
+                _ => continue,
+            };
+        }
+        line
+    }
+}
+
+/// Rewrites moves streamed to firmware without M220 and M221, for a speed and flow other than the file's.
+///
+/// Follows the positions in the file itself, so extrusion can be scaled in absolute mode too
+/// by keeping track of how far the printer's extruder has really gone.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RateRewriter {
+    file: MachineState,
+    /// Extruder position sent to the printer
+    extruded: f32,
+    /// Feedrate last sent to the printer
+    feedrate: Option<f32>,
+}
+
+impl RateRewriter {
+    /// A line of the file changed for `speed` and `flow` as fractions of the file's, 1.0 leaving them as they are
+    pub fn apply(&mut self, line: &Line, speed: f32, flow: f32) -> Line {
+        let mut line = line.clone();
+        let from = self.file.position[E];
+        let absolute_extrusion = self.file.absolute_extrusion;
+        self.file.apply(&line);
+        match line.command() {
+            Some(('G', 0..=3)) => {
+                if line.has('E') {
+                    let extruded = (self.file.position[E] - from) * flow;
+                    self.extruded += extruded;
+                    let value = if absolute_extrusion {
+                        self.extruded
+                    } else {
+                        extruded
+                    };
+                    set_word(&mut line, 'E', round_to(value, 5));
+                }
+                let feedrate = self
+                    .file
+                    .feedrate
+                    .map(|feedrate| round_to(feedrate * speed, 1));
+                if feedrate != self.feedrate {
+                    if let Some(feedrate) = feedrate {
+                        set_word(&mut line, 'F', feedrate);
+                    }
+                    self.feedrate = feedrate;
+                }
+            }
+            Some(('G', 92)) => self.extruded = self.file.position[E],
+            _ => {}
+        }
+        line
+    }
+}
+
+/// Change the value of a parameter, adding it if the line doesn't have it
+fn set_word(line: &mut Line, letter: char, value: f32) {
+    match line
+        .words
+        .iter_mut()
+        .skip(1)
+        .find(|word| word.letter == letter)
+    {
+        Some(word) => word.value = Some(value),
+        None => line.words.push(Word {
+            letter,
+            value: Some(value),
+        }),
+    }
+}
+
+/// Why a print should stop at a line and wait for the user, if it should:
+/// a `;PAUSE` comment, M0 or M1 waiting for the user, or M600 changing filament
+pub fn pause_marker(line: &str) -> Option<&'static str> {
+    if let Some((_, comment)) = line.split_once(';') {
+        if comment
+            .trim_start()
+            .to_ascii_uppercase()
+            .starts_with("PAUSE")
+        {
+            return Some("at a pause in the file");
+        }
+    }
+    let code = parse_line(sendable(line)?);
+    match code.command()? {
+        ('M', 0 | 1) => Some("for the user"),
+        ('M', 600) => Some("for a filament change"),
+        _ => None,
+    }
+}
+
+/// Follows the layer change comments slicers put in a file.
+///
+/// Layers are counted from 1, whether the slicer numbers them like Cura's `;LAYER:0`
+/// or just marks each change like PrusaSlicer's `;LAYER_CHANGE`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LayerCounter {
+    layer: usize,
+}
+
+impl LayerCounter {
+    /// Number of the layer the lines so far have started, 0 before the first
+    pub fn layer(&self) -> usize {
+        self.layer
+    }
+
+    /// Follow a line of the file, returning the number of the layer it starts if it is a layer change
+    pub fn update(&mut self, line: &str) -> Option<usize> {
+        let comment = line.trim_start().strip_prefix(';')?.trim();
+        if comment.eq_ignore_ascii_case("LAYER_CHANGE") {
+            self.layer += 1;
+        } else {
+            let number = comment.strip_prefix("LAYER:")?;
+            self.layer = number.trim().parse::<usize>().ok()? + 1;
+        }
+        Some(self.layer)
+    }
+}
+
+/// A heater a sequence can wait on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Heater {
+    Hotend,
+    Bed,
+    Chamber,
+}
+
+/// A step of a macro or repeat run by the host instead of being sent, starting with `@`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HostStep {
+    /// `@delay 5s`, wait before sending the next step; `ms`, `s`, `m` or `h`, seconds without a unit
+    Delay(Duration),
+    /// `@waittemp hotend 200`, wait until the heater reports the temperature
+    WaitTemp(Heater, f32),
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("`{0}` is not a host step, use `@delay <time>` or `@waittemp <hotend|bed|chamber> <temperature>`")]
+pub struct BadHostStep(pub String);
+
+impl HostStep {
+    /// Read a step of a sequence, `None` if it is meant for the printer
+    pub fn parse(step: &str) -> Option<Result<Self, BadHostStep>> {
+        let body = step.trim().strip_prefix('@')?;
+        let bad = || BadHostStep(step.trim().to_string());
+        let mut words = body.split_whitespace();
+        let name = words.next().unwrap_or_default().to_ascii_lowercase();
+        let args: Vec<_> = words.map(str::to_ascii_lowercase).collect();
+        let parsed = match (name.as_str(), args.as_slice()) {
+            ("delay", [time]) => parse_delay(time).map(HostStep::Delay),
+            ("waittemp", [heater, temperature]) => {
+                let heater = match heater.as_str() {
+                    "hotend" => Some(Heater::Hotend),
+                    "bed" => Some(Heater::Bed),
+                    "chamber" => Some(Heater::Chamber),
+                    _ => None,
+                };
+                heater
+                    .zip(temperature.parse().ok())
+                    .map(|(heater, temperature)| HostStep::WaitTemp(heater, temperature))
+            }
+            _ => None,
+        };
+        Some(parsed.ok_or_else(bad))
+    }
+}
+
+fn parse_delay(time: &str) -> Option<Duration> {
+    let split = time
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(time.len());
+    let (amount, unit) = time.split_at(split);
+    let scale = match unit {
+        "ms" => 0.001,
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        _ => return None,
+    };
+    Duration::try_from_secs_f32(amount.parse::<f32>().ok()? * scale).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn words() {
+        let line = parse_line("N12 G1 X10.5 Y-3 E.4 F1500 *57 ; move");
+        assert_eq!(line.command(), Some(('G', 1)));
+        assert_eq!(line.get('X'), Some(10.5));
+        assert_eq!(line.get('Y'), Some(-3.0));
+        assert_eq!(line.get('E'), Some(0.4));
+        assert_eq!(line.get('F'), Some(1500.0));
+        assert_eq!(line.get('Z'), None);
+    }
+
+    #[test]
+    fn unspaced_words() {
+        let line = parse_line("g7x1y2z3");
+        assert!(line.is('G', 7));
+        assert_eq!(line.get('Z'), Some(3.0));
+    }
+
+    #[test]
+    fn valueless_params() {
+        let line = parse_line("G28 X Y");
+        assert!(line.has('X'));
+        assert!(line.has('Y'));
+        assert!(!line.has('Z'));
+        assert_eq!(line.get('X'), None);
+    }
+
+    #[test]
+    fn text_commands() {
+        let line = parse_line("M117 X marks the spot");
+        assert!(line.is('M', 117));
+        assert!(line.params().is_empty());
+    }
+
+    #[test]
+    fn framed_lines() {
+        assert_eq!(strip_framing("N12 G1 X10*57"), "G1 X10");
+        assert_eq!(strip_framing("n3G28 * 18 "), "G28");
+        assert_eq!(strip_framing("M117 5*3=15"), "M117 5*3=15");
+        assert_eq!(sendable("N4 G1 X1 E2*33 ; extrude"), Some("G1 X1 E2"));
+        assert_eq!(sendable("N0 M110 N0*125"), None);
+        assert_eq!(sendable("   ; comment"), None);
+    }
+
+    #[test]
+    fn comment_only() {
+        let line = parse_line("; just a comment");
+        assert_eq!(line.command(), None);
+        assert!(line.words.is_empty());
+    }
+
+    #[test]
+    fn track_moves() {
+        let mut state = MachineState::default();
+        let moved = state.apply(&parse_line("G1 X10 Y10 E1 F600")).unwrap();
+        assert!(moved.is_extruding());
+        assert_eq!(state.position, [10.0, 10.0, 0.0, 1.0]);
+        state.apply(&parse_line("G91"));
+        let moved = state.apply(&parse_line("G1 X5 E-1")).unwrap();
+        assert!(!moved.is_extruding());
+        assert_eq!(moved.distance(), 5.0);
+        assert_eq!(state.position, [15.0, 10.0, 0.0, 0.0]);
+        assert_eq!(state.feedrate, Some(600.0));
+    }
+
+    #[test]
+    fn relative_moves() {
+        let words = parse_line("G0 X10 Y-5 F3000").words[1..].to_vec();
+        let mut state = MachineState::default();
+        assert_eq!(
+            state.relative_move(&words),
+            ["G91", "G0 X10 Y-5 F3000", "G90"]
+        );
+        state.apply(&parse_line("M83"));
+        assert_eq!(
+            state.relative_move(&words),
+            ["G91", "G0 X10 Y-5 F3000", "G90", "M83"]
+        );
+        state.apply(&parse_line("G91"));
+        assert_eq!(state.relative_move(&words), ["G91", "G0 X10 Y-5 F3000"]);
+        for code in state.relative_move(&words) {
+            state.apply(&parse_line(&code));
+        }
+        assert!(!state.absolute);
+    }
+
+    #[test]
+    fn relative_extrusion() {
+        let mut state = MachineState::default();
+        assert_eq!(
+            state.relative_extrude(5.0, 300.0),
+            ["M83", "G1 E5 F300", "M82"]
+        );
+        state.apply(&parse_line("G1 E10"));
+        for code in state.relative_extrude(-2.5, 1800.0) {
+            state.apply(&parse_line(&code));
+        }
+        assert_eq!(state.position[E], 7.5);
+        assert!(state.absolute_extrusion);
+        state.apply(&parse_line("M83"));
+        assert_eq!(state.relative_extrude(-2.5, 1800.0), ["G1 E-2.5 F1800"]);
+    }
+
+    #[test]
+    fn babysteps() {
+        let mut state = MachineState::default();
+        state.apply(&parse_line("G1 Z0.25"));
+        assert_eq!(state.babystep(Z, 0.05, true), "M290 Z0.05");
+        let shift = state.babystep(Z, 0.05, false);
+        assert_eq!(shift, "G92 Z0.200");
+        state.apply(&parse_line(&shift));
+        state.apply(&parse_line("G1 Z0.3"));
+        assert_eq!(state.position[Z], 0.3);
+    }
+
+    #[test]
+    fn track_homing_and_temps() {
+        let mut state = MachineState::default();
+        state.apply(&parse_line("G92 X5 Y5 Z5"));
+        state.apply(&parse_line("G28 Z"));
+        assert_eq!(state.position, [5.0, 5.0, 0.0, 0.0]);
+        state.apply(&parse_line("M109 S215"));
+        state.apply(&parse_line("M140 S60"));
+        assert_eq!(state.hotend_target(), Some(215.0));
+        assert_eq!(state.bed_target, Some(60.0));
+    }
+
+    #[test]
+    fn track_tools() {
+        let mut state = MachineState::default();
+        state.apply(&parse_line("M104 S200"));
+        state.apply(&parse_line("M104 T1 S240"));
+        state.apply(&parse_line("M218 T1 X25.5 Y0.2"));
+        assert_eq!(state.active_tool, 0);
+        assert_eq!(state.hotend_target(), Some(200.0));
+        state.apply(&parse_line("T1"));
+        assert_eq!(state.active_tool, 1);
+        assert_eq!(state.hotend_target(), Some(240.0));
+        assert_eq!(state.tool().offset, [25.5, 0.2, 0.0]);
+        state.apply(&parse_line("G10 P1 Z-0.1"));
+        assert_eq!(state.tool().offset, [25.5, 0.2, -0.1]);
+        state.apply(&parse_line("T3"));
+        assert_eq!(state.tools.len(), 4);
+        assert_eq!(state.hotend_target(), None);
+    }
+
+    #[test]
+    fn park_and_unpark() {
+        let mut state = MachineState::default();
+        state.apply(&parse_line("G1 X100 Y80.5 Z2.4 E153.2 F1800"));
+        assert_eq!(
+            state.park(10.0, 2.0),
+            [
+                "M83",
+                "G1 E-2 F1800",
+                "G91",
+                "G0 Z10 F600",
+                "G90",
+                "G0 X0 Y0 F6000",
+                "G90",
+                "M82"
+            ]
+        );
+        assert_eq!(
+            state.unpark(2.0),
+            [
+                "G90",
+                "G0 X100 Y80.5 F6000",
+                "G0 Z2.4 F600",
+                "M83",
+                "G1 E2 F1800",
+                "G92 E153.2",
+                "G0 F1800",
+                "G90",
+                "M82"
+            ]
+        );
+        state.apply(&parse_line("M83"));
+        assert_eq!(state.park(10.0, 2.0).last().unwrap(), "M83");
+    }
+
+    #[test]
+    fn restore_partway() {
+        let mut state = MachineState::default();
+        for line in [
+            "M140 S60",
+            "M104 S210",
+            "G28",
+            "M83",
+            "G1 X20 Y30 Z1.2 E0.5 F1200",
+        ] {
+            state.apply(&parse_line(line));
+        }
+        assert_eq!(
+            state.restore(10.0),
+            [
+                "M140 S60",
+                "M104 S210",
+                "G91",
+                "G0 Z10 F600",
+                "G90",
+                "M190 S60",
+                "M109 S210",
+                "G0 X20 Y30 F6000",
+                "G0 Z1.2 F600",
+                "G92 E0.5",
+                "G0 F1200",
+                "G90",
+                "M83"
+            ]
+        );
+        state.apply(&parse_line("M104 T1 S240"));
+        state.apply(&parse_line("T1"));
+        let codes = state.restore(10.0);
+        assert!(codes.contains(&"M109 T1 S240".to_string()));
+        assert!(codes.contains(&"T1".to_string()));
+    }
+
+    #[test]
+    fn flatten_arcs() {
+        let mut state = MachineState::default();
+        state.apply(&parse_line("G1 X10 Y0 F3000"));
+        let quarter = state
+            .flatten_arc(&parse_line("G3 X0 Y10 I-10 J0 E1.6 F1200"), 1.0)
+            .unwrap();
+        // a quarter of a 10mm radius circle is 15.7mm long
+        assert_eq!(quarter.len(), 16);
+        assert_eq!(quarter[0], "G1 X9.952 Y0.980 E0.10000 F1200");
+        assert_eq!(quarter[15], "G1 X0.000 Y10.000 E1.60000");
+
+        let half = state.flatten_arc(&parse_line("G2 X20 Y0 R5"), 1.0).unwrap();
+        assert_eq!(half.len(), 16);
+        assert_eq!(half[7], "G1 X15.000 Y5.000");
+        assert_eq!(state.flatten_arc(&parse_line("G1 X5"), 1.0), None);
+        assert_eq!(state.flatten_arc(&parse_line("G2 X20"), 1.0), None);
+
+        state.apply(&parse_line("G91"));
+        let relative = state
+            .flatten_arc(&parse_line("G3 X-10 Y10 I-10 J0"), 1.0)
+            .unwrap();
+        let moved: f32 = relative
+            .iter()
+            .map(|code| parse_line(code).get('X').unwrap())
+            .sum();
+        assert!((moved + 10.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn transform_lines() {
+        let mut state = MachineState::default();
+        let offset = Transform {
+            offset: [10.0, 5.0, 0.0],
+            ..Default::default()
+        };
+        assert_eq!(
+            offset
+                .apply(&parse_line("G1 X20 Y20 E1.5 F1200"), &state)
+                .to_string(),
+            "G1 X30 Y25 E1.5 F1200"
+        );
+        assert_eq!(
+            offset.apply(&parse_line("G92"), &state).to_string(),
+            "G92 X10 Y5 Z0 E0"
+        );
+        assert_eq!(
+            offset.apply(&parse_line("M104 S200"), &state).to_string(),
+            "M104 S200"
+        );
+
+        let mirrored = Transform {
+            mirror: [true, false],
+            scale: 2.0,
+            ..Default::default()
+        }
+        .around_print(["G1 X10 Y10", "G1 X20 Y30 E1", "G1 X50 Y50"]);
+        assert_eq!(mirrored.centre, [15.0, 20.0]);
+        assert_eq!(
+            mirrored
+                .apply(&parse_line("G2 X20 Y30 Z0.2 I-5 J0 E1"), &state)
+                .to_string(),
+            "G3 X5 Y40 Z0.4 I10 J0 E4"
+        );
+        state.apply(&parse_line("G91"));
+        assert_eq!(
+            mirrored.apply(&parse_line("G1 X1 Y1"), &state).to_string(),
+            "G1 X-2 Y2"
+        );
+        assert!(Transform::default().is_identity());
+        assert!(!mirrored.is_identity());
+    }
+
+    #[test]
+    fn rewrite_rates() {
+        let mut rewriter = RateRewriter::default();
+        let unchanged = parse_line("G1 X10 E1 F1200");
+        assert_eq!(rewriter.apply(&unchanged, 1.0, 1.0), unchanged);
+        let unchanged = parse_line("G1 X20 E2");
+        assert_eq!(rewriter.apply(&unchanged, 1.0, 1.0), unchanged);
+        // absolute extrusion carries on from what was really pushed out
+        assert_eq!(
+            rewriter
+                .apply(&parse_line("G1 X30 E3"), 1.5, 0.5)
+                .to_string(),
+            "G1 X30 E2.5 F1800"
+        );
+        assert_eq!(
+            rewriter
+                .apply(&parse_line("G1 X40 E4"), 1.5, 0.5)
+                .to_string(),
+            "G1 X40 E3"
+        );
+        rewriter.apply(&parse_line("G92 E0"), 1.5, 0.5);
+        assert_eq!(
+            rewriter
+                .apply(&parse_line("G1 X50 E1"), 1.0, 1.0)
+                .to_string(),
+            "G1 X50 E1 F1200"
+        );
+        rewriter.apply(&parse_line("M83"), 1.0, 1.0);
+        assert_eq!(
+            rewriter
+                .apply(&parse_line("G1 X60 E0.4"), 1.0, 1.1)
+                .to_string(),
+            "G1 X60 E0.44"
+        );
+    }
+
+    #[test]
+    fn pause_markers() {
+        assert_eq!(pause_marker(";PAUSE"), Some("at a pause in the file"));
+        assert_eq!(
+            pause_marker("; pause_print"),
+            Some("at a pause in the file")
+        );
+        assert_eq!(pause_marker("M0 ; wait"), Some("for the user"));
+        assert_eq!(pause_marker("M1"), Some("for the user"));
+        assert_eq!(pause_marker("M600"), Some("for a filament change"));
+        assert_eq!(pause_marker("M104 S200"), None);
+        assert_eq!(pause_marker("; printing object"), None);
+    }
+
+    #[test]
+    fn count_layers() {
+        let mut cura = LayerCounter::default();
+        assert_eq!(cura.update(";LAYER_COUNT:20"), None);
+        assert_eq!(cura.update(";LAYER:0"), Some(1));
+        assert_eq!(cura.update("G1 X1"), None);
+        assert_eq!(cura.update(";LAYER:1"), Some(2));
+        assert_eq!(cura.layer(), 2);
+
+        let mut prusa = LayerCounter::default();
+        assert_eq!(prusa.update(";LAYER_CHANGE"), Some(1));
+        assert_eq!(prusa.update(";Z:0.4"), None);
+        assert_eq!(prusa.update(";LAYER_CHANGE"), Some(2));
+    }
+
+    #[test]
+    fn host_steps() {
+        assert_eq!(
+            HostStep::parse("@delay 5s"),
+            Some(Ok(HostStep::Delay(Duration::from_secs(5))))
+        );
+        assert_eq!(
+            HostStep::parse("@DELAY 250MS"),
+            Some(Ok(HostStep::Delay(Duration::from_millis(250))))
+        );
+        assert_eq!(
+            HostStep::parse("@delay 2m"),
+            Some(Ok(HostStep::Delay(Duration::from_secs(120))))
+        );
+        assert_eq!(
+            HostStep::parse("@WAITTEMP HOTEND 200"),
+            Some(Ok(HostStep::WaitTemp(Heater::Hotend, 200.0)))
+        );
+        assert!(matches!(
+            HostStep::parse("@waittemp nozzle 200"),
+            Some(Err(_))
+        ));
+        assert!(matches!(HostStep::parse("@delay soon"), Some(Err(_))));
+        assert_eq!(HostStep::parse("M104 S200"), None);
+    }
+}
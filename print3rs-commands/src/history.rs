@@ -0,0 +1,171 @@
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+};
+
+/// Most entries kept before the oldest are forgotten
+pub const HISTORY_LIMIT: usize = 1000;
+
+/// Where the history is kept between runs, in the platform's data directory
+pub fn history_file() -> Option<PathBuf> {
+    directories_next::ProjectDirs::from("dev", "arades", "print3rs")
+        .map(|dirs| dirs.data_dir().join("history.txt"))
+}
+
+/// Previously submitted console commands, oldest first, without duplicates
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct History {
+    entries: VecDeque<String>,
+    /// File the history is written to whenever it changes, kept only in memory if `None`
+    file: Option<PathBuf>,
+}
+
+impl History {
+    /// A history kept only in memory
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The history saved in `file` by earlier runs, saved back there whenever it changes.
+    ///
+    /// Starts empty if the file can't be read, such as on the first run.
+    pub fn load(file: impl Into<PathBuf>) -> Self {
+        let file = file.into();
+        let mut history = Self::new();
+        if let Ok(saved) = std::fs::read_to_string(&file) {
+            for line in saved.lines() {
+                history.push(line);
+            }
+        }
+        history.file = Some(file);
+        history
+    }
+
+    /// The history saved in the platform's data directory, or one kept in memory if there is none
+    pub fn load_default() -> Self {
+        history_file().map(Self::load).unwrap_or_default()
+    }
+
+    /// File the history is saved in, if any
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    fn save(&self, file: &Path) -> std::io::Result<()> {
+        if let Some(dir) = file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut saved = String::new();
+        for entry in self.entries.iter() {
+            saved.push_str(entry);
+            saved.push('\n');
+        }
+        std::fs::write(file, saved)
+    }
+
+    /// Remember a submitted line, returns false if it was already in the history
+    pub fn push(&mut self, line: &str) -> bool {
+        if line.is_empty() || self.entries.iter().any(|entry| entry == line) {
+            return false;
+        }
+        self.entries.push_back(line.to_owned());
+        if self.entries.len() > HISTORY_LIMIT {
+            self.entries.pop_front();
+        }
+        if let Some(file) = &self.file {
+            if let Err(e) = self.save(file) {
+                tracing::warn!("could not save history to {}: {e}", file.display());
+            }
+        }
+        true
+    }
+
+    /// All entries as one slice, oldest first
+    pub fn entries(&mut self) -> &[String] {
+        self.entries.make_contiguous()
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &String> {
+        self.entries.iter()
+    }
+
+    /// Entries containing `text`, ignoring case, numbered from 1 for the oldest entry
+    pub fn search<'a>(&'a self, text: &str) -> impl Iterator<Item = (usize, &'a String)> {
+        let text = text.to_lowercase();
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(move |(_, entry)| entry.to_lowercase().contains(&text))
+            .map(|(index, entry)| (index + 1, entry))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn no_duplicates() {
+        let mut history = History::new();
+        assert!(history.push("G28"));
+        assert!(history.push("M105"));
+        assert!(!history.push("G28"));
+        assert!(!history.push(""));
+        assert_eq!(history.entries(), ["G28", "M105"]);
+    }
+
+    #[test]
+    fn limited() {
+        let mut history = History::new();
+        for i in 0..=HISTORY_LIMIT {
+            history.push(&format!("G4 P{i}"));
+        }
+        assert_eq!(history.len(), HISTORY_LIMIT);
+        assert_eq!(history.iter().next().unwrap(), "G4 P1");
+    }
+
+    #[test]
+    fn search() {
+        let mut history = History::new();
+        for line in [
+            "G28",
+            "connect serial COM3",
+            "M104 S210",
+            "Connect tcp printer",
+        ] {
+            history.push(line);
+        }
+        let found: Vec<_> = history.search("CONNECT").collect();
+        assert_eq!(
+            found,
+            [
+                (2, &"connect serial COM3".to_string()),
+                (4, &"Connect tcp printer".to_string())
+            ]
+        );
+        assert_eq!(history.search("").count(), 4);
+    }
+
+    #[test]
+    fn saved_between_runs() {
+        let file = std::env::temp_dir()
+            .join(format!("print3rs-history-{}", std::process::id()))
+            .join("history.txt");
+        let mut history = History::load(&file);
+        assert!(history.is_empty());
+        history.push("G28");
+        history.push("M105");
+        let mut loaded = History::load(&file);
+        assert_eq!(loaded.entries(), ["G28", "M105"]);
+        assert_eq!(loaded.file(), Some(file.as_path()));
+        std::fs::remove_dir_all(file.parent().unwrap()).unwrap();
+    }
+}
@@ -1,4 +1,10 @@
 pub mod commander;
 pub mod commands;
+pub mod config;
+pub mod eta;
+pub mod gcode;
+pub mod history;
+pub mod profiles;
 pub mod response;
 pub mod tasks;
+pub mod transport;
@@ -0,0 +1,149 @@
+use {
+    crate::commands::connect::{parse_protocol, Connection},
+    std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+    },
+    winnow::Parser,
+};
+
+/// Where connection profiles are kept between runs, in the platform's config directory
+pub fn profiles_file() -> Option<PathBuf> {
+    directories_next::ProjectDirs::from("dev", "arades", "print3rs")
+        .map(|dirs| dirs.config_dir().join("profiles.txt"))
+}
+
+/// Connections saved under a name with `profile save`, to be used again with `connect @<name>`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profiles {
+    profiles: BTreeMap<String, Connection<String>>,
+    /// File the profiles are written to whenever they change, kept only in memory if `None`
+    file: Option<PathBuf>,
+}
+
+impl Profiles {
+    /// Profiles kept only in memory
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The profiles saved in `file` by earlier runs, saved back there whenever they change.
+    ///
+    /// Each line of the file is a name followed by the arguments `connect` would be given,
+    /// lines which can't be parsed are skipped. Starts empty if the file can't be read.
+    pub fn load(file: impl Into<PathBuf>) -> Self {
+        let file = file.into();
+        let mut profiles = Self::new();
+        if let Ok(saved) = std::fs::read_to_string(&file) {
+            for line in saved.lines().map(str::trim).filter(|line| !line.is_empty()) {
+                let (name, connection) = line.split_once(' ').unwrap_or((line, ""));
+                match parse_protocol.parse(connection) {
+                    Ok(connection) => {
+                        profiles
+                            .profiles
+                            .insert(name.to_owned(), connection.into_owned());
+                    }
+                    Err(e) => tracing::warn!("skipping connection profile `{name}`: {e}"),
+                }
+            }
+        }
+        profiles.file = Some(file);
+        profiles
+    }
+
+    /// The profiles saved in the platform's config directory, or ones kept in memory if there is none
+    pub fn load_default() -> Self {
+        profiles_file().map(Self::load).unwrap_or_default()
+    }
+
+    /// File the profiles are saved in, if any
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    fn save(&self) -> std::io::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        if let Some(dir) = file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut saved = String::new();
+        for (name, connection) in self.profiles.iter() {
+            saved.push_str(&format!("{name} {connection}\n"));
+        }
+        std::fs::write(file, saved)
+    }
+
+    /// Connection saved under `name`
+    pub fn get(&self, name: &str) -> Option<&Connection<String>> {
+        self.profiles.get(name)
+    }
+
+    /// Save a connection under `name`, replacing any saved under the same name before
+    pub fn insert(&mut self, name: &str, connection: Connection<String>) -> std::io::Result<()> {
+        self.profiles.insert(name.to_owned(), connection);
+        self.save()
+    }
+
+    /// Forget the connection saved under `name`, returns it if there was one
+    pub fn remove(&mut self, name: &str) -> std::io::Result<Option<Connection<String>>> {
+        let removed = self.profiles.remove(name);
+        if removed.is_some() {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    /// Names and connections of every profile, sorted by name
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Connection<String>)> {
+        self.profiles.iter()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.profiles.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.profiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.profiles.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, print3rs_core::Dialect};
+
+    #[test]
+    fn saved_between_runs() {
+        let file = std::env::temp_dir()
+            .join(format!("print3rs-profiles-{}", std::process::id()))
+            .join("profiles.txt");
+        let mut profiles = Profiles::load(&file);
+        assert!(profiles.is_empty());
+        let ender = Connection::Serial {
+            port: "/dev/ttyUSB0".to_string(),
+            baud: Some(115200),
+            dialect: Dialect::Marlin,
+        };
+        let voron = Connection::Mqtt {
+            hostname: "broker.local".to_string(),
+            port: Some(1883),
+            in_topic: Some("voron/in".to_string()),
+            out_topic: Some("voron/out".to_string()),
+        };
+        profiles.insert("ender", ender.clone()).unwrap();
+        profiles.insert("voron", voron.clone()).unwrap();
+
+        let mut loaded = Profiles::load(&file);
+        assert_eq!(loaded.get("ender"), Some(&ender));
+        assert_eq!(loaded.get("voron"), Some(&voron));
+        assert_eq!(loaded.names().collect::<Vec<_>>(), ["ender", "voron"]);
+        assert_eq!(loaded.remove("ender").unwrap(), Some(ender));
+        assert_eq!(Profiles::load(&file).len(), 1);
+        std::fs::remove_dir_all(file.parent().unwrap()).unwrap();
+    }
+}
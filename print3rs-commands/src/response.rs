@@ -1,29 +1,88 @@
 use {
-    crate::commander::ErrorKindOf,
+    crate::commander::{ErrorKindOf, DEFAULT_PRINTER},
     print3rs_core::Printer,
-    std::sync::{Arc, Mutex},
+    std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    },
 };
 
+/// What an output came from, so lines from several printers and tasks can be told apart
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Source {
+    /// The commander itself, like replies to commands
+    #[default]
+    Commander,
+    /// A line received from the printer with the given name
+    Printer(Arc<str>),
+    /// A message logged by the background task with the given name
+    Task(Arc<str>),
+}
+
+impl Source {
+    /// Name to prefix the output with, if it needs one to be told apart from the rest.
+    /// Lines from the default printer are left bare so a single printer reads as before.
+    pub fn label(&self) -> Option<&str> {
+        match self {
+            Source::Commander => None,
+            Source::Printer(name) if &**name == DEFAULT_PRINTER => None,
+            Source::Printer(name) | Source::Task(name) => Some(name),
+        }
+    }
+}
+
+/// Events worth telling the user about even when they aren't watching, like with a desktop notification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Notice {
+    /// A print streamed from a file or the SD card finished
+    PrintFinished,
+    /// A log task couldn't write where it was told to, and stopped
+    LogError,
+    /// The printer's connection was lost
+    Disconnected,
+}
+
 /// Cheaply cloned 'return' of any asynchronous operations triggered by commander.
 /// These are propogated to all subscribers to allow distributed logic for handling responses.
 #[derive(Debug, Clone)]
 pub enum Response {
-    Output(Arc<str>),
+    Output(Source, Arc<str>),
     Error(ErrorKindOf),
-    AutoConnect(Arc<Mutex<Printer>>),
+    /// A printer found by autoconnecting or reconnecting, for the printer with the given name
+    AutoConnect(Arc<str>, Arc<Mutex<Printer>>),
+    /// How far along a print task is
+    Progress {
+        task: Arc<str>,
+        lines_sent: usize,
+        total_lines: usize,
+        /// How much of the file has been sent, from 0 to 100
+        percent: f32,
+        /// Estimated time left, if there is enough to go on yet
+        remaining: Option<Duration>,
+        /// Time since the task started
+        elapsed: Duration,
+    },
+    /// A print task has parked the head and is waiting for `resume`
+    Paused {
+        task: Arc<str>,
+        /// Why it paused, like `for a filament change` or `at layer 12`
+        reason: Arc<str>,
+    },
+    /// Something to notify the user of, sent along with the output or error describing it
+    Notify(Source, Notice, Arc<str>),
     Clear,
     Quit,
 }
 
 impl From<String> for Response {
     fn from(value: String) -> Self {
-        Response::Output(Arc::from(value))
+        Response::Output(Source::Commander, Arc::from(value))
     }
 }
 
 impl<'a> From<&'a str> for Response {
     fn from(value: &'a str) -> Self {
-        Response::Output(Arc::from(value))
+        Response::Output(Source::Commander, Arc::from(value))
     }
 }
 
@@ -32,9 +91,3 @@ impl From<ErrorKindOf> for Response {
         Response::Error(value)
     }
 }
-
-impl From<Printer> for Response {
-    fn from(value: Printer) -> Self {
-        Response::AutoConnect(Arc::new(Mutex::new(value)))
-    }
-}
@@ -1,35 +1,810 @@
 use {
-    crate::commands::log::{get_headers, make_parser, Segment},
-    print3rs_core::{Error as PrinterError, Printer, Socket},
+    crate::{
+        commands::{
+            heightmap,
+            lint::LintRules,
+            log::{
+                get_fields, make_parser, LogFormat, LogSink, LogValue, RecentValues, Rotation,
+                Segment,
+            },
+            sd::{self, SdStatus},
+            status,
+        },
+        eta::{format_duration, Eta},
+        gcode::{
+            parse_line, pause_marker, sendable, Heater, HostStep, LayerCounter, MachineState,
+            RateRewriter, Transform, ARC_SEGMENT_LENGTH,
+        },
+        response::{Notice, Response, Source},
+    },
+    print3rs_core::{Error as PrinterError, Printer, PrinterState, Socket},
     std::{
-        collections::HashMap,
-        time::{SystemTime, UNIX_EPOCH},
+        collections::{BTreeSet, HashMap},
+        fmt::Display,
+        future::Future,
+        sync::{
+            atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    },
+    tokio::{
+        io::AsyncWriteExt,
+        sync::{broadcast, Notify},
+        task::JoinHandle,
     },
-    tokio::{io::AsyncWriteExt, task::JoinHandle},
+    tracing::Instrument,
     winnow::Parser,
 };
 
+#[cfg(feature = "sqlite")]
+use crate::commands::log::SqliteLog;
+#[cfg(feature = "mqtt")]
+use crate::transport::mqtt;
+
+/// How much a background task reports to the console about what it is doing
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    #[default]
+    Quiet,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Verbosity {
+    const ALL: [Verbosity; 4] = [
+        Verbosity::Quiet,
+        Verbosity::Info,
+        Verbosity::Debug,
+        Verbosity::Trace,
+    ];
+
+    /// Name used for this verbosity in commands
+    pub fn name(&self) -> &'static str {
+        match self {
+            Verbosity::Quiet => "off",
+            Verbosity::Info => "info",
+            Verbosity::Debug => "debug",
+            Verbosity::Trace => "trace",
+        }
+    }
+
+    /// Verbosity with the given name, `quiet` is also accepted for `off`
+    pub fn from_name(name: &str) -> Option<Self> {
+        if name.eq_ignore_ascii_case("quiet") {
+            return Some(Verbosity::Quiet);
+        }
+        Self::ALL
+            .into_iter()
+            .find(|verbosity| verbosity.name().eq_ignore_ascii_case(name))
+    }
+}
+
+impl Display for Verbosity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Labelled log for a single background task.
+///
+/// Messages are always emitted as tracing events with the `print3rs::task` target and the task's name,
+/// and are also written to the console when allowed by the task's own verbosity,
+/// so one task can be debugged without turning up logging for everything else.
+#[derive(Debug, Clone)]
+pub struct TaskLog {
+    name: Arc<str>,
+    started: Instant,
+    verbosity: Arc<AtomicU8>,
+    responder: broadcast::Sender<Response>,
+}
+
+impl TaskLog {
+    pub fn new(name: &str, responder: broadcast::Sender<Response>) -> Self {
+        Self {
+            name: Arc::from(name),
+            started: Instant::now(),
+            verbosity: Default::default(),
+            responder,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Time since the task was started
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn verbosity(&self) -> Verbosity {
+        Verbosity::ALL[self.verbosity.load(Ordering::Relaxed) as usize]
+    }
+
+    pub fn set_verbosity(&self, verbosity: Verbosity) {
+        self.verbosity.store(verbosity as u8, Ordering::Relaxed);
+    }
+
+    fn log(&self, verbosity: Verbosity, message: impl Display) {
+        let name = &*self.name;
+        match verbosity {
+            Verbosity::Quiet => return,
+            Verbosity::Info => tracing::info!(target: "print3rs::task", task = name, "{message}"),
+            Verbosity::Debug => tracing::debug!(target: "print3rs::task", task = name, "{message}"),
+            Verbosity::Trace => tracing::trace!(target: "print3rs::task", task = name, "{message}"),
+        }
+        if verbosity <= self.verbosity() {
+            let _ = self.responder.send(Response::Output(
+                Source::Task(self.name.clone()),
+                format!("{message}\n").into(),
+            ));
+        }
+    }
+
+    pub fn info(&self, message: impl Display) {
+        self.log(Verbosity::Info, message)
+    }
+
+    pub fn debug(&self, message: impl Display) {
+        self.log(Verbosity::Debug, message)
+    }
+
+    pub fn trace(&self, message: impl Display) {
+        self.log(Verbosity::Trace, message)
+    }
+
+    /// Log a message like `info`, and send it as a `Notice` for frontends to notify the user of
+    pub fn notify(&self, notice: Notice, message: impl Display) {
+        let message = message.to_string();
+        self.info(&message);
+        let _ = self.responder.send(Response::Notify(
+            Source::Task(self.name.clone()),
+            notice,
+            format!("{}: {message}", self.name).into(),
+        ));
+    }
+
+    /// Report how far along the task is, keeping `tracker` up to date for the `progress` command
+    fn progress(
+        &self,
+        tracker: &Mutex<TaskProgress>,
+        lines_sent: usize,
+        total_lines: usize,
+        remaining: Option<Duration>,
+    ) {
+        let (percent, elapsed) = {
+            let mut tracker = tracker.lock().unwrap();
+            tracker.done = lines_sent;
+            tracker.total = total_lines;
+            tracker.remaining = remaining;
+            (tracker.percent(), tracker.elapsed())
+        };
+        let _ = self.responder.send(Response::Progress {
+            task: self.name.clone(),
+            lines_sent,
+            total_lines,
+            percent,
+            remaining,
+            elapsed,
+        });
+    }
+
+    /// Run a future as this task, inside a tracing span carrying the task's name
+    fn spawn<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let span = tracing::info_span!(target: "print3rs::task", "task", name = &*self.name);
+        tokio::spawn(future.instrument(span))
+    }
+}
+
+/// How far along a print or upload is, shared between the task and the `progress` command
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TaskProgress {
+    /// Lines sent, or bytes printed for prints from the SD card
+    pub done: usize,
+    pub total: usize,
+    pub started: Instant,
+    /// Estimated time left, if there is enough to go on yet
+    pub remaining: Option<Duration>,
+}
+
+impl TaskProgress {
+    fn start() -> Arc<Mutex<Self>> {
+        Arc::new(Mutex::new(Self {
+            done: 0,
+            total: 0,
+            started: Instant::now(),
+            remaining: None,
+        }))
+    }
+
+    /// How much of the work is done, from 0 to 100
+    pub fn percent(&self) -> f32 {
+        if self.total == 0 {
+            100.0
+        } else {
+            self.done as f32 * 100.0 / self.total as f32
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
+impl Display for TaskProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let elapsed = format_duration(self.elapsed());
+        if self.total == 0 {
+            return write!(f, "starting, {elapsed} elapsed");
+        }
+        write!(
+            f,
+            "{:.1}% ({} of {}), {elapsed} elapsed",
+            self.percent(),
+            self.done,
+            self.total
+        )?;
+        if let Some(remaining) = self.remaining {
+            write!(f, ", {} left", format_duration(remaining))?;
+        }
+        Ok(())
+    }
+}
+
+/// Marks the printer as printing for as long as it is held, even if the print task is stopped
+struct Printing(Socket);
+
+impl Printing {
+    fn start(socket: &Socket) -> Self {
+        socket.set_state(PrinterState::Printing);
+        Self(socket.clone())
+    }
+}
+
+impl Drop for Printing {
+    fn drop(&mut self) {
+        self.0.set_state(PrinterState::Idle);
+    }
+}
+
+/// Closes a file being written to the printer's storage with M29 if dropped before it was finished,
+/// so a stopped upload doesn't leave the printer writing every command it receives to the file
+struct Writing(Option<Socket>);
+
+impl Writing {
+    async fn start(socket: &Socket, remote: &str) -> Result<Self, PrinterError> {
+        socket.send(format!("M28 {remote}").as_str()).await?.await?;
+        Ok(Self(Some(socket.clone())))
+    }
+
+    async fn finish(mut self) -> Result<(), PrinterError> {
+        if let Some(socket) = self.0.take() {
+            socket.send("M29").await?.await?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Writing {
+    fn drop(&mut self) {
+        if let Some(socket) = self.0.take() {
+            let _ = socket.try_send_unsequenced("M29");
+        }
+    }
+}
+
+/// Write lines to a file named `remote` on the printer's storage with M28/M29,
+/// waiting for every line to be acknowledged and reporting progress as it goes
+async fn upload_lines(
+    lines: &[&str],
+    remote: &str,
+    socket: &Socket,
+    task_log: &TaskLog,
+    tracker: &Mutex<TaskProgress>,
+) -> Result<(), PrinterError> {
+    let writing = Writing::start(socket, remote).await?;
+    let mut percent_reported = None;
+    for (sent, line) in lines.iter().enumerate() {
+        socket.send(*line).await?.await?;
+        let percent = (sent + 1) * 100 / lines.len();
+        if percent_reported != Some(percent) {
+            percent_reported = Some(percent);
+            task_log.progress(tracker, sent + 1, lines.len(), None);
+        }
+    }
+    writing.finish().await
+}
+
+/// Longest a print goes without reporting its progress
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How far the nozzle is lifted in mm while a print is paused
+const PARK_LIFT: f32 = 10.0;
+/// How much filament is pulled back in mm while a print is paused, to keep it from oozing
+const PARK_RETRACT: f32 = 2.0;
+
+/// Layers prints should pause at, and the prints waiting to be resumed,
+/// shared between the commander and print tasks
+#[derive(Debug, Default)]
+pub struct Pauses {
+    layers: Mutex<BTreeSet<usize>>,
+    /// Set by `pause_now` until a print pauses for it
+    requested: AtomicBool,
+    waiting: AtomicUsize,
+    resume: Notify,
+}
+
+/// Counts a print as waiting until it is resumed or stopped
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Pauses {
+    /// Pause the next print to start `layer`, counting from 1
+    pub fn pause_at(&self, layer: usize) {
+        self.layers.lock().unwrap().insert(layer);
+    }
+
+    /// Pause the running print after the line it is sending
+    pub fn pause_now(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+    }
+
+    /// Stop pausing at any layers, or for a `pause_now` no print has seen yet
+    pub fn clear(&self) {
+        self.layers.lock().unwrap().clear();
+        self.requested.store(false, Ordering::SeqCst);
+    }
+
+    /// Layers prints will pause at, lowest first
+    pub fn layers(&self) -> Vec<usize> {
+        self.layers.lock().unwrap().iter().copied().collect()
+    }
+
+    /// Check if a print should pause at `layer`, which then won't pause the next one
+    fn take_layer(&self, layer: usize) -> bool {
+        self.layers.lock().unwrap().remove(&layer)
+    }
+
+    /// Check if a print should pause for `pause_now`, which then won't pause it again
+    fn take_request(&self) -> bool {
+        self.requested.swap(false, Ordering::SeqCst)
+    }
+
+    /// Check if any print is waiting to be resumed
+    pub fn is_paused(&self) -> bool {
+        self.waiting.load(Ordering::SeqCst) > 0
+    }
+
+    /// Carry on with every paused print, returning false if none were paused
+    pub fn resume(&self) -> bool {
+        let paused = self.is_paused();
+        self.resume.notify_waiters();
+        paused
+    }
+
+    /// Wait for `resume`, calling `paused` once a resume would be seen
+    async fn wait(&self, paused: impl FnOnce()) {
+        let resumed = self.resume.notified();
+        self.waiting.fetch_add(1, Ordering::SeqCst);
+        let _waiting = Waiting(&self.waiting);
+        paused();
+        resumed.await;
+    }
+}
+
+/// Which rate of a print `speed` and `flow` change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rate {
+    Speed,
+    Flow,
+}
+
+impl Rate {
+    fn gcode(self) -> &'static str {
+        match self {
+            Rate::Speed => "M220",
+            Rate::Flow => "M221",
+        }
+    }
+}
+
+/// Speed and flow of prints as fractions of what the file says,
+/// shared between the commander and print tasks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Overrides {
+    pub speed: f32,
+    pub flow: f32,
+    /// Whether streamed lines are rewritten for the speed, for firmware without M220
+    pub rewrite_speed: bool,
+    /// Whether streamed lines are rewritten for the flow, for firmware without M221
+    pub rewrite_flow: bool,
+}
+
+impl Default for Overrides {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            flow: 1.0,
+            rewrite_speed: false,
+            rewrite_flow: false,
+        }
+    }
+}
+
+impl Overrides {
+    /// Speed and flow streamed lines need rewriting for, 1.0 where the firmware takes care of it
+    pub fn rewritten(&self) -> (f32, f32) {
+        (
+            if self.rewrite_speed { self.speed } else { 1.0 },
+            if self.rewrite_flow { self.flow } else { 1.0 },
+        )
+    }
+}
+
+/// Longest the printer is given to answer M220 or M221
+const RATE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Set the speed or flow of prints with M220 or M221, or by rewriting streamed lines
+/// if the firmware says it doesn't know the command.
+///
+/// Returns whether the firmware took it.
+pub async fn set_rate(
+    socket: &Socket,
+    overrides: &Mutex<Overrides>,
+    rate: Rate,
+    percent: f32,
+) -> Result<bool, PrinterError> {
+    let reply = tokio::time::timeout(
+        RATE_TIMEOUT,
+        socket.send_captured(format!("{} S{percent}", rate.gcode())),
+    )
+    .await
+    .map_err(|_| PrinterError::Timeout)?;
+    let native = match reply {
+        Ok(reply) => !reply.iter().any(|line| line.contains("Unknown command")),
+        Err(PrinterError::Rejected(_)) => false,
+        Err(e) => return Err(e),
+    };
+    let mut overrides = overrides.lock().unwrap();
+    match rate {
+        Rate::Speed => {
+            overrides.speed = percent / 100.0;
+            overrides.rewrite_speed = !native;
+        }
+        Rate::Flow => {
+            overrides.flow = percent / 100.0;
+            overrides.rewrite_flow = !native;
+        }
+    }
+    Ok(native)
+}
+
+/// Park the head and wait for the user to `resume`, then put the head back where it was
+async fn pause_print(
+    task: &str,
+    reason: &str,
+    socket: &Socket,
+    machine_state: &Mutex<MachineState>,
+    pauses: &Pauses,
+    responder: &broadcast::Sender<Response>,
+    task_log: &TaskLog,
+) -> Result<(), TaskError> {
+    let state = machine_state.lock().unwrap().clone();
+    for code in state.park(PARK_LIFT, PARK_RETRACT) {
+        socket.send(code).await?.await?;
+    }
+    task_log.info(format_args!("paused {reason}"));
+    pauses
+        .wait(|| {
+            let _ = responder.send(Response::Paused {
+                task: task.into(),
+                reason: reason.into(),
+            });
+        })
+        .await;
+    task_log.info("resuming");
+    for code in state.unpark(PARK_RETRACT) {
+        socket.send(code).await?.await?;
+    }
+    // anything done while parked, like jogging, is undone by going back
+    *machine_state.lock().unwrap() = state;
+    Ok(())
+}
+
 /// Starts a background task which reads a .gcode file and sends the commands in sequence
-pub fn start_print_file(filename: &str, socket: Socket) -> BackgroundTask {
+///
+/// Every line sent is applied to `machine_state` to keep it in step with the printer.
+/// Progress is reported each time another percent of the file has been sent,
+/// and at least every `PROGRESS_INTERVAL` for slow lines like heating or homing,
+/// with an estimate of the time left using any slicer estimates in the file.
+///
+/// The print pauses at `;PAUSE` comments, M0, M1 and M600, which aren't sent,
+/// and at the start of any layers in `pauses`, parking the head and waiting to be resumed.
+///
+/// Starting `from` a line of the file, counting from 1, follows the lines before it without sending them,
+/// then heats up and moves back to where the print was at that line before carrying on.
+/// Every move is changed by the `transform` before it is sent,
+/// and by any speed and flow `overrides` the firmware can't apply itself.
+///
+/// With `flatten_arcs`, G2 and G3 arcs are sent as short G1 moves, for firmware without arc support.
+pub fn start_print_file(
+    filename: &str,
+    PrintOptions { from, transform }: PrintOptions,
+    flatten_arcs: bool,
+    socket: Socket,
+    machine_state: Arc<Mutex<MachineState>>,
+    pauses: Arc<Pauses>,
+    overrides: Arc<Mutex<Overrides>>,
+    responder: broadcast::Sender<Response>,
+    log: TaskLog,
+) -> BackgroundTask {
     let filename = filename.to_owned();
-    let task: JoinHandle<Result<(), TaskError>> = tokio::spawn(async move {
-        if let Ok(file) = tokio::fs::read_to_string(filename).await {
-            for line in file.lines() {
-                let line = match line.split_once(';') {
-                    Some((s, _)) => s,
-                    None => line,
-                };
-                if line.is_empty() {
-                    continue;
+    let task_log = log.clone();
+    let progress = TaskProgress::start();
+    let tracker = progress.clone();
+    let task: JoinHandle<Result<(), TaskError>> = log.spawn(async move {
+        match tokio::fs::read_to_string(&filename).await {
+            Ok(file) => {
+                let skip = from.map_or(0, |line| line - 1);
+                if skip > 0 && skip >= file.lines().count() {
+                    task_log.info(format_args!("{filename} has no line {}", skip + 1));
+                    return Ok(());
+                }
+                task_log.info(format_args!("printing {filename}"));
+                let _printing = Printing::start(&socket);
+                let transform = if transform.is_identity() {
+                    transform
+                } else {
+                    transform.around_print(file.lines())
                 };
-                socket.send(line).await?.await?;
+                let mut layers = LayerCounter::default();
+                let mut rates = RateRewriter::default();
+                let mut skipped = 0;
+                if skip > 0 {
+                    let mut state = MachineState::default();
+                    for line in file.lines().take(skip) {
+                        layers.update(line);
+                        if let Some(line) = sendable(line) {
+                            let code = transform.apply(&parse_line(line), &state);
+                            let code = rates.apply(&code, 1.0, 1.0);
+                            state.apply(&code);
+                            skipped += 1;
+                        }
+                    }
+                    task_log.info(format_args!(
+                        "starting from line {}, on layer {}",
+                        skip + 1,
+                        layers.layer()
+                    ));
+                    for code in state.restore(PARK_LIFT) {
+                        task_log.debug(format_args!("sending `{code}`"));
+                        socket.send(code).await?.await?;
+                    }
+                    *machine_state.lock().unwrap() = state;
+                }
+                let eta = Eta::for_file(&file).starting_at(skipped);
+                let total_lines = eta.total_lines();
+                let start = Instant::now();
+                let mut lines_sent = skipped;
+                let mut percent_reported = None;
+                let mut last_report = start;
+                for line in file.lines().skip(skip) {
+                    let reason = match (pause_marker(line), layers.update(line)) {
+                        (Some(reason), _) => Some(reason.to_string()),
+                        (None, Some(layer)) if pauses.take_layer(layer) => {
+                            Some(format!("at layer {layer}"))
+                        }
+                        _ if pauses.take_request() => Some("when asked".to_string()),
+                        _ => None,
+                    };
+                    if let Some(reason) = reason {
+                        pause_print(
+                            &filename,
+                            &reason,
+                            &socket,
+                            &machine_state,
+                            &pauses,
+                            &responder,
+                            &task_log,
+                        )
+                        .await?;
+                        // pausing stands in for M0, M1 and M600 rather than sending them
+                        if sendable(line).is_some() {
+                            lines_sent += 1;
+                        }
+                        continue;
+                    }
+                    let Some(line) = sendable(line) else {
+                        continue;
+                    };
+                    let original = parse_line(line);
+                    let (speed, flow) = overrides.lock().unwrap().rewritten();
+                    let (code, segments) = {
+                        let mut state = machine_state.lock().unwrap();
+                        let code = transform.apply(&original, &state);
+                        let code = rates.apply(&code, speed, flow);
+                        let segments = flatten_arcs
+                            .then(|| state.flatten_arc(&code, ARC_SEGMENT_LENGTH))
+                            .flatten();
+                        state.apply(&code);
+                        (code, segments)
+                    };
+                    // lines left as they were are sent as written, keeping any text in them
+                    let transformed = (code != original).then(|| code.to_string());
+                    let line = transformed.as_deref().unwrap_or(line);
+                    match segments {
+                        Some(segments) => {
+                            task_log.debug(format_args!(
+                                "sending `{line}` as {} straight moves",
+                                segments.len()
+                            ));
+                            for segment in segments {
+                                socket.send(segment).await?.await?;
+                            }
+                        }
+                        None => {
+                            task_log.debug(format_args!("sending `{line}`"));
+                            socket.send(line).await?.await?;
+                        }
+                    }
+                    lines_sent += 1;
+                    let percent = lines_sent * 100 / total_lines;
+                    if percent_reported != Some(percent)
+                        || last_report.elapsed() >= PROGRESS_INTERVAL
+                    {
+                        percent_reported = Some(percent);
+                        last_report = Instant::now();
+                        let remaining = eta.remaining(lines_sent, start.elapsed());
+                        task_log.progress(&tracker, lines_sent, total_lines, remaining);
+                    }
+                }
+                task_log.notify(Notice::PrintFinished, "print finished");
             }
+            Err(e) => task_log.info(format_args!("could not read {filename}: {e}")),
         }
         Ok(())
     });
     BackgroundTask {
         description: "print",
         abort_handle: task.abort_handle(),
+        log,
+        recent: None,
+        progress: Some(progress),
+    }
+}
+
+/// Starts a print of a file on the printer's SD card, then follows it with M27 every `PROGRESS_INTERVAL`
+/// until the printer reports it is done.
+///
+/// Progress is reported in bytes of the file rather than lines.
+/// Stopping the task stops following the print, not the print itself.
+pub fn start_sd_print(
+    filename: &str,
+    socket: Socket,
+    responder: broadcast::Sender<Response>,
+    log: TaskLog,
+) -> BackgroundTask {
+    let filename = filename.to_owned();
+    let task_log = log.clone();
+    let progress = TaskProgress::start();
+    let tracker = progress.clone();
+    let task = log.spawn(async move {
+        if let Err(e) = sd::start_print(&socket, &filename).await {
+            let _ = responder.send(Response::Error(
+                format!("Could not print {filename} from SD: {e}\n").into(),
+            ));
+            return;
+        }
+        task_log.info(format_args!("printing {filename} from SD"));
+        loop {
+            tokio::time::sleep(PROGRESS_INTERVAL).await;
+            match sd::status(&socket).await {
+                Ok(SdStatus::Printing { printed, total }) => {
+                    task_log.debug(format_args!("byte {printed} of {total}"));
+                    task_log.progress(&tracker, printed as usize, total as usize, None);
+                }
+                // paused prints still report where they are, so anything else means it ended
+                Ok(SdStatus::NotPrinting) => break,
+                Err(e) => task_log.info(format_args!("could not check progress: {e}")),
+            }
+        }
+        let _ = responder.send(format!("Done printing {filename} from SD\n").into());
+        let _ = responder.send(Response::Notify(
+            Source::Task(task_log.name.clone()),
+            Notice::PrintFinished,
+            format!("Done printing {filename} from SD").into(),
+        ));
+    });
+    BackgroundTask {
+        description: "sd print",
+        abort_handle: task.abort_handle(),
+        log,
+        recent: None,
+        progress: Some(progress),
+    }
+}
+
+/// Write a gcode file to the printer's storage with M28/M29 rather than streaming it line by line,
+/// for connections like PrusaLink which print jobs themselves once uploaded
+pub fn start_upload_print(filename: &str, socket: Socket, log: TaskLog) -> BackgroundTask {
+    let filename = filename.to_owned();
+    let task_log = log.clone();
+    let progress = TaskProgress::start();
+    let tracker = progress.clone();
+    let task: JoinHandle<Result<(), TaskError>> = log.spawn(async move {
+        match tokio::fs::read_to_string(&filename).await {
+            Ok(file) => {
+                let name = std::path::Path::new(&filename)
+                    .file_name()
+                    .map_or(filename.clone(), |name| name.to_string_lossy().into_owned());
+                task_log.info(format_args!("uploading {filename} as {name}"));
+                let lines: Vec<_> = file.lines().filter_map(sendable).collect();
+                upload_lines(&lines, &name, &socket, &task_log, &tracker).await?;
+                task_log.info("upload finished, printing");
+            }
+            Err(e) => task_log.info(format_args!("could not read {filename}: {e}")),
+        }
+        Ok(())
+    });
+    BackgroundTask {
+        description: "upload",
+        abort_handle: task.abort_handle(),
+        log,
+        recent: None,
+        progress: Some(progress),
+    }
+}
+
+/// Starts a background task copying a local gcode file to `remote` on the printer's SD card with M28/M29.
+///
+/// Each line waits for its `ok` before the next is sent, and progress is reported as lines are written.
+/// Stopping the task closes the file on the card, leaving whatever was written so far.
+pub fn start_sd_upload(
+    filename: &str,
+    remote: &str,
+    socket: Socket,
+    responder: broadcast::Sender<Response>,
+    log: TaskLog,
+) -> BackgroundTask {
+    let filename = filename.to_owned();
+    let remote = remote.to_owned();
+    let task_log = log.clone();
+    let progress = TaskProgress::start();
+    let tracker = progress.clone();
+    let task = log.spawn(async move {
+        let response = match tokio::fs::read_to_string(&filename).await {
+            Ok(file) => {
+                task_log.info(format_args!("uploading {filename} to SD as {remote}"));
+                let lines: Vec<_> = file.lines().filter_map(sendable).collect();
+                match upload_lines(&lines, &remote, &socket, &task_log, &tracker).await {
+                    Ok(()) => Response::Output(
+                        Source::Commander,
+                        format!("Uploaded {filename} to SD as {remote}\n").into(),
+                    ),
+                    Err(e) => Response::Error(format!("Upload of {filename} failed: {e}\n").into()),
+                }
+            }
+            Err(e) => Response::Error(format!("Could not read {filename}: {e}\n").into()),
+        };
+        let _ = responder.send(response);
+    });
+    BackgroundTask {
+        description: "sd upload",
+        abort_handle: task.abort_handle(),
+        log,
+        recent: None,
+        progress: Some(progress),
     }
 }
 
@@ -41,59 +816,518 @@ enum TaskError {
     Join(#[from] tokio::task::JoinError),
 }
 
-/// Starts a background task which listens for a pattern an writes it in a file
+/// Seconds since the unix epoch, for naming output files
+fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LogError {
+    #[error("{0}")]
+    Printer(#[from] print3rs_core::Error),
+    #[cfg(feature = "sqlite")]
+    #[error("could not open the database: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+/// A file a log task is writing, swapped for a new timestamped one when `rotation` says so
+struct LogFile {
+    name: String,
+    format: LogFormat,
+    rotation: Rotation,
+    header: String,
+    file: tokio::fs::File,
+    filename: String,
+    /// When the current file was started, in seconds since the unix epoch
+    started: u64,
+    /// Number of files started, to tell apart files started in the same second
+    files: usize,
+    written: u64,
+    opened: Instant,
+}
+
+impl LogFile {
+    async fn create(
+        name: &str,
+        format: LogFormat,
+        rotation: Rotation,
+        header: String,
+    ) -> std::io::Result<Self> {
+        if let Some(directory) = std::path::Path::new(name).parent() {
+            tokio::fs::create_dir_all(directory).await?;
+        }
+        let started = timestamp();
+        let filename = format!(
+            "{name}_{started}.{extension}",
+            extension = format.extension()
+        );
+        let mut file = tokio::fs::File::create(&filename).await?;
+        file.write_all(header.as_bytes()).await?;
+        Ok(Self {
+            name: name.to_string(),
+            format,
+            rotation,
+            written: header.len() as u64,
+            header,
+            file,
+            filename,
+            started,
+            files: 1,
+            opened: Instant::now(),
+        })
+    }
+
+    /// Finish this file and carry on in a new one
+    async fn rotate(&mut self) -> std::io::Result<()> {
+        let (name, extension) = (&self.name, self.format.extension());
+        let started = timestamp();
+        let filename = if started == self.started {
+            format!("{name}_{started}_{part}.{extension}", part = self.files)
+        } else {
+            format!("{name}_{started}.{extension}")
+        };
+        self.file.flush().await?;
+        let mut file = tokio::fs::File::create(&filename).await?;
+        file.write_all(self.header.as_bytes()).await?;
+        self.file = file;
+        self.filename = filename;
+        self.started = started;
+        self.files += 1;
+        self.written = self.header.len() as u64;
+        self.opened = Instant::now();
+        Ok(())
+    }
+
+    async fn write(&mut self, record: &str, log: &TaskLog) -> std::io::Result<()> {
+        let has_records = self.written > self.header.len() as u64;
+        if has_records && self.rotation.due(self.written, self.opened.elapsed()) {
+            self.rotate().await?;
+            log.info(format_args!("now logging to {}", self.filename));
+        }
+        self.file.write_all(record.as_bytes()).await?;
+        self.written += record.len() as u64;
+        Ok(())
+    }
+}
+
+/// Records published to an MQTT topic, with the client's event loop polled until this is dropped
+#[cfg(feature = "mqtt")]
+struct MqttLog {
+    client: rumqttc::AsyncClient,
+    topic: String,
+    eventloop: JoinHandle<()>,
+}
+
+#[cfg(feature = "mqtt")]
+impl Drop for MqttLog {
+    fn drop(&mut self) {
+        self.eventloop.abort()
+    }
+}
+
+/// Wait before a log sent over the network tries its connection again
+#[cfg(feature = "mqtt")]
+const LOG_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Where a running log task writes its records
+enum LogOutput {
+    File(LogFile),
+    #[cfg(feature = "sqlite")]
+    Sqlite(SqliteLog),
+    /// Connected again by the next record after the connection breaks
+    Tcp {
+        address: (String, u16),
+        stream: Option<tokio::net::TcpStream>,
+    },
+    Udp(tokio::net::UdpSocket),
+    #[cfg(feature = "mqtt")]
+    Mqtt(MqttLog),
+}
+
+impl LogOutput {
+    /// Start writing where `sink` says, to files starting with `file_name`, unless the output was `opened` beforehand,
+    /// like a database, so it could be reported straight away
+    async fn open(
+        sink: LogSink<String>,
+        opened: Option<LogOutput>,
+        header: String,
+        file_name: &str,
+        log: &TaskLog,
+    ) -> std::io::Result<Self> {
+        let output = match (sink, opened) {
+            (_, Some(opened)) => opened,
+            (LogSink::File(format, rotation), None) => {
+                LogOutput::File(LogFile::create(file_name, format, rotation, header).await?)
+            }
+            (LogSink::Tcp { hostname, port }, None) => LogOutput::Tcp {
+                stream: Some(tokio::net::TcpStream::connect((hostname.as_str(), port)).await?),
+                address: (hostname, port),
+            },
+            (LogSink::Udp { hostname, port }, None) => {
+                let address = tokio::net::lookup_host((hostname.as_str(), port))
+                    .await?
+                    .next()
+                    .ok_or_else(|| {
+                        std::io::Error::new(std::io::ErrorKind::NotFound, "no address found")
+                    })?;
+                let local = match address {
+                    std::net::SocketAddr::V4(_) => "0.0.0.0:0",
+                    std::net::SocketAddr::V6(_) => "[::]:0",
+                };
+                let socket = tokio::net::UdpSocket::bind(local).await?;
+                socket.connect(address).await?;
+                LogOutput::Udp(socket)
+            }
+            #[cfg(feature = "mqtt")]
+            (
+                LogSink::Mqtt {
+                    hostname,
+                    port,
+                    topic,
+                },
+                None,
+            ) => {
+                let client_id = format!("print3rs-log-{}-{}", std::process::id(), log.name());
+                let mut options = rumqttc::MqttOptions::new(
+                    client_id,
+                    hostname,
+                    port.unwrap_or(mqtt::DEFAULT_PORT),
+                );
+                options.set_keep_alive(Duration::from_secs(10));
+                let (client, mut eventloop) = rumqttc::AsyncClient::new(options, 64);
+                let eventloop_log = log.clone();
+                let eventloop = tokio::spawn(async move {
+                    loop {
+                        if let Err(e) = eventloop.poll().await {
+                            eventloop_log.info(format_args!("MQTT connection failed: {e}"));
+                            tokio::time::sleep(LOG_RECONNECT_DELAY).await;
+                        }
+                    }
+                });
+                LogOutput::Mqtt(MqttLog {
+                    client,
+                    topic,
+                    eventloop,
+                })
+            }
+            #[cfg(feature = "sqlite")]
+            (LogSink::Sqlite(_), None) => unreachable!("databases are opened before the task"),
+        };
+        Ok(output)
+    }
+
+    async fn write(&mut self, fields: &[String], values: &[LogValue], log: &TaskLog) {
+        match self {
+            LogOutput::File(file) => {
+                let record = file.format.record(fields, values);
+                log.debug(format_args!("logged {}", record.trim_end()));
+                if let Err(e) = file.write(&record, log).await {
+                    log.info(format_args!("could not write to {}: {e}", file.filename));
+                }
+            }
+            #[cfg(feature = "sqlite")]
+            LogOutput::Sqlite(database) => {
+                log.debug(format_args!("logged {values:?}"));
+                if let Err(e) = database.insert(values) {
+                    log.info(format_args!("could not add a row: {e}"));
+                }
+            }
+            LogOutput::Tcp { address, stream } => {
+                let record = LogFormat::Jsonl.record(fields, values);
+                log.debug(format_args!("sending {}", record.trim_end()));
+                let connected = match stream.take() {
+                    Some(connected) => Ok(connected),
+                    None => tokio::net::TcpStream::connect((address.0.as_str(), address.1)).await,
+                };
+                let sent = match connected {
+                    Ok(mut connected) => connected
+                        .write_all(record.as_bytes())
+                        .await
+                        .map(|()| *stream = Some(connected)),
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = sent {
+                    log.info(format_args!("could not send a record: {e}"));
+                }
+            }
+            LogOutput::Udp(socket) => {
+                let record = LogFormat::Jsonl.record(fields, values);
+                log.debug(format_args!("sending {}", record.trim_end()));
+                if let Err(e) = socket.send(record.as_bytes()).await {
+                    log.info(format_args!("could not send a record: {e}"));
+                }
+            }
+            #[cfg(feature = "mqtt")]
+            LogOutput::Mqtt(mqtt) => {
+                let record = LogFormat::Jsonl.record(fields, values);
+                log.debug(format_args!("publishing {}", record.trim_end()));
+                let published = mqtt
+                    .client
+                    .publish(
+                        mqtt.topic.as_str(),
+                        rumqttc::QoS::AtMostOnce,
+                        false,
+                        record.trim_end(),
+                    )
+                    .await;
+                if let Err(e) = published {
+                    log.info(format_args!("could not publish a record: {e}"));
+                }
+            }
+        }
+    }
+}
+
+/// Starts a background task which listens for a pattern an writes it in files, a database or over the network
 pub fn start_logging(
-    name: &str,
     pattern: Vec<Segment<&'_ str>>,
+    sink: LogSink<&str>,
+    directory: Option<&std::path::Path>,
     printer: &Printer,
-) -> std::result::Result<BackgroundTask, print3rs_core::Error> {
-    let filename = format!(
-        "{name}_{timestamp}.csv",
-        timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs()
-    );
-    let header = get_headers(&pattern);
+    log: TaskLog,
+) -> std::result::Result<BackgroundTask, LogError> {
+    let fields = get_fields(&pattern);
+    let header = match sink {
+        LogSink::File(format, _) => format.header(&pattern),
+        _ => String::new(),
+    };
+    // the database is opened here so one that can't be used is reported straight away
+    let opened = match sink {
+        #[cfg(feature = "sqlite")]
+        LogSink::Sqlite(path) => Some(LogOutput::Sqlite(SqliteLog::open(
+            path,
+            log.name(),
+            &pattern,
+        )?)),
+        _ => None,
+    };
+    let sink = sink.into_owned();
+    // files are named after the task, in the log directory if there is one
+    let file_name = match directory {
+        Some(directory) => directory.join(log.name()).display().to_string(),
+        None => log.name().to_string(),
+    };
+    let recent = Arc::new(Mutex::new(RecentValues::new(fields.clone())));
+    let task_recent = recent.clone();
 
     let mut parser = make_parser(pattern);
     let mut log_printer_reader = printer.subscribe_lines()?;
-    let log_task_handle = tokio::spawn(async move {
-        let mut log_file = tokio::fs::File::create(filename).await.unwrap();
-        log_file.write_all(header.as_bytes()).await.unwrap();
+    let task_log = log.clone();
+    let log_task_handle = log.spawn(async move {
+        let destination = sink.to_string();
+        let mut output = match LogOutput::open(sink, opened, header, &file_name, &task_log).await {
+            Ok(output) => output,
+            Err(e) => {
+                task_log.notify(
+                    Notice::LogError,
+                    format_args!("could not log to {destination}: {e}"),
+                );
+                return;
+            }
+        };
+        match &output {
+            LogOutput::File(file) => task_log.info(format_args!("logging to {}", file.filename)),
+            _ => task_log.info(format_args!("logging to {destination}")),
+        }
         while let Ok(log_line) = log_printer_reader.recv().await {
             if let Ok(parsed) = parser.parse(log_line.as_bytes()) {
-                let mut record_bytes = String::new();
-                for val in parsed {
-                    record_bytes.push_str(&val.to_string());
-                    record_bytes.push(',');
-                }
-                record_bytes.pop(); // remove trailing ','
-                record_bytes.push('\n');
-                log_file
-                    .write_all(record_bytes.as_bytes())
-                    .await
-                    .unwrap_or_default();
+                task_recent.lock().unwrap().push(&parsed);
+                output.write(&fields, &parsed, &task_log).await;
+            } else {
+                task_log.trace(format_args!("no match in `{}`", log_line.trim_end()));
             }
         }
     });
     Ok(BackgroundTask {
         description: "log",
         abort_handle: log_task_handle.abort_handle(),
+        log,
+        recent: Some(recent),
+        progress: None,
     })
 }
 
+/// Starts a background task which measures the bed and saves the heightmap in a csv file
+///
+/// The result or failure is reported through `responder` when done.
+pub fn start_heightmap(
+    grid: Option<u32>,
+    bed_size: [f32; 2],
+    socket: Socket,
+    responder: broadcast::Sender<Response>,
+    log: TaskLog,
+) -> BackgroundTask {
+    let task_log = log.clone();
+    let task = log.spawn(async move {
+        match grid {
+            Some(grid) => task_log.info(format_args!("probing {grid}x{grid} points")),
+            None => task_log.info("reading stored mesh"),
+        }
+        let response = match heightmap::measure(grid, bed_size, &socket).await {
+            Ok(heightmap) => {
+                let filename = format!("heightmap_{timestamp}.csv", timestamp = timestamp());
+                match tokio::fs::write(&filename, heightmap.to_csv()).await {
+                    Ok(()) => Response::Output(
+                        Source::Commander,
+                        format!("Heightmap saved to {filename}: {}\n", heightmap.summary()).into(),
+                    ),
+                    Err(e) => Response::Error(format!("Could not write {filename}: {e}\n").into()),
+                }
+            }
+            Err(e) => Response::Error(format!("Heightmap failed: {e}\n").into()),
+        };
+        let _ = responder.send(response);
+    });
+    BackgroundTask {
+        description: "heightmap",
+        abort_handle: task.abort_handle(),
+        log,
+        recent: None,
+        progress: None,
+    }
+}
+
+/// How `print` sends a file
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PrintOptions {
+    /// Line of the file to start at, counting from 1
+    pub from: Option<usize>,
+    /// Where on the bed the file is printed
+    pub transform: Transform,
+}
+
+/// How `extrude` and `retract` move filament
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExtrudeOptions {
+    /// Feedrate in mm/min used when a command doesn't give one
+    pub feedrate: f32,
+    /// Lowest hotend temperature to extrude at, checked with M105 first; no check if `None`
+    pub min_temp: Option<f32>,
+}
+
+impl Default for ExtrudeOptions {
+    fn default() -> Self {
+        Self {
+            feedrate: 300.0,
+            min_temp: Some(LintRules::default().min_extrude_temp),
+        }
+    }
+}
+
+/// Longest the printer is given to report its temperature before extruding
+const TEMPERATURE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Starts a background task extruding or retracting filament with the given gcodes,
+/// refusing to if the hotend is colder than `min_temp`.
+///
+/// Gcodes are sent in turn with other console gcodes, see `send_gcodes`.
+pub fn start_extrude(
+    socket: Socket,
+    codes: Vec<String>,
+    min_temp: Option<f32>,
+    state: Arc<Mutex<MachineState>>,
+    order: Arc<tokio::sync::Mutex<()>>,
+    responder: broadcast::Sender<Response>,
+    log: TaskLog,
+) -> BackgroundTask {
+    let task_log = log.clone();
+    let task: JoinHandle<Result<(), PrinterError>> = log.spawn(async move {
+        if let Some(min_temp) = min_temp {
+            let hotend = tokio::time::timeout(
+                TEMPERATURE_TIMEOUT,
+                status::query_temperatures(&socket),
+            )
+            .await
+            .ok()
+            .and_then(Result::ok)
+            .and_then(|report| report.hotend);
+            let refusal = match hotend {
+                Some(hotend) if hotend.current >= min_temp => None,
+                Some(hotend) => Some(format!(
+                    "Hotend is at {:.0}°C, heat it to at least {min_temp:.0}°C before moving filament\n",
+                    hotend.current
+                )),
+                None => Some("Could not read the hotend temperature, filament was not moved\n".to_string()),
+            };
+            if let Some(refusal) = refusal {
+                let _ = responder.send(Response::Error(refusal.into()));
+                return Ok(());
+            }
+        }
+        let _turn = order.lock_owned().await;
+        for code in codes {
+            task_log.debug(format_args!("sending `{code}`"));
+            state.lock().unwrap().apply(&parse_line(&code));
+            let _ = socket.send_unsequenced(code).await?.await;
+        }
+        Ok(())
+    });
+    BackgroundTask {
+        description: "extrude",
+        abort_handle: task.abort_handle(),
+        log,
+        recent: None,
+        progress: None,
+    }
+}
+
+/// How close a heater has to be to the temperature an `@waittemp` step waits for
+const WAIT_TEMP_TOLERANCE: f32 = 2.0;
+/// How often temperatures are asked for while an `@waittemp` step waits
+const WAIT_TEMP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Send a step of a sequence, or run it here if it is a host step like `@delay 5s`
+async fn run_step(socket: &Socket, step: &str, log: &TaskLog) -> Result<(), PrinterError> {
+    match HostStep::parse(step) {
+        Some(Ok(HostStep::Delay(delay))) => {
+            log.debug(format_args!("waiting {delay:?}"));
+            tokio::time::sleep(delay).await;
+        }
+        Some(Ok(HostStep::WaitTemp(heater, temperature))) => {
+            log.debug(format_args!(
+                "waiting for {heater:?} to reach {temperature}"
+            ));
+            loop {
+                let report = status::query_temperatures(socket).await?;
+                let current = match heater {
+                    Heater::Hotend => report.hotend,
+                    Heater::Bed => report.bed,
+                    Heater::Chamber => report.chamber,
+                };
+                if current.is_some_and(|t| (t.current - temperature).abs() <= WAIT_TEMP_TOLERANCE) {
+                    break;
+                }
+                tokio::time::sleep(WAIT_TEMP_INTERVAL).await;
+            }
+        }
+        // rejected before the sequence started
+        Some(Err(_)) => {}
+        None => {
+            log.debug(format_args!("sending `{step}`"));
+            let _ = socket.send_unsequenced(step).await?.await;
+        }
+    }
+    Ok(())
+}
+
 /// Starts a background task sending Gcodes one-at-a-time in an infinite loop
-pub fn start_repeat(gcodes: Vec<String>, socket: Socket) -> BackgroundTask {
-    let task: JoinHandle<Result<(), TaskError>> = tokio::spawn(async move {
+pub fn start_repeat(gcodes: Vec<String>, socket: Socket, log: TaskLog) -> BackgroundTask {
+    let task_log = log.clone();
+    let task: JoinHandle<Result<(), TaskError>> = log.spawn(async move {
         for ref line in gcodes.into_iter().cycle() {
-            let _ = socket.send_unsequenced(line).await?.await;
+            run_step(&socket, line, &task_log).await?;
         }
         Ok(())
     });
     BackgroundTask {
         description: "repeat",
         abort_handle: task.abort_handle(),
+        log,
+        recent: None,
+        progress: None,
     }
 }
 
@@ -105,6 +1339,11 @@ pub type Tasks = HashMap<String, BackgroundTask>;
 pub struct BackgroundTask {
     pub description: &'static str,
     pub abort_handle: tokio::task::AbortHandle,
+    pub log: TaskLog,
+    /// Latest values parsed by a log task
+    pub recent: Option<Arc<Mutex<RecentValues>>>,
+    /// How far along a print or upload is
+    pub progress: Option<Arc<Mutex<TaskProgress>>>,
 }
 
 impl Drop for BackgroundTask {
@@ -113,16 +1352,167 @@ impl Drop for BackgroundTask {
     }
 }
 
-/// Starts a background task which sends given Gcodes one-at-a-time
-pub fn send_gcodes(socket: Socket, codes: Vec<String>) -> BackgroundTask {
-    let task: JoinHandle<Result<(), PrinterError>> = tokio::spawn(async move {
+/// Starts a background task which sends given Gcodes one-at-a-time.
+///
+/// Nothing is sent until every earlier batch holding the `order` lock has been acknowledged,
+/// so batches reach the printer in the order they were started and a slow command like G28 holds back later ones.
+pub fn send_gcodes(
+    socket: Socket,
+    codes: Vec<String>,
+    order: Arc<tokio::sync::Mutex<()>>,
+    log: TaskLog,
+) -> BackgroundTask {
+    let task_log = log.clone();
+    let task: JoinHandle<Result<(), PrinterError>> = log.spawn(async move {
+        let _turn = order.lock_owned().await;
         for code in codes {
-            let _ = socket.send_unsequenced(code).await?.await;
+            run_step(&socket, &code, &task_log).await?;
         }
         Ok(())
     });
     BackgroundTask {
         description: "gcodes",
         abort_handle: task.abort_handle(),
+        log,
+        recent: None,
+        progress: None,
+    }
+}
+
+/// Starts a background task which sends a line exactly as typed, in turn with other batches like `send_gcodes`.
+///
+/// The line isn't read as a host step like `@delay`, and keeps its comments even if the format strips them.
+pub fn send_verbatim(
+    socket: Socket,
+    line: String,
+    order: Arc<tokio::sync::Mutex<()>>,
+    log: TaskLog,
+) -> BackgroundTask {
+    let task_log = log.clone();
+    let task: JoinHandle<Result<(), PrinterError>> = log.spawn(async move {
+        let _turn = order.lock_owned().await;
+        task_log.debug(format_args!("sending `{line}` as typed"));
+        let _ = socket.send_verbatim(&line).await?.await;
+        Ok(())
+    });
+    BackgroundTask {
+        description: "raw",
+        abort_handle: task.abort_handle(),
+        log,
+        recent: None,
+        progress: None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn sends_verbatim() {
+        use {
+            print3rs_core::{Format, PrinterOptions},
+            tokio::io::AsyncBufReadExt,
+        };
+
+        let (device, far_end) = tokio::io::duplex(256);
+        let options = PrinterOptions::new()
+            .identify(false)
+            .reset_line_numbers(false)
+            .format(Format::new().strip_comments(true));
+        let printer = Printer::with_options(tokio::io::BufReader::new(device), options);
+        let mut far_end = tokio::io::BufReader::new(far_end);
+        let log = TaskLog::new("raw", broadcast::channel(4).0);
+        let order = Arc::new(tokio::sync::Mutex::new(()));
+        let socket = printer.socket().unwrap().clone();
+        let _delay = send_verbatim(
+            socket.clone(),
+            "@delay 5s".to_string(),
+            order.clone(),
+            log.clone(),
+        );
+        let _message = send_verbatim(socket, "M117 Hello; World".to_string(), order, log);
+
+        let mut line = String::new();
+        far_end.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "@delay 5s\n");
+        far_end.write_all(b"ok\n").await.unwrap();
+        line.clear();
+        far_end.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "M117 Hello; World\n");
+    }
+
+    #[tokio::test]
+    async fn pause_and_resume() {
+        let pauses = Arc::new(Pauses::default());
+        pauses.pause_at(3);
+        assert_eq!(pauses.layers(), [3]);
+        assert!(pauses.take_layer(3));
+        assert!(!pauses.take_layer(3));
+        assert!(!pauses.resume());
+        pauses.pause_now();
+        assert!(pauses.take_request());
+        assert!(!pauses.take_request());
+        pauses.pause_now();
+        pauses.clear();
+        assert!(!pauses.take_request());
+
+        let (paused, was_paused) = tokio::sync::oneshot::channel();
+        let waiter = pauses.clone();
+        let waiting = tokio::spawn(async move {
+            waiter
+                .wait(|| {
+                    let _ = paused.send(());
+                })
+                .await
+        });
+        was_paused.await.unwrap();
+        assert!(pauses.is_paused());
+        assert!(pauses.resume());
+        waiting.await.unwrap();
+        assert!(!pauses.is_paused());
+    }
+
+    #[test]
+    fn progress_summary() {
+        let tracker = TaskProgress::start();
+        assert!(tracker
+            .lock()
+            .unwrap()
+            .to_string()
+            .starts_with("starting, "));
+        let log = TaskLog::new("benchy.gcode", broadcast::channel(4).0);
+        log.progress(&tracker, 421, 1000, Some(Duration::from_secs(1025)));
+        let progress = *tracker.lock().unwrap();
+        assert_eq!(progress.percent(), 42.1);
+        assert_eq!(
+            progress.to_string(),
+            "42.1% (421 of 1000), 0s elapsed, 17m05s left"
+        );
+    }
+
+    #[test]
+    fn verbosity_names() {
+        for verbosity in Verbosity::ALL {
+            assert_eq!(Verbosity::from_name(verbosity.name()), Some(verbosity));
+        }
+        assert_eq!(Verbosity::from_name("QUIET"), Some(Verbosity::Quiet));
+        assert_eq!(Verbosity::from_name("loud"), None);
+    }
+
+    #[test]
+    fn scoped_to_task() {
+        let (responder, mut responses) = broadcast::channel(8);
+        let log = TaskLog::new("temps", responder);
+        log.debug("hidden");
+        log.set_verbosity(Verbosity::Debug);
+        log.trace("hidden");
+        log.debug("shown");
+        let Ok(Response::Output(source, shown)) = responses.try_recv() else {
+            panic!("expected output");
+        };
+        assert_eq!(source, Source::Task("temps".into()));
+        assert_eq!(&*shown, "shown\n");
+        assert!(responses.try_recv().is_err());
     }
 }
@@ -0,0 +1,35 @@
+//! Transports for printers which aren't reached through a serial port or plain TCP socket.
+//!
+//! Each backend bridges its protocol to an in-memory stream given to `Printer::connect`,
+//! so everything built on `Socket` works the same no matter how the printer is reached.
+
+//!
+//! Backends needing more dependencies are behind cargo features of the same name.
+
+pub mod dryrun;
+#[cfg(feature = "duet")]
+pub mod duet;
+#[cfg(feature = "moonraker")]
+pub mod moonraker;
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+#[cfg(feature = "octoprint")]
+pub mod octoprint;
+#[cfg(feature = "prusalink")]
+pub mod prusalink;
+pub mod virtual_printer;
+
+/// Line number of a line sent with `N<number>` framing
+#[cfg(any(
+    feature = "moonraker",
+    feature = "octoprint",
+    feature = "prusalink",
+    feature = "duet"
+))]
+fn line_number(line: &str) -> Option<i32> {
+    let number = line.trim_start().strip_prefix(['N', 'n'])?;
+    let end = number
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(number.len());
+    number[..end].parse().ok()
+}
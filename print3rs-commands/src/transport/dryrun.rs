@@ -0,0 +1,72 @@
+use {
+    crate::response::{Response, Source},
+    std::sync::Arc,
+    tokio::{
+        io::{duplex, AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream},
+        sync::broadcast,
+        task::JoinHandle,
+    },
+};
+
+/// Bytes buffered in each direction between the printer and the simulated one
+const BRIDGE_BUFFER: usize = 4096;
+
+/// Stream connected to a simulated printer, ready for `Printer::connect`
+pub type DryRunTransport = BufReader<DuplexStream>;
+
+/// Start a simulated printer which answers `ok` to everything,
+/// reporting each line it is sent as a response from the printer called `name` instead.
+///
+/// The returned task runs until the transport is dropped.
+pub fn connect(
+    name: Arc<str>,
+    responder: broadcast::Sender<Response>,
+) -> (DryRunTransport, JoinHandle<std::io::Result<()>>) {
+    let (printer_end, simulated_end) = duplex(BRIDGE_BUFFER);
+    let simulated = tokio::spawn(simulate(simulated_end, name, responder));
+    (BufReader::new(printer_end), simulated)
+}
+
+async fn simulate(
+    simulated_end: DuplexStream,
+    name: Arc<str>,
+    responder: broadcast::Sender<Response>,
+) -> std::io::Result<()> {
+    let (reader, mut writer) = tokio::io::split(simulated_end);
+    let mut lines = BufReader::new(reader).lines();
+    // printer was dropped once there are no more lines
+    while let Some(line) = lines.next_line().await? {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let _ = responder.send(Response::Output(
+            Source::Printer(name.clone()),
+            format!("dry run: {line}\n").into(),
+        ));
+        writer.write_all(b"ok\n").await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, tokio::io::AsyncReadExt};
+
+    #[tokio::test]
+    async fn echoes_and_acknowledges() {
+        let (responder, mut responses) = broadcast::channel(4);
+        let (mut transport, simulated) = connect("ender".into(), responder);
+        transport.write_all(b"G28\n").await.unwrap();
+        let mut ok = [0; 3];
+        transport.read_exact(&mut ok).await.unwrap();
+        assert_eq!(&ok, b"ok\n");
+        let Ok(Response::Output(source, sent)) = responses.recv().await else {
+            panic!("expected output");
+        };
+        assert_eq!(source, Source::Printer("ender".into()));
+        assert_eq!(&*sent, "dry run: G28\n");
+        drop(transport);
+        simulated.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,160 @@
+use {
+    super::line_number,
+    crate::gcode::strip_framing,
+    serde_json::Value,
+    std::time::Duration,
+    tokio::{
+        io::{duplex, AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream},
+        task::JoinHandle,
+    },
+};
+
+/// Password Duet boards accept when none has been set with M551
+pub const DEFAULT_PASSWORD: &str = "reprap";
+
+/// Bytes buffered in each direction between the printer and the Duet
+const BRIDGE_BUFFER: usize = 4096;
+
+/// How often the Duet is asked for new replies
+const REPLY_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, thiserror::Error)]
+pub enum DuetError {
+    #[error("Duet request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Duet refused the connection: {0}")]
+    Refused(&'static str),
+    #[error("{0}")]
+    IO(#[from] std::io::Error),
+}
+
+/// Stream connected to a Duet board over HTTP, ready for `Printer::connect`
+pub type DuetTransport = BufReader<DuplexStream>;
+
+/// Connect to a Duet board running RepRapFirmware through its HTTP API.
+///
+/// A session is opened with `rr_connect`, then each line sent to the printer is run with `rr_gcode`
+/// and answered with an `ok` once the board accepts it, numbered like the line was if it had a line number.
+/// The board's replies are polled from `rr_reply` and arrive as lines from the printer.
+///
+/// The returned task runs the connection until the transport is dropped,
+/// or ends early with the error that broke the connection.
+pub fn connect(
+    hostname: &str,
+    port: Option<u16>,
+    password: Option<&str>,
+) -> (DuetTransport, JoinHandle<Result<(), DuetError>>) {
+    let base = match port {
+        Some(port) => format!("http://{hostname}:{port}"),
+        None => format!("http://{hostname}"),
+    };
+    let password = password.unwrap_or(DEFAULT_PASSWORD).to_owned();
+    let (printer_end, bridge_end) = duplex(BRIDGE_BUFFER);
+    let bridge = tokio::spawn(bridge(base, password, bridge_end));
+    (BufReader::new(printer_end), bridge)
+}
+
+/// Why `rr_connect` was refused, if it was
+fn connect_refusal(response: &Value) -> Option<&'static str> {
+    match response["err"].as_u64() {
+        None | Some(0) => None,
+        Some(1) => Some("wrong password"),
+        Some(2) => Some("no more sessions available"),
+        Some(_) => Some("unknown error"),
+    }
+}
+
+/// Lines to pass on to the printer from the text of an `rr_reply`
+fn reply_lines(reply: &str) -> impl Iterator<Item = String> + '_ {
+    reply
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty())
+        .map(|line| format!("{line}\n"))
+}
+
+async fn bridge(base: String, password: String, bridge_end: DuplexStream) -> Result<(), DuetError> {
+    let client = reqwest::Client::new();
+    let session: Value = client
+        .get(format!("{base}/rr_connect"))
+        .query(&[("password", password.as_str())])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    if let Some(reason) = connect_refusal(&session) {
+        return Err(DuetError::Refused(reason));
+    }
+    tracing::debug!("connected to duet at {base}");
+
+    let (reader, mut printer) = tokio::io::split(bridge_end);
+    let mut lines = BufReader::new(reader).lines();
+    let mut poll = tokio::time::interval(REPLY_INTERVAL);
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                let Some(line) = line? else {
+                    // printer was dropped
+                    let _ = client.get(format!("{base}/rr_disconnect")).send().await;
+                    return Ok(());
+                };
+                let status = client
+                    .get(format!("{base}/rr_gcode"))
+                    .query(&[("gcode", strip_framing(&line))])
+                    .send()
+                    .await?
+                    .status();
+                if !status.is_success() {
+                    printer.write_all(format!("!! Duet refused command: {status}\n").as_bytes()).await?;
+                }
+                let ok = match line_number(&line) {
+                    Some(number) => format!("ok N{number}\n"),
+                    None => "ok\n".to_string(),
+                };
+                printer.write_all(ok.as_bytes()).await?;
+            },
+            _ = poll.tick() => {
+                let reply = client
+                    .get(format!("{base}/rr_reply"))
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await?;
+                for line in reply_lines(&reply) {
+                    printer.write_all(line.as_bytes()).await?;
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, serde_json::json};
+
+    #[test]
+    fn sessions() {
+        assert_eq!(
+            connect_refusal(&json!({ "err": 0, "sessionTimeout": 8000 })),
+            None
+        );
+        assert_eq!(
+            connect_refusal(&json!({ "err": 1 })),
+            Some("wrong password")
+        );
+    }
+
+    #[test]
+    fn replies() {
+        let reply = "FIRMWARE_NAME: RepRapFirmware for Duet 3 MB6HC\n\nT:21.3 /0.0 B:20.9 /0.0\n";
+        assert_eq!(
+            reply_lines(reply).collect::<Vec<_>>(),
+            [
+                "FIRMWARE_NAME: RepRapFirmware for Duet 3 MB6HC\n",
+                "T:21.3 /0.0 B:20.9 /0.0\n"
+            ]
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use {
+    super::line_number,
+    crate::gcode::strip_framing,
+    futures_util::{SinkExt, StreamExt},
+    serde_json::{json, Value},
+    std::collections::HashMap,
+    tokio::{
+        io::{duplex, AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream},
+        task::JoinHandle,
+    },
+    tokio_tungstenite::{connect_async, tungstenite::Message},
+};
+
+pub const DEFAULT_PORT: u16 = 7125;
+
+/// Bytes buffered in each direction between the printer and the websocket
+const BRIDGE_BUFFER: usize = 4096;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MoonrakerError {
+    #[error("Moonraker connection failed: {0}")]
+    WebSocket(#[from] tokio_tungstenite::tungstenite::Error),
+    #[error("{0}")]
+    IO(#[from] std::io::Error),
+}
+
+/// Stream connected to a Klipper printer through Moonraker, ready for `Printer::connect`
+pub type MoonrakerTransport = BufReader<DuplexStream>;
+
+/// Connect to a Klipper printer through Moonraker's websocket API.
+///
+/// Each line sent to the printer is run with `printer.gcode.script`, and answered with an `ok`
+/// once Moonraker reports it done, numbered like the line was if it had a line number.
+/// Klipper's gcode output arrives as lines from the printer, and failed scripts as `!! <error>`.
+///
+/// The returned task runs the websocket until the transport is dropped,
+/// or ends early with the error that broke the connection.
+pub fn connect(
+    hostname: &str,
+    port: Option<u16>,
+) -> (MoonrakerTransport, JoinHandle<Result<(), MoonrakerError>>) {
+    let url = format!("ws://{hostname}:{}/websocket", port.unwrap_or(DEFAULT_PORT));
+    let (printer_end, bridge_end) = duplex(BRIDGE_BUFFER);
+    let bridge = tokio::spawn(bridge(url, bridge_end));
+    (BufReader::new(printer_end), bridge)
+}
+
+/// JSON-RPC request running a line of gcode
+fn script_request(id: u64, line: &str) -> String {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "printer.gcode.script",
+        "params": { "script": strip_framing(line) },
+        "id": id,
+    })
+    .to_string()
+}
+
+/// Lines the printer would have sent for a message from Moonraker.
+///
+/// `pending` holds the line number of every script still running, by request id.
+fn translate(message: &str, pending: &mut HashMap<u64, Option<i32>>) -> Vec<String> {
+    let Ok(message) = serde_json::from_str::<Value>(message) else {
+        return vec![];
+    };
+    if message["method"] == "notify_gcode_response" {
+        let params = message["params"].as_array().cloned().unwrap_or_default();
+        return params
+            .iter()
+            .filter_map(Value::as_str)
+            .map(|line| format!("{line}\n"))
+            .collect();
+    }
+    let Some(number) = message["id"].as_u64().and_then(|id| pending.remove(&id)) else {
+        return vec![];
+    };
+    let mut lines = vec![];
+    if let Some(error) = message["error"]["message"].as_str() {
+        lines.push(format!("!! {error}\n"));
+    }
+    lines.push(match number {
+        Some(number) => format!("ok N{number}\n"),
+        None => "ok\n".to_string(),
+    });
+    lines
+}
+
+async fn bridge(url: String, bridge_end: DuplexStream) -> Result<(), MoonrakerError> {
+    let (websocket, _) = connect_async(url.as_str()).await?;
+    tracing::debug!("connected to moonraker at {url}");
+    let (mut to_moonraker, mut from_moonraker) = websocket.split();
+    let (reader, mut printer) = tokio::io::split(bridge_end);
+    let mut lines = BufReader::new(reader).lines();
+    let mut pending = HashMap::new();
+    let mut next_id = 0;
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                let Some(line) = line? else {
+                    // printer was dropped
+                    to_moonraker.close().await?;
+                    return Ok(());
+                };
+                next_id += 1;
+                pending.insert(next_id, line_number(&line));
+                to_moonraker.send(Message::Text(script_request(next_id, &line))).await?;
+            },
+            message = from_moonraker.next() => match message.transpose()? {
+                Some(Message::Text(message)) => {
+                    for line in translate(&message, &mut pending) {
+                        printer.write_all(line.as_bytes()).await?;
+                    }
+                }
+                Some(Message::Close(_)) | None => return Ok(()),
+                Some(_) => {}
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn requests() {
+        assert_eq!(line_number("N12 G28*18\n"), Some(12));
+        assert_eq!(line_number("G28\n"), None);
+        let request: Value = serde_json::from_str(&script_request(3, "N12 G28*18\n")).unwrap();
+        assert_eq!(request["params"]["script"], "G28");
+        assert_eq!(request["id"], 3);
+    }
+
+    #[test]
+    fn responses() {
+        let mut pending = HashMap::from([(1, Some(12)), (2, None)]);
+        let output = r#"{"jsonrpc": "2.0", "method": "notify_gcode_response", "params": ["// probe at 10,10 is z=0.12"]}"#;
+        assert_eq!(
+            translate(output, &mut pending),
+            ["// probe at 10,10 is z=0.12\n"]
+        );
+        let done = r#"{"jsonrpc": "2.0", "result": "ok", "id": 1}"#;
+        assert_eq!(translate(done, &mut pending), ["ok N12\n"]);
+        let failed = r#"{"jsonrpc": "2.0", "error": {"code": 400, "message": "Must home axis first"}, "id": 2}"#;
+        assert_eq!(
+            translate(failed, &mut pending),
+            ["!! Must home axis first\n", "ok\n"]
+        );
+        assert!(pending.is_empty());
+    }
+}
@@ -0,0 +1,107 @@
+use {
+    rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, Packet, QoS},
+    std::time::Duration,
+    tokio::{
+        io::{duplex, AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream, WriteHalf},
+        task::JoinHandle,
+    },
+};
+
+pub const DEFAULT_PORT: u16 = 1883;
+/// Topic gcode is published to when none is given
+pub const DEFAULT_IN_TOPIC: &str = "print3rs/in";
+/// Topic printer output is read from when none is given
+pub const DEFAULT_OUT_TOPIC: &str = "print3rs/out";
+
+/// Bytes buffered in each direction between the printer and the MQTT client
+const BRIDGE_BUFFER: usize = 4096;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MqttError {
+    #[error("MQTT connection failed: {0}")]
+    Connection(#[from] rumqttc::ConnectionError),
+    #[error("MQTT request failed: {0}")]
+    Client(#[from] rumqttc::ClientError),
+    #[error("{0}")]
+    IO(#[from] std::io::Error),
+}
+
+/// Stream connected to a printer over MQTT, ready for `Printer::connect`
+pub type MqttTransport = BufReader<DuplexStream>;
+
+/// Connect to a printer through an MQTT broker.
+///
+/// Every line sent to the printer is published to `in_topic`,
+/// and every message on `out_topic` is read as a line from the printer.
+///
+/// The returned task runs the MQTT client until the transport is dropped,
+/// or ends early with the error that broke the connection.
+pub fn connect(
+    hostname: &str,
+    port: Option<u16>,
+    in_topic: Option<&str>,
+    out_topic: Option<&str>,
+) -> (MqttTransport, JoinHandle<Result<(), MqttError>>) {
+    let client_id = format!("print3rs-{}", std::process::id());
+    let mut options = MqttOptions::new(client_id, hostname, port.unwrap_or(DEFAULT_PORT));
+    options.set_keep_alive(Duration::from_secs(10));
+    let (client, eventloop) = AsyncClient::new(options, 64);
+    let (printer_end, bridge_end) = duplex(BRIDGE_BUFFER);
+    let in_topic = in_topic.unwrap_or(DEFAULT_IN_TOPIC).to_owned();
+    let out_topic = out_topic.unwrap_or(DEFAULT_OUT_TOPIC).to_owned();
+    let bridge = tokio::spawn(bridge(client, eventloop, bridge_end, in_topic, out_topic));
+    (BufReader::new(printer_end), bridge)
+}
+
+/// Write every message published on `out_topic` to the printer side of the bridge, one line each
+async fn receive(
+    mut eventloop: EventLoop,
+    mut printer: WriteHalf<DuplexStream>,
+    out_topic: String,
+) -> Result<(), MqttError> {
+    loop {
+        let Event::Incoming(Packet::Publish(publish)) = eventloop.poll().await? else {
+            continue;
+        };
+        if publish.topic != out_topic {
+            continue;
+        }
+        printer.write_all(&publish.payload).await?;
+        if !publish.payload.ends_with(b"\n") {
+            printer.write_all(b"\n").await?;
+        }
+    }
+}
+
+async fn bridge(
+    client: AsyncClient,
+    eventloop: EventLoop,
+    bridge_end: DuplexStream,
+    in_topic: String,
+    out_topic: String,
+) -> Result<(), MqttError> {
+    let (reader, writer) = tokio::io::split(bridge_end);
+    client.subscribe(&out_topic, QoS::AtLeastOnce).await?;
+    // polled separately so publishing never waits on itself
+    let mut receiver = tokio::spawn(receive(eventloop, writer, out_topic));
+    let mut lines = BufReader::new(reader).lines();
+    let result = loop {
+        tokio::select! {
+            line = lines.next_line() => match line {
+                Ok(Some(line)) => {
+                    tracing::debug!("publishing `{line}` to {in_topic}");
+                    client.publish(&in_topic, QoS::AtLeastOnce, false, line).await?;
+                }
+                // printer was dropped
+                Ok(None) => break Ok(()),
+                Err(e) => break Err(e.into()),
+            },
+            received = &mut receiver => {
+                break received.unwrap_or(Ok(()));
+            }
+        }
+    };
+    receiver.abort();
+    let _ = client.disconnect().await;
+    result
+}
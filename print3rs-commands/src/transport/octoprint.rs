@@ -0,0 +1,155 @@
+use {
+    super::line_number,
+    crate::gcode::strip_framing,
+    futures_util::{SinkExt, StreamExt},
+    serde_json::{json, Value},
+    tokio::{
+        io::{duplex, AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream},
+        task::JoinHandle,
+    },
+    tokio_tungstenite::{connect_async, tungstenite::Message},
+};
+
+/// Bytes buffered in each direction between the printer and OctoPrint
+const BRIDGE_BUFFER: usize = 4096;
+
+#[derive(Debug, thiserror::Error)]
+pub enum OctoPrintError {
+    #[error("OctoPrint request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("OctoPrint push connection failed: {0}")]
+    WebSocket(#[from] tokio_tungstenite::tungstenite::Error),
+    #[error("{0}")]
+    IO(#[from] std::io::Error),
+}
+
+/// Stream connected to a printer through OctoPrint, ready for `Printer::connect`
+pub type OctoPrintTransport = BufReader<DuplexStream>;
+
+/// Connect to the printer attached to an OctoPrint server.
+///
+/// Each line sent to the printer is posted to `/api/printer/command` and answered with an `ok`
+/// once OctoPrint accepts it, numbered like the line was if it had a line number.
+/// Lines OctoPrint receives from the printer are read from its push socket,
+/// without the `ok`s, as OctoPrint numbers and acknowledges lines on its own.
+///
+/// The returned task runs the connection until the transport is dropped,
+/// or ends early with the error that broke the connection.
+pub fn connect(
+    hostname: &str,
+    port: Option<u16>,
+    api_key: &str,
+) -> (OctoPrintTransport, JoinHandle<Result<(), OctoPrintError>>) {
+    let base = match port {
+        Some(port) => format!("http://{hostname}:{port}"),
+        None => format!("http://{hostname}"),
+    };
+    let (printer_end, bridge_end) = duplex(BRIDGE_BUFFER);
+    let bridge = tokio::spawn(bridge(base, api_key.to_owned(), bridge_end));
+    (BufReader::new(printer_end), bridge)
+}
+
+/// Lines received by the printer in a message from the push socket
+fn received_lines(message: &str) -> Vec<String> {
+    let Ok(message) = serde_json::from_str::<Value>(message) else {
+        return vec![];
+    };
+    let logs = message["current"]["logs"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    logs.iter()
+        .filter_map(Value::as_str)
+        .filter_map(|log| log.strip_prefix("Recv: "))
+        .filter_map(|line| {
+            // keep whatever came with an ok, like temperatures, but not the ok itself
+            let line = match line.get(..2) {
+                Some(ok) if ok.eq_ignore_ascii_case("ok") => line[2..].trim(),
+                _ => line.trim_end(),
+            };
+            (!line.is_empty()).then(|| format!("{line}\n"))
+        })
+        .collect()
+}
+
+async fn bridge(
+    base: String,
+    api_key: String,
+    bridge_end: DuplexStream,
+) -> Result<(), OctoPrintError> {
+    let client = reqwest::Client::new();
+    let login: Value = client
+        .post(format!("{base}/api/login"))
+        .header("X-Api-Key", &api_key)
+        .json(&json!({ "passive": true }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let auth = format!(
+        "{}:{}",
+        login["name"].as_str().unwrap_or_default(),
+        login["session"].as_str().unwrap_or_default()
+    );
+    let push_url = format!("{}/sockjs/websocket", base.replacen("http", "ws", 1));
+    let (websocket, _) = connect_async(push_url.as_str()).await?;
+    tracing::debug!("connected to octoprint at {base}");
+    let (mut to_octoprint, mut from_octoprint) = websocket.split();
+    to_octoprint
+        .send(Message::Text(json!({ "auth": auth }).to_string()))
+        .await?;
+
+    let (reader, mut printer) = tokio::io::split(bridge_end);
+    let mut lines = BufReader::new(reader).lines();
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                let Some(line) = line? else {
+                    // printer was dropped
+                    to_octoprint.close().await?;
+                    return Ok(());
+                };
+                let status = client
+                    .post(format!("{base}/api/printer/command"))
+                    .header("X-Api-Key", &api_key)
+                    .json(&json!({ "commands": [strip_framing(&line)] }))
+                    .send()
+                    .await?
+                    .status();
+                if !status.is_success() {
+                    printer.write_all(format!("!! OctoPrint refused command: {status}\n").as_bytes()).await?;
+                }
+                let ok = match line_number(&line) {
+                    Some(number) => format!("ok N{number}\n"),
+                    None => "ok\n".to_string(),
+                };
+                printer.write_all(ok.as_bytes()).await?;
+            },
+            message = from_octoprint.next() => match message.transpose()? {
+                Some(Message::Text(message)) => {
+                    for line in received_lines(&message) {
+                        printer.write_all(line.as_bytes()).await?;
+                    }
+                }
+                Some(Message::Close(_)) | None => return Ok(()),
+                Some(_) => {}
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn push_messages() {
+        let current = r#"{"current": {"logs": ["Send: N5 M105*20", "Recv: ok T:210.0 /210.0 B:60.0 /60.0", "Recv: echo:busy: processing", "Recv: ok"]}}"#;
+        assert_eq!(
+            received_lines(current),
+            ["T:210.0 /210.0 B:60.0 /60.0\n", "echo:busy: processing\n"]
+        );
+        assert!(received_lines(r#"{"connected": {"version": "1.10.2"}}"#).is_empty());
+    }
+}
@@ -0,0 +1,319 @@
+use {
+    super::line_number,
+    crate::gcode::{parse_line, strip_framing},
+    serde_json::Value,
+    std::time::Duration,
+    tokio::{
+        io::{duplex, AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream},
+        task::JoinHandle,
+    },
+};
+
+/// Bytes buffered in each direction between the printer and PrusaLink
+const BRIDGE_BUFFER: usize = 4096;
+
+/// How often PrusaLink is asked for the printer's status
+const STATUS_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Storage uploaded jobs are written to
+const STORAGE: &str = "usb";
+
+#[derive(Debug, thiserror::Error)]
+pub enum PrusaLinkError {
+    #[error("PrusaLink request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("{0}")]
+    IO(#[from] std::io::Error),
+}
+
+/// Stream connected to a printer through PrusaLink, ready for `Printer::connect`
+pub type PrusaLinkTransport = BufReader<DuplexStream>;
+
+/// Connect to a Prusa printer through its PrusaLink HTTP API.
+///
+/// PrusaLink can't run arbitrary gcode, so lines sent to the printer are translated instead:
+/// - `M28 <name>` ... `M29` uploads the lines in between as a job, and starts printing it
+/// - `M24`, `M25` and `M524` resume, pause and stop the current job
+/// - `M105` and `M114` are answered from the last status
+///
+/// Anything else is answered with a `!!` error. Every line gets an `ok`,
+/// numbered like the line was if it had a line number.
+/// The status is polled every few seconds and reported as temperature
+/// and position lines, with job changes announced like Marlin's host actions.
+///
+/// The returned task runs the connection until the transport is dropped,
+/// or ends early with the error that broke the connection.
+pub fn connect(
+    hostname: &str,
+    port: Option<u16>,
+    api_key: &str,
+) -> (PrusaLinkTransport, JoinHandle<Result<(), PrusaLinkError>>) {
+    let base = match port {
+        Some(port) => format!("http://{hostname}:{port}"),
+        None => format!("http://{hostname}"),
+    };
+    let (printer_end, bridge_end) = duplex(BRIDGE_BUFFER);
+    let bridge = tokio::spawn(bridge(base, api_key.to_owned(), bridge_end));
+    (BufReader::new(printer_end), bridge)
+}
+
+/// What to do for a line sent to the printer
+#[derive(Debug, PartialEq)]
+enum Request {
+    /// Answer with these lines, then `ok`
+    Reply(Vec<String>),
+    /// Upload a finished job and print it
+    Upload { name: String, gcode: String },
+    /// Act on the current job
+    Job(JobAction),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum JobAction {
+    Pause,
+    Resume,
+    Stop,
+}
+
+/// Job being written with `M28`, until `M29`
+#[derive(Debug)]
+struct Upload {
+    name: String,
+    gcode: String,
+}
+
+/// Temperature report like `M105` would give, from a `/api/v1/status` response
+fn temperature_line(status: &Value) -> Option<String> {
+    let printer = &status["printer"];
+    let nozzle = printer["temp_nozzle"].as_f64()?;
+    let bed = printer["temp_bed"].as_f64()?;
+    Some(format!(
+        "T:{nozzle:.1} /{:.1} B:{bed:.1} /{:.1}\n",
+        printer["target_nozzle"].as_f64().unwrap_or_default(),
+        printer["target_bed"].as_f64().unwrap_or_default(),
+    ))
+}
+
+/// Position report like `M114` would give, from a `/api/v1/status` response
+fn position_line(status: &Value) -> Option<String> {
+    let printer = &status["printer"];
+    Some(format!(
+        "X:{:.2} Y:{:.2} Z:{:.2}\n",
+        printer["axis_x"].as_f64()?,
+        printer["axis_y"].as_f64()?,
+        printer["axis_z"].as_f64()?,
+    ))
+}
+
+/// Line announcing the printer's state changed to `state`, if it's one to announce
+fn state_line(previous: Option<&str>, state: &str) -> Option<&'static str> {
+    match (previous, state) {
+        (Some(previous), state) if previous == state => None,
+        (_, "PAUSED") => Some("//action:paused\n"),
+        (Some("PAUSED"), "PRINTING") => Some("//action:resumed\n"),
+        (Some("PRINTING"), "FINISHED") => Some("Done printing file\n"),
+        (Some("PRINTING" | "PAUSED"), "STOPPED") => Some("//action:cancel\n"),
+        _ => None,
+    }
+}
+
+/// Decide what to do with a line sent to the printer, collecting it if a job is being written
+fn request(line: &str, upload: &mut Option<Upload>, status: &Value) -> Request {
+    let code = strip_framing(line);
+    let parsed = parse_line(code);
+    if parsed.is('M', 29) {
+        return match upload.take() {
+            Some(Upload { name, gcode }) => Request::Upload { name, gcode },
+            None => Request::Reply(vec!["!! No file is being written\n".to_string()]),
+        };
+    }
+    if let Some(upload) = upload {
+        upload.gcode.push_str(code);
+        upload.gcode.push('\n');
+        return Request::Reply(vec![]);
+    }
+    if parsed.is('M', 28) {
+        let name = code[3..].trim();
+        let name = if name.is_empty() {
+            "print3rs.gcode"
+        } else {
+            name
+        };
+        *upload = Some(Upload {
+            name: name.to_string(),
+            gcode: String::new(),
+        });
+        return Request::Reply(vec![format!("Writing to file: {name}\n")]);
+    }
+    if parsed.is('M', 24) {
+        Request::Job(JobAction::Resume)
+    } else if parsed.is('M', 25) {
+        Request::Job(JobAction::Pause)
+    } else if parsed.is('M', 524) {
+        Request::Job(JobAction::Stop)
+    } else if parsed.is('M', 105) {
+        Request::Reply(temperature_line(status).into_iter().collect())
+    } else if parsed.is('M', 114) {
+        Request::Reply(position_line(status).into_iter().collect())
+    } else {
+        Request::Reply(vec![format!("!! PrusaLink can't run `{code}`\n")])
+    }
+}
+
+async fn get_status(
+    client: &reqwest::Client,
+    base: &str,
+    api_key: &str,
+) -> Result<Value, reqwest::Error> {
+    client
+        .get(format!("{base}/api/v1/status"))
+        .header("X-Api-Key", api_key)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+}
+
+async fn bridge(
+    base: String,
+    api_key: String,
+    bridge_end: DuplexStream,
+) -> Result<(), PrusaLinkError> {
+    let client = reqwest::Client::new();
+    let mut status = get_status(&client, &base, &api_key).await?;
+    tracing::debug!("connected to prusalink at {base}");
+
+    let (reader, mut printer) = tokio::io::split(bridge_end);
+    let mut lines = BufReader::new(reader).lines();
+    let mut poll = tokio::time::interval(STATUS_INTERVAL);
+    let mut upload = None;
+    let mut state = None;
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                let Some(line) = line? else {
+                    // printer was dropped
+                    return Ok(());
+                };
+                let replies = match request(&line, &mut upload, &status) {
+                    Request::Reply(replies) => replies,
+                    Request::Upload { name, gcode } => {
+                        let response = client
+                            .put(format!("{base}/api/v1/files/{STORAGE}/{name}"))
+                            .header("X-Api-Key", &api_key)
+                            .header("Print-After-Upload", "?1")
+                            .header("Overwrite", "?1")
+                            .body(gcode)
+                            .send()
+                            .await?;
+                        if response.status().is_success() {
+                            vec!["Done saving file.\n".to_string()]
+                        } else {
+                            vec![format!("!! PrusaLink refused upload: {}\n", response.status())]
+                        }
+                    }
+                    Request::Job(action) => {
+                        match status["job"]["id"].as_u64() {
+                            Some(id) => {
+                                let url = format!("{base}/api/v1/job/{id}");
+                                let request = match action {
+                                    JobAction::Pause => client.put(format!("{url}/pause")),
+                                    JobAction::Resume => client.put(format!("{url}/resume")),
+                                    JobAction::Stop => client.delete(url),
+                                };
+                                let response = request.header("X-Api-Key", &api_key).send().await?;
+                                if response.status().is_success() {
+                                    vec![]
+                                } else {
+                                    vec![format!("!! PrusaLink refused job change: {}\n", response.status())]
+                                }
+                            }
+                            None => vec!["!! No job is running\n".to_string()],
+                        }
+                    }
+                };
+                for reply in replies {
+                    printer.write_all(reply.as_bytes()).await?;
+                }
+                let ok = match line_number(&line) {
+                    Some(number) => format!("ok N{number}\n"),
+                    None => "ok\n".to_string(),
+                };
+                printer.write_all(ok.as_bytes()).await?;
+            },
+            _ = poll.tick() => {
+                status = get_status(&client, &base, &api_key).await?;
+                let telemetry = [temperature_line(&status), position_line(&status)];
+                for line in telemetry.into_iter().flatten() {
+                    printer.write_all(line.as_bytes()).await?;
+                }
+                if let Some(current) = status["printer"]["state"].as_str() {
+                    if let Some(line) = state_line(state.as_deref(), current) {
+                        printer.write_all(line.as_bytes()).await?;
+                    }
+                    state = Some(current.to_string());
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, serde_json::json};
+
+    #[test]
+    fn telemetry() {
+        let status = json!({
+            "printer": {
+                "state": "PRINTING",
+                "temp_nozzle": 214.9, "target_nozzle": 215.0,
+                "temp_bed": 59.8, "target_bed": 60.0,
+                "axis_x": 120.5, "axis_y": 88.0, "axis_z": 1.2
+            },
+            "job": { "id": 297, "progress": 42.0 }
+        });
+        assert_eq!(
+            temperature_line(&status).unwrap(),
+            "T:214.9 /215.0 B:59.8 /60.0\n"
+        );
+        assert_eq!(position_line(&status).unwrap(), "X:120.50 Y:88.00 Z:1.20\n");
+        assert_eq!(
+            state_line(Some("PRINTING"), "PAUSED"),
+            Some("//action:paused\n")
+        );
+        assert_eq!(state_line(Some("PRINTING"), "PRINTING"), None);
+        assert_eq!(state_line(None, "PRINTING"), None);
+    }
+
+    #[test]
+    fn uploads() {
+        let status = json!({});
+        let mut upload = None;
+        assert_eq!(
+            request("M28 benchy.gcode", &mut upload, &status),
+            Request::Reply(vec!["Writing to file: benchy.gcode\n".to_string()])
+        );
+        assert_eq!(
+            request("N1G28*18", &mut upload, &status),
+            Request::Reply(vec![])
+        );
+        request("G1 X10", &mut upload, &status);
+        assert_eq!(
+            request("M29", &mut upload, &status),
+            Request::Upload {
+                name: "benchy.gcode".to_string(),
+                gcode: "G28\nG1 X10\n".to_string()
+            }
+        );
+        assert_eq!(
+            request("M25", &mut upload, &status),
+            Request::Job(JobAction::Pause)
+        );
+        assert!(matches!(
+            request("G28", &mut upload, &status),
+            Request::Reply(replies) if replies[0].starts_with("!!")
+        ));
+    }
+}
@@ -0,0 +1,289 @@
+use {
+    crate::gcode::{parse_line, MachineState},
+    tokio::{
+        io::{duplex, AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream},
+        task::JoinHandle,
+    },
+};
+
+/// Bytes buffered in each direction between the printer and the fake firmware
+const BRIDGE_BUFFER: usize = 4096;
+
+/// Temperature heaters start at and cool back down to
+const AMBIENT: f32 = 22.0;
+
+/// Stream connected to a virtual printer, ready for `Printer::connect`
+pub type VirtualTransport = BufReader<DuplexStream>;
+
+/// How a virtual printer behaves
+#[derive(Debug, Clone, PartialEq)]
+pub struct VirtualPrinter {
+    /// Ask for every line with this number of numbered lines before it to be sent again,
+    /// as if it arrived garbled, or never if `None`
+    pub resend_every: Option<usize>,
+    /// Degrees each heater moves towards its target every time temperatures are reported
+    pub heating_rate: f32,
+}
+
+impl Default for VirtualPrinter {
+    fn default() -> Self {
+        Self {
+            resend_every: Some(100),
+            heating_rate: 15.0,
+        }
+    }
+}
+
+impl VirtualPrinter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn resend_every(mut self, lines: Option<usize>) -> Self {
+        self.resend_every = lines;
+        self
+    }
+
+    pub fn heating_rate(mut self, degrees: f32) -> Self {
+        self.heating_rate = degrees;
+        self
+    }
+
+    /// Start the fake firmware in this process.
+    ///
+    /// The returned task runs it until the transport is dropped.
+    pub fn connect(self) -> (VirtualTransport, JoinHandle<std::io::Result<()>>) {
+        let (printer_end, firmware_end) = duplex(BRIDGE_BUFFER);
+        let firmware = tokio::spawn(run(Firmware::new(self), firmware_end));
+        (BufReader::new(printer_end), firmware)
+    }
+}
+
+/// Start a virtual printer which behaves like a Marlin printer with nothing attached,
+/// for trying things out without hardware
+pub fn connect() -> (VirtualTransport, JoinHandle<std::io::Result<()>>) {
+    VirtualPrinter::default().connect()
+}
+
+async fn run(mut firmware: Firmware, firmware_end: DuplexStream) -> std::io::Result<()> {
+    let (reader, mut writer) = tokio::io::split(firmware_end);
+    let mut lines = BufReader::new(reader).lines();
+    // printer was dropped once there are no more lines
+    while let Some(line) = lines.next_line().await? {
+        writer.write_all(firmware.respond(&line).as_bytes()).await?;
+    }
+    Ok(())
+}
+
+/// What the fake firmware knows about itself between lines
+#[derive(Debug)]
+struct Firmware {
+    options: VirtualPrinter,
+    state: MachineState,
+    hotend: f32,
+    bed: f32,
+    last_line: i64,
+    /// Numbered lines accepted so far, counting towards the next made up resend
+    numbered: usize,
+}
+
+impl Firmware {
+    fn new(options: VirtualPrinter) -> Self {
+        Self {
+            options,
+            state: MachineState::default(),
+            hotend: AMBIENT,
+            bed: AMBIENT,
+            last_line: 0,
+            numbered: 0,
+        }
+    }
+
+    fn resend(&self, reason: &str) -> String {
+        let last = self.last_line;
+        format!(
+            "Error:{reason}, Last Line: {last}\nResend: {}\nok\n",
+            last + 1
+        )
+    }
+
+    /// Everything the firmware writes back after receiving `line`
+    fn respond(&mut self, line: &str) -> String {
+        let line = line.trim();
+        if line.is_empty() {
+            return String::new();
+        }
+        let code = match line.strip_prefix('N') {
+            Some(numbered) => {
+                let (body, checksum) = match line.rsplit_once('*') {
+                    Some((body, checksum)) => (body, Some(checksum)),
+                    None => (line, None),
+                };
+                let expected = body.bytes().fold(0, |checksum, byte| checksum ^ byte);
+                if checksum.is_some_and(|checksum| checksum.trim().parse() != Ok(expected)) {
+                    return self.resend("checksum mismatch");
+                }
+                let digits = numbered
+                    .find(|c: char| !(c.is_ascii_digit() || c == '-'))
+                    .unwrap_or(numbered.len());
+                let Ok(number) = numbered[..digits].parse::<i64>() else {
+                    return self.resend("No Line Number with checksum");
+                };
+                let code = body[1 + digits..].trim();
+                // M110 starts counting again from its own number
+                if !parse_line(code).is('M', 110) {
+                    if number != self.last_line + 1 {
+                        return self.resend("Line Number is not Last Line Number+1");
+                    }
+                    self.numbered += 1;
+                    if self
+                        .options
+                        .resend_every
+                        .is_some_and(|every| every > 0 && self.numbered % every == 0)
+                    {
+                        return self.resend("checksum mismatch");
+                    }
+                }
+                self.last_line = number;
+                // numbered lines are acknowledged with their number, so the host knows which finished
+                return self.execute(code, &format!("ok N{number}"));
+            }
+            None => line.split('*').next().unwrap_or_default().trim(),
+        };
+        self.execute(code, "ok")
+    }
+
+    /// Carry out a gcode without its line number or checksum, finishing with `ok`
+    fn execute(&mut self, code: &str, ok: &str) -> String {
+        let line = parse_line(code);
+        let Some(command) = line.command() else {
+            return format!("{ok}\n");
+        };
+        self.state.apply(&line);
+        match command {
+            ('M', 105) => {
+                self.heat();
+                format!(
+                    "{ok} T:{:.1} /{:.1} B:{:.1} /{:.1} @:0 B@:0\n",
+                    self.hotend,
+                    self.state.hotend_target().unwrap_or_default(),
+                    self.bed,
+                    self.state.bed_target.unwrap_or_default()
+                )
+            }
+            ('M', 109) => {
+                self.hotend = self.state.hotend_target().unwrap_or(AMBIENT);
+                format!("{ok}\n")
+            }
+            ('M', 190) => {
+                self.bed = self.state.bed_target.unwrap_or(AMBIENT);
+                format!("{ok}\n")
+            }
+            ('M', 110) => {
+                if let Some(number) = line.get('N') {
+                    self.last_line = number as i64;
+                }
+                format!("{ok}\n")
+            }
+            ('M', 114) => {
+                let [x, y, z, e] = self.state.position;
+                format!("X:{x:.2} Y:{y:.2} Z:{z:.2} E:{e:.2} Count X:0 Y:0 Z:0\n{ok}\n")
+            }
+            ('M', 115) => format!(
+                concat!(
+                    "FIRMWARE_NAME:print3rs virtual printer PROTOCOL_VERSION:1.0 MACHINE_TYPE:Virtual EXTRUDER_COUNT:1\n",
+                    "Cap:EEPROM:0\n",
+                    "Cap:AUTOREPORT_TEMP:0\n",
+                    "Cap:ARCS:1\n",
+                    "Cap:EMERGENCY_PARSER:0\n",
+                    "{}\n"
+                ),
+                ok
+            ),
+            _ => format!("{ok}\n"),
+        }
+    }
+
+    /// Move each heater a step closer to its target, or back towards the room's temperature when off
+    fn heat(&mut self) {
+        let rate = self.options.heating_rate;
+        let step = |current: f32, target: Option<f32>| {
+            let target = target.filter(|target| *target > 0.0).unwrap_or(AMBIENT);
+            current + (target - current).clamp(-rate, rate)
+        };
+        self.hotend = step(self.hotend, self.state.hotend_target());
+        self.bed = step(self.bed, self.state.bed_target);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, print3rs_core::Printer};
+
+    fn numbered(number: i64, code: &str) -> String {
+        let body = format!("N{number} {code}");
+        let checksum = body.bytes().fold(0, |checksum, byte| checksum ^ byte);
+        format!("{body}*{checksum}")
+    }
+
+    #[test]
+    fn sequence_numbers() {
+        let mut firmware = Firmware::new(VirtualPrinter::new().resend_every(Some(3)));
+        assert_eq!(firmware.respond(&numbered(1, "G28")), "ok N1\n");
+        assert_eq!(
+            firmware.respond(&numbered(3, "G1 X10")),
+            "Error:Line Number is not Last Line Number+1, Last Line: 1\nResend: 2\nok\n"
+        );
+        assert_eq!(
+            firmware.respond("N2 G1 X10*0"),
+            "Error:checksum mismatch, Last Line: 1\nResend: 2\nok\n"
+        );
+        assert_eq!(firmware.respond(&numbered(2, "G1 X10")), "ok N2\n");
+        // the third numbered line is asked for again once
+        assert!(firmware
+            .respond(&numbered(3, "G1 Y5"))
+            .contains("Resend: 3"));
+        assert_eq!(firmware.respond(&numbered(3, "G1 Y5")), "ok N3\n");
+        assert_eq!(firmware.respond(&numbered(7, "M110 N0")), "ok N7\n");
+        assert_eq!(
+            firmware.respond(&numbered(1, "M105")),
+            "ok N1 T:22.0 /0.0 B:22.0 /0.0 @:0 B@:0\n"
+        );
+        assert_eq!(
+            firmware.respond(&numbered(2, "M114")).lines().next(),
+            Some("X:10.00 Y:5.00 Z:0.00 E:0.00 Count X:0 Y:0 Z:0")
+        );
+    }
+
+    #[test]
+    fn heaters() {
+        let mut firmware = Firmware::new(VirtualPrinter::new().heating_rate(100.0));
+        firmware.respond("M104 S200");
+        firmware.respond("M140 S60");
+        assert_eq!(
+            firmware.respond("M105"),
+            "ok T:122.0 /200.0 B:60.0 /60.0 @:0 B@:0\n"
+        );
+        assert_eq!(
+            firmware.respond("M105"),
+            "ok T:200.0 /200.0 B:60.0 /60.0 @:0 B@:0\n"
+        );
+        assert!(firmware
+            .respond("M115")
+            .starts_with("FIRMWARE_NAME:print3rs"));
+    }
+
+    #[tokio::test]
+    async fn printer_round_trip() {
+        let (transport, firmware) = VirtualPrinter::new().resend_every(Some(2)).connect();
+        let mut printer = Printer::new(transport);
+        let socket = printer.socket().unwrap().clone();
+        for code in ["G28", "G1 X10 Y10", "G1 Z5", "M400"] {
+            socket.send(code).await.unwrap().await.unwrap();
+        }
+        printer.shutdown().await.unwrap();
+        drop(printer);
+        drop(socket);
+        firmware.await.unwrap().unwrap();
+    }
+}
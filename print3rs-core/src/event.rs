@@ -0,0 +1,17 @@
+use crate::PrinterState;
+
+/// Changes in the connection noticed by the background communication task
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PrinterEvent {
+    /// Nothing was heard from the device after a keepalive probe
+    Unresponsive,
+    /// The device was heard from again after being unresponsive
+    Responsive,
+    /// The connection to the device was lost, like a USB cable being unplugged.
+    ///
+    /// Not sent when disconnecting on purpose.
+    Disconnected,
+    /// The device moved into a different `PrinterState`
+    StateChanged(PrinterState),
+}
@@ -0,0 +1,240 @@
+//! Typed versions of common gcode commands, to send with any of the `send` methods
+//! instead of formatting command strings by hand.
+//!
+//! ```ignore
+//! use print3rs_core::gcode::{Home, LinearMove, SetHotendTemp};
+//!
+//! printer.send(Home::all()).await?.await?;
+//! printer.send(SetHotendTemp { temperature: 210.0, tool: None }).await?;
+//! printer.send(LinearMove { x: Some(10.0), feedrate: Some(3000.0), ..Default::default() }).await?;
+//! ```
+//!
+//! Parameters that are `None` are left out of the line.
+
+use print3rs_serializer::Gcode;
+
+/// Move in a straight line while extruding, G1
+#[derive(Debug, Clone, Copy, Default, PartialEq, Gcode)]
+#[gcode(code = "G1")]
+pub struct LinearMove {
+    pub x: Option<f32>,
+    pub y: Option<f32>,
+    pub z: Option<f32>,
+    pub e: Option<f32>,
+    /// Speed in mm/min
+    #[gcode(rename = "F")]
+    pub feedrate: Option<f32>,
+}
+
+/// Move as fast as possible without extruding, G0
+#[derive(Debug, Clone, Copy, Default, PartialEq, Gcode)]
+#[gcode(code = "G0")]
+pub struct RapidMove {
+    pub x: Option<f32>,
+    pub y: Option<f32>,
+    pub z: Option<f32>,
+    /// Speed in mm/min
+    #[gcode(rename = "F")]
+    pub feedrate: Option<f32>,
+}
+
+/// Wait before running the next command, G4
+#[derive(Debug, Clone, Copy, Default, PartialEq, Gcode)]
+#[gcode(code = "G4")]
+pub struct Dwell {
+    #[gcode(rename = "P")]
+    pub milliseconds: u32,
+}
+
+/// Home the given axes, or all of them if none are given, G28
+#[derive(Debug, Clone, Copy, Default, PartialEq, Gcode)]
+#[gcode(code = "G28")]
+pub struct Home {
+    #[gcode(flag)]
+    pub x: bool,
+    #[gcode(flag)]
+    pub y: bool,
+    #[gcode(flag)]
+    pub z: bool,
+}
+
+impl Home {
+    /// Home every axis
+    pub fn all() -> Self {
+        Self::default()
+    }
+}
+
+/// Interpret positions as absolute coordinates, G90
+#[derive(Debug, Clone, Copy, Default, PartialEq, Gcode)]
+#[gcode(code = "G90")]
+pub struct AbsolutePositioning;
+
+/// Interpret positions as distances from the current position, G91
+#[derive(Debug, Clone, Copy, Default, PartialEq, Gcode)]
+#[gcode(code = "G91")]
+pub struct RelativePositioning;
+
+/// Declare the current position to be at the given coordinates without moving, G92
+#[derive(Debug, Clone, Copy, Default, PartialEq, Gcode)]
+#[gcode(code = "G92")]
+pub struct SetPosition {
+    pub x: Option<f32>,
+    pub y: Option<f32>,
+    pub z: Option<f32>,
+    pub e: Option<f32>,
+}
+
+/// Set the number of the last line received, so the next numbered line is `line + 1`, M110
+#[derive(Debug, Clone, Copy, Default, PartialEq, Gcode)]
+#[gcode(code = "M110")]
+pub struct SetLineNumber {
+    #[gcode(rename = "N")]
+    pub line: i32,
+}
+
+/// Turn off the stepper motors, M84
+#[derive(Debug, Clone, Copy, Default, PartialEq, Gcode)]
+#[gcode(code = "M84")]
+pub struct DisableSteppers;
+
+/// Set a hotend's target temperature without waiting, M104
+#[derive(Debug, Clone, Copy, Default, PartialEq, Gcode)]
+#[gcode(code = "M104")]
+pub struct SetHotendTemp {
+    #[gcode(rename = "S")]
+    pub temperature: f32,
+    /// Hotend to heat, the active one if not given
+    #[gcode(rename = "T")]
+    pub tool: Option<u8>,
+}
+
+/// Set a hotend's target temperature and wait for it to be reached, M109
+#[derive(Debug, Clone, Copy, Default, PartialEq, Gcode)]
+#[gcode(code = "M109")]
+pub struct WaitHotendTemp {
+    #[gcode(rename = "S")]
+    pub temperature: f32,
+    /// Hotend to heat, the active one if not given
+    #[gcode(rename = "T")]
+    pub tool: Option<u8>,
+}
+
+/// Set the bed's target temperature without waiting, M140
+#[derive(Debug, Clone, Copy, Default, PartialEq, Gcode)]
+#[gcode(code = "M140")]
+pub struct SetBedTemp {
+    #[gcode(rename = "S")]
+    pub temperature: f32,
+}
+
+/// Set the bed's target temperature and wait for it to be reached, M190
+#[derive(Debug, Clone, Copy, Default, PartialEq, Gcode)]
+#[gcode(code = "M190")]
+pub struct WaitBedTemp {
+    #[gcode(rename = "S")]
+    pub temperature: f32,
+}
+
+/// Set the speed of a fan from 0 to 255, M106
+#[derive(Debug, Clone, Copy, Default, PartialEq, Gcode)]
+#[gcode(code = "M106")]
+pub struct SetFanSpeed {
+    #[gcode(rename = "S")]
+    pub speed: u8,
+    /// Fan to set, the part cooling fan if not given
+    #[gcode(rename = "P")]
+    pub fan: Option<u8>,
+}
+
+/// Turn off a fan, M107
+#[derive(Debug, Clone, Copy, Default, PartialEq, Gcode)]
+#[gcode(code = "M107")]
+pub struct FanOff {
+    /// Fan to turn off, the part cooling fan if not given
+    #[gcode(rename = "P")]
+    pub fan: Option<u8>,
+}
+
+/// Ask for the current temperatures, M105, see `TemperatureReport`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Gcode)]
+#[gcode(code = "M105")]
+pub struct ReportTemperatures;
+
+/// Ask for the current position, M114, see `Position`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Gcode)]
+#[gcode(code = "M114")]
+pub struct ReportPosition;
+
+/// Ask for the firmware's name and capabilities, M115, see `InfoMap`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Gcode)]
+#[gcode(code = "M115")]
+pub struct FirmwareInfo;
+
+/// Have the temperatures reported every `interval` seconds, or stop with 0, M155
+#[derive(Debug, Clone, Copy, Default, PartialEq, Gcode)]
+#[gcode(code = "M155")]
+pub struct AutoreportTemperatures {
+    #[gcode(rename = "S")]
+    pub interval: u32,
+}
+
+/// Have the position reported every `interval` seconds, or stop with 0, M154
+#[derive(Debug, Clone, Copy, Default, PartialEq, Gcode)]
+#[gcode(code = "M154")]
+pub struct AutoreportPosition {
+    #[gcode(rename = "S")]
+    pub interval: u32,
+}
+
+#[cfg(test)]
+mod test {
+    use {super::*, print3rs_serializer::serialize_unsequenced};
+
+    #[test]
+    fn moves() {
+        let line = LinearMove {
+            x: Some(10.0),
+            y: Some(-2.5),
+            feedrate: Some(3000.0),
+            ..Default::default()
+        };
+        assert_eq!(*serialize_unsequenced(line), *b"G1X10.0Y-2.5F3000.0\n");
+        assert_eq!(*serialize_unsequenced(RapidMove::default()), *b"G0\n");
+        assert_eq!(
+            *serialize_unsequenced(Dwell { milliseconds: 500 }),
+            *b"G4P500\n"
+        );
+    }
+
+    #[test]
+    fn homing() {
+        assert_eq!(*serialize_unsequenced(Home::all()), *b"G28\n");
+        let home = Home {
+            z: true,
+            ..Default::default()
+        };
+        assert_eq!(*serialize_unsequenced(home), *b"G28Z\n");
+    }
+
+    #[test]
+    fn temperatures() {
+        let hotend = SetHotendTemp {
+            temperature: 210.0,
+            tool: Some(1),
+        };
+        assert_eq!(*serialize_unsequenced(hotend), *b"M104S210.0T1\n");
+        let bed = WaitBedTemp { temperature: 60.0 };
+        assert_eq!(*serialize_unsequenced(bed), *b"M190S60.0\n");
+        assert_eq!(*serialize_unsequenced(ReportTemperatures), *b"M105\n");
+    }
+
+    #[test]
+    fn reports() {
+        assert_eq!(*serialize_unsequenced(ReportPosition), *b"M114\n");
+        assert_eq!(
+            *serialize_unsequenced(AutoreportPosition { interval: 1 }),
+            *b"M154S1\n"
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//! Support for CNC machines and lasers running Grbl, see `Dialect::Grbl`.
+//!
+//! Grbl acts on a few single-byte commands the moment they arrive, without a line ending
+//! or waiting behind queued lines. Send them with `Socket::send_realtime`.
+
+use crate::PrinterState;
+
+/// Ask for a status report, answered with a line like `<Idle|MPos:0.000,0.000,0.000|FS:0,0>`
+pub const STATUS_REPORT: u8 = b'?';
+/// Pause motion, decelerating to a stop
+pub const FEED_HOLD: u8 = b'!';
+/// Resume after a feed hold
+pub const CYCLE_START: u8 = b'~';
+/// Stop immediately and reset, like a power cycle but keeping the position
+pub const SOFT_RESET: u8 = 0x18;
+/// Stop a jog in progress, discarding any jogs queued after it
+pub const JOG_CANCEL: u8 = 0x85;
+
+/// Machine state at the start of a status report
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrblState {
+    Idle,
+    Run,
+    Hold,
+    Jog,
+    Alarm,
+    Door,
+    Check,
+    Home,
+    Sleep,
+}
+
+impl GrblState {
+    fn parse(name: &str) -> Option<Self> {
+        // substates follow a `:`, like `Hold:0`
+        let name = name.split(':').next()?;
+        Some(match name {
+            "Idle" => Self::Idle,
+            "Run" => Self::Run,
+            "Hold" => Self::Hold,
+            "Jog" => Self::Jog,
+            "Alarm" => Self::Alarm,
+            "Door" => Self::Door,
+            "Check" => Self::Check,
+            "Home" => Self::Home,
+            "Sleep" => Self::Sleep,
+            _ => return None,
+        })
+    }
+
+    /// Closest `PrinterState` to this machine state
+    pub fn printer_state(self) -> PrinterState {
+        match self {
+            Self::Idle | Self::Check | Self::Sleep => PrinterState::Idle,
+            Self::Run => PrinterState::Printing,
+            Self::Hold | Self::Door => PrinterState::Paused,
+            Self::Jog | Self::Home => PrinterState::Busy,
+            Self::Alarm => PrinterState::Error,
+        }
+    }
+}
+
+/// Status report sent by Grbl in reply to `STATUS_REPORT`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GrblStatus {
+    pub state: GrblState,
+    /// Position in machine coordinates, `MPos`
+    pub machine_position: Option<[f32; 3]>,
+    /// Position in work coordinates, `WPos`
+    pub work_position: Option<[f32; 3]>,
+    /// Current feed rate, from `FS` or `F`
+    pub feed: Option<f32>,
+    /// Current spindle speed or laser power, from `FS`
+    pub spindle: Option<f32>,
+}
+
+impl GrblStatus {
+    /// Read a line received from the device, if it is a status report
+    pub fn parse(line: &str) -> Option<Self> {
+        let report = line.trim().strip_prefix('<')?.strip_suffix('>')?;
+        let mut fields = report.split('|');
+        let mut status = Self {
+            state: GrblState::parse(fields.next()?)?,
+            machine_position: None,
+            work_position: None,
+            feed: None,
+            spindle: None,
+        };
+        for field in fields {
+            let Some((name, values)) = field.split_once(':') else {
+                continue;
+            };
+            let values: Vec<f32> = values.split(',').filter_map(|v| v.parse().ok()).collect();
+            match (name, values.as_slice()) {
+                ("MPos", [x, y, z, ..]) => status.machine_position = Some([*x, *y, *z]),
+                ("WPos", [x, y, z, ..]) => status.work_position = Some([*x, *y, *z]),
+                ("FS", [feed, spindle, ..]) => {
+                    status.feed = Some(*feed);
+                    status.spindle = Some(*spindle);
+                }
+                ("F", [feed, ..]) => status.feed = Some(*feed),
+                _ => {}
+            }
+        }
+        Some(status)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn status_reports() {
+        let status =
+            GrblStatus::parse("<Idle|MPos:1.000,-2.500,0.000|FS:0,0|WCO:0.000,0.000,0.000>\r\n")
+                .unwrap();
+        assert_eq!(status.state, GrblState::Idle);
+        assert_eq!(status.machine_position, Some([1.0, -2.5, 0.0]));
+        assert_eq!(status.work_position, None);
+        assert_eq!(status.feed, Some(0.0));
+
+        let status = GrblStatus::parse("<Hold:0|WPos:10.000,0.000,0.000|F:500>").unwrap();
+        assert_eq!(status.state.printer_state(), PrinterState::Paused);
+        assert_eq!(status.work_position, Some([10.0, 0.0, 0.0]));
+        assert_eq!(status.feed, Some(500.0));
+
+        assert!(GrblStatus::parse("ok").is_none());
+        assert!(GrblStatus::parse("<Unknown|MPos:0,0,0>").is_none());
+    }
+}
@@ -68,6 +68,7 @@ impl From<InfoMap> for InfoMapInner {
 
 /// Known named capabilities of devices
 #[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Capability {
     AutoreportTemp,
     AutoreportPos,
@@ -78,6 +79,7 @@ pub enum Capability {
     BuildPercent,
     Progress,
     AdvancedOk,
+    Babystepping,
 }
 
 impl AsRef<str> for Capability {
@@ -98,11 +100,86 @@ impl Capability {
             Capability::BuildPercent => "BUILD_PERCENT",
             Capability::Progress => "PROGRESS",
             Capability::AdvancedOk => "ADVANCED_OK",
+            Capability::Babystepping => "BABYSTEPPING",
         }
     }
 }
 
+/// Key of a `KEY:value` pair in a firmware report, like `FIRMWARE_NAME` or `EXTRUDER_COUNT`
+fn is_report_key(key: &str) -> bool {
+    key.starts_with(|c: char| c.is_ascii_uppercase())
+        && key
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Most specific `Info` a reported value can be read as
+fn report_value(value: &str) -> Info {
+    let value = value.trim();
+    if value.is_empty() {
+        Info::Key
+    } else if let Ok(int) = value.parse() {
+        Info::Int(int)
+    } else if let Ok(float) = value.parse() {
+        Info::Float(float)
+    } else {
+        Info::Str(value.to_string())
+    }
+}
+
 impl InfoMap {
+    /// Read all the information in an M115 firmware report
+    pub fn from_report<'a>(report: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut info = Self::default();
+        for line in report {
+            info.parse_report_line(line);
+        }
+        info
+    }
+
+    /// Add what a single line of an M115 firmware report says about the device.
+    ///
+    /// Understands the `FIRMWARE_NAME:Marlin 2.1.2 MACHINE_TYPE:Ender-3 ...` line,
+    /// where values may contain spaces, and `Cap:AUTOREPORT_TEMP:1` capability lines.
+    /// Returns false if the line is not part of a firmware report.
+    pub fn parse_report_line(&mut self, line: &str) -> bool {
+        let line = line.trim();
+        if let Some(capability) = line.strip_prefix("Cap:") {
+            let Some((name, supported)) = capability.split_once(':') else {
+                return false;
+            };
+            self.0
+                .insert(name.to_string(), Info::Bool(supported.trim() == "1"));
+            return true;
+        }
+        if !line.starts_with("FIRMWARE_NAME:") {
+            return false;
+        }
+        let mut entry: Option<(&str, String)> = None;
+        for word in line.split_whitespace() {
+            match word.split_once(':') {
+                Some((key, value)) if is_report_key(key) => {
+                    if let Some((key, value)) = entry.take() {
+                        self.0.insert(key.to_string(), report_value(&value));
+                    }
+                    entry = Some((key, value.to_string()));
+                }
+                _ => {
+                    if let Some((_, value)) = &mut entry {
+                        if !value.is_empty() {
+                            value.push(' ');
+                        }
+                        value.push_str(word);
+                    }
+                }
+            }
+        }
+        if let Some((key, value)) = entry {
+            self.0.insert(key.to_string(), report_value(&value));
+        }
+        true
+    }
+
     /// Check if a named known capability is supported on this device.
     pub fn has_capability(&self, capability: Capability) -> bool {
         self.0.get(capability.as_str()).is_some_and(Info::is_true)
@@ -150,6 +227,38 @@ mod test {
         assert_eq!(og, converted.into())
     }
 
+    #[test]
+    fn firmware_report() {
+        let info = InfoMap::from_report([
+            "FIRMWARE_NAME:Marlin bugfix-2.1.x (Sep 12 2023) SOURCE_CODE_URL:https://github.com/MarlinFirmware/Marlin PROTOCOL_VERSION:1.0 MACHINE_TYPE:Ender-3 V2 EXTRUDER_COUNT:1 UUID:cede2a2f-41a2-4748-9b12-c55c62f367ff\n",
+            "Cap:AUTOREPORT_TEMP:1\n",
+            "Cap:ARCS:0\n",
+            "ok\n",
+        ]);
+        assert_eq!(
+            info["FIRMWARE_NAME"],
+            Info::Str("Marlin bugfix-2.1.x (Sep 12 2023)".to_string())
+        );
+        assert_eq!(
+            info["SOURCE_CODE_URL"],
+            Info::Str("https://github.com/MarlinFirmware/Marlin".to_string())
+        );
+        assert_eq!(info["MACHINE_TYPE"], Info::Str("Ender-3 V2".to_string()));
+        assert_eq!(info["EXTRUDER_COUNT"], Info::Int(1));
+        assert_eq!(info["PROTOCOL_VERSION"], Info::Float(1.0));
+        assert!(info.has_capability(Capability::AutoreportTemp));
+        assert!(!info.has_capability(Capability::Arcs));
+        assert!(!info.has_capability(Capability::Progress));
+    }
+
+    #[test]
+    fn not_a_report() {
+        let mut info = InfoMap::default();
+        assert!(!info.parse_report_line("T:21.0 /0.0 B:20.9 /0.0"));
+        assert!(!info.parse_report_line("echo:Cap:SOMETHING"));
+        assert!(info.is_empty());
+    }
+
     #[test]
     fn info_conversion() {
         let cap = Capability::AutoreportPos;
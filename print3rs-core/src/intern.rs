@@ -0,0 +1,61 @@
+use std::sync::Arc;
+
+/// How many distinct recent lines are kept around to be handed out again
+const CACHED_LINES: usize = 16;
+
+/// Lines longer than this are rarely repeated word for word, so aren't worth comparing against
+const MAX_CACHED_LEN: usize = 96;
+
+/// Recently received lines, so ones the device repeats a lot, like `ok`, `wait`,
+/// `echo:busy: processing` or an unchanged temperature report, share one allocation
+/// instead of getting a new one every time they are broadcast
+#[derive(Debug, Default)]
+pub(crate) struct LineCache {
+    lines: Vec<Arc<str>>,
+    /// Slot the next new line replaces once every slot is taken
+    next: usize,
+}
+
+impl LineCache {
+    /// Shared copy of `line`, reusing one handed out before when it's the same
+    pub(crate) fn intern(&mut self, line: &str) -> Arc<str> {
+        if line.len() > MAX_CACHED_LEN {
+            return Arc::from(line);
+        }
+        if let Some(cached) = self.lines.iter().find(|cached| ***cached == *line) {
+            return cached.clone();
+        }
+        let line: Arc<str> = Arc::from(line);
+        if self.lines.len() < CACHED_LINES {
+            self.lines.push(line.clone());
+        } else {
+            self.lines[self.next] = line.clone();
+            self.next = (self.next + 1) % CACHED_LINES;
+        }
+        line
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reuses_repeated_lines() {
+        let mut cache = LineCache::default();
+        let ok = cache.intern("ok\n");
+        let report = cache.intern("T:21.0 /0.0 B:20.5 /0.0 @:0 B@:0\n");
+        assert!(Arc::ptr_eq(&ok, &cache.intern("ok\n")));
+        assert!(Arc::ptr_eq(
+            &report,
+            &cache.intern("T:21.0 /0.0 B:20.5 /0.0 @:0 B@:0\n")
+        ));
+        // the oldest line makes way once the cache is full
+        for n in 0..CACHED_LINES {
+            cache.intern(&format!("echo:{n}\n"));
+        }
+        assert!(!Arc::ptr_eq(&ok, &cache.intern("ok\n")));
+        let long = "M117 ".repeat(MAX_CACHED_LEN);
+        assert!(!Arc::ptr_eq(&cache.intern(&long), &cache.intern(&long)));
+    }
+}
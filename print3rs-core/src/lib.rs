@@ -1,47 +1,199 @@
-use std::{collections::BTreeMap, fmt::Debug, future::Future, sync::Arc};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::Debug,
+    future::Future,
+    sync::{Arc, Mutex, RwLock},
+};
 
+use bytes::Bytes;
 use serde::Serialize;
 use winnow::Parser;
 
+mod event;
+pub mod gcode;
+pub mod grbl;
 mod info;
+mod intern;
+mod options;
+mod record;
 mod response;
+mod state;
+mod stats;
+#[cfg(feature = "futures")]
+mod stream;
 
+pub use event::PrinterEvent;
+pub use info::{Capability, Info, InfoMap};
+use intern::LineCache;
+pub use options::{Dialect, PrinterOptions};
+pub use record::{Direction, Entry, Recorder, Replay};
 use response::response;
-pub use response::Response;
+pub use response::{BufferSpace, Position, Response, Temperature, TemperatureReport};
+pub use state::PrinterState;
+use state::{track, StateTracker};
+use stats::StatCounters;
+pub use stats::{ChannelStats, ACK_LATENCY_BUCKETS};
+#[cfg(feature = "futures")]
+pub use stream::{GcodeSink, Lines};
 
-use print3rs_serializer::{serialize_unsequenced, Sequenced};
+use print3rs_serializer::Sequenced;
+pub use print3rs_serializer::{Checksum, Format, LineEnding, LineTooLong};
 
 use tokio::{
     io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt},
-    sync::{broadcast, mpsc, oneshot},
+    sync::{broadcast, mpsc, oneshot, Notify},
     task::JoinHandle,
+    time::{sleep_until, Instant},
 };
 
 pub type LineStream = broadcast::Receiver<Arc<str>>;
 
+/// Receiver of every temperature report from the printer, see `Socket::temperatures`
+#[derive(Debug)]
+pub struct TemperatureStream {
+    lines: LineStream,
+    stats: Arc<StatCounters>,
+}
+
+impl TemperatureStream {
+    /// Wait for the next temperature report, skipping any other lines
+    pub async fn recv(&mut self) -> Result<TemperatureReport, Error> {
+        loop {
+            let line = self.stats.received(self.lines.recv().await)?;
+            if let Some(report) = TemperatureReport::parse(&line) {
+                return Ok(report);
+            }
+        }
+    }
+}
+
+/// Receiver of every position report from the printer, see `Socket::positions`
+#[derive(Debug)]
+pub struct PositionStream {
+    lines: LineStream,
+    stats: Arc<StatCounters>,
+}
+
+impl PositionStream {
+    /// Wait for the next position report, skipping any other lines
+    pub async fn recv(&mut self) -> Result<Position, Error> {
+        loop {
+            let line = self.stats.received(self.lines.recv().await)?;
+            if let Some(position) = Position::parse(&line) {
+                return Ok(position);
+            }
+        }
+    }
+}
+
+/// Line written to the device which is waiting on an `ok`
+#[derive(Debug)]
+struct Pending {
+    responder: oneshot::Sender<Result<(), Error>>,
+    content: Bytes,
+    /// When the line was last written
+    sent: Instant,
+    /// How many times the line has been written again without getting an answer
+    retries: u32,
+}
+
+/// Expect an answer from Grbl for every line ending in a write, as it answers even empty lines,
+/// with `responder` waiting on the last of them.
+///
+/// Lines nobody waits on still take their place, so their answers aren't given to later lines.
+/// Real-time commands are single bytes without a line ending, so they add nothing.
+fn expect_grbl_answers(
+    answers: &mut VecDeque<Option<Pending>>,
+    content: Bytes,
+    responder: Option<oneshot::Sender<Result<(), Error>>>,
+    sent: Instant,
+) {
+    let lines = line_endings(&content);
+    if lines == 0 {
+        // nothing will be answered, so there is nothing to wait for
+        if let Some(responder) = responder {
+            let _ = responder.send(Ok(()));
+        }
+        return;
+    }
+    answers.extend((1..lines).map(|_| None));
+    answers.push_back(responder.map(|responder| Pending {
+        responder,
+        content,
+        sent,
+        retries: 0,
+    }));
+}
+
+/// How many lines a write ends, counting `\r` and `\n` apart as Grbl does
+fn line_endings(content: &[u8]) -> usize {
+    content
+        .iter()
+        .filter(|byte| matches!(byte, b'\r' | b'\n'))
+        .count()
+}
+
+/// Take the line an `ok` answers, for firmware answering in the order lines arrive:
+/// whichever was written first of the line waiting under `key` and the oldest urgent line.
+///
+/// Neither unnumbered nor urgent lines are written again, so `sent` keeps their order.
+fn answered(
+    pending: &mut BTreeMap<Option<i32>, Pending>,
+    key: Option<Option<i32>>,
+    urgent: &mut VecDeque<Pending>,
+) -> Option<Pending> {
+    let queued = key
+        .and_then(|key| pending.get(&key))
+        .map(|pending| pending.sent);
+    let urgent_first = urgent
+        .front()
+        .is_some_and(|first| !queued.is_some_and(|queued| queued <= first.sent));
+    if urgent_first {
+        urgent.pop_front()
+    } else {
+        key.and_then(|key| pending.remove(&key))
+    }
+}
+
+/// Line on its way to the communication task.
+///
+/// The content is shared rather than copied, so resending it or holding on to it while
+/// waiting for an `ok` doesn't allocate again.
 #[derive(Debug)]
 struct SendContent {
-    content: Box<[u8]>,
+    content: Bytes,
     sequence: Option<i32>,
-    responder: Option<oneshot::Sender<()>>,
+    responder: Option<oneshot::Sender<Result<(), Error>>>,
 }
 
 impl SendContent {
-    const fn new(
-        content: Box<[u8]>,
+    fn new(
+        content: impl Into<Bytes>,
         sequence: Option<i32>,
-        responder: Option<oneshot::Sender<()>>,
+        responder: Option<oneshot::Sender<Result<(), Error>>>,
     ) -> Self {
         Self {
-            content,
+            content: content.into(),
             sequence,
             responder,
         }
     }
 }
 
-impl From<(Box<[u8]>, Option<i32>, Option<oneshot::Sender<()>>)> for SendContent {
-    fn from(value: (Box<[u8]>, Option<i32>, Option<oneshot::Sender<()>>)) -> Self {
+impl
+    From<(
+        Bytes,
+        Option<i32>,
+        Option<oneshot::Sender<Result<(), Error>>>,
+    )> for SendContent
+{
+    fn from(
+        value: (
+            Bytes,
+            Option<i32>,
+            Option<oneshot::Sender<Result<(), Error>>>,
+        ),
+    ) -> Self {
         SendContent::new(value.0, value.1, value.2)
     }
 }
@@ -49,16 +201,30 @@ impl From<(Box<[u8]>, Option<i32>, Option<oneshot::Sender<()>>)> for SendContent
 #[derive(Debug)]
 pub struct Socket {
     sender: mpsc::Sender<SendContent>,
+    urgent: mpsc::Sender<SendContent>,
     serializer: Sequenced,
-    pub responses: broadcast::Receiver<Arc<str>>,
+    lines: broadcast::Sender<Arc<str>>,
+    /// Lines for `read_next_line`, only subscribed once it is first called
+    /// so a socket which never reads doesn't hold lines back from the channel
+    responses: Option<broadcast::Receiver<Arc<str>>>,
+    events: broadcast::Sender<PrinterEvent>,
+    state: Arc<Mutex<StateTracker>>,
+    stats: Arc<StatCounters>,
+    dialect: Dialect,
 }
 
 impl Clone for Socket {
     fn clone(&self) -> Self {
         Self {
             sender: self.sender.clone(),
+            urgent: self.urgent.clone(),
             serializer: self.serializer.clone(),
-            responses: self.responses.resubscribe(),
+            lines: self.lines.clone(),
+            responses: None,
+            events: self.events.clone(),
+            state: self.state.clone(),
+            stats: self.stats.clone(),
+            dialect: self.dialect,
         }
     }
 }
@@ -78,10 +244,10 @@ impl Socket {
         gcode: impl Serialize + Debug,
     ) -> Result<impl Future<Output = Result<(), Error>>, Error> {
         let send_slot = self.sender.reserve().await?;
-        let (sequence, bytes) = self.serializer.serialize(gcode);
+        let (sequence, bytes) = self.serializer.try_serialize(gcode)?;
         let (responder, response) = oneshot::channel();
         send_slot.send(SendContent::new(bytes, Some(sequence), Some(responder)));
-        let response = async { response.await.map_err(|_| Error::WontRespond) };
+        let response = async { response.await.map_err(|_| Error::WontRespond)? };
         Ok(response)
     }
 
@@ -93,11 +259,11 @@ impl Socket {
         &self,
         gcode: impl Serialize + Debug,
     ) -> Result<impl Future<Output = Result<(), Error>>, Error> {
-        let send_slot = self.sender.try_reserve()?;
-        let (sequence, bytes) = self.serializer.serialize(gcode);
+        let send_slot = self.stats.reserved(self.sender.try_reserve())?;
+        let (sequence, bytes) = self.serializer.try_serialize(gcode)?;
         let (responder, response) = oneshot::channel();
         send_slot.send(SendContent::new(bytes, Some(sequence), Some(responder)));
-        let response = async { response.await.map_err(|_| Error::WontRespond) };
+        let response = async { response.await.map_err(|_| Error::WontRespond)? };
         Ok(response)
     }
 
@@ -112,11 +278,11 @@ impl Socket {
         &self,
         gcode: impl Serialize + Debug,
     ) -> Result<impl Future<Output = Result<(), Error>>, Error> {
-        let bytes = serialize_unsequenced(gcode);
+        let bytes = self.serializer.try_serialize_unsequenced(gcode)?;
         let (responder, response) = oneshot::channel();
         let send_slot = self.sender.reserve().await?;
         send_slot.send(SendContent::new(bytes, None, Some(responder)));
-        let response = async { response.await.map_err(|_| Error::WontRespond) };
+        let response = async { response.await.map_err(|_| Error::WontRespond)? };
         Ok(response)
     }
 
@@ -127,33 +293,125 @@ impl Socket {
         &self,
         gcode: impl Serialize + Debug,
     ) -> Result<impl Future<Output = Result<(), Error>>, Error> {
-        let bytes = serialize_unsequenced(gcode);
+        let bytes = self.serializer.try_serialize_unsequenced(gcode)?;
         let (responder, response) = oneshot::channel();
-        let send_slot = self.sender.try_reserve()?;
+        let send_slot = self.stats.reserved(self.sender.try_reserve())?;
         send_slot.send(SendContent::new(bytes, None, Some(responder)));
-        let response = async { response.await.map_err(|_| Error::WontRespond) };
+        let response = async { response.await.map_err(|_| Error::WontRespond)? };
         Ok(response)
     }
 
+    /// Send a line exactly as given, followed only by the format's line ending.
+    ///
+    /// Unlike every other send, comments are kept even if the format strips them,
+    /// and the line is neither numbered nor checked. Its `ok` is waited on like `send_unsequenced`.
+    pub async fn send_verbatim(
+        &self,
+        line: &str,
+    ) -> Result<impl Future<Output = Result<(), Error>>, Error> {
+        let line_ending = self.serializer.format().line_ending.as_bytes();
+        let mut content = Vec::with_capacity(line.len() + line_ending.len());
+        content.extend_from_slice(line.as_bytes());
+        content.extend_from_slice(line_ending);
+        let (responder, response) = oneshot::channel();
+        let send_slot = self.sender.reserve().await?;
+        send_slot.send(SendContent::new(content, None, Some(responder)));
+        let response = async { response.await.map_err(|_| Error::WontRespond)? };
+        Ok(response)
+    }
+
+    /// Send a real-time command like M112, M410 or M105 ahead of everything already queued.
+    ///
+    /// The line is written as soon as the communication task is free, even if the printer
+    /// hasn't acknowledged earlier commands yet. It is sent without a line number,
+    /// so lines numbered by `send` still arrive in order.
+    pub async fn send_urgent(
+        &self,
+        gcode: impl Serialize + Debug,
+    ) -> Result<impl Future<Output = Result<(), Error>>, Error> {
+        let bytes = self.serializer.try_serialize_unsequenced(gcode)?;
+        let (responder, response) = oneshot::channel();
+        let send_slot = self.urgent.reserve().await?;
+        send_slot.send(SendContent::new(bytes, None, Some(responder)));
+        let response = async { response.await.map_err(|_| Error::WontRespond)? };
+        Ok(response)
+    }
+
+    /// Send one of Grbl's single-byte real-time commands, like `grbl::FEED_HOLD`,
+    /// ahead of everything already queued and without a line ending.
+    pub async fn send_realtime(&self, command: u8) -> Result<(), Error> {
+        let send_slot = self.urgent.reserve().await?;
+        send_slot.send(SendContent::new(vec![command], None, None));
+        Ok(())
+    }
+
+    /// Ask Grbl for its status with `grbl::STATUS_REPORT`, and wait for the report
+    pub async fn request_status(&self) -> Result<grbl::GrblStatus, Error> {
+        let mut lines = self.subscribe_lines()?;
+        self.send_realtime(grbl::STATUS_REPORT).await?;
+        loop {
+            let line = self.stats.received(lines.recv().await)?;
+            if let Some(status) = grbl::GrblStatus::parse(&line) {
+                return Ok(status);
+            }
+        }
+    }
+
+    /// Restart line numbering so the next sequenced line is `N<next>`, telling the device with M110.
+    ///
+    /// The M110 is queued in the same step as the counter is changed,
+    /// so lines sent afterwards are always numbered after it.
+    pub async fn reset_sequence(&self, next: i32) -> Result<(), Error> {
+        let send_slot = self.sender.reserve().await?;
+        self.serializer.set_sequence(next);
+        let bytes = self
+            .serializer
+            .serialize_unsequenced(gcode::SetLineNumber { line: next - 1 });
+        send_slot.send(SendContent::new(bytes, None, None));
+        Ok(())
+    }
+
+    /// The bytes `send_bytes` should write, with comments stripped if the format asks for it
+    fn raw_content(&self, gcode: Bytes) -> Bytes {
+        if self.serializer.format().strip_comments {
+            print3rs_serializer::strip_comments(&gcode).into()
+        } else {
+            gcode
+        }
+    }
+
     /// Send any raw sequence of bytes to the printer
+    ///
+    /// If the format strips comments, they are removed from every line
+    /// and nothing is sent if no gcode is left.
     pub async fn send_raw(&self, gcode: &[u8]) -> Result<(), Error> {
-        let sender = self.sender.reserve().await?;
-        sender.send(SendContent::new(
-            gcode.to_owned().into_boxed_slice(),
-            None,
-            None,
-        ));
-        Ok(())
+        self.send_bytes(Bytes::copy_from_slice(gcode)).await
     }
 
     /// Send any raw sequence of bytes to the printer
     pub fn try_send_raw(&self, gcode: &[u8]) -> Result<(), Error> {
-        let sender = self.sender.try_reserve()?;
-        sender.send(SendContent::new(
-            gcode.to_owned().into_boxed_slice(),
-            None,
-            None,
-        ));
+        self.try_send_bytes(Bytes::copy_from_slice(gcode))
+    }
+
+    /// Like `send_raw`, but taking bytes which are already owned, so they are handed over without a copy
+    pub async fn send_bytes(&self, gcode: impl Into<Bytes>) -> Result<(), Error> {
+        let content = self.raw_content(gcode.into());
+        if content.is_empty() {
+            return Ok(());
+        }
+        let sender = self.sender.reserve().await?;
+        sender.send(SendContent::new(content, None, None));
+        Ok(())
+    }
+
+    /// Non-blocking non-async version of `send_bytes`, returns an error where that method would wait
+    pub fn try_send_bytes(&self, gcode: impl Into<Bytes>) -> Result<(), Error> {
+        let content = self.raw_content(gcode.into());
+        if content.is_empty() {
+            return Ok(());
+        }
+        let sender = self.stats.reserved(self.sender.try_reserve())?;
+        sender.send(SendContent::new(content, None, None));
         Ok(())
     }
 
@@ -162,8 +420,11 @@ impl Socket {
     /// May not recieve all lines, if calls to this function are spaced
     /// far apart, the buffer may overfill and the oldest messages will
     /// be dropped. In this case the oldest available message is returned.
+    ///
+    /// Lines are only kept for this socket from its first read on.
     pub async fn read_next_line(&mut self) -> Result<Arc<str>, Error> {
-        let line = self.responses.recv().await?;
+        let responses = self.responses.get_or_insert_with(|| self.lines.subscribe());
+        let line = self.stats.received(responses.recv().await)?;
         Ok(line)
     }
 
@@ -171,13 +432,118 @@ impl Socket {
     ///
     /// Where that method would await, this will return immediately with an error.
     pub fn try_read_next_line(&mut self) -> Result<Arc<str>, Error> {
-        let line = self.responses.try_recv()?;
+        let responses = self.responses.get_or_insert_with(|| self.lines.subscribe());
+        let line = self.stats.try_received(responses.try_recv())?;
         Ok(line)
     }
 
     /// Obtain a broadcast receiver returning all lines received by the printer
     pub fn subscribe_lines(&self) -> Result<LineStream, Error> {
-        Ok(self.responses.resubscribe())
+        Ok(self.lines.subscribe())
+    }
+
+    /// The receiver `read_next_line` reads from, for using it directly
+    ///
+    /// Like that method, lines are only kept for this socket from the first call on.
+    pub fn responses(&mut self) -> &mut LineStream {
+        self.responses.get_or_insert_with(|| self.lines.subscribe())
+    }
+
+    /// Traffic with the printer so far, and how many messages have been lost to full channels
+    pub fn stats(&self) -> ChannelStats {
+        self.stats.snapshot()
+    }
+
+    /// How many lines are queued and not yet written to the printer
+    pub fn queued(&self) -> usize {
+        self.sender.max_capacity() - self.sender.capacity()
+    }
+
+    /// Obtain a `Sink` which sends sequenced gcode to the printer, like `send` without waiting on the `ok`
+    #[cfg(feature = "futures")]
+    pub fn sink(&self) -> Result<GcodeSink, Error> {
+        Ok(GcodeSink::new(self.sender.clone(), self.serializer.clone()))
+    }
+
+    /// Obtain a `Stream` of every line the printer sends
+    #[cfg(feature = "futures")]
+    pub fn lines(&self) -> Result<Lines, Error> {
+        Ok(Lines::new(self.subscribe_lines()?, self.stats.clone()))
+    }
+
+    /// Obtain a receiver of the temperatures the printer reports.
+    ///
+    /// Reports only arrive in reply to M105, or periodically after enabling autoreport with `M155 S<seconds>`.
+    pub fn temperatures(&self) -> Result<TemperatureStream, Error> {
+        Ok(TemperatureStream {
+            lines: self.subscribe_lines()?,
+            stats: self.stats.clone(),
+        })
+    }
+
+    /// Obtain a receiver of the positions the printer reports.
+    ///
+    /// Reports only arrive in reply to M114, or periodically after enabling autoreport with `M154 S<seconds>`.
+    pub fn positions(&self) -> Result<PositionStream, Error> {
+        Ok(PositionStream {
+            lines: self.subscribe_lines()?,
+            stats: self.stats.clone(),
+        })
+    }
+
+    /// Obtain a broadcast receiver for changes in the connection, like the device becoming unresponsive
+    pub fn subscribe_events(&self) -> broadcast::Receiver<PrinterEvent> {
+        self.events.subscribe()
+    }
+
+    /// What the device is doing, as tracked from the commands sent to it and its responses
+    pub fn state(&self) -> PrinterState {
+        self.state
+            .lock()
+            .map(|state| state.state())
+            .unwrap_or_default()
+    }
+
+    /// Tell the printer's state tracking what the host is doing, like streaming a print.
+    ///
+    /// Prints run from the device's own storage are followed automatically.
+    /// Setting `Idle`, `Printing` or `Paused` also clears an `Error` state.
+    pub fn set_state(&self, state: PrinterState) {
+        track(&self.state, &self.events, |tracker| tracker.set(state));
+    }
+
+    /// Send gcode and collect every line received until the printer acknowledges it with an `ok`.
+    ///
+    /// Capture starts as soon as the command is queued, so output belonging to
+    /// other commands in flight at the same time may be included.
+    /// The `ok` itself is not part of the captured output.
+    pub async fn send_captured(
+        &self,
+        gcode: impl Serialize + Debug,
+    ) -> Result<Vec<Arc<str>>, Error> {
+        let mut lines = self.subscribe_lines()?;
+        let _ = self.send_unsequenced(gcode).await?;
+        let mut captured = vec![];
+        loop {
+            let line = lines.recv().await?;
+            match response.parse_peek(line.as_bytes()) {
+                Ok((_, Response::Ok(_))) => return Ok(captured),
+                Ok((_, Response::Error(code))) if self.dialect == Dialect::Grbl => {
+                    return Err(Error::Rejected(code))
+                }
+                _ => {}
+            }
+            captured.push(line);
+        }
+    }
+
+    /// Ask the printer where the toolhead is with M114
+    pub async fn request_position(&self) -> Result<Position, Error> {
+        self.send_captured("M114")
+            .await?
+            .iter()
+            .find_map(|line| Position::parse(line))
+            .ok_or(Error::NoPosition)
     }
 }
 
@@ -188,7 +554,9 @@ pub enum Printer {
     Disconnected,
     Connected {
         socket: Socket,
-        com_task: tokio::task::JoinHandle<()>,
+        com_task: tokio::task::JoinHandle<Result<(), Error>>,
+        shutdown: Arc<Notify>,
+        info: Arc<RwLock<InfoMap>>,
     },
 }
 
@@ -226,54 +594,337 @@ pub enum Error {
     #[error("Ok not received")]
     WontRespond,
 
+    #[error("No ok received in time, even after resending")]
+    Timeout,
+
+    #[error("Printer did not report its position")]
+    NoPosition,
+
+    #[error("Command not sent, {0}")]
+    LineTooLong(#[from] LineTooLong),
+
+    #[error("Device rejected command with error {}", .0.map_or("without a code".to_string(), |code| code.to_string()))]
+    Rejected(Option<i32>),
+
     #[error("No responses recieved, try again")]
     TryReadLine(#[from] broadcast::error::TryRecvError),
 
     #[error("No responses received, printer may have disconnected")]
     ReadLine(#[from] broadcast::error::RecvError),
+
+    #[error("Communication task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
 }
 
 /// Loop for handling sending/receiving in the background with possible split senders/receivers
+///
+/// Runs until the transport fails or is closed by the device, or `shutdown` is notified,
+/// after which anything already queued is written and the transport is closed.
+///
+/// In half-duplex mode every write, including raw ones, waits for an `ok` to the previous one,
+/// except for what is still queued at shutdown.
+///
+/// The device's state is followed from every line sent and received, announcing changes on `events`.
+///
+/// Any firmware report seen is read into `info`, and one is asked for first if `options.identify` is set.
+///
+/// Firmware reporting its free buffer space with each `ok` (ADVANCED_OK) is sent as many
+/// commands at once as it has room for, rather than a fixed number.
+///
+/// With an acknowledgment timeout, numbered lines which don't get an `ok` in time are written again,
+/// and after `ack_retries` attempts the sender is given `Error::Timeout`.
+/// Lines without a number get `Error::Timeout` straight away, as the firmware
+/// couldn't tell a second copy from a new command and would run it twice.
+///
+/// With a keepalive interval, M105 is sent after that long without traffic,
+/// and the device is reported unresponsive if there is still nothing after another interval.
+/// The probe is only sent with nothing waiting on an `ok`, so its own `ok` can't be mistaken
+/// for another command's, and isn't sent again until the device is heard from.
+///
+/// Anything on `urgentrx` is written before what is waiting on `gcoderx`,
+/// without waiting for room in flight. Urgent lines wait for their `ok` in a queue of their own,
+/// so they never take the place of an unnumbered line already in flight.
+///
+/// Grbl answers every line it is sent with an `ok` or `error`, in the order they arrived,
+/// so in that dialect each line written, waited on or not, takes its turn for an answer,
+/// and lines which time out are never written again.
+#[allow(clippy::too_many_arguments)]
 async fn printer_com_task(
     mut transport: impl AsyncBufRead + AsyncWrite + Unpin,
     mut gcoderx: mpsc::Receiver<SendContent>,
+    mut urgentrx: mpsc::Receiver<SendContent>,
     responsetx: broadcast::Sender<Arc<str>>,
-) {
+    events: broadcast::Sender<PrinterEvent>,
+    shutdown: Arc<Notify>,
+    info: Arc<RwLock<InfoMap>>,
+    state: Arc<Mutex<StateTracker>>,
+    stats: Arc<StatCounters>,
+    options: PrinterOptions,
+) -> Result<(), Error> {
     tracing::debug!("Started background printer communications");
+    if options.identify {
+        transport
+            .write_all(options.dialect.identify_request())
+            .await?;
+        transport
+            .write_all(options.format.line_ending.as_bytes())
+            .await?;
+        transport.flush().await?;
+        stats.sent_command(
+            options.dialect.identify_request().len() + options.format.line_ending.as_bytes().len(),
+        );
+        tracing::debug!("Asked printer for firmware info");
+    }
+    let grbl = options.dialect == Dialect::Grbl;
     let mut buf = String::new();
+    let mut lines = LineCache::default();
     let mut pending_responses = BTreeMap::new();
+    let mut urgent_responses = VecDeque::new();
+    // lines written to Grbl in order, each waiting on its answer, with nobody to tell for some
+    let mut grbl_answers: VecDeque<Option<Pending>> = VecDeque::new();
+    if grbl && options.identify {
+        // the report asked for above ends with an ok of its own
+        grbl_answers.push_back(None);
+    }
+    let mut max_in_flight = options.max_in_flight();
+    // only used in half-duplex mode, where sends without a responder also need an ok
+    let mut awaiting_ok = options.half_duplex && options.identify;
+    let keepalive = options.keepalive.unwrap_or_default();
+    let mut last_traffic = Instant::now();
+    let mut probing = false;
+    // the probe's ok is the next one to arrive, as nothing was in flight when it was sent
+    let mut probe_in_flight = false;
+    let mut unresponsive = false;
     loop {
+        let ack_deadline = options.ack_timeout.and_then(|timeout| {
+            pending_responses
+                .values()
+                .chain(&urgent_responses)
+                .chain(grbl_answers.iter().flatten())
+                .map(|pending: &Pending| pending.sent + timeout)
+                .min()
+        });
+        let in_flight = if grbl {
+            grbl_answers.len()
+        } else {
+            pending_responses.len()
+        };
         tokio::select! {
-            Some(SendContent{content, sequence, responder}) = gcoderx.recv(), if pending_responses.len() < 4 => {
-                if transport.write_all(&content).await.is_err() {return;}
-                if transport.flush().await.is_err() {return;}
-                tracing::debug!("Sent `{}` to printer", String::from_utf8_lossy(&content).trim());
-                if let Some(responder) = responder {
-                    // dropping anything in slot, gives WontRespond error
-                    pending_responses.insert(sequence, (responder, content));
+            biased;
+            Some(SendContent{content, responder, ..}) = urgentrx.recv() => {
+                // real-time commands are handled by the firmware as soon as they arrive,
+                // so they don't need to wait behind what is already in flight
+                transport.write_all(&content).await?;
+                transport.flush().await?;
+                tracing::debug!("Sent urgent `{}` to printer", String::from_utf8_lossy(&content).trim());
+                stats.sent_command(content.len());
+                track(&state, &events, |tracker| tracker.sent(&content));
+                last_traffic = Instant::now();
+                if grbl {
+                    expect_grbl_answers(&mut grbl_answers, content, responder, last_traffic);
+                } else if let Some(responder) = responder {
+                    urgent_responses.push_back(Pending { responder, content, sent: last_traffic, retries: 0 });
+                }
+            },
+            Some(first) = gcoderx.recv(), if !awaiting_ok && in_flight < max_in_flight => {
+                // everything else already queued that fits in flight goes out in the same write,
+                // as each write can cost as much as a whole line on USB serial links
+                let mut batch = Vec::new();
+                let mut next = Some(first);
+                let mut in_flight = in_flight;
+                last_traffic = Instant::now();
+                while let Some(SendContent{content, sequence, responder}) = next.take() {
+                    batch.extend_from_slice(&content);
+                    tracing::debug!("Sending `{}` to printer", String::from_utf8_lossy(&content).trim());
+                    stats.sent_command(content.len());
+                    track(&state, &events, |tracker| tracker.sent(&content));
+                    awaiting_ok = options.half_duplex;
+                    if grbl {
+                        expect_grbl_answers(&mut grbl_answers, content, responder, last_traffic);
+                        in_flight = grbl_answers.len();
+                    } else if let Some(responder) = responder {
+                        // dropping anything in slot, gives WontRespond error
+                        pending_responses.insert(sequence, Pending { responder, content, sent: last_traffic, retries: 0 });
+                        in_flight = pending_responses.len();
+                    }
+                    if !awaiting_ok && in_flight < max_in_flight {
+                        next = gcoderx.try_recv().ok();
+                    }
                 }
+                transport.write_all(&batch).await?;
+                transport.flush().await?;
             },
-            Ok(1..) = transport.read_line(&mut buf) => {
+            read = transport.read_line(&mut buf) => {
+                if read? == 0 {
+                    tracing::warn!("Printer closed the connection");
+                    return Err(Error::Disconnected);
+                }
                 tracing::debug!("Received `{buf}` from printer");
-                if let Ok(ok_res) = response.parse(buf.as_bytes()) {
+                stats.received_line(buf.len());
+                last_traffic = Instant::now();
+                probing = false;
+                if let Ok(mut info) = info.write() {
+                    info.parse_report_line(&buf);
+                }
+                track(&state, &events, |tracker| tracker.received(&buf));
+                if unresponsive {
+                    unresponsive = false;
+                    tracing::info!("Printer is responding again");
+                    let _ = events.send(PrinterEvent::Responsive);
+                }
+                // oks can carry more, like temperatures or buffer space
+                if let Ok((_, ok_res)) = response.parse_peek(buf.as_bytes()) {
                     match ok_res {
                         Response::Ok(ref maybe_seq) => {
-                            if let Some((responder, _)) = pending_responses.remove(maybe_seq){
-                                 let _ = responder.send(());
+                            stats.ok();
+                            if let (false, Some(space)) = (options.half_duplex, BufferSpace::parse(&buf)) {
+                                // everything still in flight may already be taking up room,
+                                // so never have more outstanding than the firmware has free
+                                max_in_flight = (space.buffer as usize).max(1);
+                            }
+                            let acknowledged = if std::mem::take(&mut probe_in_flight) {
+                                awaiting_ok = false;
+                                None
+                            } else if grbl {
+                                // Grbl answers everything in the order it was written, without numbers
+                                awaiting_ok = false;
+                                grbl_answers.pop_front().flatten()
+                            } else if options.half_duplex {
+                                // with one command in flight any ok is for it, numbered or not
+                                awaiting_ok = false;
+                                let first = pending_responses.keys().next().copied();
+                                answered(&mut pending_responses, first, &mut urgent_responses)
+                            } else if maybe_seq.is_some() {
+                                pending_responses.remove(maybe_seq)
+                            } else {
+                                answered(&mut pending_responses, Some(None), &mut urgent_responses)
+                            };
+                            if let Some(pending) = acknowledged {
+                                 stats.acknowledged(pending.sent.elapsed());
+                                 let _ = pending.responder.send(Ok(()));
                             }
                         },
                         Response::Resend(ref maybe_seq) => {
-                            if let Some((_, ref line)) = pending_responses.get(maybe_seq) {
-                                if transport.write_all(line).await.is_err() {return;}
-                                if transport.flush().await.is_err() {return;}
-                                tracing::debug!("Resent `{}` to printer", String::from_utf8_lossy(line).trim());
+                            if let Some(pending) = pending_responses.get_mut(maybe_seq) {
+                                transport.write_all(&pending.content).await?;
+                                transport.flush().await?;
+                                pending.sent = Instant::now();
+                                stats.resent(pending.content.len());
+                                tracing::debug!("Resent `{}` to printer", String::from_utf8_lossy(&pending.content).trim());
+                            }
+                        },
+                        Response::Error(code) if grbl => {
+                            awaiting_ok = false;
+                            if let Some(pending) = grbl_answers.pop_front().flatten() {
+                                let _ = pending.responder.send(Err(Error::Rejected(code)));
                             }
                         },
+                        // other firmware follows errors with a resend or nothing at all
+                        Response::Error(_) => {},
                     }
                 }
-                if responsetx.send(Arc::from(buf.split_off(0))).is_err() {return;}
+                stats.broadcasting(&responsetx, options.line_capacity.max(1));
+                let line = lines.intern(&buf);
+                buf.clear();
+                // there may be nobody listening right now, but there can be later
+                let _ = responsetx.send(line);
             },
-            else => return,
+            _ = sleep_until(last_traffic + keepalive), if options.keepalive.is_some() => {
+                last_traffic = Instant::now();
+                if probing && !unresponsive {
+                    unresponsive = true;
+                    tracing::warn!("Printer did not answer keepalive probe");
+                    let _ = events.send(PrinterEvent::Unresponsive);
+                    // the probe is taken as lost, so the next ok is for whatever is sent next
+                    if std::mem::take(&mut probe_in_flight) {
+                        awaiting_ok = false;
+                    }
+                }
+                probing = true;
+                // a printer still working on a command is left to answer it,
+                // as there's no telling which ok would be for the probe
+                // nor is one which has stopped answering, as a probe it missed
+                // would take the ok of the first command after it recovers
+                if !awaiting_ok
+                    && !unresponsive
+                    && !probe_in_flight
+                    && pending_responses.is_empty()
+                    && urgent_responses.is_empty()
+                    && grbl_answers.is_empty()
+                {
+                    let probe = options.dialect.keepalive_probe(options.format.line_ending);
+                    transport.write_all(&probe).await?;
+                    transport.flush().await?;
+                    stats.sent_bytes(probe.len());
+                    tracing::debug!("Sent keepalive probe to printer");
+                    // Grbl answers its probe with a status report rather than an ok
+                    probe_in_flight = !grbl;
+                    awaiting_ok = options.half_duplex && probe_in_flight;
+                }
+            },
+            _ = sleep_until(ack_deadline.unwrap_or_else(Instant::now)), if ack_deadline.is_some() => {
+                let timeout = options.ack_timeout.unwrap_or_default();
+                let now = Instant::now();
+                let expired: Vec<_> = pending_responses
+                    .iter()
+                    .filter(|(_, pending)| pending.sent + timeout <= now)
+                    .map(|(sequence, _)| *sequence)
+                    .collect();
+                for sequence in expired {
+                    let Some(pending) = pending_responses.get_mut(&sequence) else {
+                        continue;
+                    };
+                    if sequence.is_some() && pending.retries < options.ack_retries {
+                        pending.retries += 1;
+                        pending.sent = now;
+                        transport.write_all(&pending.content).await?;
+                        transport.flush().await?;
+                        stats.resent(pending.content.len());
+                        tracing::warn!("No ok for `{}`, resending", String::from_utf8_lossy(&pending.content).trim());
+                    } else if let Some(pending) = pending_responses.remove(&sequence) {
+                        tracing::warn!("No ok for `{}`, giving up", String::from_utf8_lossy(&pending.content).trim());
+                        stats.timed_out();
+                        let _ = pending.responder.send(Err(Error::Timeout));
+                        awaiting_ok = false;
+                    }
+                }
+                // urgent lines are never numbered, so they aren't written again either
+                while let Some(pending) = urgent_responses.pop_front() {
+                    if pending.sent + timeout > now {
+                        urgent_responses.push_front(pending);
+                        break;
+                    }
+                    tracing::warn!("No ok for `{}`, giving up", String::from_utf8_lossy(&pending.content).trim());
+                    stats.timed_out();
+                    let _ = pending.responder.send(Err(Error::Timeout));
+                }
+                // Grbl's late answer still comes in its turn, so the line keeps its place without anyone to tell
+                for slot in grbl_answers.iter_mut() {
+                    if slot.as_ref().is_some_and(|pending| pending.sent + timeout <= now) {
+                        if let Some(pending) = slot.take() {
+                            tracing::warn!("No ok for `{}`, giving up", String::from_utf8_lossy(&pending.content).trim());
+                            stats.timed_out();
+                            let _ = pending.responder.send(Err(Error::Timeout));
+                            awaiting_ok = false;
+                        }
+                    }
+                }
+            },
+            _ = shutdown.notified() => {
+                tracing::debug!("Shutting down printer communications");
+                urgentrx.close();
+                while let Ok(SendContent{content, ..}) = urgentrx.try_recv() {
+                    transport.write_all(&content).await?;
+                }
+                gcoderx.close();
+                while let Ok(SendContent{content, ..}) = gcoderx.try_recv() {
+                    transport.write_all(&content).await?;
+                }
+                transport.flush().await?;
+                transport.shutdown().await?;
+                return Ok(());
+            },
+            else => return Ok(()),
         }
     }
 }
@@ -282,22 +933,85 @@ impl Printer {
     /// Create a new printer from a SerialStream.
     ///
     /// Starts a local task to handle printer communication asynchronously
-    #[tracing::instrument(level = "debug")]
     pub fn new<S>(port: S) -> Self
     where
         S: AsyncBufRead + AsyncWrite + Unpin + Send + 'static + Debug,
     {
-        let (sender, gcoderx) = mpsc::channel::<SendContent>(16);
-        let (response_sender, responses) = broadcast::channel(64);
-        let com_task = tokio::task::spawn(printer_com_task(port, gcoderx, response_sender));
-        let serializer = Sequenced::default();
+        Self::with_options(port, PrinterOptions::default())
+    }
+
+    /// Start building a printer, see `PrinterOptions`
+    pub fn builder() -> PrinterOptions {
+        PrinterOptions::new()
+    }
+
+    /// Create a new printer which communicates according to the given options
+    #[tracing::instrument(level = "debug")]
+    pub fn with_options<S>(port: S, options: PrinterOptions) -> Self
+    where
+        S: AsyncBufRead + AsyncWrite + Unpin + Send + 'static + Debug,
+    {
+        let (sender, gcoderx) = mpsc::channel::<SendContent>(options.send_capacity.max(1));
+        let (urgent, urgentrx) = mpsc::channel::<SendContent>(4);
+        let (response_sender, _) = broadcast::channel(options.line_capacity.max(1));
+        let (events, _) = broadcast::channel(16);
+        let shutdown = Arc::new(Notify::new());
+        let info = Arc::new(RwLock::new(InfoMap::default()));
+        let state = Arc::new(Mutex::new(StateTracker::default()));
+        let stats = Arc::new(StatCounters::default());
+        let dialect = options.dialect;
+        let format = match options.dialect {
+            Dialect::Grbl => options.format.checksum(Checksum::None),
+            _ => options.format,
+        };
+        let serializer = Sequenced::with_format(format);
+        serializer.set_sequence(options.initial_sequence);
+        // Grbl doesn't keep track of line numbers
+        if options.reset_line_numbers && options.dialect != Dialect::Grbl {
+            // the channel was just made, so there is room
+            let reset = serializer.serialize_unsequenced(gcode::SetLineNumber {
+                line: options.initial_sequence - 1,
+            });
+            let _ = sender.try_send(SendContent::new(reset, None, None));
+        }
+        let com_task = tokio::task::spawn({
+            let events = events.clone();
+            let com = printer_com_task(
+                port,
+                gcoderx,
+                urgentrx,
+                response_sender.clone(),
+                events.clone(),
+                shutdown.clone(),
+                info.clone(),
+                state.clone(),
+                stats.clone(),
+                options,
+            );
+            async move {
+                let result = com.await;
+                if let Err(ref e) = result {
+                    tracing::warn!("Lost connection to printer: {e}");
+                    let _ = events.send(PrinterEvent::Disconnected);
+                }
+                result
+            }
+        });
         Self::Connected {
             socket: Socket {
                 sender,
+                urgent,
                 serializer,
-                responses,
+                lines: response_sender,
+                responses: None,
+                events,
+                state,
+                stats,
+                dialect,
             },
             com_task,
+            shutdown,
+            info,
         }
     }
 
@@ -309,6 +1023,14 @@ impl Printer {
         *self = Printer::new(port);
     }
 
+    /// Connect to a device, communicating according to the given options
+    pub fn connect_with<S>(&mut self, port: S, options: PrinterOptions)
+    where
+        S: AsyncBufRead + AsyncWrite + Unpin + Send + 'static + Debug,
+    {
+        *self = Printer::with_options(port, options);
+    }
+
     /// Obtain a cloneable socket handle to talk to printer
     pub fn socket(&self) -> Result<&Socket, Error> {
         match self {
@@ -330,16 +1052,50 @@ impl Printer {
         core::mem::take(self);
     }
 
-    /// Check if there is an active connection, convenience method for testing enum state.
+    /// Disconnect the printer, letting background communication finish cleanly.
+    ///
+    /// Where `disconnect` aborts communication immediately, this lets a write in progress
+    /// finish, sends anything already queued, then flushes and closes the transport.
+    /// Returns the error that ended communication, if it ended with one.
+    pub async fn shutdown(&mut self) -> Result<(), Error> {
+        let result = match self {
+            Printer::Disconnected => Ok(()),
+            Printer::Connected {
+                com_task, shutdown, ..
+            } => {
+                shutdown.notify_one();
+                com_task.await.unwrap_or_else(|e| Err(e.into()))
+            }
+        };
+        self.disconnect();
+        result
+    }
+
+    /// Firmware information and capabilities the device has reported.
+    ///
+    /// Empty until the device answers M115, see `PrinterOptions::identify`.
+    pub fn info(&self) -> Result<InfoMap, Error> {
+        match self {
+            Printer::Disconnected => Err(Error::Disconnected),
+            Printer::Connected { info, .. } => {
+                Ok(info.read().map(|info| info.clone()).unwrap_or_default())
+            }
+        }
+    }
+
+    /// Check if there is an active connection.
+    ///
+    /// A printer whose connection was lost is no longer connected,
+    /// even though it stays `Printer::Connected` until it is disconnected or replaced.
     pub fn is_connected(&self) -> bool {
         match self {
             Printer::Disconnected => false,
-            Printer::Connected { .. } => true,
+            Printer::Connected { com_task, .. } => !com_task.is_finished(),
         }
     }
 
     /// Get a handle to background processing task if one is active.
-    pub fn background_task(&self) -> Option<&JoinHandle<()>> {
+    pub fn background_task(&self) -> Option<&JoinHandle<Result<(), Error>>> {
         match self {
             Printer::Disconnected => None,
             Printer::Connected { com_task, .. } => Some(com_task),
@@ -403,6 +1159,24 @@ impl Printer {
         self.socket()?.try_send_raw(gcode)
     }
 
+    /// Send a line exactly as given, see `Socket::send_verbatim`
+    pub async fn send_verbatim(
+        &self,
+        line: &str,
+    ) -> Result<impl Future<Output = Result<(), Error>>, Error> {
+        self.socket()?.send_verbatim(line).await
+    }
+
+    /// Send bytes which are already owned to the printer without copying them, see `Socket::send_bytes`
+    pub async fn send_bytes(&self, gcode: impl Into<Bytes>) -> Result<(), Error> {
+        self.socket()?.send_bytes(gcode).await
+    }
+
+    /// Non blocking, non-async version of `send_bytes`, instantly returns an error where that method would wait
+    pub fn try_send_bytes(&self, gcode: impl Into<Bytes>) -> Result<(), Error> {
+        self.socket()?.try_send_bytes(gcode)
+    }
+
     /// Read the next line from the printer
     ///
     /// May not recieve all lines, if calls to this function are spaced
@@ -421,6 +1195,84 @@ impl Printer {
     pub fn subscribe_lines(&self) -> Result<LineStream, Error> {
         self.socket()?.subscribe_lines()
     }
+
+    /// Obtain a `Sink` of gcode for the printer, see `Socket::sink`
+    #[cfg(feature = "futures")]
+    pub fn sink(&self) -> Result<GcodeSink, Error> {
+        self.socket()?.sink()
+    }
+
+    /// Obtain a `Stream` of lines from the printer, see `Socket::lines`
+    #[cfg(feature = "futures")]
+    pub fn lines(&self) -> Result<Lines, Error> {
+        self.socket()?.lines()
+    }
+
+    /// Obtain a receiver of the temperatures the printer reports, see `Socket::temperatures`
+    pub fn temperatures(&self) -> Result<TemperatureStream, Error> {
+        self.socket()?.temperatures()
+    }
+
+    /// Obtain a receiver of the positions the printer reports, see `Socket::positions`
+    pub fn positions(&self) -> Result<PositionStream, Error> {
+        self.socket()?.positions()
+    }
+
+    /// Obtain a broadcast receiver for changes in the connection, see `Socket::subscribe_events`
+    pub fn subscribe_events(&self) -> Result<broadcast::Receiver<PrinterEvent>, Error> {
+        Ok(self.socket()?.subscribe_events())
+    }
+
+    /// Send gcode and collect the printer's output for it, see `Socket::send_captured`
+    pub async fn send_captured(
+        &self,
+        gcode: impl Serialize + Debug,
+    ) -> Result<Vec<Arc<str>>, Error> {
+        self.socket()?.send_captured(gcode).await
+    }
+
+    /// What the device is doing, see `Socket::state`
+    pub fn state(&self) -> Result<PrinterState, Error> {
+        Ok(self.socket()?.state())
+    }
+
+    /// Send a real-time command ahead of everything queued, see `Socket::send_urgent`
+    pub async fn send_urgent(
+        &self,
+        gcode: impl Serialize + Debug,
+    ) -> Result<impl Future<Output = Result<(), Error>>, Error> {
+        self.socket()?.send_urgent(gcode).await
+    }
+
+    /// How many messages have been lost to full channels, see `Socket::stats`
+    pub fn stats(&self) -> Result<ChannelStats, Error> {
+        Ok(self.socket()?.stats())
+    }
+
+    /// How many lines are waiting to be written, see `Socket::queued`
+    pub fn queued(&self) -> Result<usize, Error> {
+        Ok(self.socket()?.queued())
+    }
+
+    /// Send a Grbl real-time command, see `Socket::send_realtime`
+    pub async fn send_realtime(&self, command: u8) -> Result<(), Error> {
+        self.socket()?.send_realtime(command).await
+    }
+
+    /// Ask Grbl for its status, see `Socket::request_status`
+    pub async fn request_status(&self) -> Result<grbl::GrblStatus, Error> {
+        self.socket()?.request_status().await
+    }
+
+    /// Restart line numbering, see `Socket::reset_sequence`
+    pub async fn reset_sequence(&self, next: i32) -> Result<(), Error> {
+        self.socket()?.reset_sequence(next).await
+    }
+
+    /// Ask the printer where the toolhead is, see `Socket::request_position`
+    pub async fn request_position(&self) -> Result<Position, Error> {
+        self.socket()?.request_position().await
+    }
 }
 
 impl From<Option<Printer>> for Printer {
@@ -448,6 +1300,13 @@ impl<'a> From<&'a Printer> for Option<&'a Socket> {
 mod test {
     use super::*;
 
+    /// Options for a printer which sends nothing it wasn't asked to
+    fn quiet() -> PrinterOptions {
+        PrinterOptions::new()
+            .identify(false)
+            .reset_line_numbers(false)
+    }
+
     #[test]
     fn disconnected_is_disconnected() {
         let mut disconnected = Printer::Disconnected;
@@ -477,4 +1336,546 @@ mod test {
         let maybe_socket: Option<&Socket> = (&disconnected).into();
         assert!(maybe_socket.is_none());
     }
+
+    #[tokio::test]
+    async fn captures_output_until_ok() {
+        let (device, far_end) = tokio::io::duplex(256);
+        let options = quiet();
+        let printer = Printer::with_options(tokio::io::BufReader::new(device), options);
+        let mut far_end = tokio::io::BufReader::new(far_end);
+        let device_task = tokio::spawn(async move {
+            let mut sent = String::new();
+            far_end.read_line(&mut sent).await.unwrap();
+            far_end
+                .write_all(b"Bed X: 10.00 Y: 20.00 Z: 0.12\nok\nT:21.0 /0.0\n")
+                .await
+                .unwrap();
+            sent
+        });
+        let captured = printer.send_captured("G30 X10 Y20").await.unwrap();
+        assert_eq!(captured.len(), 1);
+        assert_eq!(&*captured[0], "Bed X: 10.00 Y: 20.00 Z: 0.12\n");
+        assert_eq!(device_task.await.unwrap(), "G30 X10 Y20\n");
+    }
+
+    #[tokio::test]
+    async fn requests_position() {
+        let (device, far_end) = tokio::io::duplex(256);
+        let options = quiet();
+        let printer = Printer::with_options(tokio::io::BufReader::new(device), options);
+        let mut far_end = tokio::io::BufReader::new(far_end);
+        tokio::spawn(async move {
+            let mut sent = String::new();
+            far_end.read_line(&mut sent).await.unwrap();
+            assert_eq!(sent, "M114\n");
+            far_end
+                .write_all(b"X:10.00 Y:20.00 Z:0.30 E:0.00 Count X:800 Y:1600 Z:120\nok\n")
+                .await
+                .unwrap();
+        });
+        let position = printer.request_position().await.unwrap();
+        assert_eq!(position.x, 10.0);
+        assert_eq!(position.z, 0.3);
+    }
+
+    #[tokio::test]
+    async fn throttles_to_buffer_space() {
+        use std::time::Duration;
+
+        let (device, far_end) = tokio::io::duplex(256);
+        let options = quiet();
+        let printer = Printer::with_options(tokio::io::BufReader::new(device), options);
+        let mut far_end = tokio::io::BufReader::new(far_end);
+        let mut line = String::new();
+
+        let first = printer.send("G28").await.unwrap();
+        far_end.read_line(&mut line).await.unwrap();
+        far_end.write_all(b"ok N1 P15 B1\n").await.unwrap();
+        first.await.unwrap();
+
+        let _second = printer.send("G1 X1").await.unwrap();
+        let _third = printer.send("G1 X2").await.unwrap();
+        line.clear();
+        far_end.read_line(&mut line).await.unwrap();
+        assert!(line.contains("X1"));
+        let early = tokio::time::timeout(Duration::from_millis(50), far_end.read_line(&mut line));
+        assert!(
+            early.await.is_err(),
+            "sent more than the firmware has room for"
+        );
+
+        far_end.write_all(b"ok N2 P14 B1\n").await.unwrap();
+        line.clear();
+        far_end.read_line(&mut line).await.unwrap();
+        assert!(line.contains("X2"));
+    }
+
+    #[tokio::test]
+    async fn resends_then_times_out() {
+        use std::time::Duration;
+
+        let (device, far_end) = tokio::io::duplex(256);
+        let options = quiet()
+            .ack_timeout(Some(Duration::from_millis(20)))
+            .ack_retries(1);
+        let printer = Printer::with_options(tokio::io::BufReader::new(device), options);
+        let mut far_end = tokio::io::BufReader::new(far_end);
+        let acknowledged = printer.send("G28").await.unwrap();
+
+        let mut first = String::new();
+        far_end.read_line(&mut first).await.unwrap();
+        let mut resent = String::new();
+        far_end.read_line(&mut resent).await.unwrap();
+        assert_eq!(first, resent);
+        assert!(matches!(acknowledged.await, Err(Error::Timeout)));
+    }
+
+    #[tokio::test]
+    async fn unnumbered_lines_are_not_resent() {
+        use std::time::Duration;
+
+        let (device, far_end) = tokio::io::duplex(256);
+        let options = quiet()
+            .ack_timeout(Some(Duration::from_millis(20)))
+            .ack_retries(1);
+        let printer = Printer::with_options(tokio::io::BufReader::new(device), options);
+        let mut far_end = tokio::io::BufReader::new(far_end);
+        let acknowledged = printer.send_unsequenced("G28").await.unwrap();
+
+        let mut line = String::new();
+        far_end.read_line(&mut line).await.unwrap();
+        assert!(matches!(acknowledged.await, Err(Error::Timeout)));
+        line.clear();
+        let resent = tokio::time::timeout(Duration::from_millis(50), far_end.read_line(&mut line));
+        assert!(resent.await.is_err(), "wrote `{line}` again");
+    }
+
+    #[tokio::test]
+    async fn half_duplex_waits_for_ok() {
+        use std::time::Duration;
+
+        let (device, far_end) = tokio::io::duplex(256);
+        let options = quiet().half_duplex(true);
+        let printer = Printer::with_options(tokio::io::BufReader::new(device), options);
+        let mut far_end = tokio::io::BufReader::new(far_end);
+        printer.send_raw(b"M105\n").await.unwrap();
+        let acknowledged = printer.send("G28").await.unwrap();
+
+        let mut line = String::new();
+        far_end.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "M105\n");
+        let early = tokio::time::timeout(Duration::from_millis(50), far_end.read_line(&mut line));
+        assert!(early.await.is_err(), "sent a second line before ok");
+
+        far_end.write_all(b"ok\n").await.unwrap();
+        line.clear();
+        far_end.read_line(&mut line).await.unwrap();
+        assert!(line.contains("G28"));
+        far_end.write_all(b"ok\n").await.unwrap();
+        acknowledged.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn keepalive_detects_silence() {
+        use std::time::Duration;
+
+        let (device, far_end) = tokio::io::duplex(256);
+        let options = quiet().keepalive(Some(Duration::from_millis(20)));
+        let printer = Printer::with_options(tokio::io::BufReader::new(device), options);
+        let mut events = printer.subscribe_events().unwrap();
+        let mut far_end = tokio::io::BufReader::new(far_end);
+
+        let mut probe = String::new();
+        far_end.read_line(&mut probe).await.unwrap();
+        assert_eq!(probe, "M105\n");
+        assert_eq!(events.recv().await.unwrap(), PrinterEvent::Unresponsive);
+
+        far_end.write_all(b"ok T:21.0\n").await.unwrap();
+        assert_eq!(events.recv().await.unwrap(), PrinterEvent::Responsive);
+    }
+
+    #[tokio::test]
+    async fn keepalive_probe_keeps_its_ok() {
+        use std::time::Duration;
+
+        let (device, far_end) = tokio::io::duplex(256);
+        let options = quiet().keepalive(Some(Duration::from_millis(20)));
+        let printer = Printer::with_options(tokio::io::BufReader::new(device), options);
+        let mut far_end = tokio::io::BufReader::new(far_end);
+
+        let mut line = String::new();
+        far_end.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "M105\n");
+        let homed = printer.send_unsequenced("G28").await.unwrap();
+        tokio::pin!(homed);
+        line.clear();
+        far_end.read_line(&mut line).await.unwrap();
+        assert!(line.contains("G28"));
+
+        far_end.write_all(b"ok T:21.0\n").await.unwrap();
+        let early = tokio::time::timeout(Duration::from_millis(50), &mut homed);
+        assert!(early.await.is_err(), "probe's ok acknowledged G28");
+        far_end.write_all(b"ok\n").await.unwrap();
+        homed.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn sends_after_unanswered_probe() {
+        use std::time::Duration;
+
+        let (device, far_end) = tokio::io::duplex(256);
+        let options = quiet()
+            .half_duplex(true)
+            .keepalive(Some(Duration::from_millis(20)));
+        let printer = Printer::with_options(tokio::io::BufReader::new(device), options);
+        let mut events = printer.subscribe_events().unwrap();
+        let mut far_end = tokio::io::BufReader::new(far_end);
+
+        let mut line = String::new();
+        far_end.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "M105\n");
+        assert_eq!(events.recv().await.unwrap(), PrinterEvent::Unresponsive);
+
+        // the lost probe doesn't hold G28 back or take its ok
+        let homed = printer.send_unsequenced("G28").await.unwrap();
+        line.clear();
+        far_end.read_line(&mut line).await.unwrap();
+        assert!(line.contains("G28"), "{line}");
+        far_end.write_all(b"ok\n").await.unwrap();
+        homed.await.unwrap();
+        assert_eq!(events.recv().await.unwrap(), PrinterEvent::Responsive);
+    }
+
+    #[tokio::test]
+    async fn identifies_on_connect() {
+        let (device, far_end) = tokio::io::duplex(256);
+        let printer = Printer::new(tokio::io::BufReader::new(device));
+        let mut far_end = tokio::io::BufReader::new(far_end);
+        let mut request = String::new();
+        far_end.read_line(&mut request).await.unwrap();
+        assert_eq!(request, "M115\n");
+        let mut lines = printer.subscribe_lines().unwrap();
+        far_end
+            .write_all(b"FIRMWARE_NAME:Marlin 2.1.2 EXTRUDER_COUNT:1\nCap:ARCS:1\nok\n")
+            .await
+            .unwrap();
+        while !lines.recv().await.unwrap().starts_with("ok") {}
+        let info = printer.info().unwrap();
+        assert_eq!(info["FIRMWARE_NAME"], Info::Str("Marlin 2.1.2".to_string()));
+        assert!(info.has_capability(Capability::Arcs));
+    }
+
+    #[tokio::test]
+    async fn notices_lost_connection() {
+        let (device, far_end) = tokio::io::duplex(64);
+        let printer = Printer::new(tokio::io::BufReader::new(device));
+        let mut events = printer.subscribe_events().unwrap();
+        assert!(printer.is_connected());
+        drop(far_end);
+        assert_eq!(events.recv().await.unwrap(), PrinterEvent::Disconnected);
+        tokio::task::yield_now().await;
+        assert!(!printer.is_connected());
+    }
+
+    #[tokio::test]
+    async fn refuses_long_lines() {
+        let (device, _far_end) = tokio::io::duplex(64);
+        let options = quiet().format(Format::new().max_line_length(Some(16)));
+        let printer = Printer::with_options(tokio::io::BufReader::new(device), options);
+        assert!(matches!(
+            printer
+                .send_unsequenced("M117 This message is too long")
+                .await,
+            Err(Error::LineTooLong(_))
+        ));
+        assert!(printer.send_unsequenced("M117 Short").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn strips_raw_comments() {
+        let (device, far_end) = tokio::io::duplex(256);
+        let options = quiet().format(Format::new().strip_comments(true));
+        let printer = Printer::with_options(tokio::io::BufReader::new(device), options);
+        let mut far_end = tokio::io::BufReader::new(far_end);
+        printer.send_raw(b"; only a comment\n").await.unwrap();
+        printer.send_raw(b"G28 ; home\n").await.unwrap();
+        let mut line = String::new();
+        far_end.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "G28\n");
+    }
+
+    #[tokio::test]
+    async fn sends_owned_bytes() {
+        let (device, far_end) = tokio::io::duplex(256);
+        let printer = Printer::with_options(tokio::io::BufReader::new(device), quiet());
+        let mut far_end = tokio::io::BufReader::new(far_end);
+        printer.send_bytes(String::from("M105\n")).await.unwrap();
+        printer.try_send_bytes(b"M114\n".to_vec()).unwrap();
+        printer.send_bytes(Vec::new()).await.unwrap();
+        let mut line = String::new();
+        far_end.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "M105\n");
+        line.clear();
+        far_end.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "M114\n");
+    }
+
+    #[tokio::test]
+    async fn urgent_jumps_the_queue() {
+        let (device, far_end) = tokio::io::duplex(256);
+        let options = quiet().half_duplex(true);
+        let printer = Printer::with_options(tokio::io::BufReader::new(device), options);
+        let mut far_end = tokio::io::BufReader::new(far_end);
+        let _ = printer.send("G28").await.unwrap();
+        let mut line = String::new();
+        far_end.read_line(&mut line).await.unwrap();
+        assert!(line.starts_with("N1G28*"));
+        // waits for the ok to G28
+        let _ = printer.send("G1 X10").await.unwrap();
+        let _ = printer.send_urgent("M112").await.unwrap();
+        line.clear();
+        far_end.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "M112\n");
+    }
+
+    #[tokio::test]
+    async fn urgent_keeps_its_own_ok() {
+        let (device, far_end) = tokio::io::duplex(256);
+        let printer = Printer::with_options(tokio::io::BufReader::new(device), quiet());
+        let mut far_end = tokio::io::BufReader::new(far_end);
+        let homed = printer.send_unsequenced("G28").await.unwrap();
+        let mut line = String::new();
+        far_end.read_line(&mut line).await.unwrap();
+        let stopped = printer.send_urgent("M410").await.unwrap();
+        line.clear();
+        far_end.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "M410\n");
+
+        // answered in the order they arrived, G28 first
+        far_end.write_all(b"ok\n").await.unwrap();
+        homed.await.unwrap();
+        far_end.write_all(b"ok\n").await.unwrap();
+        stopped.await.unwrap();
+    }
+
+    /// Transport counting how many writes it is given
+    #[derive(Debug)]
+    struct CountingWrites {
+        inner: tokio::io::DuplexStream,
+        writes: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    impl tokio::io::AsyncRead for CountingWrites {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::pin::Pin::new(&mut self.inner).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for CountingWrites {
+        fn poll_write(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            self.writes
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            std::pin::Pin::new(&mut self.inner).poll_write(cx, buf)
+        }
+
+        fn poll_flush(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::pin::Pin::new(&mut self.inner).poll_flush(cx)
+        }
+
+        fn poll_shutdown(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::pin::Pin::new(&mut self.inner).poll_shutdown(cx)
+        }
+    }
+
+    #[tokio::test]
+    async fn coalesces_queued_lines() {
+        let (device, far_end) = tokio::io::duplex(256);
+        let writes = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let device = CountingWrites {
+            inner: device,
+            writes: writes.clone(),
+        };
+        let options = quiet().window(2);
+        let printer = Printer::with_options(tokio::io::BufReader::new(device), options);
+        let mut far_end = tokio::io::BufReader::new(far_end);
+        // queued before the communication task gets to run
+        for code in ["G28", "G1 X10", "G1 Y10"] {
+            let _ = printer.try_send(code).unwrap();
+        }
+        let mut line = String::new();
+        for expected in ["N1G28*", "N2G1 X10*"] {
+            line.clear();
+            far_end.read_line(&mut line).await.unwrap();
+            assert!(line.starts_with(expected), "{line}");
+        }
+        // the third has to wait for room in flight
+        assert_eq!(writes.load(std::sync::atomic::Ordering::Relaxed), 1);
+        far_end.write_all(b"ok N1\n").await.unwrap();
+        line.clear();
+        far_end.read_line(&mut line).await.unwrap();
+        assert!(line.starts_with("N3G1 Y10*"), "{line}");
+        assert_eq!(writes.load(std::sync::atomic::Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn reads_responses_directly() {
+        let (device, mut far_end) = tokio::io::duplex(256);
+        let mut printer = Printer::with_options(tokio::io::BufReader::new(device), quiet());
+        let socket = printer.socket_mut().unwrap();
+        let responses = socket.responses();
+        far_end.write_all(b"echo\nok\n").await.unwrap();
+        assert_eq!(&*responses.recv().await.unwrap(), "echo\n");
+        // shared with read_next_line, so lines aren't seen twice
+        assert_eq!(&*socket.read_next_line().await.unwrap(), "ok\n");
+    }
+
+    #[tokio::test]
+    async fn counts_missed_lines() {
+        let (device, far_end) = tokio::io::duplex(256);
+        let options = quiet().line_capacity(2);
+        let mut printer = Printer::with_options(tokio::io::BufReader::new(device), options);
+        let mut far_end = tokio::io::BufReader::new(far_end);
+        let mut lines = printer.subscribe_lines().unwrap();
+        // a socket which has never read lines doesn't hold any back
+        for _ in 0..4 {
+            far_end.write_all(b"echo\n").await.unwrap();
+            lines.recv().await.unwrap();
+        }
+        assert_eq!(printer.stats().unwrap().dropped_lines, 0);
+        let socket = printer.socket_mut().unwrap();
+        assert!(socket.try_read_next_line().is_err());
+        far_end.write_all(b"one\ntwo\nthree\n").await.unwrap();
+        while &*lines.recv().await.unwrap_or_else(|_| "".into()) != "three\n" {}
+        let socket = printer.socket_mut().unwrap();
+        assert!(socket.read_next_line().await.is_err());
+        let stats = printer.stats().unwrap();
+        assert_eq!(stats.dropped_lines, 1);
+        assert_eq!(stats.lagged_lines, 1);
+    }
+
+    #[tokio::test]
+    async fn builds_with_options() {
+        let (device, far_end) = tokio::io::duplex(256);
+        let printer = Printer::builder()
+            .identify(false)
+            .initial_sequence(10)
+            .line_ending(LineEnding::CrLf)
+            .build(tokio::io::BufReader::new(device));
+        let mut far_end = tokio::io::BufReader::new(far_end);
+        let _ = printer.send("G28").await.unwrap();
+        let mut line = String::new();
+        far_end.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "M110N9\r\n");
+        line.clear();
+        far_end.read_line(&mut line).await.unwrap();
+        assert!(line.starts_with("N10G28*") && line.ends_with("\r\n"));
+    }
+
+    #[tokio::test]
+    async fn speaks_grbl() {
+        use tokio::io::AsyncReadExt;
+
+        let (device, far_end) = tokio::io::duplex(256);
+        let options = quiet().dialect(Dialect::Grbl).reset_line_numbers(true);
+        let printer = Printer::with_options(tokio::io::BufReader::new(device), options);
+        let mut far_end = tokio::io::BufReader::new(far_end);
+        let first = printer.send("G0 X10").await.unwrap();
+        let second = printer.send("G5").await.unwrap();
+        let mut line = String::new();
+        far_end.read_line(&mut line).await.unwrap();
+        // no M110 and no checksum
+        assert_eq!(line, "N1G0 X10\n");
+        line.clear();
+        far_end.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "N2G5\n");
+        far_end.write_all(b"ok\r\nerror:20\r\n").await.unwrap();
+        first.await.unwrap();
+        assert!(matches!(second.await, Err(Error::Rejected(Some(20)))));
+
+        let status = tokio::spawn(async move { printer.request_status().await });
+        let mut request = [0];
+        far_end.read_exact(&mut request).await.unwrap();
+        assert_eq!(request[0], grbl::STATUS_REPORT);
+        far_end
+            .write_all(b"<Run|MPos:10.000,0.000,0.000|FS:500,0>\r\n")
+            .await
+            .unwrap();
+        let status = status.await.unwrap().unwrap();
+        assert_eq!(status.state, grbl::GrblState::Run);
+    }
+
+    #[tokio::test]
+    async fn grbl_answers_lines_in_order() {
+        let (device, far_end) = tokio::io::duplex(256);
+        let options = quiet().dialect(Dialect::Grbl);
+        let printer = Printer::with_options(tokio::io::BufReader::new(device), options);
+        let mut far_end = tokio::io::BufReader::new(far_end);
+        printer.send_raw(b"G1 X1\n").await.unwrap();
+        let moved = printer.send_unsequenced("G1 X2").await.unwrap();
+        tokio::pin!(moved);
+        let mut line = String::new();
+        far_end.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "G1 X1\n");
+        line.clear();
+        far_end.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "G1 X2\n");
+        // the first ok is for the raw line, which nobody is waiting on
+        far_end.write_all(b"ok\r\n").await.unwrap();
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_millis(50), &mut moved)
+                .await
+                .is_err()
+        );
+        far_end.write_all(b"ok\r\n").await.unwrap();
+        moved.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn resets_line_numbers() {
+        let (device, far_end) = tokio::io::duplex(256);
+        let options = quiet().reset_line_numbers(true);
+        let printer = Printer::with_options(tokio::io::BufReader::new(device), options);
+        let mut far_end = tokio::io::BufReader::new(far_end);
+        let mut line = String::new();
+        far_end.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "M110N0\n");
+
+        printer.reset_sequence(100).await.unwrap();
+        let _ = printer.send("G28").await.unwrap();
+        line.clear();
+        far_end.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "M110N99\n");
+        line.clear();
+        far_end.read_line(&mut line).await.unwrap();
+        assert!(line.starts_with("N100G28*"));
+    }
+
+    #[tokio::test]
+    async fn clean_shutdown() {
+        use tokio::io::AsyncReadExt;
+
+        let (device, mut far_end) = tokio::io::duplex(64);
+        let options = quiet();
+        let mut printer = Printer::with_options(tokio::io::BufReader::new(device), options);
+        printer.send_raw(b"M84\n").await.unwrap();
+        printer.shutdown().await.unwrap();
+        assert!(!printer.is_connected());
+        let mut received = vec![];
+        far_end.read_to_end(&mut received).await.unwrap();
+        assert_eq!(received, b"M84\n");
+    }
 }
@@ -0,0 +1,214 @@
+use {
+    crate::Printer,
+    print3rs_serializer::{Format, LineEnding, SEQUENCE_START},
+    std::{fmt::Debug, time::Duration},
+    tokio::io::{AsyncBufRead, AsyncWrite},
+};
+
+/// Protocol spoken by the firmware on the other end
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Dialect {
+    /// Marlin, and firmwares which talk like it such as Prusa, Klipper or RepRapFirmware
+    #[default]
+    Marlin,
+    /// Grbl, for CNC machines and lasers.
+    ///
+    /// Lines are answered in order with `ok` or `error:<code>`, without line numbers or checksums,
+    /// and the machine is probed and identified with `?` and `$I`, see `crate::grbl`.
+    Grbl,
+}
+
+impl Dialect {
+    /// Request for firmware information
+    pub(crate) fn identify_request(self) -> &'static [u8] {
+        match self {
+            Self::Marlin => b"M115",
+            Self::Grbl => b"$I",
+        }
+    }
+
+    /// Write a keepalive probe, which for Grbl is a real-time status request without a line ending
+    pub(crate) fn keepalive_probe(self, line_ending: LineEnding) -> Vec<u8> {
+        match self {
+            Self::Marlin => [b"M105", line_ending.as_bytes()].concat(),
+            Self::Grbl => vec![crate::grbl::STATUS_REPORT],
+        }
+    }
+}
+
+/// Settings for how a `Printer` talks to its device, which can also build one.
+///
+/// ```ignore
+/// let printer = Printer::builder()
+///     .keepalive(Some(Duration::from_secs(10)))
+///     .window(8)
+///     .build(port);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PrinterOptions {
+    /// Only ever have one command in flight.
+    ///
+    /// Nothing else is written, not even raw sends, until the device answers
+    /// the last line with an `ok`. Needed for some TFT bridges and old firmwares
+    /// which corrupt input if several commands are pipelined.
+    pub half_duplex: bool,
+    /// Most commands written without an `ok` yet, 4 by default.
+    ///
+    /// Firmware reporting its free buffer space (ADVANCED_OK) adjusts this as it goes.
+    pub window: usize,
+    /// Probe the device with M105 after this long without any traffic.
+    ///
+    /// If nothing is heard for another interval after the probe,
+    /// `PrinterEvent::Unresponsive` is sent, so a dead link is noticed right away.
+    pub keepalive: Option<Duration>,
+    /// Ask the device for its firmware information and capabilities with M115 on connect.
+    ///
+    /// The report is read into the `InfoMap` available from `Printer::info`.
+    /// On by default.
+    pub identify: bool,
+    /// Write a line again if it is not answered with an `ok` within this long.
+    /// Lines sent without a line number aren't written again, but fail with `Error::Timeout`.
+    ///
+    /// Should be longer than the slowest command sent, like homing or waiting to heat.
+    pub ack_timeout: Option<Duration>,
+    /// Times a line is written again after `ack_timeout` before giving up on it with `Error::Timeout`
+    pub ack_retries: u32,
+    /// Send `M110 N0` on connect, so the device accepts the first numbered line as `N1`
+    /// whatever line number it was left at. On by default.
+    pub reset_line_numbers: bool,
+    /// Line number of the first sequenced line
+    pub initial_sequence: i32,
+    /// Protocol spoken by the device
+    pub dialect: Dialect,
+    /// How values in typed commands are written, like how many decimals floats are rounded to
+    pub format: Format,
+    /// Commands which can be queued before sending has to wait, 16 by default
+    pub send_capacity: usize,
+    /// Lines from the device kept for receivers which haven't read them yet, 64 by default.
+    ///
+    /// Firmware autoreporting temperatures or positions quickly can fill this up,
+    /// see `ChannelStats` to find out if lines are being missed.
+    pub line_capacity: usize,
+}
+
+impl Default for PrinterOptions {
+    fn default() -> Self {
+        Self {
+            half_duplex: false,
+            window: 4,
+            keepalive: None,
+            identify: true,
+            ack_timeout: None,
+            ack_retries: 2,
+            reset_line_numbers: true,
+            initial_sequence: SEQUENCE_START,
+            dialect: Dialect::default(),
+            format: Format::default(),
+            send_capacity: 16,
+            line_capacity: 64,
+        }
+    }
+}
+
+impl PrinterOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set `half_duplex`
+    pub fn half_duplex(mut self, half_duplex: bool) -> Self {
+        self.half_duplex = half_duplex;
+        self
+    }
+
+    /// Set `window`
+    pub fn window(mut self, window: usize) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Set `keepalive`
+    pub fn keepalive(mut self, keepalive: Option<Duration>) -> Self {
+        self.keepalive = keepalive;
+        self
+    }
+
+    /// Set `identify`
+    pub fn identify(mut self, identify: bool) -> Self {
+        self.identify = identify;
+        self
+    }
+
+    /// Set `ack_timeout`
+    pub fn ack_timeout(mut self, ack_timeout: Option<Duration>) -> Self {
+        self.ack_timeout = ack_timeout;
+        self
+    }
+
+    /// Set `ack_retries`
+    pub fn ack_retries(mut self, ack_retries: u32) -> Self {
+        self.ack_retries = ack_retries;
+        self
+    }
+
+    /// Set `reset_line_numbers`
+    pub fn reset_line_numbers(mut self, reset_line_numbers: bool) -> Self {
+        self.reset_line_numbers = reset_line_numbers;
+        self
+    }
+
+    /// Set `initial_sequence`
+    pub fn initial_sequence(mut self, initial_sequence: i32) -> Self {
+        self.initial_sequence = initial_sequence;
+        self
+    }
+
+    /// Set the `line_ending` of `format`
+    pub fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.format = self.format.line_ending(line_ending);
+        self
+    }
+
+    /// Set `dialect`
+    pub fn dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// Set `format`
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set `send_capacity`
+    pub fn send_capacity(mut self, send_capacity: usize) -> Self {
+        self.send_capacity = send_capacity;
+        self
+    }
+
+    /// Set `line_capacity`
+    pub fn line_capacity(mut self, line_capacity: usize) -> Self {
+        self.line_capacity = line_capacity;
+        self
+    }
+
+    /// Start talking to a device with these options, same as `Printer::with_options`
+    pub fn build<S>(self, port: S) -> Printer
+    where
+        S: AsyncBufRead + AsyncWrite + Unpin + Send + 'static + Debug,
+    {
+        Printer::with_options(port, self)
+    }
+
+    /// Most commands waiting on an `ok` at once
+    pub(crate) fn max_in_flight(&self) -> usize {
+        if self.half_duplex {
+            1
+        } else {
+            self.window.max(1)
+        }
+    }
+}
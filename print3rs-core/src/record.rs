@@ -0,0 +1,407 @@
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    fs::File,
+    future::Future,
+    io::{self, BufRead, LineWriter, Write},
+    path::Path,
+    pin::Pin,
+    str::FromStr,
+    task::{ready, Context, Poll, Waker},
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    time::{sleep_until, Instant, Sleep},
+};
+
+/// Which way recorded bytes were travelling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Written by the host to the device
+    Sent,
+    /// Read by the host from the device
+    Received,
+}
+
+/// One chunk of traffic in a recording, timestamped from the start of the session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub time: Duration,
+    pub direction: Direction,
+    pub data: Vec<u8>,
+}
+
+/// Recordings are stored one entry per line as `<millis> <'>' or '<'> <escaped bytes>`
+impl Display for Entry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let arrow = match self.direction {
+            Direction::Sent => '>',
+            Direction::Received => '<',
+        };
+        write!(f, "{} {arrow} ", self.time.as_millis())?;
+        for byte in self
+            .data
+            .iter()
+            .copied()
+            .flat_map(std::ascii::escape_default)
+        {
+            write!(f, "{}", char::from(byte))?;
+        }
+        Ok(())
+    }
+}
+
+fn unescape(s: &str) -> Option<Vec<u8>> {
+    let mut unescaped = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte != b'\\' {
+            unescaped.push(byte);
+            continue;
+        }
+        let escaped = match bytes.next()? {
+            b'n' => b'\n',
+            b'r' => b'\r',
+            b't' => b'\t',
+            b'x' => {
+                let hex = [bytes.next()?, bytes.next()?];
+                u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?
+            }
+            other => other,
+        };
+        unescaped.push(escaped);
+    }
+    Some(unescaped)
+}
+
+impl FromStr for Entry {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("bad entry `{s}`"));
+        let (millis, rest) = s.split_once(' ').ok_or_else(invalid)?;
+        let (arrow, data) = rest.split_once(' ').unwrap_or((rest, ""));
+        let direction = match arrow {
+            ">" => Direction::Sent,
+            "<" => Direction::Received,
+            _ => return Err(invalid()),
+        };
+        Ok(Entry {
+            time: Duration::from_millis(millis.parse().map_err(|_| invalid())?),
+            direction,
+            data: unescape(data).ok_or_else(invalid)?,
+        })
+    }
+}
+
+/// Transport wrapper which copies all traffic in both directions into a recording.
+///
+/// Wrap the raw device before buffering, e.g. `BufReader::new(Recorder::new(port, log))`,
+/// and the resulting session can be played back later with a `Replay`.
+#[derive(Debug)]
+pub struct Recorder<T, W> {
+    inner: T,
+    log: W,
+    start: std::time::Instant,
+}
+
+impl<T, W: Write> Recorder<T, W> {
+    /// Record all traffic of `inner` into `log`
+    pub fn new(inner: T, log: W) -> Self {
+        Self {
+            inner,
+            log,
+            start: std::time::Instant::now(),
+        }
+    }
+
+    fn record(&mut self, direction: Direction, data: &[u8]) {
+        let entry = Entry {
+            time: self.start.elapsed(),
+            direction,
+            data: data.to_vec(),
+        };
+        if let Err(e) = writeln!(self.log, "{entry}") {
+            tracing::warn!("failed to record traffic: {e}");
+        }
+    }
+
+    /// Stop recording and get back the wrapped transport
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T> Recorder<T, LineWriter<File>> {
+    /// Record all traffic of `inner` into a newly created file at `path`
+    pub fn create(inner: T, path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(inner, LineWriter::new(File::create(path)?)))
+    }
+}
+
+impl<T, W> AsyncRead for Recorder<T, W>
+where
+    T: AsyncRead + Unpin,
+    W: Write + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let already_filled = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        let received = &buf.filled()[already_filled..];
+        if !received.is_empty() {
+            this.record(Direction::Received, received);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T, W> AsyncWrite for Recorder<T, W>
+where
+    T: AsyncWrite + Unpin,
+    W: Write + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let written = ready!(Pin::new(&mut this.inner).poll_write(cx, buf))?;
+        this.record(Direction::Sent, &buf[..written]);
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let _ = this.log.flush();
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+/// Transport which plays back the device side of a recording as if it were a live device.
+///
+/// Received data is delivered with the same timing it was recorded with,
+/// anything written is accepted and discarded.
+/// Once the recording is exhausted the transport reads as closed.
+#[derive(Debug)]
+pub struct Replay {
+    entries: VecDeque<Entry>,
+    offset: usize,
+    start: Option<Instant>,
+    delay: Option<Pin<Box<Sleep>>>,
+    lockstep: bool,
+    /// Bytes written in lockstep mode not yet matched against sent entries
+    written: usize,
+    /// Reader waiting in lockstep mode for the host to write what was sent before the next received entry
+    waiting: Option<Waker>,
+}
+
+impl Replay {
+    /// Play back the received side of the given entries
+    pub fn new(entries: impl IntoIterator<Item = Entry>) -> Self {
+        let entries = entries
+            .into_iter()
+            .filter(|entry| !entry.data.is_empty())
+            .collect();
+        Self {
+            entries,
+            offset: 0,
+            start: None,
+            delay: None,
+            lockstep: false,
+            written: 0,
+            waiting: None,
+        }
+    }
+
+    /// Ignore the recorded timing, instead delivering each received entry as soon as
+    /// as many bytes have been written as were sent before it in the recording.
+    ///
+    /// This makes a replay deterministic however fast or slow the host is,
+    /// so captures can be used to reproduce problems in tests.
+    pub fn lockstep(mut self) -> Self {
+        self.lockstep = true;
+        self
+    }
+
+    /// Load a recording made by a `Recorder`
+    pub fn from_reader(reader: impl BufRead) -> io::Result<Self> {
+        let mut entries: Vec<Entry> = vec![];
+        for line in reader.lines() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            entries.push(line.parse()?);
+        }
+        Ok(Self::new(entries))
+    }
+
+    /// Load a recording file made by a `Recorder`
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_reader(io::BufReader::new(File::open(path)?))
+    }
+}
+
+impl AsyncRead for Replay {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let start = *this.start.get_or_insert_with(Instant::now);
+        while let Some(sent) = this
+            .entries
+            .front()
+            .filter(|entry| entry.direction == Direction::Sent)
+        {
+            if !this.lockstep {
+                this.entries.pop_front();
+            } else if this.written >= sent.data.len() {
+                this.written -= sent.data.len();
+                this.entries.pop_front();
+            } else {
+                this.waiting = Some(cx.waker().clone());
+                return Poll::Pending;
+            }
+        }
+        let Some(entry) = this.entries.front() else {
+            return Poll::Ready(Ok(()));
+        };
+        if this.offset == 0 && !this.lockstep {
+            let due = start + entry.time;
+            if due > Instant::now() {
+                let delay = this.delay.get_or_insert_with(|| Box::pin(sleep_until(due)));
+                ready!(delay.as_mut().poll(cx));
+            }
+            this.delay = None;
+        }
+        let remaining = &entry.data[this.offset..];
+        let len = remaining.len().min(buf.remaining());
+        buf.put_slice(&remaining[..len]);
+        this.offset += len;
+        if this.offset == entry.data.len() {
+            this.entries.pop_front();
+            this.offset = 0;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for Replay {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.lockstep {
+            this.written += buf.len();
+            if let Some(waiting) = this.waiting.take() {
+                waiting.wake();
+            }
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+    #[test]
+    fn entry_round_trip() {
+        let entry = Entry {
+            time: Duration::from_millis(1500),
+            direction: Direction::Received,
+            data: b"ok T:21.3 /0.0\r\n\x00\\".to_vec(),
+        };
+        let line = entry.to_string();
+        assert!(!line.contains('\n'));
+        assert_eq!(line.parse::<Entry>().unwrap(), entry);
+    }
+
+    #[test]
+    fn bad_entry() {
+        assert!("12 ? M105".parse::<Entry>().is_err());
+        assert!("soon > M105".parse::<Entry>().is_err());
+    }
+
+    #[tokio::test]
+    async fn records_both_directions() {
+        let (device, mut far_end) = tokio::io::duplex(64);
+        let mut recorder = Recorder::new(device, Vec::new());
+        recorder.write_all(b"M105\n").await.unwrap();
+        far_end.write_all(b"ok\n").await.unwrap();
+        let mut buf = [0; 3];
+        recorder.read_exact(&mut buf).await.unwrap();
+
+        let entries: Vec<Entry> = String::from_utf8(recorder.log)
+            .unwrap()
+            .lines()
+            .map(|line| line.parse().unwrap())
+            .collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].direction, Direction::Sent);
+        assert_eq!(entries[0].data, b"M105\n");
+        assert_eq!(entries[1].direction, Direction::Received);
+        assert_eq!(entries[1].data, b"ok\n");
+    }
+
+    #[tokio::test]
+    async fn replays_received() {
+        let recording = "0 > M115\\n\n1 < FIRMWARE_NAME:Marlin\\n\n2 < ok\\n\n";
+        let replay = Replay::from_reader(recording.as_bytes()).unwrap();
+        let mut replay = BufReader::new(replay);
+        replay.write_all(b"M115\n").await.unwrap();
+        let mut line = String::new();
+        replay.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "FIRMWARE_NAME:Marlin\n");
+        line.clear();
+        replay.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "ok\n");
+        line.clear();
+        assert_eq!(replay.read_line(&mut line).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn lockstep_waits_for_writes() {
+        let recording = "0 > M115\\n\n60000 < ok\\n\n60001 > G28\\n\n60002 < ok\\n\n";
+        let replay = Replay::from_reader(recording.as_bytes())
+            .unwrap()
+            .lockstep();
+        let (reader, mut writer) = tokio::io::split(replay);
+        let mut reader = BufReader::new(reader);
+        let mut line = String::new();
+        let early = tokio::time::timeout(Duration::from_millis(10), reader.read_line(&mut line));
+        assert!(early.await.is_err());
+        writer.write_all(b"M115\n").await.unwrap();
+        reader.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "ok\n");
+        writer.write_all(b"G28\n").await.unwrap();
+        line.clear();
+        reader.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "ok\n");
+    }
+}
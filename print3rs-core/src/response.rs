@@ -1,16 +1,20 @@
 use winnow::{
-    ascii::{dec_int, multispace0, space0, Caseless},
-    combinator::{alt, opt, preceded, terminated},
+    ascii::{dec_int, dec_uint, float, multispace0, space0, space1, Caseless},
+    combinator::{alt, opt, preceded, separated, terminated},
     prelude::*,
+    token::take_while,
 };
 
 /// Response from connected device to indicate if a command
 /// * has finished execution, possibly with a sequence number
 /// * failed parsing, possibly with a sequence number
+/// * was rejected, possibly with an error code
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Response {
     Ok(Option<i32>),
     Resend(Option<i32>),
+    /// `error:<code>`, how Grbl answers a line it can't run
+    Error(Option<i32>),
 }
 
 fn ok_response(input: &mut &[u8]) -> PResult<Response> {
@@ -31,9 +35,202 @@ fn resend_response(input: &mut &[u8]) -> PResult<Response> {
     .parse_next(input)
 }
 
+fn error_response(input: &mut &[u8]) -> PResult<Response> {
+    preceded(
+        (space0, Caseless("error:"), space0),
+        terminated(opt(dec_int), multispace0),
+    )
+    .map(Response::Error)
+    .parse_next(input)
+}
+
 /// try to parse a `Response` out of a byte stream
 pub fn response(input: &mut &[u8]) -> PResult<Response> {
-    alt((ok_response, resend_response)).parse_next(input)
+    alt((ok_response, resend_response, error_response)).parse_next(input)
+}
+
+/// Free space left in the firmware's buffers, reported with each `ok` by firmware built with ADVANCED_OK
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferSpace {
+    /// Free moves in the planner buffer, `P`
+    pub planner: u32,
+    /// Free lines in the serial command buffer, `B`
+    pub buffer: u32,
+}
+
+impl BufferSpace {
+    /// Read an `ok N<line> P<planner> B<buffer>` line, if it has buffer information
+    pub fn parse(line: &str) -> Option<Self> {
+        buffer_space
+            .parse_peek(line.as_bytes())
+            .ok()
+            .map(|(_, space)| space)
+    }
+}
+
+/// try to parse the `BufferSpace` of an ADVANCED_OK response out of a byte stream
+pub fn buffer_space(input: &mut &[u8]) -> PResult<BufferSpace> {
+    preceded(
+        (
+            space0,
+            Caseless("ok"),
+            opt(":"),
+            space0,
+            opt((b'N', dec_int::<_, i32, _>, space1)),
+        ),
+        (preceded(b'P', dec_uint), preceded((space1, b'B'), dec_uint)),
+    )
+    .map(|(planner, buffer)| BufferSpace { planner, buffer })
+    .parse_next(input)
+}
+
+/// Reading of a single heater
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Temperature {
+    pub current: f32,
+    /// Temperature the heater is set to reach, if it was reported
+    pub target: Option<f32>,
+}
+
+/// Temperatures reported by the device, in reply to M105 or as an autoreport (M155).
+///
+/// Parsed from lines like `ok T:200.1 /200.0 B:60.0 /60.0 T0:200.1 /200.0 @:127 B@:0`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TemperatureReport {
+    /// Active hotend, `T:`
+    pub hotend: Option<Temperature>,
+    /// Each hotend by index, `T0:`, `T1:`... only reported by machines with several
+    pub tools: Vec<Temperature>,
+    /// `B:`
+    pub bed: Option<Temperature>,
+    /// `C:`
+    pub chamber: Option<Temperature>,
+    /// Raw heater output of the active hotend, `@:`, 0 to 127 on Marlin
+    pub hotend_power: Option<f32>,
+    /// Raw heater output of the bed, `B@:`
+    pub bed_power: Option<f32>,
+}
+
+impl TemperatureReport {
+    /// Read a line received from the device, if it is a temperature report
+    pub fn parse(line: &str) -> Option<Self> {
+        temperature_report
+            .parse_peek(line.as_bytes())
+            .ok()
+            .map(|(_, report)| report)
+    }
+
+    fn from_readings(readings: Vec<(&[u8], f32, Option<f32>)>) -> Option<Self> {
+        let mut report = Self::default();
+        for (label, current, target) in readings {
+            let temperature = Temperature { current, target };
+            match label {
+                b"T" => report.hotend = Some(temperature),
+                b"B" => report.bed = Some(temperature),
+                b"C" => report.chamber = Some(temperature),
+                b"@" => report.hotend_power = Some(current),
+                b"B@" => report.bed_power = Some(current),
+                [b'T', index @ ..] => {
+                    let Some(index) = std::str::from_utf8(index)
+                        .ok()
+                        .and_then(|index| index.parse::<usize>().ok())
+                    else {
+                        continue;
+                    };
+                    if report.tools.len() <= index {
+                        report.tools.resize(index + 1, Temperature::default());
+                    }
+                    report.tools[index] = temperature;
+                }
+                // anything else, like the E: and W: of M109 wait reports, is not a temperature
+                _ => {}
+            }
+        }
+        // single hotend machines only report T:
+        if report.hotend.is_none() {
+            report.hotend = report.tools.first().copied();
+        }
+        (report.hotend.is_some() || report.bed.is_some()).then_some(report)
+    }
+}
+
+/// Position of the toolhead, as reported in reply to M114 or by autoreport (M154)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Position {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    /// Extruder position, not reported by every firmware
+    pub e: Option<f32>,
+}
+
+impl Position {
+    /// Read a line received from the device, if it is a position report like
+    /// `X:10.00 Y:0.00 Z:0.20 E:0.00 Count X:800 Y:0 Z:80`.
+    ///
+    /// Only the logical position is read, not the stepper counts after `Count`.
+    pub fn parse(line: &str) -> Option<Self> {
+        position_report
+            .parse_peek(line.as_bytes())
+            .ok()
+            .map(|(_, position)| position)
+    }
+
+    fn from_readings(readings: Vec<(&[u8], f32, Option<f32>)>) -> Option<Self> {
+        let axis = |name: &[u8]| {
+            readings
+                .iter()
+                .find(|(label, _, _)| *label == name)
+                .map(|(_, value, _)| *value)
+        };
+        Some(Self {
+            x: axis(b"X")?,
+            y: axis(b"Y")?,
+            z: axis(b"Z")?,
+            e: axis(b"E"),
+        })
+    }
+}
+
+/// A labeled reading like `T:200.1 /200.0` or `@:127`
+fn reading<'a>(input: &mut &'a [u8]) -> PResult<(&'a [u8], f32, Option<f32>)> {
+    (
+        terminated(
+            take_while(1.., |c: u8| c.is_ascii_alphanumeric() || c == b'@'),
+            ':',
+        ),
+        preceded(space0, float),
+        opt(preceded((space0, '/', space0), float)),
+    )
+        .parse_next(input)
+}
+
+/// The `ok` a report may be put on, with the line number it acknowledges like `ok N12 `
+fn report_ok(input: &mut &[u8]) -> PResult<()> {
+    (
+        space0,
+        opt((
+            Caseless("ok"),
+            space1,
+            opt(terminated(preceded('N', dec_uint::<_, u32, _>), space1)),
+        )),
+    )
+        .void()
+        .parse_next(input)
+}
+
+/// try to parse a `TemperatureReport` out of a byte stream
+pub fn temperature_report(input: &mut &[u8]) -> PResult<TemperatureReport> {
+    preceded(report_ok, separated(1.., reading, space1))
+        .verify_map(TemperatureReport::from_readings)
+        .parse_next(input)
+}
+
+/// try to parse a `Position` out of a byte stream
+pub fn position_report(input: &mut &[u8]) -> PResult<Position> {
+    preceded(report_ok, separated(1.., reading, space1))
+        .verify_map(Position::from_readings)
+        .parse_next(input)
 }
 
 #[cfg(test)]
@@ -46,6 +243,15 @@ mod test {
         assert_eq!(ok, Response::Ok(None));
     }
 
+    #[test]
+    fn test_error_response() {
+        let error = response.parse_peek(b"error:22\r\n").unwrap().1;
+        assert_eq!(error, Response::Error(Some(22)));
+        // Marlin errors are followed by a resend, so they only matter to Grbl
+        let error = response.parse_peek(b"Error:checksum mismatch").unwrap().1;
+        assert_eq!(error, Response::Error(None));
+    }
+
     #[test]
     fn test_ok_num_response() {
         let ok = ok_response.parse(b"ok: 100").unwrap();
@@ -58,6 +264,97 @@ mod test {
         assert_eq!(ok, Response::Resend(Some(100)));
     }
 
+    #[test]
+    fn test_temperature_report() {
+        let report =
+            TemperatureReport::parse("ok T:200.1 /200.0 B:60.0 /60.0 @:127 B@:0\n").unwrap();
+        assert_eq!(
+            report.hotend,
+            Some(Temperature {
+                current: 200.1,
+                target: Some(200.0)
+            })
+        );
+        assert_eq!(report.bed.unwrap().target, Some(60.0));
+        assert_eq!(report.hotend_power, Some(127.0));
+        assert_eq!(report.bed_power, Some(0.0));
+        assert!(report.tools.is_empty());
+        assert!(report.chamber.is_none());
+        // a numbered M105 is answered on its numbered ok
+        let numbered = TemperatureReport::parse("ok N12 T:200.1 /200.0 B:60.0 /60.0").unwrap();
+        assert_eq!(numbered.hotend, report.hotend);
+        assert_eq!(TemperatureReport::parse("ok N12"), None);
+    }
+
+    #[test]
+    fn test_multi_tool_temperatures() {
+        let report = TemperatureReport::parse(
+            "T:210.0 /210.0 T0:210.0 /210.0 T1:24.5 /0.0 B:60.1 /60.0 C:31.2 /0.0",
+        )
+        .unwrap();
+        assert_eq!(report.tools.len(), 2);
+        assert_eq!(report.tools[1].current, 24.5);
+        assert_eq!(report.chamber.unwrap().current, 31.2);
+    }
+
+    #[test]
+    fn test_wait_report() {
+        let report = TemperatureReport::parse(" T:195.5 E:0 W:?").unwrap();
+        assert_eq!(report.hotend.unwrap().current, 195.5);
+        assert_eq!(report.hotend.unwrap().target, None);
+    }
+
+    #[test]
+    fn test_not_temperatures() {
+        assert_eq!(TemperatureReport::parse("ok"), None);
+        assert_eq!(
+            TemperatureReport::parse("X:10.00 Y:0.00 Z:0.20 E:0.00 Count X:800 Y:0 Z:80"),
+            None
+        );
+        assert_eq!(TemperatureReport::parse("echo:busy: processing"), None);
+    }
+
+    #[test]
+    fn test_position_report() {
+        let position =
+            Position::parse("X:10.00 Y:0.00 Z:0.20 E:-1.50 Count X:800 Y:0 Z:80\n").unwrap();
+        assert_eq!(
+            position,
+            Position {
+                x: 10.0,
+                y: 0.0,
+                z: 0.2,
+                e: Some(-1.5)
+            }
+        );
+        let position = Position::parse("ok X:1.000 Y:2.000 Z:3.000").unwrap();
+        assert_eq!(position.e, None);
+        assert_eq!(Position::parse("T:200.1 /200.0 B:60.0 /60.0"), None);
+        assert_eq!(Position::parse("X:10.00 Y:0.00"), None);
+    }
+
+    #[test]
+    fn test_buffer_space() {
+        assert_eq!(
+            BufferSpace::parse("ok N1234 P15 B3\n"),
+            Some(BufferSpace {
+                planner: 15,
+                buffer: 3
+            })
+        );
+        assert_eq!(
+            BufferSpace::parse("ok P0 B0"),
+            Some(BufferSpace {
+                planner: 0,
+                buffer: 0
+            })
+        );
+        assert_eq!(BufferSpace::parse("ok N12"), None);
+        assert_eq!(BufferSpace::parse("ok T:200.0 /200.0"), None);
+        let ok = response.parse_peek(b"ok N1234 P15 B3\n").unwrap().1;
+        assert_eq!(ok, Response::Ok(Some(1234)));
+    }
+
     #[test]
     fn test_response() {
         let ok = response.parse(b"ok").unwrap();
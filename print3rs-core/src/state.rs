@@ -0,0 +1,213 @@
+use {
+    crate::{grbl::GrblStatus, PrinterEvent},
+    std::sync::Mutex,
+    tokio::sync::broadcast,
+};
+
+/// What the device is doing, as far as can be told from what is sent to and received from it
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PrinterState {
+    /// Ready for commands
+    #[default]
+    Idle,
+    /// Running a print, from its own storage (M24) or streamed by the host
+    Printing,
+    /// A print was paused and can be resumed
+    Paused,
+    /// Working through a long command like homing or heating, outside of a print
+    Busy,
+    /// Halted after an emergency stop or a fatal firmware error, needs M999 or a reset
+    Error,
+}
+
+/// Follows the device's state from the lines going back and forth
+#[derive(Debug, Default)]
+pub(crate) struct StateTracker {
+    /// Idle, Printing or Paused
+    job: PrinterState,
+    busy: bool,
+    halted: bool,
+}
+
+impl StateTracker {
+    pub(crate) fn state(&self) -> PrinterState {
+        match self {
+            Self { halted: true, .. } => PrinterState::Error,
+            Self {
+                job: job @ (PrinterState::Printing | PrinterState::Paused),
+                ..
+            } => *job,
+            Self { busy: true, .. } => PrinterState::Busy,
+            _ => PrinterState::Idle,
+        }
+    }
+
+    /// Set by the host, like when it starts or finishes streaming a print
+    pub(crate) fn set(&mut self, state: PrinterState) {
+        match state {
+            PrinterState::Error => self.halted = true,
+            PrinterState::Busy => self.busy = true,
+            job => {
+                self.job = job;
+                self.halted = false;
+            }
+        }
+    }
+
+    /// Account for a line written to the device
+    pub(crate) fn sent(&mut self, line: &[u8]) {
+        let line = String::from_utf8_lossy(line);
+        let line = line.split('*').next().unwrap_or_default().trim_start();
+        // skip the line number of sequenced lines, which the serializer writes as `N12M24`
+        let line = match line.strip_prefix(['N', 'n']) {
+            Some(numbered) => numbered
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .trim_start(),
+            None => line,
+        };
+        // a letter and number, whether or not a space comes before the parameters
+        let length = line
+            .char_indices()
+            .skip(1)
+            .find(|(_, c)| !c.is_ascii_digit())
+            .map_or(line.len(), |(index, _)| index);
+        let command = &line[..length];
+        match command.to_ascii_uppercase().as_str() {
+            "M24" | "M602" => self.job = PrinterState::Printing,
+            "M25" | "M601" => self.job = PrinterState::Paused,
+            "M524" => self.job = PrinterState::Idle,
+            "M112" => self.halted = true,
+            "M999" => self.halted = false,
+            _ => {}
+        }
+    }
+
+    /// Account for a line received from the device
+    pub(crate) fn received(&mut self, line: &str) {
+        let line = line.trim();
+        if line.starts_with("echo:busy:") || line.starts_with("busy:") {
+            self.busy = true;
+        } else if line.starts_with("ok") {
+            self.busy = false;
+        } else if let Some(action) = line.strip_prefix("//action:") {
+            match action.trim() {
+                "paused" | "pause" => self.job = PrinterState::Paused,
+                "resumed" | "resume" => self.job = PrinterState::Printing,
+                "cancel" => self.job = PrinterState::Idle,
+                _ => {}
+            }
+        } else if line.starts_with("Done printing file") {
+            self.job = PrinterState::Idle;
+        } else if line.starts_with("Error:")
+            && ["halted", "kill", "stopped"]
+                .iter()
+                .any(|fatal| line.contains(fatal))
+        {
+            self.halted = true;
+        } else if line == "start" || line.starts_with("Grbl ") {
+            // the firmware restarted
+            *self = Self::default();
+        } else if line.starts_with("ALARM:") {
+            self.halted = true;
+        } else if let Some(status) = GrblStatus::parse(line) {
+            // Grbl reports its whole state, there's no job to keep track of separately
+            *self = Self::default();
+            self.set(status.state.printer_state());
+        }
+    }
+}
+
+/// Change the tracked state, announcing it if that changes the state of the device
+pub(crate) fn track(
+    tracker: &Mutex<StateTracker>,
+    events: &broadcast::Sender<PrinterEvent>,
+    change: impl FnOnce(&mut StateTracker),
+) {
+    let Ok(mut tracker) = tracker.lock() else {
+        return;
+    };
+    let before = tracker.state();
+    change(&mut tracker);
+    let after = tracker.state();
+    if before != after {
+        tracing::debug!("Printer went from {before:?} to {after:?}");
+        let _ = events.send(PrinterEvent::StateChanged(after));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sd_print() {
+        let mut tracker = StateTracker::default();
+        let serializer = print3rs_serializer::Sequenced::new();
+        serializer.set_sequence(10);
+        let (_, start) = serializer.serialize("M24");
+        tracker.sent(&start);
+        assert_eq!(tracker.state(), PrinterState::Printing);
+        tracker.received("echo:busy: processing\n");
+        assert_eq!(tracker.state(), PrinterState::Printing);
+        tracker.sent(b"M25\n");
+        assert_eq!(tracker.state(), PrinterState::Paused);
+        tracker.received("//action:resumed\n");
+        assert_eq!(tracker.state(), PrinterState::Printing);
+        tracker.received("Done printing file\n");
+        assert_eq!(tracker.state(), PrinterState::Idle);
+    }
+
+    #[test]
+    fn busy_until_ok() {
+        let mut tracker = StateTracker::default();
+        tracker.received("echo:busy: processing\n");
+        assert_eq!(tracker.state(), PrinterState::Busy);
+        tracker.received("ok\n");
+        assert_eq!(tracker.state(), PrinterState::Idle);
+    }
+
+    #[test]
+    fn halted() {
+        let mut tracker = StateTracker::default();
+        tracker.received("Error:checksum mismatch, Last Line: 3\n");
+        assert_eq!(tracker.state(), PrinterState::Idle);
+        tracker.received("Error:Printer halted. kill() called!\n");
+        assert_eq!(tracker.state(), PrinterState::Error);
+        tracker.sent(b"M999\n");
+        assert_eq!(tracker.state(), PrinterState::Idle);
+        let (_, stop) = print3rs_serializer::Sequenced::new().serialize("M112");
+        tracker.sent(&stop);
+        assert_eq!(tracker.state(), PrinterState::Error);
+        tracker.received("start\n");
+        assert_eq!(tracker.state(), PrinterState::Idle);
+    }
+
+    #[test]
+    fn grbl_status() {
+        let mut tracker = StateTracker::default();
+        tracker.received("<Run|MPos:0.000,0.000,0.000|FS:500,0>\r\n");
+        assert_eq!(tracker.state(), PrinterState::Printing);
+        tracker.received("<Hold:0|MPos:0.000,0.000,0.000|FS:0,0>\r\n");
+        assert_eq!(tracker.state(), PrinterState::Paused);
+        tracker.received("ALARM:1\r\n");
+        assert_eq!(tracker.state(), PrinterState::Error);
+        tracker.received("Grbl 1.1h ['$' for help]\r\n");
+        assert_eq!(tracker.state(), PrinterState::Idle);
+    }
+
+    #[test]
+    fn announces_changes() {
+        let tracker = Mutex::new(StateTracker::default());
+        let (events, mut receiver) = broadcast::channel(4);
+        track(&tracker, &events, |tracker| tracker.received("ok\n"));
+        track(&tracker, &events, |tracker| {
+            tracker.set(PrinterState::Printing)
+        });
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            PrinterEvent::StateChanged(PrinterState::Printing)
+        );
+        assert!(receiver.try_recv().is_err());
+    }
+}
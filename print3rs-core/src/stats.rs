@@ -0,0 +1,277 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::{broadcast, mpsc};
+
+/// Upper bounds of the buckets acknowledgement times are counted in, a last bucket holds anything slower
+pub const ACK_LATENCY_BUCKETS: [Duration; 4] = [
+    Duration::from_millis(10),
+    Duration::from_millis(50),
+    Duration::from_millis(200),
+    Duration::from_secs(1),
+];
+
+/// Counts of traffic with the device and of messages lost to full channels,
+/// to diagnose slow or flaky links and tell whether responses may have been missed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ChannelStats {
+    /// Lines from the device overwritten before the slowest receiver read them
+    pub dropped_lines: u64,
+    /// Lines skipped by receivers in this crate, like `Socket::read_next_line`, for falling behind
+    pub lagged_lines: u64,
+    /// Non-blocking sends refused because the send queue was full
+    pub full_sends: u64,
+    /// Commands written to the device, not counting resends
+    pub commands_sent: u64,
+    /// Lines read from the device
+    pub lines_received: u64,
+    /// Bytes written to the device, including resends and keepalive probes
+    pub bytes_sent: u64,
+    /// Bytes read from the device
+    pub bytes_received: u64,
+    /// `ok`s read from the device
+    pub oks: u64,
+    /// Lines written again, because the device asked for them or didn't acknowledge them in time
+    pub resends: u64,
+    /// Lines given up on after going unacknowledged for every retry
+    pub timeouts: u64,
+    /// Commands waited on which were acknowledged, the number of times in `ack_latency_total`
+    pub acknowledged: u64,
+    /// Time from sending to acknowledgement, summed over every acknowledged command
+    pub ack_latency_total: Duration,
+    /// Slowest acknowledgement seen
+    pub ack_latency_max: Duration,
+    /// Acknowledgements counted by how long they took, bucketed by `ACK_LATENCY_BUCKETS`
+    pub ack_latency_histogram: [u64; ACK_LATENCY_BUCKETS.len() + 1],
+    /// Time since the connection was made
+    pub connected_for: Duration,
+}
+
+impl ChannelStats {
+    /// Mean time from sending a command to its acknowledgement, if any were acknowledged
+    pub fn average_ack_latency(&self) -> Option<Duration> {
+        let acknowledged = u32::try_from(self.acknowledged).ok().filter(|n| *n > 0)?;
+        Some(self.ack_latency_total / acknowledged)
+    }
+
+    /// Mean bytes written to the device each second since connecting
+    pub fn bytes_sent_per_second(&self) -> f64 {
+        per_second(self.bytes_sent, self.connected_for)
+    }
+
+    /// Mean bytes read from the device each second since connecting
+    pub fn bytes_received_per_second(&self) -> f64 {
+        per_second(self.bytes_received, self.connected_for)
+    }
+}
+
+fn per_second(count: u64, time: Duration) -> f64 {
+    if time.is_zero() {
+        0.0
+    } else {
+        count as f64 / time.as_secs_f64()
+    }
+}
+
+/// Shared counters behind `ChannelStats`
+#[derive(Debug)]
+pub(crate) struct StatCounters {
+    dropped_lines: AtomicU64,
+    lagged_lines: AtomicU64,
+    full_sends: AtomicU64,
+    commands_sent: AtomicU64,
+    lines_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    oks: AtomicU64,
+    resends: AtomicU64,
+    timeouts: AtomicU64,
+    acknowledged: AtomicU64,
+    ack_latency_micros: AtomicU64,
+    ack_latency_max_micros: AtomicU64,
+    ack_latency_histogram: [AtomicU64; ACK_LATENCY_BUCKETS.len() + 1],
+    started: Instant,
+}
+
+impl Default for StatCounters {
+    fn default() -> Self {
+        Self {
+            dropped_lines: Default::default(),
+            lagged_lines: Default::default(),
+            full_sends: Default::default(),
+            commands_sent: Default::default(),
+            lines_received: Default::default(),
+            bytes_sent: Default::default(),
+            bytes_received: Default::default(),
+            oks: Default::default(),
+            resends: Default::default(),
+            timeouts: Default::default(),
+            acknowledged: Default::default(),
+            ack_latency_micros: Default::default(),
+            ack_latency_max_micros: Default::default(),
+            ack_latency_histogram: Default::default(),
+            started: Instant::now(),
+        }
+    }
+}
+
+impl StatCounters {
+    pub(crate) fn snapshot(&self) -> ChannelStats {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        ChannelStats {
+            dropped_lines: load(&self.dropped_lines),
+            lagged_lines: load(&self.lagged_lines),
+            full_sends: load(&self.full_sends),
+            commands_sent: load(&self.commands_sent),
+            lines_received: load(&self.lines_received),
+            bytes_sent: load(&self.bytes_sent),
+            bytes_received: load(&self.bytes_received),
+            oks: load(&self.oks),
+            resends: load(&self.resends),
+            timeouts: load(&self.timeouts),
+            acknowledged: load(&self.acknowledged),
+            ack_latency_total: Duration::from_micros(load(&self.ack_latency_micros)),
+            ack_latency_max: Duration::from_micros(load(&self.ack_latency_max_micros)),
+            ack_latency_histogram: std::array::from_fn(|bucket| {
+                load(&self.ack_latency_histogram[bucket])
+            }),
+            connected_for: self.started.elapsed(),
+        }
+    }
+
+    /// Count a command written to the device
+    pub(crate) fn sent_command(&self, bytes: usize) {
+        self.commands_sent.fetch_add(1, Ordering::Relaxed);
+        self.sent_bytes(bytes);
+    }
+
+    /// Count bytes written to the device which aren't a new command, like a keepalive probe
+    pub(crate) fn sent_bytes(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Count a line read from the device
+    pub(crate) fn received_line(&self, bytes: usize) {
+        self.lines_received.fetch_add(1, Ordering::Relaxed);
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn ok(&self) {
+        self.oks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a line written again
+    pub(crate) fn resent(&self, bytes: usize) {
+        self.resends.fetch_add(1, Ordering::Relaxed);
+        self.sent_bytes(bytes);
+    }
+
+    pub(crate) fn timed_out(&self) {
+        self.timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a command acknowledged `latency` after it was sent
+    pub(crate) fn acknowledged(&self, latency: Duration) {
+        let micros = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX);
+        self.acknowledged.fetch_add(1, Ordering::Relaxed);
+        self.ack_latency_micros.fetch_add(micros, Ordering::Relaxed);
+        self.ack_latency_max_micros
+            .fetch_max(micros, Ordering::Relaxed);
+        let bucket = ACK_LATENCY_BUCKETS
+            .iter()
+            .position(|bound| latency < *bound)
+            .unwrap_or(ACK_LATENCY_BUCKETS.len());
+        self.ack_latency_histogram[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Account for a line about to be broadcast on a channel holding `capacity` lines
+    pub(crate) fn broadcasting<T>(&self, sender: &broadcast::Sender<T>, capacity: usize) {
+        if sender.len() >= capacity {
+            self.dropped_lines.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Count the lines a receiver missed, if it fell behind
+    pub(crate) fn received<T>(
+        &self,
+        result: Result<T, broadcast::error::RecvError>,
+    ) -> Result<T, broadcast::error::RecvError> {
+        if let Err(broadcast::error::RecvError::Lagged(missed)) = result {
+            self.lagged(missed);
+        }
+        result
+    }
+
+    /// See `received`
+    pub(crate) fn try_received<T>(
+        &self,
+        result: Result<T, broadcast::error::TryRecvError>,
+    ) -> Result<T, broadcast::error::TryRecvError> {
+        if let Err(broadcast::error::TryRecvError::Lagged(missed)) = result {
+            self.lagged(missed);
+        }
+        result
+    }
+
+    pub(crate) fn lagged(&self, missed: u64) {
+        tracing::warn!("Fell {missed} lines behind the printer");
+        self.lagged_lines.fetch_add(missed, Ordering::Relaxed);
+    }
+
+    /// Count a send refused for a full queue
+    pub(crate) fn reserved<T>(
+        &self,
+        result: Result<T, mpsc::error::TrySendError<()>>,
+    ) -> Result<T, mpsc::error::TrySendError<()>> {
+        if let Err(mpsc::error::TrySendError::Full(_)) = result {
+            self.full_sends.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn counts_overflow() {
+        let stats = StatCounters::default();
+        let (sender, mut receiver) = broadcast::channel(2);
+        for line in 0..3 {
+            stats.broadcasting(&sender, 2);
+            sender.send(line).unwrap();
+        }
+        assert!(stats.try_received(receiver.try_recv()).is_err());
+
+        let (sender, _receiver) = mpsc::channel::<()>(1);
+        let _permit = stats.reserved(sender.try_reserve()).unwrap();
+        assert!(stats.reserved(sender.try_reserve()).is_err());
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.dropped_lines, 1);
+        assert_eq!(snapshot.lagged_lines, 1);
+        assert_eq!(snapshot.full_sends, 1);
+    }
+
+    #[test]
+    fn ack_latency() {
+        let stats = StatCounters::default();
+        assert_eq!(stats.snapshot().average_ack_latency(), None);
+        stats.acknowledged(Duration::from_millis(4));
+        stats.acknowledged(Duration::from_millis(100));
+        stats.acknowledged(Duration::from_secs(3));
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.acknowledged, 3);
+        assert_eq!(
+            snapshot.average_ack_latency(),
+            Some(Duration::from_nanos(1_034_666_666))
+        );
+        assert_eq!(snapshot.ack_latency_max, Duration::from_secs(3));
+        assert_eq!(snapshot.ack_latency_histogram, [1, 0, 1, 0, 1]);
+    }
+}
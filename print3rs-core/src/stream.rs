@@ -0,0 +1,166 @@
+//! `futures` `Sink` and `Stream` adapters for a `Socket`, for use with stream combinators
+//! or anything else built on those traits.
+
+use std::{
+    fmt::Debug,
+    pin::Pin,
+    sync::Arc,
+    task::{ready, Context, Poll},
+};
+
+use futures_core::Stream;
+use futures_sink::Sink;
+use print3rs_serializer::Sequenced;
+use serde::Serialize;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+use tokio_util::sync::PollSender;
+
+use crate::{stats::StatCounters, Error, SendContent};
+
+/// Sink of gcode for the printer, see `Socket::sink`.
+///
+/// Lines are sequenced like `Socket::send` and kept in flight until their `ok` the same way,
+/// so they are written again when the printer asks and never fill more than the window,
+/// but nobody is told when they are answered.
+#[derive(Debug)]
+pub struct GcodeSink {
+    sender: PollSender<SendContent>,
+    serializer: Sequenced,
+}
+
+impl GcodeSink {
+    pub(crate) fn new(
+        sender: tokio::sync::mpsc::Sender<SendContent>,
+        serializer: Sequenced,
+    ) -> Self {
+        Self {
+            sender: PollSender::new(sender),
+            serializer,
+        }
+    }
+}
+
+impl<T: Serialize + Debug> Sink<T> for GcodeSink {
+    type Error = Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        self.sender
+            .poll_reserve(cx)
+            .map_err(|_| Error::Disconnected)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, gcode: T) -> Result<(), Error> {
+        let (sequence, bytes) = self.serializer.try_serialize(gcode)?;
+        // a responder no one waits on still keeps the line in flight until its ok
+        let (responder, _) = tokio::sync::oneshot::channel();
+        self.sender
+            .send_item(SendContent::new(bytes, Some(sequence), Some(responder)))
+            .map_err(|_| Error::Disconnected)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        // lines are handed to the communication task as soon as they are sent
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        self.sender.close();
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Stream of every line from the printer, see `Socket::lines`.
+///
+/// Lines missed from falling behind are skipped with a warning.
+#[derive(Debug)]
+pub struct Lines {
+    inner: BroadcastStream<Arc<str>>,
+    stats: Arc<StatCounters>,
+}
+
+impl Lines {
+    pub(crate) fn new(lines: crate::LineStream, stats: Arc<StatCounters>) -> Self {
+        Self {
+            inner: BroadcastStream::new(lines),
+            stats,
+        }
+    }
+}
+
+impl Stream for Lines {
+    type Item = Arc<str>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Arc<str>>> {
+        loop {
+            match ready!(Pin::new(&mut self.inner).poll_next(cx)) {
+                Some(Ok(line)) => return Poll::Ready(Some(line)),
+                Some(Err(BroadcastStreamRecvError::Lagged(missed))) => self.stats.lagged(missed),
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use {
+        crate::{Printer, PrinterOptions},
+        futures_util::{SinkExt, StreamExt},
+        tokio::io::{AsyncBufReadExt, AsyncWriteExt},
+    };
+
+    #[tokio::test]
+    async fn sink_and_stream() {
+        let (device, far_end) = tokio::io::duplex(256);
+        let options = PrinterOptions::new()
+            .identify(false)
+            .reset_line_numbers(false);
+        let printer = Printer::with_options(tokio::io::BufReader::new(device), options);
+        let mut far_end = tokio::io::BufReader::new(far_end);
+        let mut sink = printer.sink().unwrap();
+        let mut lines = printer.lines().unwrap();
+
+        sink.send("M84").await.unwrap();
+        let mut line = String::new();
+        far_end.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "N1M84*62\n");
+
+        far_end.write_all(b"ok\n").await.unwrap();
+        assert_eq!(&*lines.next().await.unwrap(), "ok\n");
+    }
+
+    #[tokio::test]
+    async fn sink_keeps_lines_in_flight() {
+        let (device, far_end) = tokio::io::duplex(256);
+        let options = PrinterOptions::new()
+            .identify(false)
+            .reset_line_numbers(false)
+            .window(1);
+        let printer = Printer::with_options(tokio::io::BufReader::new(device), options);
+        let mut far_end = tokio::io::BufReader::new(far_end);
+        let mut sink = printer.sink().unwrap();
+
+        sink.send("M84").await.unwrap();
+        sink.send("M84").await.unwrap();
+        let mut line = String::new();
+        far_end.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "N1M84*62\n");
+        // the second line waits for the first to be answered
+        line.clear();
+        let waiting = tokio::time::timeout(
+            std::time::Duration::from_millis(50),
+            far_end.read_line(&mut line),
+        )
+        .await;
+        assert!(waiting.is_err());
+
+        far_end.write_all(b"Resend: 1\n").await.unwrap();
+        line.clear();
+        far_end.read_line(&mut line).await.unwrap();
+        assert_eq!(line, "N1M84*62\n");
+        far_end.write_all(b"ok N1\n").await.unwrap();
+        line.clear();
+        far_end.read_line(&mut line).await.unwrap();
+        assert!(line.starts_with("N2M84*"));
+    }
+}
@@ -0,0 +1,220 @@
+//! # print3rs-derive
+//! `#[derive(Gcode)]` for types sent through print3rs-serializer.
+//!
+//! The command is named after the type (or enum variant), and each named field
+//! becomes a parameter using the uppercased first letter of the field name.
+//!
+//! ```ignore
+//! #[derive(Gcode)]
+//! #[gcode(code = "G1")]
+//! struct LinearMove {
+//!     x: Option<f32>,
+//!     y: Option<f32>,
+//!     #[gcode(precision = 5)]
+//!     e: Option<f32>,
+//!     #[gcode(rename = "F")]
+//!     speed: Option<u32>,
+//! }
+//! ```
+//!
+//! Attributes on the type or variant:
+//! * `code = "M104"`: command to send instead of the type name
+//!
+//! Attributes on fields:
+//! * `rename = "S"`: parameter letter to use instead of the first letter of the field
+//! * `precision = 3`: number of decimal places to send a number with
+//! * `flag`: for `bool` fields, send only the letter when true and nothing when false
+//! * `skip`: never send this field
+//!
+//! Fields of type `Option<_>` are only sent when they are `Some`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Field, Fields, LitInt, LitStr, Type};
+
+#[proc_macro_derive(Gcode, attributes(gcode))]
+pub fn derive_gcode(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let code = code(&input.attrs, &input.ident)?;
+            let fields = named_fields(&data.fields)?;
+            let bindings = fields.iter().map(|field| &field.ident);
+            let params = fields.iter().map(param).collect::<syn::Result<Vec<_>>>()?;
+            quote! {
+                let Self { #(#bindings,)* .. } = self;
+                ::serde::ser::SerializeSeq::serialize_element(&mut seq, #code)?;
+                #(#params)*
+            }
+        }
+        Data::Enum(data) => {
+            let mut arms = vec![];
+            for variant in &data.variants {
+                let variant_name = &variant.ident;
+                let code = code(&variant.attrs, variant_name)?;
+                let fields = named_fields(&variant.fields)?;
+                let bindings = fields.iter().map(|field| &field.ident);
+                let params = fields.iter().map(param).collect::<syn::Result<Vec<_>>>()?;
+                arms.push(quote! {
+                    Self::#variant_name { #(#bindings,)* .. } => {
+                        ::serde::ser::SerializeSeq::serialize_element(&mut seq, #code)?;
+                        #(#params)*
+                    }
+                });
+            }
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "Gcode can't be derived for unions",
+            ))
+        }
+    };
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::serde::Serialize for #name #type_generics #where_clause {
+            #[allow(unused_variables)]
+            fn serialize<__S>(&self, serializer: __S) -> ::core::result::Result<__S::Ok, __S::Error>
+            where
+                __S: ::serde::Serializer,
+            {
+                let mut seq = serializer.serialize_seq(::core::option::Option::None)?;
+                #body
+                ::serde::ser::SerializeSeq::end(seq)
+            }
+        }
+    })
+}
+
+/// Command word of a type or variant, from its `code` attribute or its name
+fn code(attrs: &[Attribute], name: &syn::Ident) -> syn::Result<String> {
+    let mut code = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("gcode")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("code") {
+                code = Some(meta.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            } else {
+                Err(meta.error("unknown gcode attribute, expected `code`"))
+            }
+        })?;
+    }
+    Ok(code.unwrap_or_else(|| name.to_string()))
+}
+
+fn named_fields(fields: &Fields) -> syn::Result<Vec<&Field>> {
+    match fields {
+        Fields::Named(named) => Ok(named.named.iter().collect()),
+        Fields::Unit => Ok(vec![]),
+        Fields::Unnamed(unnamed) => Err(syn::Error::new_spanned(
+            unnamed,
+            "Gcode parameters need names, use named fields instead",
+        )),
+    }
+}
+
+#[derive(Default)]
+struct FieldOptions {
+    rename: Option<String>,
+    precision: Option<usize>,
+    flag: bool,
+    skip: bool,
+}
+
+fn field_options(field: &Field) -> syn::Result<FieldOptions> {
+    let mut options = FieldOptions::default();
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("gcode"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                options.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("precision") {
+                options.precision = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+            } else if meta.path.is_ident("flag") {
+                options.flag = true;
+            } else if meta.path.is_ident("skip") {
+                options.skip = true;
+            } else {
+                return Err(meta.error(
+                    "unknown gcode attribute, expected `rename`, `precision`, `flag` or `skip`",
+                ));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(options)
+}
+
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
+
+/// Serialization of a single field, which is bound by reference to its own name
+fn param(field: &&Field) -> syn::Result<TokenStream2> {
+    let options = field_options(field)?;
+    let ident = field.ident.as_ref().expect("only named fields are used");
+    if options.skip {
+        return Ok(quote! {});
+    }
+    let letter = match options.rename {
+        Some(letter) => letter,
+        None => ident
+            .to_string()
+            .trim_start_matches("r#")
+            .chars()
+            .next()
+            .unwrap_or_default()
+            .to_ascii_uppercase()
+            .to_string(),
+    };
+    if options.flag {
+        return Ok(quote! {
+            if *#ident {
+                ::serde::ser::SerializeSeq::serialize_element(&mut seq, #letter)?;
+            }
+        });
+    }
+    let value = match options.precision {
+        Some(precision) => quote! { &::std::format!("{:.*}", #precision, value) },
+        None => quote! { value },
+    };
+    let send = quote! {
+        ::serde::ser::SerializeSeq::serialize_element(&mut seq, #letter)?;
+        ::serde::ser::SerializeSeq::serialize_element(&mut seq, #value)?;
+    };
+    if is_option(&field.ty) {
+        Ok(quote! {
+            if let ::core::option::Option::Some(value) = #ident {
+                #send
+            }
+        })
+    } else {
+        Ok(quote! {
+            let value = #ident;
+            #send
+        })
+    }
+}
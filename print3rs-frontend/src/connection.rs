@@ -0,0 +1,116 @@
+use print3rs_commands::commands::connect::Connection;
+
+/// Kinds of connection a user can choose between
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Auto,
+    Serial,
+    Tcp,
+    Mqtt,
+    Moonraker,
+    OctoPrint,
+    PrusaLink,
+    Duet,
+    Virtual,
+}
+
+impl Protocol {
+    /// Every protocol, in the order they should be offered
+    pub const ALL: [Protocol; 9] = [
+        Protocol::Auto,
+        Protocol::Serial,
+        Protocol::Tcp,
+        Protocol::Mqtt,
+        Protocol::Moonraker,
+        Protocol::OctoPrint,
+        Protocol::PrusaLink,
+        Protocol::Duet,
+        Protocol::Virtual,
+    ];
+
+    /// Protocol used by a connection, `None` for ones the frontends don't offer
+    pub fn from_connection<S>(connection: &Connection<S>) -> Option<Self> {
+        match connection {
+            Connection::Auto(_) => Some(Protocol::Auto),
+            Connection::Serial { .. } => Some(Protocol::Serial),
+            Connection::Tcp { .. } => Some(Protocol::Tcp),
+            Connection::Mqtt { .. } => Some(Protocol::Mqtt),
+            Connection::Moonraker { .. } => Some(Protocol::Moonraker),
+            Connection::OctoPrint { .. } => Some(Protocol::OctoPrint),
+            Connection::PrusaLink { .. } => Some(Protocol::PrusaLink),
+            Connection::Duet { .. } => Some(Protocol::Duet),
+            Connection::Virtual => Some(Protocol::Virtual),
+            _ => None,
+        }
+    }
+
+    /// Name to show the user
+    pub fn label(&self) -> &'static str {
+        match self {
+            Protocol::Auto => "Auto",
+            Protocol::Serial => "Serial",
+            Protocol::Tcp => "TCP/IP",
+            Protocol::Mqtt => "MQTT",
+            Protocol::Moonraker => "Moonraker",
+            Protocol::OctoPrint => "OctoPrint",
+            Protocol::PrusaLink => "PrusaLink",
+            Protocol::Duet => "Duet",
+            Protocol::Virtual => "Virtual",
+        }
+    }
+
+    /// Blank connection details to start filling in after the protocol is picked
+    pub fn empty_connection(&self) -> Connection<String> {
+        match self {
+            Protocol::Auto => Connection::default(),
+            Protocol::Serial => Connection::Serial {
+                port: String::new(),
+                baud: None,
+                dialect: Default::default(),
+            },
+            Protocol::Tcp => Connection::Tcp {
+                hostname: String::new(),
+                port: None,
+            },
+            Protocol::Mqtt => Connection::Mqtt {
+                hostname: String::new(),
+                port: None,
+                in_topic: None,
+                out_topic: None,
+            },
+            Protocol::Moonraker => Connection::Moonraker {
+                hostname: String::new(),
+                port: None,
+            },
+            Protocol::OctoPrint => Connection::OctoPrint {
+                hostname: String::new(),
+                port: None,
+                api_key: String::new(),
+            },
+            Protocol::PrusaLink => Connection::PrusaLink {
+                hostname: String::new(),
+                port: None,
+                api_key: String::new(),
+            },
+            Protocol::Duet => Connection::Duet {
+                hostname: String::new(),
+                port: None,
+                password: None,
+            },
+            Protocol::Virtual => Connection::Virtual,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for protocol in Protocol::ALL {
+            let connection = protocol.empty_connection();
+            assert_eq!(Protocol::from_connection(&connection), Some(protocol));
+        }
+    }
+}
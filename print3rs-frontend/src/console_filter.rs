@@ -0,0 +1,112 @@
+use {print3rs_core::TemperatureReport, regex::Regex};
+
+/// Which lines of printer chatter to keep out of the console, so long prints don't bury what matters
+#[derive(Debug, Clone, Default)]
+pub struct ConsoleFilter {
+    /// Hide acknowledgements like `ok` or `ok N12`
+    pub hide_ok: bool,
+    /// Hide temperature reports, whether asked for with M105 or autoreported
+    pub hide_temperatures: bool,
+    /// Only show lines matching this, if set
+    include: Option<Regex>,
+    /// Hide lines matching this, if set
+    exclude: Option<Regex>,
+}
+
+/// Text of a line without the `[label] ` prefix lines from named printers get
+fn body(line: &str) -> &str {
+    let line = line.trim();
+    line.strip_prefix('[')
+        .and_then(|rest| rest.split_once("] "))
+        .map_or(line, |(_, body)| body.trim_start())
+}
+
+/// Read a pattern typed in by the user, with an empty one meaning no pattern
+fn pattern(input: &str) -> Result<Option<Regex>, regex::Error> {
+    let input = input.trim();
+    if input.is_empty() {
+        Ok(None)
+    } else {
+        Regex::new(input).map(Some)
+    }
+}
+
+impl ConsoleFilter {
+    /// Only show lines matching a regex, or every line if `input` is empty
+    pub fn set_include(&mut self, input: &str) -> Result<(), regex::Error> {
+        self.include = pattern(input)?;
+        Ok(())
+    }
+
+    /// Hide lines matching a regex, or none if `input` is empty
+    pub fn set_exclude(&mut self, input: &str) -> Result<(), regex::Error> {
+        self.exclude = pattern(input)?;
+        Ok(())
+    }
+
+    /// Check if a single line should be shown
+    pub fn shows(&self, line: &str) -> bool {
+        let body = body(line);
+        let is_temperature = TemperatureReport::parse(body).is_some();
+        if self.hide_temperatures && is_temperature {
+            return false;
+        }
+        // `ok T:...` replies are temperatures, left to `hide_temperatures`
+        let is_ok = body == "ok" || (body.starts_with("ok ") && !is_temperature);
+        if self.hide_ok && is_ok {
+            return false;
+        }
+        if self
+            .include
+            .as_ref()
+            .is_some_and(|regex| !regex.is_match(line))
+        {
+            return false;
+        }
+        !self
+            .exclude
+            .as_ref()
+            .is_some_and(|regex| regex.is_match(line))
+    }
+
+    /// The lines of `text` which should be shown, or `None` if every line is hidden
+    pub fn apply(&self, text: &str) -> Option<String> {
+        let shown: Vec<&str> = text.lines().filter(|line| self.shows(line)).collect();
+        (!shown.is_empty()).then(|| shown.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hides_chatter() {
+        let mut filter = ConsoleFilter::default();
+        let chatter = "ok\n[ender] ok N12\nT:200.1 /200.0 B:60.0 /60.0\nok T:199.9 /200.0\necho:busy: processing";
+        assert_eq!(filter.apply(chatter).as_deref(), Some(chatter));
+        filter.hide_ok = true;
+        assert_eq!(
+            filter.apply(chatter).as_deref(),
+            Some("T:200.1 /200.0 B:60.0 /60.0\nok T:199.9 /200.0\necho:busy: processing")
+        );
+        filter.hide_temperatures = true;
+        assert_eq!(
+            filter.apply(chatter).as_deref(),
+            Some("echo:busy: processing")
+        );
+        filter.set_exclude("^echo:busy").unwrap();
+        assert_eq!(filter.apply(chatter), None);
+    }
+
+    #[test]
+    fn patterns() {
+        let mut filter = ConsoleFilter::default();
+        assert!(filter.set_include("(unclosed").is_err());
+        filter.set_include("(?i)error|warning").unwrap();
+        assert!(filter.shows("Error: printer halted"));
+        assert!(!filter.shows("ok"));
+        filter.set_include(" ").unwrap();
+        assert!(filter.shows("ok"));
+    }
+}
@@ -0,0 +1,97 @@
+use {
+    crate::PrintProgress,
+    print3rs_commands::response::{Notice, Response, Source},
+    print3rs_core::Printer,
+    std::sync::{Arc, Mutex},
+};
+
+/// What a frontend should do in reaction to a `Response` from the commander
+#[derive(Debug)]
+pub enum Event {
+    /// Show text in the console, already prefixed with where it came from if that matters
+    Output(Arc<str>),
+    /// Tell the user something went wrong
+    Error(String),
+    /// A printer was found for the named printer, hand it to the commander with `Commander::set_printer_of`
+    Connected(Arc<str>, Printer),
+    /// A print task has made progress
+    Progress(PrintProgress),
+    /// Something happened the user should be told about even if they aren't watching, see `notify`
+    Notify(Source, Notice, Arc<str>),
+    /// Empty the console
+    Clear,
+    /// Close the frontend
+    Quit,
+}
+
+/// Take a printer out of a shared handle, disconnected if it is still shared or poisoned
+pub fn take_printer(printer: Arc<Mutex<Printer>>) -> Printer {
+    Arc::into_inner(printer)
+        .unwrap_or_default()
+        .into_inner()
+        .unwrap_or_default()
+}
+
+/// Prefix every line of `text` with the label of its source, like `[ender] ok`
+pub fn labelled(source: &Source, text: Arc<str>) -> Arc<str> {
+    let Some(label) = source.label() else {
+        return text;
+    };
+    let mut prefixed = String::with_capacity(text.len() + label.len() + 3);
+    for line in text.split_inclusive('\n') {
+        prefixed.push_str(&format!("[{label}] {line}"));
+    }
+    prefixed.into()
+}
+
+impl From<Response> for Event {
+    fn from(response: Response) -> Self {
+        match response {
+            Response::Output(source, s) => Event::Output(labelled(&source, s)),
+            Response::Error(e) => Event::Error(e.0),
+            Response::AutoConnect(name, printer) => Event::Connected(name, take_printer(printer)),
+            Response::Progress {
+                task,
+                lines_sent,
+                total_lines,
+                percent,
+                remaining,
+                elapsed,
+            } => Event::Progress(PrintProgress {
+                task,
+                lines_sent,
+                total_lines,
+                percent,
+                remaining,
+                elapsed,
+            }),
+            Response::Paused { task, reason } => {
+                Event::Output(format!("{task} paused {reason}, use `resume` to carry on\n").into())
+            }
+            Response::Notify(source, notice, message) => Event::Notify(source, notice, message),
+            Response::Clear => Event::Clear,
+            Response::Quit => Event::Quit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn labels_lines() {
+        let printer = Source::Printer("ender".into());
+        assert_eq!(
+            &*labelled(&printer, "ok\nT:200\n".into()),
+            "[ender] ok\n[ender] T:200\n"
+        );
+        let default = Source::Printer(print3rs_commands::commander::DEFAULT_PRINTER.into());
+        assert_eq!(&*labelled(&default, "ok\n".into()), "ok\n");
+        assert_eq!(&*labelled(&Source::Commander, "Done\n".into()), "Done\n");
+        assert_eq!(
+            &*labelled(&Source::Task("temps".into()), "shown\n".into()),
+            "[temps] shown\n"
+        );
+    }
+}
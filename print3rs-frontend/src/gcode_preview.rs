@@ -0,0 +1,282 @@
+use {
+    print3rs_commands::gcode::{parse_line, MachineState, Move, ARC_SEGMENT_LENGTH, X, Y, Z},
+    std::fmt::Write,
+};
+
+/// Heights closer than this are taken to be the same layer, in mm
+const LAYER_TOLERANCE: f32 = 0.001;
+
+const EXTRUDE_COLOR: &str = "#e5484d";
+const TRAVEL_COLOR: &str = "#3e63dd";
+const BELOW_COLOR: &str = "#888888";
+
+/// Border left around the drawing, in pixels
+const MARGIN: f32 = 4.0;
+
+/// A run of connected moves which either all extrude or all travel
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreviewPath {
+    pub extruding: bool,
+    pub points: Vec<[f32; 3]>,
+}
+
+/// Everything the head does from the first extrusion at a height until it extrudes at another
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreviewLayer {
+    pub z: f32,
+    pub paths: Vec<PreviewPath>,
+}
+
+/// Which way the preview looks at the print
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Projection {
+    /// Straight down, showing one layer with the one under it
+    #[default]
+    Top,
+    /// From the front corner, showing every layer up to the selected one
+    Isometric,
+}
+
+/// Paths the head follows through a gcode file, split into layers, to see what a file prints before sending it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GcodePreview {
+    layers: Vec<PreviewLayer>,
+    /// Corners of the box holding every extruding move
+    min: [f32; 3],
+    max: [f32; 3],
+}
+
+impl GcodePreview {
+    /// Follow every move in a gcode file.
+    ///
+    /// Arcs are split into short lines, and moves before the first extrusion like homing are left out.
+    pub fn parse(gcode: &str) -> Self {
+        let mut preview = Self {
+            layers: Vec::new(),
+            min: [f32::INFINITY; 3],
+            max: [f32::NEG_INFINITY; 3],
+        };
+        let mut state = MachineState::default();
+        for line in gcode.lines() {
+            let line = parse_line(line);
+            match state.flatten_arc(&line, ARC_SEGMENT_LENGTH) {
+                Some(segments) => {
+                    for segment in segments {
+                        if let Some(movement) = state.apply(&parse_line(&segment)) {
+                            preview.add(movement);
+                        }
+                    }
+                }
+                None => {
+                    if let Some(movement) = state.apply(&line) {
+                        preview.add(movement);
+                    }
+                }
+            }
+        }
+        if preview.layers.is_empty() {
+            preview.min = [0.0; 3];
+            preview.max = [0.0; 3];
+        }
+        preview
+    }
+
+    fn add(&mut self, movement: Move) {
+        let point = |position: [f32; 4]| [position[X], position[Y], position[Z]];
+        let (from, to) = (point(movement.from), point(movement.to));
+        // retractions and feedrate changes don't go anywhere
+        if from == to {
+            return;
+        }
+        let extruding = movement.is_extruding();
+        if extruding {
+            let same_layer = self
+                .layers
+                .last()
+                .is_some_and(|layer| (layer.z - to[Z]).abs() <= LAYER_TOLERANCE);
+            if !same_layer {
+                self.layers.push(PreviewLayer {
+                    z: to[Z],
+                    paths: Vec::new(),
+                });
+            }
+            for corner in [from, to] {
+                for axis in X..=Z {
+                    self.min[axis] = self.min[axis].min(corner[axis]);
+                    self.max[axis] = self.max[axis].max(corner[axis]);
+                }
+            }
+        }
+        let Some(layer) = self.layers.last_mut() else {
+            return;
+        };
+        match layer.paths.last_mut() {
+            Some(path) if path.extruding == extruding && path.points.last() == Some(&from) => {
+                path.points.push(to)
+            }
+            _ => layer.paths.push(PreviewPath {
+                extruding,
+                points: vec![from, to],
+            }),
+        }
+    }
+
+    pub fn layers(&self) -> &[PreviewLayer] {
+        &self.layers
+    }
+
+    /// Height of the print, from the lowest extrusion to the highest
+    pub fn height(&self) -> f32 {
+        self.max[Z] - self.min[Z]
+    }
+
+    /// Short description like `120 layers, 40.0 x 40.0 x 24.0 mm`
+    pub fn summary(&self) -> String {
+        if self.layers.is_empty() {
+            return "nothing is extruded".to_string();
+        }
+        format!(
+            "{} layers, {:.1} x {:.1} x {:.1} mm",
+            self.layers.len(),
+            self.max[X] - self.min[X],
+            self.max[Y] - self.min[Y],
+            self.height()
+        )
+    }
+
+    /// Draw the print up to `layer`, counting from 0, as an SVG image of the given size.
+    ///
+    /// Extrusion on the selected layer is red and travel blue,
+    /// with the layers below it in grey.
+    pub fn svg(&self, layer: usize, projection: Projection, width: f32, height: f32) -> String {
+        let mut svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+        );
+        let Some(selected) = self.layers.get(layer) else {
+            svg.push_str("</svg>");
+            return svg;
+        };
+        let project = |[x, y, z]: [f32; 3]| match projection {
+            Projection::Top => (x, -y),
+            Projection::Isometric => {
+                let (sin, cos) = std::f32::consts::FRAC_PI_6.sin_cos();
+                ((x - y) * cos, -(x + y) * sin - z)
+            }
+        };
+        // every corner of the bounding box, so the scale stays put while moving through layers
+        let corners = (0..8).map(|corner: usize| {
+            project(std::array::from_fn(|axis| {
+                if corner & (1 << axis) == 0 {
+                    self.min[axis]
+                } else {
+                    self.max[axis]
+                }
+            }))
+        });
+        let (mut left, mut top) = (f32::INFINITY, f32::INFINITY);
+        let (mut right, mut bottom) = (f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for (x, y) in corners {
+            (left, right) = (left.min(x), right.max(x));
+            (top, bottom) = (top.min(y), bottom.max(y));
+        }
+        let scale = ((width - 2.0 * MARGIN) / (right - left).max(f32::EPSILON))
+            .min((height - 2.0 * MARGIN) / (bottom - top).max(f32::EPSILON));
+        let to_pixels = |point| {
+            let (x, y) = project(point);
+            (MARGIN + (x - left) * scale, MARGIN + (y - top) * scale)
+        };
+        let mut draw = |path: &PreviewPath, color: &str, extras: &str| {
+            let mut points = String::new();
+            for point in &path.points {
+                let (x, y) = to_pixels(*point);
+                let _ = write!(points, "{x:.1},{y:.1} ");
+            }
+            let _ = write!(
+                svg,
+                r#"<polyline points="{}" fill="none" stroke="{color}"{extras}/>"#,
+                points.trim_end()
+            );
+        };
+
+        let below = match projection {
+            Projection::Top => layer.saturating_sub(1)..layer,
+            Projection::Isometric => 0..layer,
+        };
+        for path in self.layers[below]
+            .iter()
+            .flat_map(|layer| &layer.paths)
+            .filter(|path| path.extruding)
+        {
+            draw(path, BELOW_COLOR, r#" stroke-opacity="0.5""#);
+        }
+        for path in &selected.paths {
+            if path.extruding {
+                draw(path, EXTRUDE_COLOR, r#" stroke-width="1.5""#);
+            } else {
+                draw(
+                    path,
+                    TRAVEL_COLOR,
+                    r#" stroke-width="0.5" stroke-dasharray="2 2""#,
+                );
+            }
+        }
+        svg.push_str("</svg>");
+        svg
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SQUARES: &str = "\
+G28 ; home
+G90
+M83
+G1 Z0.2 F600
+G0 X10 Y10
+G1 X20 Y10 E1 ; first layer
+G1 X20 Y20 E1
+G1 E-0.8 ; retract
+G0 X10 Y20
+G1 E0.8
+G1 X10 Y10 E1
+G1 Z0.4
+G1 X20 Y10 E1 ; second layer
+G2 X20 Y20 I0 J5 E1
+";
+
+    #[test]
+    fn splits_layers() {
+        let preview = GcodePreview::parse(SQUARES);
+        assert_eq!(preview.layers().len(), 2);
+        let first = &preview.layers()[0];
+        assert_eq!(first.z, 0.2);
+        // extrusion, the travel across, then extrusion again
+        let kinds: Vec<bool> = first.paths.iter().map(|path| path.extruding).collect();
+        assert_eq!(kinds, [true, false, true, false]);
+        assert_eq!(first.paths[0].points.len(), 3);
+        // the arc is split into short lines
+        assert!(preview.layers()[1].paths[0].points.len() > 10);
+        assert_eq!(preview.summary(), "2 layers, 10.0 x 10.0 x 0.2 mm");
+    }
+
+    #[test]
+    fn draws_layers() {
+        let preview = GcodePreview::parse(SQUARES);
+        let top = preview.svg(1, Projection::Top, 100.0, 100.0);
+        assert_eq!(top.matches(BELOW_COLOR).count(), 2);
+        assert_eq!(top.matches(EXTRUDE_COLOR).count(), 1);
+        let first = preview.svg(0, Projection::Isometric, 100.0, 100.0);
+        assert!(!first.contains(BELOW_COLOR));
+        assert_eq!(first.matches(TRAVEL_COLOR).count(), 2);
+        assert_eq!(
+            preview.svg(5, Projection::Top, 100.0, 100.0),
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100" viewBox="0 0 100 100"></svg>"#
+        );
+        assert_eq!(
+            GcodePreview::parse("G28\nM104 S200").summary(),
+            "nothing is extruded"
+        );
+    }
+}
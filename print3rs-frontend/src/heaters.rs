@@ -0,0 +1,115 @@
+use print3rs_core::{Temperature, TemperatureReport};
+
+/// Hottest target that can be typed in, above what any common hotend can take
+pub const MAX_TARGET: f32 = 400.0;
+
+/// Hotend and bed targets to heat up to for a material
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeaterPreset {
+    pub name: &'static str,
+    pub hotend: f32,
+    pub bed: f32,
+}
+
+/// Usual temperatures for common materials, and turning both heaters off
+pub const HEATER_PRESETS: [HeaterPreset; 4] = [
+    HeaterPreset {
+        name: "PLA",
+        hotend: 200.0,
+        bed: 60.0,
+    },
+    HeaterPreset {
+        name: "PETG",
+        hotend: 235.0,
+        bed: 80.0,
+    },
+    HeaterPreset {
+        name: "ABS",
+        hotend: 245.0,
+        bed: 100.0,
+    },
+    HeaterPreset {
+        name: "off",
+        hotend: 0.0,
+        bed: 0.0,
+    },
+];
+
+/// Read a target temperature typed in by the user, in degrees from 0 to `MAX_TARGET`
+pub fn parse_target(input: &str) -> Option<f32> {
+    input
+        .trim()
+        .trim_end_matches("°C")
+        .trim_end()
+        .parse()
+        .ok()
+        .filter(|target| (0.0..=MAX_TARGET).contains(target))
+}
+
+/// Reading and target of a heater like `200.1 / 200`, or `-` if it wasn't reported
+pub fn describe_heater(temperature: Option<&Temperature>) -> String {
+    match temperature {
+        Some(Temperature {
+            current,
+            target: Some(target),
+        }) => format!("{current:.1} / {target:.0}"),
+        Some(Temperature {
+            current,
+            target: None,
+        }) => format!("{current:.1}"),
+        None => "-".to_string(),
+    }
+}
+
+/// Warning to show next to extrude controls when the hotend was last reported colder than `min_temp`.
+///
+/// Nothing is said while the temperature isn't known, the printer will refuse a cold extrusion anyway.
+pub fn cold_extrusion_warning(
+    latest: Option<&TemperatureReport>,
+    min_temp: Option<f32>,
+) -> Option<String> {
+    let min_temp = min_temp?;
+    let hotend = latest?.hotend.as_ref()?;
+    (hotend.current < min_temp).then(|| {
+        format!(
+            "hotend is {:.0}°C, heat it to {min_temp:.0}°C before extruding",
+            hotend.current
+        )
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn targets() {
+        assert_eq!(parse_target(" 215 "), Some(215.0));
+        assert_eq!(parse_target("60°C"), Some(60.0));
+        assert_eq!(parse_target("0"), Some(0.0));
+        assert_eq!(parse_target("-5"), None);
+        assert_eq!(parse_target("500"), None);
+        assert_eq!(parse_target("hot"), None);
+        assert_eq!(
+            describe_heater(Some(&Temperature {
+                current: 200.06,
+                target: Some(200.0)
+            })),
+            "200.1 / 200"
+        );
+        assert_eq!(describe_heater(None), "-");
+    }
+
+    #[test]
+    fn cold_extrusion() {
+        let cold = TemperatureReport::parse("T:24.6 /0.0 B:22.0 /0.0");
+        let hot = TemperatureReport::parse("T:199.0 /200.0 B:60.0 /60.0");
+        assert_eq!(
+            cold_extrusion_warning(cold.as_ref(), Some(170.0)).as_deref(),
+            Some("hotend is 25°C, heat it to 170°C before extruding")
+        );
+        assert_eq!(cold_extrusion_warning(hot.as_ref(), Some(170.0)), None);
+        assert_eq!(cold_extrusion_warning(cold.as_ref(), None), None);
+        assert_eq!(cold_extrusion_warning(None, Some(170.0)), None);
+    }
+}
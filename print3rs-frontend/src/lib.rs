@@ -0,0 +1,28 @@
+//! # print3rs-frontend
+//! State and logic shared by every print3rs user interface,
+//! so each frontend only has to deal with drawing and input.
+
+mod connection;
+mod console_filter;
+mod event;
+mod gcode_preview;
+mod heaters;
+mod notify;
+mod progress;
+mod sparkline;
+mod submit;
+mod temperature_graph;
+
+pub use connection::Protocol;
+pub use console_filter::ConsoleFilter;
+pub use event::{take_printer, Event};
+pub use gcode_preview::{GcodePreview, PreviewLayer, PreviewPath, Projection};
+pub use heaters::{
+    cold_extrusion_warning, describe_heater, parse_target, HeaterPreset, HEATER_PRESETS, MAX_TARGET,
+};
+pub use notify::notify;
+pub use print3rs_commands::{history::History, profiles::Profiles};
+pub use progress::{format_duration, PrintProgress};
+pub use sparkline::{log_sparklines, sparkline};
+pub use submit::{submit, SubmitError};
+pub use temperature_graph::{TemperatureHistory, GRAPH_WINDOWS};
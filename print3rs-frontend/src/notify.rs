@@ -0,0 +1,24 @@
+use print3rs_commands::{config::Notifications, response::Notice};
+
+/// Show a notice as a desktop notification, if `preferences` allow it.
+///
+/// The notification is shown from its own thread since some platforms wait on a reply,
+/// and failing to show one isn't worth bothering the user about.
+pub fn notify(preferences: &Notifications, notice: Notice, message: &str) {
+    if !preferences.enabled(notice) {
+        return;
+    }
+    let summary = match notice {
+        Notice::PrintFinished => "Print finished",
+        Notice::LogError => "Logging stopped",
+        Notice::Disconnected => "Printer disconnected",
+    };
+    let message = message.to_owned();
+    std::thread::spawn(move || {
+        let _ = notify_rust::Notification::new()
+            .appname("print3rs")
+            .summary(summary)
+            .body(&message)
+            .show();
+    });
+}
@@ -0,0 +1,67 @@
+pub use print3rs_commands::eta::format_duration;
+use std::{sync::Arc, time::Duration};
+
+/// Latest progress reported by a print task
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrintProgress {
+    pub task: Arc<str>,
+    pub lines_sent: usize,
+    pub total_lines: usize,
+    /// How much of the file has been sent, from 0 to 100
+    pub percent: f32,
+    /// Estimated time left, if there is enough to go on yet
+    pub remaining: Option<Duration>,
+    /// Time since the print started
+    pub elapsed: Duration,
+}
+
+impl PrintProgress {
+    pub fn is_finished(&self) -> bool {
+        self.lines_sent >= self.total_lines
+    }
+
+    /// Short description like `benchy.gcode 42% 1h05m left`
+    pub fn summary(&self) -> String {
+        let percent = self.percent.floor();
+        match self.remaining {
+            Some(remaining) => format!(
+                "{} {percent}% {} left",
+                self.task,
+                format_duration(remaining)
+            ),
+            None => format!("{} {percent}%", self.task),
+        }
+    }
+
+    /// Times for showing next to a progress bar, like `12m30s elapsed, 1h05m left`
+    pub fn times(&self) -> String {
+        let elapsed = format_duration(self.elapsed);
+        match self.remaining {
+            Some(remaining) => format!("{elapsed} elapsed, {} left", format_duration(remaining)),
+            None => format!("{elapsed} elapsed"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn summaries() {
+        let mut progress = PrintProgress {
+            task: Arc::from("benchy.gcode"),
+            lines_sent: 421,
+            total_lines: 1000,
+            percent: 42.1,
+            remaining: Some(Duration::from_secs(3900)),
+            elapsed: Duration::from_secs(750),
+        };
+        assert_eq!(progress.summary(), "benchy.gcode 42% 1h05m left");
+        assert_eq!(progress.times(), "12m30s elapsed, 1h05m left");
+        progress.remaining = None;
+        assert_eq!(progress.summary(), "benchy.gcode 42%");
+        assert_eq!(progress.times(), "12m30s elapsed");
+        assert!(!progress.is_finished());
+    }
+}
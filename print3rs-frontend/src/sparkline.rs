@@ -0,0 +1,56 @@
+use print3rs_commands::tasks::Tasks;
+
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Draw values as a row of bars scaled between their lowest and highest value
+pub fn sparkline(values: impl IntoIterator<Item = f32> + Clone) -> String {
+    let (min, max) = values
+        .clone()
+        .into_iter()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), value| {
+            (min.min(value), max.max(value))
+        });
+    let range = max - min;
+    values
+        .into_iter()
+        .map(|value| {
+            if range > 0.0 {
+                let level = ((value - min) / range * (BARS.len() - 1) as f32).round();
+                BARS[level as usize]
+            } else {
+                BARS[BARS.len() / 2]
+            }
+        })
+        .collect()
+}
+
+/// A sparkline and latest value for every field of every running log task, sorted by task name
+pub fn log_sparklines(tasks: &Tasks) -> Vec<String> {
+    let mut lines = vec![];
+    for (name, task) in tasks {
+        let Some(recent) = &task.recent else {
+            continue;
+        };
+        for (field, values) in recent.lock().unwrap().iter() {
+            let Some(latest) = values.back() else {
+                continue;
+            };
+            let graph = sparkline(values.iter().copied());
+            lines.push(format!("{name} {field} {graph} {latest:.1}"));
+        }
+    }
+    lines.sort();
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn scaled_bars() {
+        assert_eq!(sparkline([0.0, 1.0, 2.0, 7.0]), "▁▂▃█");
+        assert_eq!(sparkline([20.0, 20.0]), "▅▅");
+        assert_eq!(sparkline([]), "");
+    }
+}
@@ -0,0 +1,96 @@
+use {
+    print3rs_commands::{
+        commander::Commander,
+        commands::{parse_command_line, SyntaxError},
+    },
+    std::fmt::Display,
+};
+
+/// Reason a line submitted by the user was not run
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubmitError {
+    /// The line could not be parsed, possibly because of a typo of `suggestion`
+    Syntax {
+        error: SyntaxError,
+        suggestion: Option<String>,
+    },
+    /// The command was understood but could not be carried out
+    Dispatch(String),
+}
+
+impl SubmitError {
+    /// A line of `^` pointing at the problem, to show under the submitted line
+    pub fn underline(&self) -> Option<String> {
+        match self {
+            SubmitError::Syntax { error, .. } => Some(format!(
+                "{:>start$}{:^<len$}",
+                "",
+                "^",
+                start = error.span.start,
+                len = error.span.len().max(1)
+            )),
+            SubmitError::Dispatch(_) => None,
+        }
+    }
+}
+
+impl Display for SubmitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubmitError::Syntax {
+                error,
+                suggestion: Some(suggestion),
+            } => write!(f, "{error}, did you mean `{suggestion}`?"),
+            SubmitError::Syntax { error, .. } => write!(f, "{error}"),
+            SubmitError::Dispatch(e) => f.write_str(e),
+        }
+    }
+}
+
+impl std::error::Error for SubmitError {}
+
+/// Parse and run a line typed by the user, remembering it in the commander's history if it ran
+pub fn submit(commander: &mut Commander, line: &str) -> Result<(), SubmitError> {
+    let command = match parse_command_line(line) {
+        Ok(command) => command,
+        Err(error) => {
+            let suggestion = commander.suggest(line, &error).map(str::to_owned);
+            return Err(SubmitError::Syntax { error, suggestion });
+        }
+    };
+    commander
+        .dispatch(command)
+        .map_err(|e| SubmitError::Dispatch(e.0))?;
+    commander.history.push(line);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn typo() {
+        let mut commander = Commander::new();
+        let error = submit(&mut commander, "conect serial COM3").unwrap_err();
+        assert_eq!(error.underline().as_deref(), Some("^^^^^^"));
+        assert!(error.to_string().ends_with("did you mean `connect`?"));
+        assert!(commander.history.is_empty());
+    }
+
+    #[test]
+    fn remembered() {
+        let mut commander = Commander::new();
+        let _responses = commander.subscribe_responses();
+        submit(&mut commander, "version").unwrap();
+        assert_eq!(commander.history.entries(), ["version"]);
+    }
+
+    #[test]
+    fn not_connected() {
+        let mut commander = Commander::new();
+        let error = submit(&mut commander, "G28").unwrap_err();
+        assert!(matches!(error, SubmitError::Dispatch(_)));
+        assert!(error.underline().is_none());
+    }
+}
@@ -0,0 +1,218 @@
+use {
+    print3rs_core::{Temperature, TemperatureReport},
+    std::{
+        collections::VecDeque,
+        fmt::Write,
+        time::{Duration, Instant},
+    },
+};
+
+/// Spans of time the graph can show, shortest first
+pub const GRAPH_WINDOWS: [Duration; 4] = [
+    Duration::from_secs(60),
+    Duration::from_secs(5 * 60),
+    Duration::from_secs(15 * 60),
+    Duration::from_secs(30 * 60),
+];
+
+/// Degrees between the graph's grid lines
+const GRID_STEP: f32 = 50.0;
+
+const HOTEND_COLOR: &str = "#e5484d";
+const BED_COLOR: &str = "#3e63dd";
+
+/// Temperatures received over time, drawn as a graph of each heater's reading and target
+#[derive(Debug, Clone)]
+pub struct TemperatureHistory {
+    started: Instant,
+    /// Reports with the time since `started` they arrived, oldest first
+    samples: VecDeque<(Duration, TemperatureReport)>,
+    window: Duration,
+}
+
+impl Default for TemperatureHistory {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            samples: VecDeque::new(),
+            window: GRAPH_WINDOWS[1],
+        }
+    }
+}
+
+impl TemperatureHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How far back the graph goes
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    pub fn set_window(&mut self, window: Duration) {
+        self.window = window;
+    }
+
+    /// Add a report which just arrived
+    pub fn record(&mut self, report: TemperatureReport) {
+        self.record_at(self.started.elapsed(), report)
+    }
+
+    /// Add a report which arrived `at` after the history was made.
+    ///
+    /// Reports older than the longest window are forgotten.
+    pub fn record_at(&mut self, at: Duration, report: TemperatureReport) {
+        self.samples.push_back((at, report));
+        let longest = GRAPH_WINDOWS[GRAPH_WINDOWS.len() - 1];
+        while self
+            .samples
+            .front()
+            .is_some_and(|(oldest, _)| at.saturating_sub(*oldest) > longest)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// The most recent report, if any arrived yet
+    pub fn latest(&self) -> Option<&TemperatureReport> {
+        self.samples.back().map(|(_, report)| report)
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Short description of the latest temperatures like `hotend 200.1/200 bed 60.0/60`
+    pub fn summary(&self) -> String {
+        let Some(latest) = self.latest() else {
+            return "no temperatures yet".to_string();
+        };
+        let describe = |name, temperature: &Option<Temperature>| {
+            temperature
+                .as_ref()
+                .map(|temperature| match temperature.target {
+                    Some(target) => format!("{name} {:.1}/{target:.0}", temperature.current),
+                    None => format!("{name} {:.1}", temperature.current),
+                })
+        };
+        [
+            describe("hotend", &latest.hotend),
+            describe("bed", &latest.bed),
+            describe("chamber", &latest.chamber),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ")
+    }
+
+    /// Draw the window leading up to now as an SVG image of the given size
+    pub fn svg(&self, width: f32, height: f32) -> String {
+        self.svg_at(self.started.elapsed(), width, height)
+    }
+
+    /// Draw the window leading up to `now` as an SVG image of the given size.
+    ///
+    /// Readings are solid lines and targets dashed, hotend in red and bed in blue,
+    /// over grid lines every 50 degrees.
+    pub fn svg_at(&self, now: Duration, width: f32, height: f32) -> String {
+        let start = now.saturating_sub(self.window);
+        let visible: Vec<_> = self.samples.iter().filter(|(at, _)| *at >= start).collect();
+        let hottest = visible
+            .iter()
+            .flat_map(|(_, report)| [&report.hotend, &report.bed])
+            .flatten()
+            .flat_map(|temperature| [Some(temperature.current), temperature.target])
+            .flatten()
+            .fold(0.0, f32::max);
+        let top = ((hottest / GRID_STEP).ceil() * GRID_STEP).max(GRID_STEP);
+        let x = |at: Duration| {
+            width * (at.saturating_sub(start).as_secs_f32() / self.window.as_secs_f32())
+        };
+        let y = |degrees: f32| height * (1.0 - degrees / top);
+
+        let mut svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+        );
+        let mut degrees = GRID_STEP;
+        while degrees < top + 1.0 {
+            let line = y(degrees);
+            let _ = write!(
+                svg,
+                r##"<line x1="0" y1="{line:.1}" x2="{width}" y2="{line:.1}" stroke="#888888" stroke-opacity="0.4"/><text x="2" y="{:.1}" font-size="10" fill="#888888">{degrees}</text>"##,
+                line + 11.0
+            );
+            degrees += GRID_STEP;
+        }
+        let heaters: [(fn(&TemperatureReport) -> Option<&Temperature>, &str); 2] = [
+            (|report| report.hotend.as_ref(), HOTEND_COLOR),
+            (|report| report.bed.as_ref(), BED_COLOR),
+        ];
+        for (heater, color) in heaters {
+            let mut current = String::new();
+            let mut target = String::new();
+            for (at, report) in &visible {
+                let Some(temperature) = heater(report) else {
+                    continue;
+                };
+                let _ = write!(current, "{:.1},{:.1} ", x(*at), y(temperature.current));
+                if let Some(set) = temperature.target {
+                    let _ = write!(target, "{:.1},{:.1} ", x(*at), y(set));
+                }
+            }
+            for (points, dashes) in [(target, r#" stroke-dasharray="4 3""#), (current, "")] {
+                if !points.is_empty() {
+                    let _ = write!(
+                        svg,
+                        r#"<polyline points="{}" fill="none" stroke="{color}" stroke-width="1.5"{dashes}/>"#,
+                        points.trim_end()
+                    );
+                }
+            }
+        }
+        svg.push_str("</svg>");
+        svg
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn report(line: &str) -> TemperatureReport {
+        TemperatureReport::parse(line).unwrap()
+    }
+
+    #[test]
+    fn forgets_old_reports() {
+        let mut history = TemperatureHistory::new();
+        history.record_at(Duration::ZERO, report("T:20.0 /0.0 B:20.0 /0.0"));
+        history.record_at(Duration::from_secs(10), report("T:25.0 /200.0 B:20.0 /0.0"));
+        assert_eq!(history.summary(), "hotend 25.0/200 bed 20.0/0");
+        history.record_at(
+            Duration::from_secs(30 * 60 + 5),
+            report("T:200.0 /200.0 B:60.0 /60.0"),
+        );
+        assert_eq!(history.samples.len(), 2);
+        history.clear();
+        assert_eq!(history.summary(), "no temperatures yet");
+    }
+
+    #[test]
+    fn draws_window() {
+        let mut history = TemperatureHistory::new();
+        history.set_window(Duration::from_secs(100));
+        history.record_at(Duration::ZERO, report("T:20.0 /0.0"));
+        history.record_at(Duration::from_secs(100), report("T:50.0 /200.0"));
+        history.record_at(Duration::from_secs(200), report("T:100.0 /200.0"));
+        let svg = history.svg_at(Duration::from_secs(200), 100.0, 200.0);
+        // the first report is out of the window, and the scale tops out at the hottest target
+        assert!(svg.contains(
+            r##"<polyline points="0.0,150.0 100.0,100.0" fill="none" stroke="#e5484d""##
+        ));
+        assert!(svg.contains(r#"<polyline points="0.0,0.0 100.0,0.0""#));
+        assert_eq!(svg.matches("<line").count(), 4);
+        assert!(!svg.contains(BED_COLOR));
+    }
+}
@@ -0,0 +1,108 @@
+## Menus
+
+file = File
+print = Print
+save = Save
+clear = Clear
+quit = Quit
+macros = Macros
+edit-macros = Edit macros
+tasks = Tasks
+
+## Connecting
+
+printer-port = printer port
+host-port = hostname:port
+api-key = API key
+password = password
+in-topic = in topic
+out-topic = out topic
+protocol = Protocol:
+profile = Profile:
+saved-connection = saved connection
+connect = connect
+disconnect = disconnect
+
+## Printer tabs
+
+new-printer = new printer
+add = add
+
+## Console
+
+send = send
+command-placeholder = type `help` for list of commands
+hide-ok = hide ok
+show-ok = show ok
+hide-temperatures = hide temperatures
+show-temperatures = show temperatures
+include-lines = only lines matching
+exclude-lines = hide lines matching
+
+## Jogging
+
+home = home
+keys-on = keys on
+keys-off = keys off
+fan = fan
+off = off
+extrude = extrude
+retract = retract
+mm = mm
+mm-at = mm at
+mm-per-min = mm/min
+extrude-input-error = Enter a length in mm and a feedrate in mm/min above 0
+
+## Heaters
+
+hotend = hotend
+bed = bed
+set = set
+temperature-input-error = Enter a temperature from 0 to {$max}°C
+
+## Temperature graph
+
+graph-window = {$minutes}m
+
+## SD card
+
+sd-card = SD card ({$files} files)
+refresh = refresh
+delete = delete
+sd-list-error = Could not list SD card: {$error}
+
+## Tasks and progress
+
+task-list = Tasks ({$tasks})
+pause = pause
+resume = resume
+stop = stop
+cancel = cancel
+
+## Print preview
+
+layer = layer {$layer} of {$layers} at {$height} mm
+top-view = top
+isometric-view = 3D
+close = close
+read-error = Could not read {$file}: {$error}
+
+## Position readout
+
+position = Position
+
+## Macro editor
+
+macro-name = macro name
+new = new
+step-placeholder = gcode or macro
+remove = remove
+add-step = add step
+macro-needs-name = give the macro a name
+macro-name-spaces = macro names can't have spaces
+macro-needs-step = add at least one step
+
+## Lost connections
+
+connection-lost = Lost the connection to {$printer}
+reconnect = Reconnect
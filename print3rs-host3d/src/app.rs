@@ -1,24 +1,52 @@
 use cosmic::{
     app::Core,
-    iced::Subscription,
+    iced::{futures::stream, Subscription},
     prelude::*,
     widget::{self, combo_box::State as ComboState, toaster, Toast, Toasts},
     Application, Command,
 };
 use {
-    crate::components, print3rs_commands::commander::Commander, print3rs_core::Printer,
-    std::sync::Arc,
+    crate::components::{Console, MacroEditor, Preview, PrinterTab},
+    print3rs_commands::commands::{
+        connect::Connection,
+        sd::{self, SdFile},
+    },
+};
+use {
+    crate::{components, fl},
+    print3rs_commands::{
+        commander::Commander,
+        response::{Notice, Source},
+    },
+    print3rs_core::{
+        gcode::{
+            AutoreportPosition, FanOff, Home, ReportPosition, ReportTemperatures, SetBedTemp,
+            SetFanSpeed, SetHotendTemp,
+        },
+        Capability, Position, Printer,
+    },
+    print3rs_frontend::{
+        notify, parse_target, submit, GcodePreview, History, PrintProgress, Profiles,
+        TemperatureHistory, HEATER_PRESETS, MAX_TARGET,
+    },
+    std::{
+        collections::BTreeMap,
+        sync::Arc,
+        time::{Duration, Instant},
+    },
 };
-use {crate::components::Console, print3rs_commands::commands::connect::Connection};
 
 use tokio_serial::available_ports;
 use tokio_stream::{wrappers::BroadcastStream, StreamExt};
 
-use winnow::prelude::*;
-
 use rfd::AsyncFileDialog;
 
-use crate::messages::{JogMove, Message};
+use crate::messages::{Heater, JogMove, Message};
+
+/// How often the selected printer is asked for its temperatures, to keep the graph going
+const TEMPERATURE_POLL: Duration = Duration::from_secs(2);
+/// How often the position readout is brought up to date, with M114 unless the firmware autoreports it
+const POSITION_POLL: Duration = Duration::from_secs(1);
 
 pub(crate) struct App {
     pub(crate) cosmic: Core,
@@ -28,6 +56,33 @@ pub(crate) struct App {
     pub(crate) console: Console,
     pub(crate) toasts: Toasts<Message>,
     pub(crate) jog_scale: f32,
+    /// Whether jog keys move the printer
+    pub(crate) keyboard_jog: bool,
+    /// Latest progress of the running print, if any
+    pub(crate) progress: Option<PrintProgress>,
+    pub(crate) temperatures: TemperatureHistory,
+    /// Latest position the printer reported, and when it arrived
+    pub(crate) position: Option<(Instant, Position)>,
+    /// Target temperatures being typed in, not yet sent
+    pub(crate) hotend_input: String,
+    pub(crate) bed_input: String,
+    /// Part cooling fan speed in percent, as last picked
+    pub(crate) fan_percent: f32,
+    /// Filament length and feedrate for the extrude buttons, as typed in
+    pub(crate) extrude_length: String,
+    pub(crate) extrude_feedrate: String,
+    /// Files on the printer's SD card, as last listed
+    pub(crate) sd_files: Vec<SdFile>,
+    /// Macro being edited, shown instead of the console
+    pub(crate) macro_editor: Option<MacroEditor>,
+    /// File picked to print, shown instead of the console until it is printed
+    pub(crate) preview: Option<Preview>,
+    /// Printer whose tab the fields above belong to, following the commander's selected printer
+    pub(crate) tab: String,
+    /// State of the other printers' tabs, by printer name
+    pub(crate) tabs: BTreeMap<String, PrinterTab>,
+    /// Name for a new printer tab, as typed in
+    pub(crate) new_tab: String,
 }
 
 impl Application for App {
@@ -44,18 +99,49 @@ impl Application for App {
             .map(|port| port.port_name)
             .collect();
         ports.push("auto".to_string());
-        (
-            Self {
-                cosmic: core,
-                ports: ComboState::new(ports),
-                connection: Connection::Auto,
-                commander: Default::default(),
-                console: Default::default(),
-                toasts: Toasts::new(Message::PopToast),
-                jog_scale: 10.0,
-            },
-            Command::none(),
-        )
+        let mut commander = Commander::new();
+        commander.history = History::load_default();
+        commander.profiles = Profiles::load_default();
+        let config = commander.load_default_config();
+        let extrude_feedrate = commander.extrude_options().feedrate.to_string();
+        let mut console = Console::default();
+        console.update_command_state(&commander.history);
+        let tab = commander.printer_name().to_owned();
+        let mut app = Self {
+            cosmic: core,
+            ports: ComboState::new(ports),
+            connection: Connection::default(),
+            commander,
+            console,
+            toasts: Toasts::new(Message::PopToast),
+            jog_scale: 10.0,
+            keyboard_jog: false,
+            progress: None,
+            temperatures: TemperatureHistory::new(),
+            position: None,
+            hotend_input: String::new(),
+            bed_input: String::new(),
+            fan_percent: 0.0,
+            extrude_length: "5".to_string(),
+            extrude_feedrate,
+            sd_files: Vec::new(),
+            macro_editor: None,
+            preview: None,
+            tab,
+            tabs: BTreeMap::new(),
+            new_tab: String::new(),
+        };
+        let config_error = match config {
+            Ok(()) => Command::none(),
+            Err(e) => app
+                .toasts
+                .push(Toast::new(e.to_string()))
+                .map(cosmic::app::Message::App),
+        };
+        // run once the console is listening for responses, so the script's output shows up
+        let init_script =
+            cosmic::command::message(cosmic::app::Message::App(Message::RunInitScript));
+        (app, Command::batch([config_error, init_script]))
     }
 
     fn core(&self) -> &Core {
@@ -75,13 +161,24 @@ impl Application for App {
         let responses = self.commander.subscribe_responses();
         let response_stream =
             BroadcastStream::new(responses).map(|response| Message::from(response.unwrap()));
-        cosmic::iced::subscription::run_with_id(
+        let responses = cosmic::iced::subscription::run_with_id(
             std::any::TypeId::of::<PrinterResponseSubscription>(),
             response_stream,
-        )
+        );
+        let mut subscriptions = vec![
+            responses,
+            self.temperature_subscription(),
+            self.position_subscription(),
+        ];
+        if self.keyboard_jog && self.commander.printer().is_connected() {
+            subscriptions.push(cosmic::iced::keyboard::on_key_press(jog_key));
+        }
+        Subscription::batch(subscriptions)
     }
 
     fn update(&mut self, message: Self::Message) -> Command<cosmic::app::Message<Self::Message>> {
+        // `use` on the console switches printers too
+        self.sync_tab();
         match message {
             Message::Jog(JogMove { x, y, z }) => {
                 if let Err(msg) = self
@@ -118,39 +215,31 @@ impl Application for App {
                 Command::none()
             }
             Message::SubmitCommand => {
-                let command_string = &mut self.console.command;
-                if command_string.is_empty() {
+                if self.console.command.is_empty() {
                     return Command::none();
                 }
-                if let Ok(command) =
-                    print3rs_commands::commands::parse_command.parse(command_string)
-                {
-                    if let Err(msg) = self.commander.dispatch(command) {
-                        return self
-                            .toasts
-                            .push(Toast::new(msg.0))
-                            .map(cosmic::app::Message::App);
-                    }
-                    if !self.console.command_history.contains(command_string) {
-                        self.console
-                            .command_history
-                            .push_back(command_string.clone());
-                        if self.console.command_history.len() > 1000 {
-                            self.console.command_history.pop_front();
-                        }
-                        self.console.command_history.make_contiguous();
-                        self.console.command_state =
-                            ComboState::new(self.console.command_history.as_slices().0.to_owned());
-                    }
-                    command_string.clear();
-                } else {
+                let history_len = self.commander.history.len();
+                if let Err(e) = submit(&mut self.commander, &self.console.command) {
                     return self
                         .toasts
-                        .push(Toast::new("Could not parse command"))
+                        .push(Toast::new(e.to_string()))
                         .map(cosmic::app::Message::App);
                 }
+                if self.commander.history.len() != history_len {
+                    self.console.update_command_state(&self.commander.history);
+                }
+                self.console.command.clear();
                 Command::none()
             }
+            Message::RunInitScript => {
+                if let Err(msg) = self.commander.run_init_script() {
+                    self.toasts
+                        .push(Toast::new(msg.0))
+                        .map(cosmic::app::Message::App)
+                } else {
+                    Command::none()
+                }
+            }
             Message::ProcessCommand(command) => {
                 if let Err(msg) = self.commander.dispatch(&command) {
                     self.toasts
@@ -161,20 +250,58 @@ impl Application for App {
                 }
             }
             Message::ConsoleAppend(s) => {
-                use widget::text_editor::{Action, Edit};
-                for c in s.chars() {
-                    let action = Action::Edit(Edit::Insert(c));
-                    self.console.output.perform(action)
+                self.console.append(&s);
+                Command::none()
+            }
+            Message::PrinterOutput(name, s) => {
+                if *name == self.tab {
+                    self.console.append(&s);
+                } else {
+                    let tab = self
+                        .tabs
+                        .entry(name.to_string())
+                        .or_insert_with(|| PrinterTab::new(&self.commander));
+                    tab.console.append(&s);
+                }
+                Command::none()
+            }
+            Message::SelectTab(name) => {
+                if let Err(msg) =
+                    self.commander
+                        .dispatch(print3rs_commands::commands::Command::Use(Some(
+                            name.as_str(),
+                        )))
+                {
+                    return self
+                        .toasts
+                        .push(Toast::new(msg.0))
+                        .map(cosmic::app::Message::App);
                 }
-                self.console.output.perform(Action::Edit(Edit::Enter));
+                self.sync_tab();
                 Command::none()
             }
-            Message::AutoConnectComplete(a_printer) => {
-                let printer = Arc::into_inner(a_printer)
-                    .unwrap_or_default()
-                    .into_inner()
-                    .unwrap_or_default();
-                self.commander.set_printer(printer);
+            Message::NewTabName(name) => {
+                self.new_tab = name;
+                Command::none()
+            }
+            Message::AddTab => {
+                let name = self.new_tab.trim().to_owned();
+                if name.is_empty() || name.contains(char::is_whitespace) {
+                    return Command::none();
+                }
+                self.commander.use_printer(&name);
+                self.sync_tab();
+                self.new_tab.clear();
+                Command::none()
+            }
+            Message::FilterConsole(change) => {
+                self.console.change_filter(change);
+                Command::none()
+            }
+            Message::AutoConnectComplete(name, slot) => {
+                if let Some(printer) = slot.lock().ok().and_then(|mut slot| slot.take()) {
+                    self.commander.set_printer_of(&name, printer);
+                }
                 Command::none()
             }
             Message::ClearConsole => {
@@ -189,14 +316,106 @@ impl Application for App {
                     .set_directory(directories_next::BaseDirs::new().unwrap().home_dir())
                     .pick_file(),
                 |f| match f {
-                    Some(file) => cosmic::app::Message::App(Message::ProcessCommand(
-                        print3rs_commands::commands::Command::Print(
-                            file.path().to_string_lossy().into_owned(),
-                        ),
-                    )),
+                    Some(file) => cosmic::app::Message::App(Message::OpenPreview(file.into())),
                     None => cosmic::app::Message::App(Message::NoOp),
                 },
             ),
+            Message::OpenPreview(path) => Command::perform(
+                async move {
+                    let preview = tokio::fs::read_to_string(&path)
+                        .await
+                        .map(|gcode| Arc::new(GcodePreview::parse(&gcode)))
+                        .map_err(|e| e.to_string());
+                    (path, preview)
+                },
+                |(path, preview)| cosmic::app::Message::App(Message::PreviewLoaded(path, preview)),
+            ),
+            Message::PreviewLoaded(path, Ok(preview)) => {
+                self.preview = Some(Preview::new(path, preview));
+                Command::none()
+            }
+            Message::PreviewLoaded(path, Err(msg)) => self
+                .toasts
+                .push(Toast::new(fl!(
+                    "read-error",
+                    file = path.display().to_string(),
+                    error = msg
+                )))
+                .map(cosmic::app::Message::App),
+            Message::PreviewLayer(layer) => {
+                if let Some(preview) = &mut self.preview {
+                    preview.layer = layer as usize;
+                }
+                Command::none()
+            }
+            Message::PreviewProjection(projection) => {
+                if let Some(preview) = &mut self.preview {
+                    preview.projection = projection;
+                }
+                Command::none()
+            }
+            Message::PrintPreviewed => {
+                let Some(preview) = self.preview.take() else {
+                    return Command::none();
+                };
+                if let Err(msg) =
+                    self.commander
+                        .dispatch(print3rs_commands::commands::Command::Print(
+                            &*preview.path.to_string_lossy(),
+                            Default::default(),
+                        ))
+                {
+                    self.toasts
+                        .push(Toast::new(msg.0))
+                        .map(cosmic::app::Message::App)
+                } else {
+                    Command::none()
+                }
+            }
+            Message::Notify(source, notice, message) => {
+                notify(&self.commander.config().notifications, notice, &message);
+                // with reconnecting on the commander opens the connection again by itself
+                let Source::Printer(name) = source else {
+                    return Command::none();
+                };
+                if notice != Notice::Disconnected
+                    || self.commander.reconnects()
+                    || *name != *self.commander.printer_name()
+                {
+                    return Command::none();
+                }
+                let Some(connection) = self.commander.last_connection().cloned() else {
+                    return Command::none();
+                };
+                self.toasts
+                    .push(
+                        Toast::new(fl!("connection-lost", printer = name.to_string()))
+                            .action(fl!("reconnect"), move |id| {
+                                Message::Reconnect(name.clone(), connection.clone(), id)
+                            }),
+                    )
+                    .map(cosmic::app::Message::App)
+            }
+            Message::Reconnect(name, connection, toast) => {
+                self.toasts.remove(toast);
+                let reconnect = print3rs_commands::commands::Command::On(
+                    &*name,
+                    Box::new(print3rs_commands::commands::Command::Connect(
+                        connection.to_borrowed(),
+                    )),
+                );
+                if let Err(msg) = self.commander.dispatch(reconnect) {
+                    self.toasts
+                        .push(Toast::new(msg.0))
+                        .map(cosmic::app::Message::App)
+                } else {
+                    Command::none()
+                }
+            }
+            Message::ClosePreview => {
+                self.preview = None;
+                Command::none()
+            }
             Message::SaveDialog => Command::perform(
                 AsyncFileDialog::new()
                     .set_directory(directories_next::BaseDirs::new().unwrap().home_dir())
@@ -230,18 +449,34 @@ impl Application for App {
                 self.jog_scale = scale;
                 Command::none()
             }
+            Message::KeyboardJog(on) => {
+                self.keyboard_jog = on;
+                Command::none()
+            }
+            Message::KeyJog(direction) => {
+                if !self.keyboard_jog || !self.commander.printer().is_connected() {
+                    return Command::none();
+                }
+                let scale = self.jog_scale.round().max(1.0);
+                self.update(Message::Jog(direction.scaled(scale)))
+            }
             Message::Home(axis) => {
-                let arg = match axis {
-                    crate::messages::MoveAxis::X => "X",
-                    crate::messages::MoveAxis::Y => "Y",
-                    crate::messages::MoveAxis::Z => "Z",
-                    crate::messages::MoveAxis::All => "",
+                let home = match axis {
+                    crate::messages::MoveAxis::X => Home {
+                        x: true,
+                        ..Home::default()
+                    },
+                    crate::messages::MoveAxis::Y => Home {
+                        y: true,
+                        ..Home::default()
+                    },
+                    crate::messages::MoveAxis::Z => Home {
+                        z: true,
+                        ..Home::default()
+                    },
+                    crate::messages::MoveAxis::All => Home::all(),
                 };
-                if let Err(msg) = self
-                    .commander
-                    .printer()
-                    .try_send_unsequenced(format!("G28{arg}"))
-                {
+                if let Err(msg) = self.commander.printer().try_send_unsequenced(home) {
                     self.toasts
                         .push(Toast::new(msg.to_string()))
                         .map(cosmic::app::Message::App)
@@ -249,30 +484,20 @@ impl Application for App {
                     Command::none()
                 }
             }
-            Message::SelectProtocol(proto) => {
-                self.connection = match proto {
-                    components::Protocol::Auto => Connection::Auto,
-                    components::Protocol::Serial => Connection::Serial {
-                        port: "".to_string(),
-                        baud: None,
-                    },
-                    components::Protocol::Tcp => Connection::Tcp {
-                        hostname: "".to_string(),
-                        port: None,
-                    },
-                    components::Protocol::Mqtt => Connection::Mqtt {
-                        hostname: "".to_string(),
-                        port: None,
-                        in_topic: None,
-                        out_topic: None,
-                    },
-                };
+            Message::SelectProtocol(protocol) => {
+                self.connection = protocol.empty_connection();
                 Command::none()
             }
             Message::ChangeConnection(connection) => {
                 self.connection = connection;
                 Command::none()
             }
+            Message::SelectProfile(name) => {
+                if let Some(connection) = self.commander.profiles.get(&name) {
+                    self.connection = connection.clone();
+                }
+                Command::none()
+            }
             Message::DoMacro(index) => {
                 if let Some((_name, commands)) = self.commander.macros.iter().nth(index) {
                     cosmic::command::message(Message::ProcessCommand(
@@ -288,20 +513,475 @@ impl Application for App {
                 }
                 Command::none()
             }
+            Message::CancelTask(task) => {
+                if let Err(msg) = self
+                    .commander
+                    .dispatch(print3rs_commands::commands::Command::Stop(task.as_str()))
+                {
+                    return self
+                        .toasts
+                        .push(Toast::new(msg.0))
+                        .map(cosmic::app::Message::App);
+                }
+                if self
+                    .progress
+                    .as_ref()
+                    .is_some_and(|progress| *progress.task == *task)
+                {
+                    self.progress = None;
+                }
+                Command::none()
+            }
+            Message::PausePrint | Message::ResumePrint => {
+                let command = if matches!(message, Message::PausePrint) {
+                    print3rs_commands::commands::Command::PauseNow
+                } else {
+                    print3rs_commands::commands::Command::Resume
+                };
+                if let Err(msg) = self.commander.dispatch(command) {
+                    self.toasts
+                        .push(Toast::new(msg.0))
+                        .map(cosmic::app::Message::App)
+                } else {
+                    Command::none()
+                }
+            }
+            Message::NewMacro => {
+                self.macro_editor = Some(MacroEditor::default());
+                Command::none()
+            }
+            Message::OpenMacroEditor(name) => {
+                self.macro_editor = Some(MacroEditor::load(&self.commander.macros, &name));
+                Command::none()
+            }
+            Message::EditMacro(edit) => {
+                if let Some(editor) = &mut self.macro_editor {
+                    editor.edit(edit);
+                }
+                Command::none()
+            }
+            Message::SaveMacro => {
+                let Some(editor) = self.macro_editor.take() else {
+                    return Command::none();
+                };
+                let name = editor.name.trim();
+                // renaming a macro replaces the old one
+                if let Some(old) = editor
+                    .editing
+                    .as_deref()
+                    .filter(|old| !old.eq_ignore_ascii_case(name))
+                {
+                    let _ = self
+                        .commander
+                        .dispatch(print3rs_commands::commands::Command::DeleteMacro(old));
+                }
+                let steps = editor.steps().collect();
+                let saved = self
+                    .commander
+                    .dispatch(print3rs_commands::commands::Command::Macro(name, steps));
+                self.macro_editor = Some(MacroEditor::load(&self.commander.macros, name));
+                if let Err(msg) = saved {
+                    self.toasts
+                        .push(Toast::new(msg.0))
+                        .map(cosmic::app::Message::App)
+                } else {
+                    Command::none()
+                }
+            }
+            Message::DeleteMacro(name) => {
+                let _ = self
+                    .commander
+                    .dispatch(print3rs_commands::commands::Command::DeleteMacro(
+                        name.as_str(),
+                    ));
+                self.macro_editor = Some(MacroEditor::default());
+                Command::none()
+            }
+            Message::CloseMacroEditor => {
+                self.macro_editor = None;
+                Command::none()
+            }
+            Message::Progress(progress) => {
+                self.progress = (!progress.is_finished()).then_some(progress);
+                Command::none()
+            }
+            Message::Temperatures(report) => {
+                self.temperatures.record(report);
+                Command::none()
+            }
+            Message::Position(position) => {
+                if let Some(position) = position {
+                    self.position = Some((Instant::now(), position));
+                }
+                Command::none()
+            }
+            Message::TemperatureWindow(window) => {
+                self.temperatures.set_window(window);
+                Command::none()
+            }
+            Message::HeaterInput(heater, input) => {
+                match heater {
+                    Heater::Hotend => self.hotend_input = input,
+                    Heater::Bed => self.bed_input = input,
+                }
+                Command::none()
+            }
+            Message::SubmitHeater(heater) => {
+                let input = match heater {
+                    Heater::Hotend => &self.hotend_input,
+                    Heater::Bed => &self.bed_input,
+                };
+                match parse_target(input) {
+                    Some(target) => self.update(Message::SetHeater(heater, target)),
+                    None => self
+                        .toasts
+                        .push(Toast::new(fl!("temperature-input-error", max = MAX_TARGET)))
+                        .map(cosmic::app::Message::App),
+                }
+            }
+            Message::SetHeater(heater, temperature) => {
+                let printer = self.commander.printer();
+                let sent = match heater {
+                    Heater::Hotend => printer
+                        .try_send_unsequenced(SetHotendTemp {
+                            temperature,
+                            tool: None,
+                        })
+                        .map(drop),
+                    Heater::Bed => printer
+                        .try_send_unsequenced(SetBedTemp { temperature })
+                        .map(drop),
+                };
+                if let Err(msg) = sent {
+                    self.toasts
+                        .push(Toast::new(msg.to_string()))
+                        .map(cosmic::app::Message::App)
+                } else {
+                    Command::none()
+                }
+            }
+            Message::Preheat(index) => {
+                let Some(preset) = HEATER_PRESETS.get(index) else {
+                    return Command::none();
+                };
+                Command::batch([
+                    self.update(Message::SetHeater(Heater::Hotend, preset.hotend)),
+                    self.update(Message::SetHeater(Heater::Bed, preset.bed)),
+                ])
+            }
+            Message::FanSpeed(percent) => {
+                self.fan_percent = percent;
+                Command::none()
+            }
+            Message::SendFanSpeed => {
+                let printer = self.commander.printer();
+                let sent = if self.fan_percent > 0.0 {
+                    let speed = (self.fan_percent / 100.0 * 255.0).round() as u8;
+                    printer
+                        .try_send_unsequenced(SetFanSpeed { speed, fan: None })
+                        .map(drop)
+                } else {
+                    printer.try_send_unsequenced(FanOff { fan: None }).map(drop)
+                };
+                if let Err(msg) = sent {
+                    self.toasts
+                        .push(Toast::new(msg.to_string()))
+                        .map(cosmic::app::Message::App)
+                } else {
+                    Command::none()
+                }
+            }
+            Message::FanOff => {
+                self.fan_percent = 0.0;
+                self.update(Message::SendFanSpeed)
+            }
+            Message::ExtrudeLength(length) => {
+                self.extrude_length = length;
+                Command::none()
+            }
+            Message::ExtrudeFeedrate(feedrate) => {
+                self.extrude_feedrate = feedrate;
+                Command::none()
+            }
+            Message::RefreshSdFiles => {
+                let Ok(socket) = self.commander.printer().socket().cloned() else {
+                    return Command::none();
+                };
+                Command::perform(
+                    async move { sd::list(&socket).await.map_err(|e| e.to_string()) },
+                    |files| cosmic::app::Message::App(Message::SdFiles(files)),
+                )
+            }
+            Message::SdFiles(Ok(files)) => {
+                self.sd_files = files;
+                Command::none()
+            }
+            Message::SdFiles(Err(msg)) => self
+                .toasts
+                .push(Toast::new(fl!("sd-list-error", error = msg)))
+                .map(cosmic::app::Message::App),
+            Message::SdPrint(filename) => {
+                if let Err(msg) =
+                    self.commander
+                        .dispatch(print3rs_commands::commands::Command::SdPrint(
+                            filename.as_str(),
+                        ))
+                {
+                    self.toasts
+                        .push(Toast::new(msg.0))
+                        .map(cosmic::app::Message::App)
+                } else {
+                    Command::none()
+                }
+            }
+            Message::SdDelete(filename) => {
+                let Ok(socket) = self.commander.printer().socket().cloned() else {
+                    return Command::none();
+                };
+                // listed again afterwards, so the panel shows what is really left on the card
+                Command::perform(
+                    async move {
+                        sd::delete(&socket, &filename).await?;
+                        sd::list(&socket).await
+                    },
+                    |files| {
+                        cosmic::app::Message::App(Message::SdFiles(
+                            files.map_err(|e| e.to_string()),
+                        ))
+                    },
+                )
+            }
+            Message::Extrude { retract } => {
+                let positive = |input: &str| {
+                    input
+                        .trim()
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|value| *value > 0.0)
+                };
+                let (Some(length), Some(feedrate)) = (
+                    positive(&self.extrude_length),
+                    positive(&self.extrude_feedrate),
+                ) else {
+                    return self
+                        .toasts
+                        .push(Toast::new(fl!("extrude-input-error")))
+                        .map(cosmic::app::Message::App);
+                };
+                let length = if retract { -length } else { length };
+                // the extrude command checks the hotend is hot enough before moving anything
+                if let Err(msg) =
+                    self.commander
+                        .dispatch(print3rs_commands::commands::Command::Extrude(
+                            length,
+                            Some(feedrate),
+                        ))
+                {
+                    self.toasts
+                        .push(Toast::new(msg.0))
+                        .map(cosmic::app::Message::App)
+                } else {
+                    Command::none()
+                }
+            }
         }
     }
 
     fn view(&self) -> Element<'_, Message> {
+        let mut output = widget::column();
+        if let Some(progress) = components::print_progress(self) {
+            output = output.push(progress);
+        }
+        if !self.commander.tasks.is_empty() {
+            output = output.push(components::task_list(self));
+        }
         let main_content = widget::row()
             .push(
                 widget::column()
                     .push(components::connector(self))
                     .push(cosmic::iced::widget::horizontal_rule(4))
-                    .push(components::jogger(self))
+                    .push(
+                        widget::row()
+                            .push(components::jogger(self))
+                            .push(components::position(self)),
+                    )
+                    .push(cosmic::iced::widget::horizontal_rule(4))
+                    .push(components::heaters(self))
+                    .push(cosmic::iced::widget::horizontal_rule(4))
+                    .push(components::temperature_graph(self))
+                    .push(cosmic::iced::widget::horizontal_rule(4))
+                    .push(components::sd_card(self))
                     .padding(10),
             )
-            .push(self.console.view())
+            .push(match (&self.macro_editor, &self.preview) {
+                (Some(editor), _) => editor.view(&self.commander.macros),
+                (None, Some(preview)) => preview.view(),
+                (None, None) => output.push(self.console.view()).into(),
+            })
             .padding(10);
-        toaster(&self.toasts, main_content)
+        let content = widget::column()
+            .push(components::printer_tabs(self))
+            .push(main_content);
+        toaster(&self.toasts, content)
+    }
+}
+
+impl App {
+    /// Exchange the fields shown for the open tab with `tab`
+    fn swap_tab(&mut self, tab: &mut PrinterTab) {
+        use std::mem::swap;
+        swap(&mut self.console, &mut tab.console);
+        swap(&mut self.connection, &mut tab.connection);
+        swap(&mut self.jog_scale, &mut tab.jog_scale);
+        swap(&mut self.keyboard_jog, &mut tab.keyboard_jog);
+        swap(&mut self.temperatures, &mut tab.temperatures);
+        swap(&mut self.position, &mut tab.position);
+        swap(&mut self.hotend_input, &mut tab.hotend_input);
+        swap(&mut self.bed_input, &mut tab.bed_input);
+        swap(&mut self.fan_percent, &mut tab.fan_percent);
+        swap(&mut self.extrude_length, &mut tab.extrude_length);
+        swap(&mut self.extrude_feedrate, &mut tab.extrude_feedrate);
+        swap(&mut self.sd_files, &mut tab.sd_files);
+    }
+
+    /// Open the tab of the commander's selected printer, if it changed since the last message
+    fn sync_tab(&mut self) {
+        if self.commander.printer_name() == self.tab {
+            return;
+        }
+        let name = self.commander.printer_name().to_owned();
+        let mut tab = self
+            .tabs
+            .remove(&name)
+            .unwrap_or_else(|| PrinterTab::new(&self.commander));
+        self.swap_tab(&mut tab);
+        // the history is shared, and may have grown while the tab was closed
+        self.console.update_command_state(&self.commander.history);
+        let previous = std::mem::replace(&mut self.tab, name);
+        self.tabs.insert(previous, tab);
+    }
+
+    /// Temperatures from the selected printer, which is polled with M105 to keep them coming.
+    ///
+    /// The subscription is made again whenever a different printer is selected or connected.
+    fn temperature_subscription(&self) -> Subscription<Message> {
+        struct TemperatureSubscription;
+        let printer = self.commander.printer();
+        let (Ok(temperatures), Ok(socket)) = (printer.temperatures(), printer.socket()) else {
+            return Subscription::none();
+        };
+        let socket = socket.clone();
+        let reports = stream::unfold(
+            (temperatures, socket, None),
+            |(mut temperatures, socket, poll)| async move {
+                // made here rather than up front, where there may be no runtime to make it in
+                let mut poll = poll.unwrap_or_else(|| tokio::time::interval(TEMPERATURE_POLL));
+                let report = loop {
+                    tokio::select! {
+                        report = temperatures.recv() => match report {
+                            Ok(report) => break report,
+                            // missed some lines, the next report will do
+                            Err(print3rs_core::Error::ReadLine(
+                                tokio::sync::broadcast::error::RecvError::Lagged(_),
+                            )) => continue,
+                            Err(_) => return None,
+                        },
+                        _ = poll.tick() => {
+                            // a full queue means the printer is busy, so skip this poll
+                            let _ = socket.try_send_unsequenced(ReportTemperatures);
+                        }
+                    }
+                };
+                Some((
+                    Message::Temperatures(report),
+                    (temperatures, socket, Some(poll)),
+                ))
+            },
+        );
+        cosmic::iced::subscription::run_with_id(
+            (
+                std::any::TypeId::of::<TemperatureSubscription>(),
+                self.commander.printer_name().to_owned(),
+            ),
+            reports,
+        )
+    }
+
+    /// Positions from the selected printer, autoreported with M154 if the firmware can, polled with M114 if not.
+    ///
+    /// Every poll sends a message even without a new position, so the readout can gray out once it's stale.
+    fn position_subscription(&self) -> Subscription<Message> {
+        struct PositionSubscription;
+        let printer = self.commander.printer();
+        let (Ok(positions), Ok(socket)) = (printer.positions(), printer.socket()) else {
+            return Subscription::none();
+        };
+        let socket = socket.clone();
+        let autoreports = printer
+            .info()
+            .is_ok_and(|info| info.has_capability(Capability::AutoreportPos));
+        let reports = stream::unfold(
+            (positions, socket, None),
+            move |(mut positions, socket, poll)| async move {
+                let mut poll = match poll {
+                    Some(poll) => poll,
+                    None => {
+                        if autoreports {
+                            let _ = socket.try_send_unsequenced(AutoreportPosition {
+                                interval: POSITION_POLL.as_secs() as u32,
+                            });
+                        }
+                        tokio::time::interval(POSITION_POLL)
+                    }
+                };
+                let position = tokio::select! {
+                    position = positions.recv() => match position {
+                        Ok(position) => Some(position),
+                        Err(print3rs_core::Error::ReadLine(
+                            tokio::sync::broadcast::error::RecvError::Lagged(_),
+                        )) => None,
+                        Err(_) => return None,
+                    },
+                    _ = poll.tick() => {
+                        if !autoreports {
+                            let _ = socket.try_send_unsequenced(ReportPosition);
+                        }
+                        None
+                    }
+                };
+                Some((Message::Position(position), (positions, socket, Some(poll))))
+            },
+        );
+        cosmic::iced::subscription::run_with_id(
+            (
+                std::any::TypeId::of::<PositionSubscription>(),
+                self.commander.printer_name().to_owned(),
+            ),
+            reports,
+        )
+    }
+}
+
+/// Jog direction for a key pressed outside of any text input: arrows for X and Y, page up and down for Z
+fn jog_key(
+    key: cosmic::iced::keyboard::Key,
+    modifiers: cosmic::iced::keyboard::Modifiers,
+) -> Option<Message> {
+    use cosmic::iced::keyboard::{key::Named, Key};
+    if !modifiers.is_empty() {
+        return None;
     }
+    let Key::Named(key) = key else {
+        return None;
+    };
+    let direction = match key {
+        Named::ArrowLeft => JogMove::x(-1.0),
+        Named::ArrowRight => JogMove::x(1.0),
+        Named::ArrowUp => JogMove::y(1.0),
+        Named::ArrowDown => JogMove::y(-1.0),
+        Named::PageUp => JogMove::z(1.0),
+        Named::PageDown => JogMove::z(-1.0),
+        _ => return None,
+    };
+    Some(Message::KeyJog(direction))
 }
@@ -1,11 +1,12 @@
 use {cosmic::widget::menu, std::collections::HashMap};
 
-use crate::app::App;
 use crate::messages::Message;
+use crate::{app::App, fl};
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 enum MenuAction {
     DoMacro(usize),
+    EditMacros,
     KillTask(usize),
     Print,
     Clear,
@@ -19,6 +20,7 @@ impl menu::Action for MenuAction {
     fn message(&self) -> Self::Message {
         match self {
             MenuAction::DoMacro(index) => Message::DoMacro(*index),
+            MenuAction::EditMacros => Message::NewMacro,
             MenuAction::KillTask(index) => Message::KillTask(*index),
             MenuAction::Print => Message::PrintDialog,
             MenuAction::Clear => Message::ClearConsole,
@@ -31,40 +33,54 @@ impl menu::Action for MenuAction {
 pub(crate) fn app_menu(app: &App) -> menu::MenuBar<'_, Message> {
     let keybinds = HashMap::new();
     let file = menu::Tree::with_children(
-        menu::root("File"),
+        menu::root(fl!("file")),
         menu::items(
             &keybinds,
             vec![
-                menu::Item::Button("Print", MenuAction::Print),
-                menu::Item::Button("Save", MenuAction::Save),
-                menu::Item::Button("Clear", MenuAction::Clear),
-                menu::Item::Button("Quit", MenuAction::Quit),
+                menu::Item::Button(fl!("print"), MenuAction::Print),
+                menu::Item::Button(fl!("save"), MenuAction::Save),
+                menu::Item::Button(fl!("clear"), MenuAction::Clear),
+                menu::Item::Button(fl!("quit"), MenuAction::Quit),
             ],
         ),
     );
     let macros = menu::Tree::with_children(
-        menu::root("Macros"),
+        menu::root(fl!("macros")),
         menu::items(
             &keybinds,
-            app.commander
-                .macros
-                .iter()
-                .enumerate()
-                .map(|(index, (name, _content))| {
-                    menu::Item::Button(name.clone(), MenuAction::DoMacro(index))
-                })
-                .collect(),
+            std::iter::once(menu::Item::Button(
+                fl!("edit-macros"),
+                MenuAction::EditMacros,
+            ))
+            .chain(
+                app.commander
+                    .macros
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (name, _content))| {
+                        menu::Item::Button(name.clone(), MenuAction::DoMacro(index))
+                    }),
+            )
+            .collect(),
         ),
     );
     let tasks = menu::Tree::with_children(
-        menu::root("Tasks"),
+        menu::root(fl!("tasks")),
         menu::items(
             &keybinds,
             app.commander
                 .tasks
                 .keys()
                 .enumerate()
-                .map(|(index, name)| menu::Item::Button(name.clone(), MenuAction::KillTask(index)))
+                .map(|(index, name)| {
+                    let label = match &app.progress {
+                        Some(progress) if *progress.task == **name => {
+                            format!("{name} ({:.0}%)", progress.percent.floor())
+                        }
+                        _ => name.clone(),
+                    };
+                    menu::Item::Button(label, MenuAction::KillTask(index))
+                })
                 .collect(),
         ),
     );
@@ -2,51 +2,47 @@ use cosmic::{
     iced::widget::{button, column, row},
     iced_widget::pick_list,
 };
-use cosmic::{widget::combo_box, Element};
+use cosmic::{
+    widget::{combo_box, text},
+    Element,
+};
 use {super::centered_row::centered_row, cosmic::widget::radio};
 use {
     cosmic::widget::text_input, print3rs_commands::commands::connect::HostPort, std::str::FromStr,
 };
 
-use print3rs_commands::commands::connect::Connection;
+use {print3rs_commands::commands::connect::Connection, print3rs_frontend::Protocol};
 
-use crate::app::App;
 use crate::messages::Message;
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub(crate) enum Protocol {
-    Auto,
-    Serial,
-    Tcp,
-    Mqtt,
-}
-
-impl Protocol {
-    fn from_connection(connection: &Connection<String>) -> Self {
-        match connection {
-            Connection::Auto => Protocol::Auto,
-            Connection::Serial { .. } => Protocol::Serial,
-            Connection::Tcp { .. } => Protocol::Tcp,
-            Connection::Mqtt { .. } => Protocol::Mqtt,
-            _ => todo!(),
-        }
-    }
-}
+use crate::{app::App, fl};
 
 pub(crate) fn connector(app: &App) -> Element<'_, Message> {
     let connection_details: Element<'_, Message> = match app.connection.clone() {
-        Connection::Auto => "".into(),
-        Connection::Serial { port, baud } => column![
-            combo_box(&app.ports, "printer port", Some(&port), move |port| {
-                Message::ChangeConnection(Connection::Serial { port, baud })
+        Connection::Auto(_) | Connection::Virtual | Connection::Replay { .. } => "".into(),
+        Connection::Serial {
+            port,
+            baud,
+            dialect,
+        } => column![
+            combo_box(&app.ports, &fl!("printer-port"), Some(&port), move |port| {
+                Message::ChangeConnection(Connection::Serial {
+                    port,
+                    baud,
+                    dialect,
+                })
             },)
-            .on_input(move |port| Message::ChangeConnection(Connection::Serial { port, baud })),
+            .on_input(move |port| Message::ChangeConnection(Connection::Serial {
+                port,
+                baud,
+                dialect
+            })),
             pick_list(
                 &[9600, 115200],
                 baud,
                 move |baud| Message::ChangeConnection(Connection::Serial {
                     port: port.clone(),
-                    baud: Some(baud)
+                    baud: Some(baud),
+                    dialect,
                 }),
             ),
         ]
@@ -58,7 +54,7 @@ pub(crate) fn connector(app: &App) -> Element<'_, Message> {
             } else {
                 hostname
             };
-            text_input("hostname:port", host_port_string)
+            text_input(fl!("host-port"), host_port_string)
                 .on_input(move |hostname| {
                     let HostPort(hostname, port) = if hostname.ends_with(':') {
                         HostPort(hostname, None)
@@ -69,6 +65,136 @@ pub(crate) fn connector(app: &App) -> Element<'_, Message> {
                 })
                 .into()
         }
+        Connection::Moonraker { hostname, port } => {
+            let host_port_string = if let Some(port) = port {
+                format!("{hostname}:{port}")
+            } else {
+                hostname
+            };
+            text_input(fl!("host-port"), host_port_string)
+                .on_input(move |hostname| {
+                    let HostPort(hostname, port) = if hostname.ends_with(':') {
+                        HostPort(hostname, None)
+                    } else {
+                        HostPort::from_str(&hostname).unwrap_or(HostPort(hostname, None))
+                    };
+                    Message::ChangeConnection(Connection::Moonraker { hostname, port })
+                })
+                .into()
+        }
+        Connection::OctoPrint {
+            hostname,
+            port,
+            api_key,
+        } => {
+            let host_port_string = if let Some(port) = port {
+                format!("{hostname}:{port}")
+            } else {
+                hostname.clone()
+            };
+            column![
+                text_input(fl!("host-port"), host_port_string).on_input({
+                    let api_key = api_key.clone();
+                    move |hostname| {
+                        let HostPort(hostname, port) = if hostname.ends_with(':') {
+                            HostPort(hostname, None)
+                        } else {
+                            HostPort::from_str(&hostname).unwrap_or(HostPort(hostname, None))
+                        };
+                        Message::ChangeConnection(Connection::OctoPrint {
+                            hostname,
+                            port,
+                            api_key: api_key.clone(),
+                        })
+                    }
+                }),
+                text_input(fl!("api-key"), api_key).on_input(move |api_key| {
+                    Message::ChangeConnection(Connection::OctoPrint {
+                        hostname: hostname.clone(),
+                        port,
+                        api_key,
+                    })
+                })
+            ]
+            .spacing(5)
+            .into()
+        }
+        Connection::PrusaLink {
+            hostname,
+            port,
+            api_key,
+        } => {
+            let host_port_string = if let Some(port) = port {
+                format!("{hostname}:{port}")
+            } else {
+                hostname.clone()
+            };
+            column![
+                text_input(fl!("host-port"), host_port_string).on_input({
+                    let api_key = api_key.clone();
+                    move |hostname| {
+                        let HostPort(hostname, port) = if hostname.ends_with(':') {
+                            HostPort(hostname, None)
+                        } else {
+                            HostPort::from_str(&hostname).unwrap_or(HostPort(hostname, None))
+                        };
+                        Message::ChangeConnection(Connection::PrusaLink {
+                            hostname,
+                            port,
+                            api_key: api_key.clone(),
+                        })
+                    }
+                }),
+                text_input(fl!("api-key"), api_key).on_input(move |api_key| {
+                    Message::ChangeConnection(Connection::PrusaLink {
+                        hostname: hostname.clone(),
+                        port,
+                        api_key,
+                    })
+                })
+            ]
+            .spacing(5)
+            .into()
+        }
+        Connection::Duet {
+            hostname,
+            port,
+            password,
+        } => {
+            let host_port_string = if let Some(port) = port {
+                format!("{hostname}:{port}")
+            } else {
+                hostname.clone()
+            };
+            column![
+                text_input(fl!("host-port"), host_port_string).on_input({
+                    let password = password.clone();
+                    move |hostname| {
+                        let HostPort(hostname, port) = if hostname.ends_with(':') {
+                            HostPort(hostname, None)
+                        } else {
+                            HostPort::from_str(&hostname).unwrap_or(HostPort(hostname, None))
+                        };
+                        Message::ChangeConnection(Connection::Duet {
+                            hostname,
+                            port,
+                            password: password.clone(),
+                        })
+                    }
+                }),
+                text_input(fl!("password"), password.unwrap_or_default()).on_input(
+                    move |password| {
+                        Message::ChangeConnection(Connection::Duet {
+                            hostname: hostname.clone(),
+                            port,
+                            password: (!password.is_empty()).then_some(password),
+                        })
+                    }
+                )
+            ]
+            .spacing(5)
+            .into()
+        }
         Connection::Mqtt {
             hostname,
             port,
@@ -81,7 +207,7 @@ pub(crate) fn connector(app: &App) -> Element<'_, Message> {
                 hostname.clone()
             };
             column![
-                text_input("hostname:port", host_port_string).on_input({
+                text_input(fl!("host-port"), host_port_string).on_input({
                     let in_topic = in_topic.clone();
                     let out_topic = out_topic.clone();
                     move |hostname| {
@@ -97,7 +223,7 @@ pub(crate) fn connector(app: &App) -> Element<'_, Message> {
                         })
                     }
                 }),
-                text_input("in topic", in_topic.clone().unwrap_or_default()).on_input({
+                text_input(fl!("in-topic"), in_topic.clone().unwrap_or_default()).on_input({
                     let hostname = hostname.clone();
                     let out_topic = out_topic.clone();
                     move |in_topic| {
@@ -116,7 +242,7 @@ pub(crate) fn connector(app: &App) -> Element<'_, Message> {
                         })
                     }
                 }),
-                text_input("out topic", out_topic.unwrap_or_default()).on_input({
+                text_input(fl!("out-topic"), out_topic.unwrap_or_default()).on_input({
                     let hostname = hostname.clone();
                     let in_topic = in_topic.clone();
                     move |out_topic| {
@@ -141,45 +267,45 @@ pub(crate) fn connector(app: &App) -> Element<'_, Message> {
         .into(),
         _ => todo!(),
     };
-    let auto = radio(
-        "Auto",
-        Protocol::Auto,
-        Some(Protocol::from_connection(&app.connection)),
-        Message::SelectProtocol,
-    )
-    .spacing(5);
-    let serial = radio(
-        "Serial",
-        Protocol::Serial,
-        Some(Protocol::from_connection(&app.connection)),
-        Message::SelectProtocol,
-    )
-    .spacing(5);
-    let tcp = radio(
-        "TCP/IP",
-        Protocol::Tcp,
-        Some(Protocol::from_connection(&app.connection)),
-        Message::SelectProtocol,
-    )
-    .spacing(5);
-    let mqtt = radio(
-        "MQTT",
-        Protocol::Mqtt,
-        Some(Protocol::from_connection(&app.connection)),
-        Message::SelectProtocol,
-    )
-    .spacing(5);
-    let protocol_selector = row!["Protocol:", auto, serial, tcp, mqtt]
-        .spacing(20.0)
-        .align_items(cosmic::iced::Alignment::Center);
+    let selected = Protocol::from_connection(&app.connection);
+    let protocol_selector = Protocol::ALL.into_iter().fold(
+        row![text(fl!("protocol"))]
+            .spacing(20.0)
+            .align_items(cosmic::iced::Alignment::Center),
+        |selector, protocol| {
+            selector.push(
+                radio(
+                    protocol.label(),
+                    protocol,
+                    selected,
+                    Message::SelectProtocol,
+                )
+                .spacing(5),
+            )
+        },
+    );
+    let profiles: Vec<String> = app.commander.profiles.names().map(str::to_owned).collect();
+    let profile = app
+        .commander
+        .profiles
+        .iter()
+        .find(|(_, connection)| **connection == app.connection)
+        .map(|(name, _)| name.clone());
+    let profile_selector = row![
+        text(fl!("profile")),
+        pick_list(profiles, profile, Message::SelectProfile).placeholder(fl!("saved-connection"))
+    ]
+    .spacing(20.0)
+    .align_items(cosmic::iced::Alignment::Center);
     column![
+        profile_selector,
         protocol_selector,
         connection_details,
-        centered_row![button(if app.commander.printer().is_connected() {
-            "disconnect"
+        centered_row![button(text(if app.commander.printer().is_connected() {
+            fl!("disconnect")
         } else {
-            "connect"
-        })
+            fl!("connect")
+        }))
         .on_press(Message::ToggleConnect)]
     ]
     .spacing(10)
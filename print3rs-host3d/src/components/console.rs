@@ -1,46 +1,139 @@
 use {
     cosmic::{
+        iced::Alignment,
         iced_widget::{button, column, row},
-        widget::{combo_box::State as ComboState, text_editor, text_editor::Content, text_input},
+        widget::{
+            combo_box::State as ComboState, text, text_editor, text_editor::Content, text_input,
+        },
         Element,
     },
-    std::collections::VecDeque,
+    print3rs_frontend::{ConsoleFilter, History},
 };
 
-use crate::messages::Message;
+use crate::{
+    fl,
+    messages::{FilterChange, Message},
+};
 
 #[derive(Debug)]
 pub(crate) struct State {
     pub(crate) output: Content,
     pub(crate) command_state: ComboState<String>,
-    pub(crate) command_history: VecDeque<String>,
     pub(crate) command: String,
+    /// Lines kept out of the output as they arrive
+    pub(crate) filter: ConsoleFilter,
+    /// Include and exclude patterns as typed in, which may not be valid yet
+    pub(crate) include: String,
+    pub(crate) exclude: String,
+    pub(crate) filter_error: Option<String>,
 }
 
 impl Default for State {
     fn default() -> Self {
         Self {
             output: Default::default(),
-            command_state: ComboState::new(vec![]), // TODO: load history from file here
-            command_history: Default::default(),
+            command_state: ComboState::new(vec![]),
             command: Default::default(),
+            filter: Default::default(),
+            include: Default::default(),
+            exclude: Default::default(),
+            filter_error: None,
         }
     }
 }
 
 impl State {
+    /// Refresh the command suggestions after the history changed
+    pub(crate) fn update_command_state(&mut self, history: &History) {
+        self.command_state = ComboState::new(history.entries().to_owned());
+    }
+
+    /// Add output to the end of the console, leaving out lines the filter hides
+    pub(crate) fn append(&mut self, output: &str) {
+        use text_editor::{Action, Edit};
+        let Some(output) = self.filter.apply(output) else {
+            return;
+        };
+        for c in output.chars() {
+            self.output.perform(Action::Edit(Edit::Insert(c)));
+        }
+        self.output.perform(Action::Edit(Edit::Enter));
+    }
+
+    /// Change the filter, keeping the last valid pattern while one being typed doesn't parse
+    pub(crate) fn change_filter(&mut self, change: FilterChange) {
+        let result = match change {
+            FilterChange::HideOk(hide) => {
+                self.filter.hide_ok = hide;
+                return;
+            }
+            FilterChange::HideTemperatures(hide) => {
+                self.filter.hide_temperatures = hide;
+                return;
+            }
+            FilterChange::Include(input) => {
+                let result = self.filter.set_include(&input);
+                self.include = input;
+                result
+            }
+            FilterChange::Exclude(input) => {
+                let result = self.filter.set_exclude(&input);
+                self.exclude = input;
+                result
+            }
+        };
+        self.filter_error = result.err().map(|e| e.to_string());
+    }
+
+    fn filter_view(&self) -> Element<'_, Message> {
+        let toggle = |hidden: bool, labels: [String; 2], change: fn(bool) -> FilterChange| {
+            let [hide, show] = labels;
+            button(text(if hidden { show } else { hide }))
+                .on_press(Message::FilterConsole(change(!hidden)))
+        };
+        let mut filters = row![
+            toggle(
+                self.filter.hide_ok,
+                [fl!("hide-ok"), fl!("show-ok")],
+                FilterChange::HideOk
+            ),
+            toggle(
+                self.filter.hide_temperatures,
+                [fl!("hide-temperatures"), fl!("show-temperatures")],
+                FilterChange::HideTemperatures
+            ),
+            text_input(fl!("include-lines"), self.include.as_str())
+                .font(cosmic::font::Font::MONOSPACE)
+                .on_input(|input| Message::FilterConsole(FilterChange::Include(input))),
+            text_input(fl!("exclude-lines"), self.exclude.as_str())
+                .font(cosmic::font::Font::MONOSPACE)
+                .on_input(|input| Message::FilterConsole(FilterChange::Exclude(input))),
+        ]
+        .spacing(5.0)
+        .align_items(Alignment::Center);
+        if let Some(error) = &self.filter_error {
+            filters = filters.push(text(error.lines().last().unwrap_or_default().to_string()));
+        }
+        filters.into()
+    }
+
     pub(crate) fn view(&self) -> Element<'_, Message> {
         let content = text_editor(&self.output)
             .font(cosmic::font::Font::MONOSPACE)
             .on_action(Message::OutputAction);
         column![
+            self.filter_view(),
             content,
             row![
-                text_input("type `help` for list of commands", self.command.as_str())
+                text_input(fl!("command-placeholder"), self.command.as_str())
                     .font(cosmic::font::Font::MONOSPACE)
                     .on_input(Message::CommandInput)
                     .on_submit(Message::SubmitCommand)
-                    .trailing_icon(button("send").on_press(Message::SubmitCommand).into()),
+                    .trailing_icon(
+                        button(text(fl!("send")))
+                            .on_press(Message::SubmitCommand)
+                            .into()
+                    ),
             ]
         ]
         .into()
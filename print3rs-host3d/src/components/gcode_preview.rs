@@ -0,0 +1,97 @@
+use {
+    crate::{fl, messages::Message},
+    cosmic::{
+        iced::{Alignment, Length},
+        iced_widget::{button, column, row},
+        widget::{slider, svg, text, Space},
+        Element,
+    },
+    print3rs_frontend::{GcodePreview, Projection},
+    std::{path::PathBuf, sync::Arc},
+};
+
+const PREVIEW_SIZE: f32 = 480.0;
+
+/// A file picked to print, shown layer by layer in place of the console until it is printed or closed
+#[derive(Debug, Clone)]
+pub(crate) struct State {
+    pub(crate) path: PathBuf,
+    pub(crate) preview: Arc<GcodePreview>,
+    /// Layer being looked at, counting from 0
+    pub(crate) layer: usize,
+    pub(crate) projection: Projection,
+}
+
+impl State {
+    /// Start by looking at the top layer, which shows the whole print from the side
+    pub(crate) fn new(path: PathBuf, preview: Arc<GcodePreview>) -> Self {
+        let layer = preview.layers().len().saturating_sub(1);
+        Self {
+            path,
+            preview,
+            layer,
+            projection: Projection::Isometric,
+        }
+    }
+
+    pub(crate) fn view(&self) -> Element<'_, Message> {
+        let name = self
+            .path
+            .file_name()
+            .unwrap_or(self.path.as_os_str())
+            .to_string_lossy();
+        let mut header = row![
+            text(format!("{name}: {}", self.preview.summary())),
+            Space::with_width(Length::Fill),
+        ]
+        .spacing(5.0)
+        .align_items(Alignment::Center);
+        for (label, projection) in [
+            (fl!("top-view"), Projection::Top),
+            (fl!("isometric-view"), Projection::Isometric),
+        ] {
+            header = header.push(button(text(label)).on_press_maybe(
+                (projection != self.projection).then_some(Message::PreviewProjection(projection)),
+            ));
+        }
+
+        let image = svg(svg::Handle::from_memory(
+            self.preview
+                .svg(self.layer, self.projection, PREVIEW_SIZE, PREVIEW_SIZE)
+                .into_bytes(),
+        ))
+        .width(PREVIEW_SIZE)
+        .height(PREVIEW_SIZE);
+
+        let layers = self.preview.layers();
+        let mut footer = row![].spacing(5.0).align_items(Alignment::Center);
+        if let Some(layer) = layers.get(self.layer) {
+            footer = footer
+                .push(text(fl!(
+                    "layer",
+                    layer = self.layer + 1,
+                    layers = layers.len(),
+                    height = format!("{:.2}", layer.z)
+                )))
+                .push(
+                    slider(
+                        0.0..=(layers.len() - 1) as f32,
+                        self.layer as f32,
+                        Message::PreviewLayer,
+                    )
+                    .step(1.0)
+                    .width(Length::Fill),
+                );
+        } else {
+            footer = footer.push(Space::with_width(Length::Fill));
+        }
+        footer = footer
+            .push(button(text(fl!("close"))).on_press(Message::ClosePreview))
+            .push(button(text(fl!("print"))).on_press(Message::PrintPreviewed));
+
+        column![header, image, footer]
+            .spacing(10.0)
+            .padding(10)
+            .into()
+    }
+}
@@ -0,0 +1,65 @@
+use {
+    crate::{
+        app::App,
+        fl,
+        messages::{Heater, Message},
+    },
+    cosmic::{
+        iced::{alignment, Alignment},
+        iced_widget::{button, column, row},
+        widget::{text, text_input},
+        Element,
+    },
+    print3rs_frontend::{describe_heater, HEATER_PRESETS},
+};
+
+const LABEL_WIDTH: f32 = 56.0;
+const READING_WIDTH: f32 = 96.0;
+const INPUT_WIDTH: f32 = 72.0;
+
+pub(crate) fn heaters(app: &App) -> Element<'_, Message> {
+    let connected = app.commander.printer().is_connected();
+    let if_connected = |message| connected.then_some(message);
+    let latest = app.temperatures.latest();
+    let heater_row = |heater: Heater| {
+        let (label, reading, input) = match heater {
+            Heater::Hotend => (
+                fl!("hotend"),
+                latest.and_then(|report| report.hotend.as_ref()),
+                &app.hotend_input,
+            ),
+            Heater::Bed => (
+                fl!("bed"),
+                latest.and_then(|report| report.bed.as_ref()),
+                &app.bed_input,
+            ),
+        };
+        row![
+            text(label).width(LABEL_WIDTH),
+            text(describe_heater(reading))
+                .font(cosmic::font::Font::MONOSPACE)
+                .width(READING_WIDTH),
+            text_input("°C", input.as_str())
+                .on_input(move |input| Message::HeaterInput(heater, input))
+                .on_submit(Message::SubmitHeater(heater))
+                .width(INPUT_WIDTH),
+            button(text(fl!("set")).horizontal_alignment(alignment::Horizontal::Center))
+                .on_press_maybe(if_connected(Message::SubmitHeater(heater))),
+            button(text(fl!("off")).horizontal_alignment(alignment::Horizontal::Center))
+                .on_press_maybe(if_connected(Message::SetHeater(heater, 0.0))),
+        ]
+        .spacing(5.0)
+        .align_items(Alignment::Center)
+    };
+    let mut presets = row![].spacing(5.0);
+    for (index, preset) in HEATER_PRESETS.iter().enumerate() {
+        presets = presets.push(
+            button(text(preset.name).horizontal_alignment(alignment::Horizontal::Center))
+                .on_press_maybe(if_connected(Message::Preheat(index))),
+        );
+    }
+    column![heater_row(Heater::Hotend), heater_row(Heater::Bed), presets]
+        .spacing(10.0)
+        .padding(10)
+        .into()
+}
@@ -1,9 +1,13 @@
 use crate::messages::{JogMove, Message, MoveAxis};
 use cosmic::iced_widget::{button, column, row};
-use cosmic::widget::{container, slider, text, Space};
+use cosmic::widget::{container, slider, text, text_input, Space};
 use cosmic::Element;
+use print3rs_frontend::cold_extrusion_warning;
 use {super::centered_row::centered_row, cosmic::iced::alignment};
-use {crate::app::App, cosmic::iced::Alignment};
+use {
+    crate::{app::App, fl},
+    cosmic::iced::Alignment,
+};
 
 pub(crate) fn jogger(app: &App) -> Element<'_, Message> {
     enum Jog {
@@ -44,41 +48,95 @@ pub(crate) fn jogger(app: &App) -> Element<'_, Message> {
     .spacing(0.0)
     .align_items(Alignment::Center);
 
-    container(
-        column![
-            centered_row![
-                xy_buttons,
-                column![
-                    Space::with_height(10.0),
-                    jog_button(Jog::Z(scale)),
-                    Space::with_height(10.0),
-                    jog_button(Jog::Z(-scale))
-                ]
-                .spacing(10.0),
+    let mut controls = column![
+        centered_row![
+            xy_buttons,
+            column![
+                Space::with_height(10.0),
+                jog_button(Jog::Z(scale)),
+                Space::with_height(10.0),
+                jog_button(Jog::Z(-scale))
             ]
-            .spacing(10.0)
-            .align_items(Alignment::Center),
+            .spacing(10.0),
+        ]
+        .spacing(10.0)
+        .align_items(Alignment::Center),
+        centered_row![
             slider(0.0..=100.0, app.jog_scale, Message::JogScale)
                 .step(1.0)
                 .width(240),
-            centered_row![
-                button(text("home").horizontal_alignment(alignment::Horizontal::Center))
-                    .width(BUTTON_WIDTH)
-                    .on_press_maybe(if_connected(Message::Home(MoveAxis::All))),
-                button(text("X").horizontal_alignment(alignment::Horizontal::Center))
-                    .width(BUTTON_WIDTH / 2.0)
-                    .on_press_maybe(if_connected(Message::Home(MoveAxis::X))),
-                button(text("Y").horizontal_alignment(alignment::Horizontal::Center))
-                    .width(BUTTON_WIDTH / 2.0)
-                    .on_press_maybe(if_connected(Message::Home(MoveAxis::Y))),
-                button(text("Z").horizontal_alignment(alignment::Horizontal::Center))
-                    .width(BUTTON_WIDTH / 2.0)
-                    .on_press_maybe(if_connected(Message::Home(MoveAxis::Z))),
-            ],
+            // arrow keys jog X and Y, page up and down jog Z, while this is on
+            button(
+                text(if app.keyboard_jog {
+                    fl!("keys-on")
+                } else {
+                    fl!("keys-off")
+                })
+                .horizontal_alignment(alignment::Horizontal::Center)
+            )
+            .width(BUTTON_WIDTH)
+            .on_press_maybe(if_connected(Message::KeyboardJog(!app.keyboard_jog))),
+        ]
+        .spacing(10.0)
+        .align_items(Alignment::Center),
+        centered_row![
+            button(text(fl!("home")).horizontal_alignment(alignment::Horizontal::Center))
+                .width(BUTTON_WIDTH)
+                .on_press_maybe(if_connected(Message::Home(MoveAxis::All))),
+            button(text("X").horizontal_alignment(alignment::Horizontal::Center))
+                .width(BUTTON_WIDTH / 2.0)
+                .on_press_maybe(if_connected(Message::Home(MoveAxis::X))),
+            button(text("Y").horizontal_alignment(alignment::Horizontal::Center))
+                .width(BUTTON_WIDTH / 2.0)
+                .on_press_maybe(if_connected(Message::Home(MoveAxis::Y))),
+            button(text("Z").horizontal_alignment(alignment::Horizontal::Center))
+                .width(BUTTON_WIDTH / 2.0)
+                .on_press_maybe(if_connected(Message::Home(MoveAxis::Z))),
+        ],
+        centered_row![
+            text(fl!("fan")),
+            // sliders can't be disabled, so one that goes nowhere stands in while disconnected
+            if app.commander.printer().is_connected() {
+                slider(0.0..=100.0, app.fan_percent, Message::FanSpeed)
+                    .on_release(Message::SendFanSpeed)
+            } else {
+                slider(0.0..=100.0, app.fan_percent, |_| Message::NoOp)
+            }
+            .step(1.0)
+            .width(160),
+            text(format!("{:.0}%", app.fan_percent)).width(40),
+            button(text(fl!("off")).horizontal_alignment(alignment::Horizontal::Center))
+                .width(BUTTON_WIDTH / 2.0)
+                .on_press_maybe(if_connected(Message::FanOff)),
+        ]
+        .spacing(10.0)
+        .align_items(Alignment::Center),
+        centered_row![
+            button(text(fl!("extrude")).horizontal_alignment(alignment::Horizontal::Center))
+                .width(BUTTON_WIDTH)
+                .on_press_maybe(if_connected(Message::Extrude { retract: false })),
+            button(text(fl!("retract")).horizontal_alignment(alignment::Horizontal::Center))
+                .width(BUTTON_WIDTH)
+                .on_press_maybe(if_connected(Message::Extrude { retract: true })),
+            text_input(fl!("mm"), app.extrude_length.as_str())
+                .on_input(Message::ExtrudeLength)
+                .width(48),
+            text(fl!("mm-at")),
+            text_input(fl!("mm-per-min"), app.extrude_feedrate.as_str())
+                .on_input(Message::ExtrudeFeedrate)
+                .width(64),
+            text(fl!("mm-per-min")),
         ]
-        .spacing(10.0),
-    )
-    .center_x()
-    .padding(10)
-    .into()
+        .spacing(5.0)
+        .align_items(Alignment::Center),
+    ]
+    .spacing(10.0);
+    if let Some(warning) = cold_extrusion_warning(
+        app.temperatures.latest(),
+        app.commander.extrude_options().min_temp,
+    ) {
+        controls = controls.push(text(warning));
+    }
+
+    container(controls).center_x().padding(10).into()
 }
@@ -0,0 +1,122 @@
+use {
+    crate::{
+        fl,
+        messages::{MacroEdit, Message},
+    },
+    cosmic::{
+        iced::{Alignment, Length},
+        iced_widget::{button, column, pick_list, row, scrollable},
+        widget::{text, text_input, Space},
+        Element,
+    },
+    print3rs_commands::commands::macros::Macros,
+};
+
+/// A macro being written or changed, shown in place of the console while open
+#[derive(Debug, Clone, Default)]
+pub(crate) struct State {
+    pub(crate) name: String,
+    pub(crate) steps: Vec<String>,
+    /// Name the macro was loaded with, so it can be renamed
+    pub(crate) editing: Option<String>,
+}
+
+impl State {
+    /// Start editing a macro which is already defined
+    pub(crate) fn load(macros: &Macros, name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            steps: macros.get(name).cloned().unwrap_or_default(),
+            editing: Some(name.to_string()),
+        }
+    }
+
+    pub(crate) fn edit(&mut self, edit: MacroEdit) {
+        match edit {
+            MacroEdit::Name(name) => self.name = name,
+            MacroEdit::Step(index, step) => {
+                if let Some(old) = self.steps.get_mut(index) {
+                    *old = step;
+                }
+            }
+            MacroEdit::AddStep => self.steps.push(String::new()),
+            MacroEdit::RemoveStep(index) => {
+                if index < self.steps.len() {
+                    self.steps.remove(index);
+                }
+            }
+        }
+    }
+
+    /// Steps with blank ones left out
+    pub(crate) fn steps(&self) -> impl Iterator<Item = &str> {
+        self.steps
+            .iter()
+            .map(|step| step.trim())
+            .filter(|step| !step.is_empty())
+    }
+
+    /// What's wrong with the macro as it is, checked the same way as `macro` on the console
+    pub(crate) fn problem(&self, macros: &Macros) -> Option<String> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Some(fl!("macro-needs-name"));
+        }
+        if name.contains(char::is_whitespace) {
+            return Some(fl!("macro-name-spaces"));
+        }
+        if self.steps().next().is_none() {
+            return Some(fl!("macro-needs-step"));
+        }
+        macros.check(self.steps()).err().map(|e| e.to_string())
+    }
+
+    pub(crate) fn view<'a>(&'a self, macros: &'a Macros) -> Element<'a, Message> {
+        let mut names: Vec<String> = macros.iter().map(|(name, _)| name.clone()).collect();
+        names.sort();
+        let header = row![
+            text_input(fl!("macro-name"), self.name.as_str())
+                .on_input(|name| Message::EditMacro(MacroEdit::Name(name))),
+            pick_list(names, self.editing.clone(), Message::OpenMacroEditor),
+            button(text(fl!("new"))).on_press(Message::NewMacro),
+        ]
+        .spacing(5.0)
+        .align_items(Alignment::Center);
+
+        let mut steps = column![].spacing(5.0);
+        for (index, step) in self.steps.iter().enumerate() {
+            steps = steps.push(
+                row![
+                    text_input(fl!("step-placeholder"), step.as_str())
+                        .font(cosmic::font::Font::MONOSPACE)
+                        .on_input(move |step| Message::EditMacro(MacroEdit::Step(index, step))),
+                    button(text(fl!("remove")))
+                        .on_press(Message::EditMacro(MacroEdit::RemoveStep(index))),
+                ]
+                .spacing(5.0)
+                .align_items(Alignment::Center),
+            );
+        }
+        steps = steps
+            .push(button(text(fl!("add-step"))).on_press(Message::EditMacro(MacroEdit::AddStep)));
+
+        let problem = self.problem(macros);
+        let status = text(problem.clone().unwrap_or_default());
+        let footer = row![
+            status,
+            Space::with_width(Length::Fill),
+            button(text(fl!("delete")))
+                .on_press_maybe(self.editing.clone().map(Message::DeleteMacro)),
+            button(text(fl!("close"))).on_press(Message::CloseMacroEditor),
+            button(text(fl!("save")))
+                .on_press_maybe(problem.is_none().then_some(Message::SaveMacro)),
+        ]
+        .spacing(5.0)
+        .align_items(Alignment::Center);
+
+        column![header, scrollable(steps).height(Length::Fill), footer]
+            .spacing(10.0)
+            .padding(10)
+            .into()
+    }
+}
@@ -2,10 +2,27 @@ mod app_menu;
 mod centered_row;
 mod connector;
 mod console;
+mod gcode_preview;
+mod heaters;
 mod jogger;
+mod macro_editor;
+mod position;
+mod print_progress;
+mod printer_tabs;
+mod sd_card;
+mod task_list;
+mod temperature_graph;
 
 pub(crate) use app_menu::app_menu;
 pub(crate) use connector::connector;
-pub(crate) use connector::Protocol;
 pub(crate) use console::State as Console;
+pub(crate) use gcode_preview::State as Preview;
+pub(crate) use heaters::heaters;
 pub(crate) use jogger::jogger;
+pub(crate) use macro_editor::State as MacroEditor;
+pub(crate) use position::position;
+pub(crate) use print_progress::print_progress;
+pub(crate) use printer_tabs::{printer_tabs, PrinterTab};
+pub(crate) use sd_card::sd_card;
+pub(crate) use task_list::task_list;
+pub(crate) use temperature_graph::temperature_graph;
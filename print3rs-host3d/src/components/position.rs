@@ -0,0 +1,49 @@
+use {
+    crate::{app::App, fl, messages::Message},
+    cosmic::{
+        iced::{Alignment, Color},
+        iced_widget::{column, row},
+        widget::text,
+        Element,
+    },
+    std::time::Duration,
+};
+
+const LABEL_WIDTH: f32 = 24.0;
+const READING_WIDTH: f32 = 80.0;
+
+/// Positions older than this are grayed out, since the head may have moved since
+const STALE_AFTER: Duration = Duration::from_secs(3);
+
+/// Where the head is, as last reported by the printer
+pub(crate) fn position(app: &App) -> Element<'_, Message> {
+    let fresh = app.commander.printer().is_connected()
+        && app
+            .position
+            .is_some_and(|(received, _)| received.elapsed() < STALE_AFTER);
+    let position = app.position.map(|(_, position)| position);
+    let reading = |value: Option<f32>| match value {
+        Some(value) => format!("{value:.2}"),
+        None => "-".to_string(),
+    };
+    let mut readout = column![text(fl!("position"))].spacing(5.0);
+    for (axis, value) in [
+        ("X", position.map(|position| position.x)),
+        ("Y", position.map(|position| position.y)),
+        ("Z", position.map(|position| position.z)),
+        ("E", position.and_then(|position| position.e)),
+    ] {
+        let mut value = text(reading(value))
+            .font(cosmic::font::Font::MONOSPACE)
+            .width(READING_WIDTH);
+        if !fresh {
+            value = value.style(cosmic::theme::Text::Color(Color::from_rgb(0.5, 0.5, 0.5)));
+        }
+        readout = readout.push(
+            row![text(axis).width(LABEL_WIDTH), value]
+                .spacing(5.0)
+                .align_items(Alignment::Center),
+        );
+    }
+    readout.padding(10).into()
+}
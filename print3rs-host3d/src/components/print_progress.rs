@@ -0,0 +1,32 @@
+use {
+    crate::{app::App, fl, messages::Message},
+    cosmic::{
+        iced::{Alignment, Length},
+        iced_widget::{button, column, progress_bar, row},
+        widget::{text, Space},
+        Element,
+    },
+};
+
+/// Progress of the running print with its times and a button to stop it, if a print is running
+pub(crate) fn print_progress(app: &App) -> Option<Element<'_, Message>> {
+    let progress = app.progress.as_ref()?;
+    let panel = column![
+        row![
+            text(format!(
+                "{} {:.0}%",
+                progress.task,
+                progress.percent.floor()
+            )),
+            Space::with_width(Length::Fill),
+            text(progress.times()),
+            button(text(fl!("cancel"))).on_press(Message::CancelTask(progress.task.to_string())),
+        ]
+        .spacing(10.0)
+        .align_items(Alignment::Center),
+        progress_bar(0.0..=100.0, progress.percent),
+    ]
+    .spacing(5.0)
+    .padding(10);
+    Some(panel.into())
+}
@@ -0,0 +1,81 @@
+use {
+    super::Console,
+    crate::{app::App, fl, messages::Message},
+    cosmic::{
+        iced::Alignment,
+        iced_widget::{button, row},
+        widget::{text, text_input},
+        Element,
+    },
+    print3rs_commands::{
+        commander::Commander,
+        commands::{connect::Connection, sd::SdFile},
+    },
+    print3rs_core::Position,
+    print3rs_frontend::TemperatureHistory,
+    std::time::Instant,
+};
+
+/// Everything shown for a printer while another printer's tab is open,
+/// swapped with the fields of `App` when switching tabs
+#[derive(Debug)]
+pub(crate) struct PrinterTab {
+    pub(crate) console: Console,
+    pub(crate) connection: Connection<String>,
+    pub(crate) jog_scale: f32,
+    pub(crate) keyboard_jog: bool,
+    pub(crate) temperatures: TemperatureHistory,
+    pub(crate) position: Option<(Instant, Position)>,
+    pub(crate) hotend_input: String,
+    pub(crate) bed_input: String,
+    pub(crate) fan_percent: f32,
+    pub(crate) extrude_length: String,
+    pub(crate) extrude_feedrate: String,
+    pub(crate) sd_files: Vec<SdFile>,
+}
+
+impl PrinterTab {
+    pub(crate) fn new(commander: &Commander) -> Self {
+        let mut console = Console::default();
+        console.update_command_state(&commander.history);
+        Self {
+            console,
+            connection: Connection::default(),
+            jog_scale: 10.0,
+            keyboard_jog: false,
+            temperatures: TemperatureHistory::new(),
+            position: None,
+            hotend_input: String::new(),
+            bed_input: String::new(),
+            fan_percent: 0.0,
+            extrude_length: "5".to_string(),
+            extrude_feedrate: commander.extrude_options().feedrate.to_string(),
+            sd_files: Vec::new(),
+        }
+    }
+}
+
+/// A tab for every printer the commander knows, and a box to name a new one
+pub(crate) fn printer_tabs(app: &App) -> Element<'_, Message> {
+    let selected = app.commander.printer_name();
+    let mut tabs = row![].spacing(5.0).align_items(Alignment::Center);
+    for name in app.commander.printer_names() {
+        tabs = tabs.push(
+            button(text(name))
+                .on_press_maybe((name != selected).then(|| Message::SelectTab(name.to_string()))),
+        );
+    }
+    let new_name = app.new_tab.trim();
+    let can_add = !new_name.is_empty()
+        && !new_name.contains(char::is_whitespace)
+        && !app.commander.printer_names().contains(&new_name);
+    tabs.push(
+        text_input(fl!("new-printer"), app.new_tab.as_str())
+            .on_input(Message::NewTabName)
+            .on_submit(Message::AddTab)
+            .width(160),
+    )
+    .push(button(text(fl!("add"))).on_press_maybe(can_add.then_some(Message::AddTab)))
+    .padding([0, 10])
+    .into()
+}
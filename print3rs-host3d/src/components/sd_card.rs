@@ -0,0 +1,47 @@
+use {
+    crate::{app::App, fl, messages::Message},
+    cosmic::{
+        iced::{Alignment, Length},
+        iced_widget::{button, column, row, scrollable},
+        widget::{text, Space},
+        Element,
+    },
+};
+
+const LIST_HEIGHT: f32 = 160.0;
+
+pub(crate) fn sd_card(app: &App) -> Element<'_, Message> {
+    let if_connected = |message| app.commander.printer().is_connected().then_some(message);
+    let header = row![
+        text(fl!("sd-card", files = app.sd_files.len())),
+        Space::with_width(Length::Fill),
+        button(text(fl!("refresh"))).on_press_maybe(if_connected(Message::RefreshSdFiles)),
+    ]
+    .align_items(Alignment::Center);
+
+    let mut files = column![].spacing(5.0);
+    for file in &app.sd_files {
+        let size = file
+            .size
+            .map(|size| format!("{:.1} kB", size as f32 / 1000.0))
+            .unwrap_or_default();
+        files = files.push(
+            row![
+                text(file.long_name.as_deref().unwrap_or(&file.name)).width(Length::Fill),
+                text(size),
+                button(text(fl!("print")))
+                    .on_press_maybe(if_connected(Message::SdPrint(file.name.clone()))),
+                button(text(fl!("delete")))
+                    .on_press_maybe(if_connected(Message::SdDelete(file.name.clone()))),
+            ]
+            .spacing(5.0)
+            .align_items(Alignment::Center),
+        );
+    }
+
+    // SD prints are followed with M27 by a task named after the file, shown with `print_progress`
+    column![header, scrollable(files).height(LIST_HEIGHT)]
+        .spacing(10.0)
+        .padding(10)
+        .into()
+}
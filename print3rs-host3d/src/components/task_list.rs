@@ -0,0 +1,43 @@
+use {
+    crate::{app::App, fl, messages::Message},
+    cosmic::{
+        iced::{Alignment, Length},
+        iced_widget::{button, column, progress_bar, row},
+        widget::text,
+        Element,
+    },
+    print3rs_frontend::format_duration,
+};
+
+const PROGRESS_WIDTH: f32 = 80.0;
+
+/// Every running background task with how long it has run, its progress, and buttons to control it
+pub(crate) fn task_list(app: &App) -> Element<'_, Message> {
+    let mut names: Vec<&String> = app.commander.tasks.keys().collect();
+    names.sort();
+    let mut tasks = column![text(fl!("task-list", tasks = names.len()))].spacing(5.0);
+    for name in names {
+        let task = &app.commander.tasks[name];
+        let mut line = row![
+            text(format!("{name} ({})", task.description)).width(Length::Fill),
+            text(format_duration(task.log.elapsed())),
+        ]
+        .spacing(5.0)
+        .align_items(Alignment::Center);
+        if let Some(progress) = &task.progress {
+            let percent = progress.lock().unwrap().percent();
+            line = line.push(progress_bar(0.0..=100.0, percent).width(PROGRESS_WIDTH));
+        }
+        // only prints streamed from a file know how to park and wait
+        if task.description == "print" {
+            line = if app.commander.is_paused() {
+                line.push(button(text(fl!("resume"))).on_press(Message::ResumePrint))
+            } else {
+                line.push(button(text(fl!("pause"))).on_press(Message::PausePrint))
+            };
+        }
+        line = line.push(button(text(fl!("stop"))).on_press(Message::CancelTask(name.clone())));
+        tasks = tasks.push(line);
+    }
+    tasks.padding(10).into()
+}
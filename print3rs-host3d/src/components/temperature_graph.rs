@@ -0,0 +1,33 @@
+use {
+    crate::{app::App, fl, messages::Message},
+    cosmic::{
+        iced::{Alignment, Length},
+        iced_widget::{button, column, row},
+        widget::{svg, text, Space},
+        Element,
+    },
+    print3rs_frontend::GRAPH_WINDOWS,
+};
+
+const GRAPH_WIDTH: f32 = 320.0;
+const GRAPH_HEIGHT: f32 = 160.0;
+
+pub(crate) fn temperature_graph(app: &App) -> Element<'_, Message> {
+    let history = &app.temperatures;
+    let mut header = row![text(history.summary()), Space::with_width(Length::Fill)]
+        .spacing(4.0)
+        .align_items(Alignment::Center);
+    for window in GRAPH_WINDOWS {
+        let selected = window == history.window();
+        header = header.push(
+            button(text(fl!("graph-window", minutes = window.as_secs() / 60)))
+                .on_press_maybe((!selected).then_some(Message::TemperatureWindow(window))),
+        );
+    }
+    let graph = svg(svg::Handle::from_memory(
+        history.svg(GRAPH_WIDTH, GRAPH_HEIGHT).into_bytes(),
+    ))
+    .width(GRAPH_WIDTH)
+    .height(GRAPH_HEIGHT);
+    column![header, graph].spacing(10.0).padding(10).into()
+}
@@ -0,0 +1,51 @@
+//! Translations of the user-facing strings, read from the Fluent bundles in `i18n/<language>/host3d.ftl`.
+//!
+//! Text is looked up with the `fl!` macro, which checks at compile time that the message exists
+//! in the fallback language. Output from the commander, like `help`, isn't translated.
+
+use {
+    i18n_embed::{
+        fluent::{fluent_language_loader, FluentLanguageLoader},
+        DefaultLocalizer, LanguageLoader, Localizer,
+    },
+    rust_embed::RustEmbed,
+    std::sync::OnceLock,
+};
+
+#[derive(RustEmbed)]
+#[folder = "i18n/"]
+struct Localizations;
+
+/// Loader holding the bundle of the language picked by `init`, falling back to English
+pub(crate) fn loader() -> &'static FluentLanguageLoader {
+    static LOADER: OnceLock<FluentLanguageLoader> = OnceLock::new();
+    LOADER.get_or_init(|| {
+        let loader: FluentLanguageLoader = fluent_language_loader!();
+        loader
+            .load_fallback_language(&Localizations)
+            .expect("the fallback language is embedded");
+        // isolation marks around arguments show up as boxes in some fonts
+        loader.set_use_isolating(false);
+        loader
+    })
+}
+
+/// Pick the best translation for the languages the desktop asks for
+pub(crate) fn init() {
+    let requested = i18n_embed::DesktopLanguageRequester::requested_languages();
+    let localizer = DefaultLocalizer::new(loader(), &Localizations);
+    if let Err(e) = localizer.select(&requested) {
+        eprintln!("could not load translations: {e}");
+    }
+}
+
+/// Look up a translated message by id, with `name = value` arguments for its placeholders
+#[macro_export]
+macro_rules! fl {
+    ($message_id:literal) => {{
+        i18n_embed_fl::fl!($crate::i18n::loader(), $message_id)
+    }};
+    ($message_id:literal, $($args:expr),*) => {{
+        i18n_embed_fl::fl!($crate::i18n::loader(), $message_id, $($args),*)
+    }};
+}
@@ -2,9 +2,11 @@ use {app::App, cosmic::app::Settings, std::error::Error};
 
 mod app;
 mod components;
+mod i18n;
 mod messages;
 
 fn main() -> Result<(), Box<dyn Error>> {
+    i18n::init();
     cosmic::app::run::<App>(Settings::default(), ())?;
     Ok(())
 }
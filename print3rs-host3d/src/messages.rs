@@ -1,18 +1,18 @@
 use {
     cosmic::widget::ToastId,
     print3rs_commands::{
-        commands::{connect::Connection, Command},
-        response::Response,
+        commands::{connect::Connection, sd::SdFile, Command},
+        response::{Notice, Response, Source},
     },
-    print3rs_core::Printer,
+    print3rs_core::{Position, Printer, TemperatureReport},
+    print3rs_frontend::{Event, GcodePreview, PrintProgress, Projection, Protocol},
     std::{
         path::PathBuf,
         sync::{Arc, Mutex},
+        time::Duration,
     },
 };
 
-use crate::components::Protocol;
-
 #[derive(Debug, Clone, Default)]
 pub(crate) struct JogMove {
     pub(crate) x: f32,
@@ -39,6 +39,15 @@ impl JogMove {
             ..Default::default()
         }
     }
+
+    /// This move made `scale` times longer, with Z moving a tenth as far like the Z buttons
+    pub(crate) fn scaled(&self, scale: f32) -> Self {
+        Self {
+            x: self.x * scale,
+            y: self.y * scale,
+            z: self.z * scale / 10.0,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -49,40 +58,154 @@ pub enum MoveAxis {
     All,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Heater {
+    Hotend,
+    Bed,
+}
+
+/// A change made in the macro editor
+#[derive(Debug, Clone)]
+pub(crate) enum MacroEdit {
+    Name(String),
+    Step(usize, String),
+    AddStep,
+    RemoveStep(usize),
+}
+
+/// A change to which lines the console keeps out of its output
+#[derive(Debug, Clone)]
+pub(crate) enum FilterChange {
+    HideOk(bool),
+    HideTemperatures(bool),
+    /// Regex lines must match to be shown, as typed in
+    Include(String),
+    /// Regex of lines to hide, as typed in
+    Exclude(String),
+}
+
 #[derive(Debug, Clone)]
 pub(crate) enum Message {
     Jog(JogMove),
     Home(MoveAxis),
     SelectProtocol(Protocol),
     ChangeConnection(Connection<String>),
+    /// Fill in the connector with a connection saved with `profile save`
+    SelectProfile(String),
     ToggleConnect,
     JogScale(f32),
+    /// Turn jogging with the keyboard on or off
+    KeyboardJog(bool),
+    /// A jog key was pressed, moving one step of the jog scale in this direction
+    KeyJog(JogMove),
     CommandInput(String),
     SubmitCommand,
     ProcessCommand(Command<String>),
+    /// Run the console commands in the init file, once at startup
+    RunInitScript,
     Quit,
     ClearConsole,
     PrintDialog,
+    /// Read a file picked to print and show what it prints
+    OpenPreview(PathBuf),
+    /// Moves read from a file picked to print, or why it couldn't be read
+    PreviewLoaded(PathBuf, Result<Arc<GcodePreview>, String>),
+    /// Look at another layer of the previewed file, counting from 0
+    PreviewLayer(f32),
+    PreviewProjection(Projection),
+    /// Print the previewed file, like `print <file>`
+    PrintPreviewed,
+    ClosePreview,
     SaveDialog,
     SaveConsole(PathBuf),
     ConsoleAppend(String),
-    AutoConnectComplete(Arc<Mutex<Printer>>),
+    /// Lines received from the printer with this name, for its tab's console
+    PrinterOutput(Arc<str>, String),
+    FilterConsole(FilterChange),
+    AutoConnectComplete(Arc<str>, Arc<Mutex<Option<Printer>>>),
     PushToast(String),
     PopToast(ToastId),
     OutputAction(cosmic::widget::text_editor::Action),
     DoMacro(usize),
     KillTask(usize),
+    /// Stop a task by name, like `stop <task>`
+    CancelTask(String),
+    /// Pause the running print after the line being sent, like `pause now`
+    PausePrint,
+    ResumePrint,
+    /// Open the macro editor on an empty macro
+    NewMacro,
+    /// Open the macro editor on the macro with this name
+    OpenMacroEditor(String),
+    EditMacro(MacroEdit),
+    /// Save the macro in the editor with `macro`
+    SaveMacro,
+    /// Remove the macro with this name with `delmacro`
+    DeleteMacro(String),
+    CloseMacroEditor,
+    Progress(PrintProgress),
+    /// Temperatures reported by the selected printer, for the graph
+    Temperatures(TemperatureReport),
+    /// Position reported by the selected printer, or `None` if none came in since the last poll
+    Position(Option<Position>),
+    /// Change how far back the temperature graph goes
+    TemperatureWindow(Duration),
+    /// Target temperature being typed in for a heater
+    HeaterInput(Heater, String),
+    /// Set a heater to the target typed in for it
+    SubmitHeater(Heater),
+    SetHeater(Heater, f32),
+    /// Heat up to one of `HEATER_PRESETS`, by index
+    Preheat(usize),
+    /// Part cooling fan speed being picked, in percent
+    FanSpeed(f32),
+    /// Set the part cooling fan to the picked speed
+    SendFanSpeed,
+    FanOff,
+    /// Length of filament to extrude or retract being typed in, in mm
+    ExtrudeLength(String),
+    /// Feedrate to extrude or retract at being typed in, in mm/min
+    ExtrudeFeedrate(String),
+    Extrude {
+        retract: bool,
+    },
+    /// List the files on the printer's SD card again
+    RefreshSdFiles,
+    /// Files listed on the SD card, or why they couldn't be
+    SdFiles(Result<Vec<SdFile>, String>),
+    /// Start printing a file on the SD card, by its short name
+    SdPrint(String),
+    /// Delete a file from the SD card, by its short name
+    SdDelete(String),
+    /// Show a desktop notification, if the config allows it
+    Notify(Source, Notice, Arc<str>),
+    /// Connect the printer with this name again after its connection was lost,
+    /// closing the toast that offered to
+    Reconnect(Arc<str>, Connection<String>, ToastId),
+    /// Show the tab of the printer with this name, and send commands to it
+    SelectTab(String),
+    /// Name for a new printer tab being typed in
+    NewTabName(String),
+    AddTab,
     NoOp,
 }
 
 impl From<Response> for Message {
     fn from(value: Response) -> Self {
-        match value {
-            Response::Output(s) => Message::ConsoleAppend(s.to_string()),
-            Response::Error(e) => Message::PushToast(e.0),
-            Response::AutoConnect(a) => Message::AutoConnectComplete(a),
-            Response::Clear => Message::ClearConsole,
-            Response::Quit => Message::Quit,
+        if let Response::Output(Source::Printer(name), output) = &value {
+            return Message::PrinterOutput(name.clone(), output.to_string());
+        }
+        match Event::from(value) {
+            Event::Output(s) => Message::ConsoleAppend(s.to_string()),
+            Event::Error(e) => Message::PushToast(e),
+            // messages must be Clone, so the printer is passed along in a slot to be taken once
+            Event::Connected(name, printer) => {
+                Message::AutoConnectComplete(name, Arc::new(Mutex::new(Some(printer))))
+            }
+            Event::Progress(progress) => Message::Progress(progress),
+            Event::Notify(source, notice, message) => Message::Notify(source, notice, message),
+            Event::Clear => Message::ClearConsole,
+            Event::Quit => Message::Quit,
         }
     }
 }
@@ -3,17 +3,16 @@
 //!
 
 use {
-    print3rs_commands::{commander::Commander, commands::version::VERSION, response::Response},
-    print3rs_core::Printer,
-    std::{fmt::Debug, sync::Arc},
+    print3rs_commands::{commander::Commander, commands::version::VERSION},
+    print3rs_frontend::{
+        log_sparklines, notify, submit, Event, History, PrintProgress, Profiles, SubmitError,
+    },
+    std::{fmt::Debug, time::Duration},
 };
 
 use futures_util::AsyncWriteExt;
 use rustyline_async::{Readline, ReadlineEvent, SharedWriter};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-use winnow::Parser;
-
-use print3rs_commands::commands;
 
 #[derive(Debug, thiserror::Error)]
 enum AppError {
@@ -27,12 +26,36 @@ enum AppError {
     Writer(#[from] futures_util::io::Error),
 }
 
-fn prompt_string(printer: &Printer) -> String {
-    let status = match printer {
+/// How often the prompt is redrawn to show new log values
+const SPARKLINE_REFRESH: Duration = Duration::from_secs(1);
+
+fn prompt_string(commander: &Commander, progress: Option<&PrintProgress>) -> String {
+    let mut status = match commander.printer() {
         print3rs_core::Printer::Disconnected => "Disconnected",
         print3rs_core::Printer::Connected { .. } => "Connected",
-    };
-    format!("[{status}]> ")
+    }
+    .to_string();
+    // with several printers, show which one commands go to
+    if commander.printer_names().len() > 1 {
+        status = format!("{}: {status}", commander.printer_name());
+    }
+    let mut details = vec![];
+    if let Some(progress) = progress {
+        details.push(progress.summary());
+    }
+    if commander.show_sparklines() {
+        details.extend(log_sparklines(&commander.tasks));
+    }
+    if details.is_empty() {
+        format!("[{status}]> ")
+    } else {
+        format!("[{status}] {}> ", details.join(" | "))
+    }
+}
+
+/// Escape sequence setting the terminal title
+fn title(text: &str) -> String {
+    format!("\x1b]0;{text}\x07")
 }
 
 fn setup_logging(writer: SharedWriter) {
@@ -54,34 +77,59 @@ fn setup_logging(writer: SharedWriter) {
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<(), AppError> {
     let mut commander = Commander::new();
+    commander.history = History::load_default();
+    commander.profiles = Profiles::load_default();
+    let config = commander.load_default_config();
 
-    let (mut readline, mut writer) = Readline::new(prompt_string(commander.printer()))?;
+    let (mut readline, mut writer) = Readline::new(prompt_string(&commander, None))?;
+    for entry in commander.history.iter() {
+        readline.add_history_entry(entry.clone());
+    }
 
     writer.write_all(VERSION.as_bytes()).await?;
     writer
         .write_all(b"\ntype `help` for a list of commands\n")
         .await?;
+    if let Err(e) = config {
+        writer.write_all(format!("Error: {e}\n").as_bytes()).await?;
+    }
     setup_logging(writer.clone());
 
     let mut responses = commander.subscribe_responses();
+    if let Err(e) = commander.run_init_script() {
+        writer
+            .write_all(format!("Error: {}\n", e.0).as_bytes())
+            .await?;
+    }
+    let mut progress: Option<PrintProgress> = None;
+    let mut refresh = tokio::time::interval(SPARKLINE_REFRESH);
 
     loop {
         tokio::select! {
+            _ = refresh.tick(), if commander.show_sparklines() => {}
             Ok(response) = responses.recv() => {
-                match response {
-                    Response::Output(s) => {
+                match Event::from(response) {
+                    Event::Output(s) => {
                         writer.write_all(s.as_bytes()).await?;
                     },
-                    Response::Error(e) => {
-                        writer.write_all(format!("Error: {}", e.0).as_bytes()).await?;
+                    Event::Error(e) => {
+                        writer.write_all(format!("Error: {e}").as_bytes()).await?;
+                    },
+                    Event::Connected(name, printer) => {
+                        commander.set_printer_of(&name, printer);
+                    },
+                    Event::Progress(update) => {
+                        let text = format!("{} - lin3d", update.summary());
+                        writer.write_all(title(&text).as_bytes()).await?;
+                        progress = Some(update);
                     },
-                    Response::AutoConnect(a_printer) => {
-                        commander.set_printer(Arc::into_inner(a_printer).unwrap_or_default().into_inner().unwrap_or_default());
+                    Event::Notify(_, notice, message) => {
+                        notify(&commander.config().notifications, notice, &message);
                     },
-                    Response::Clear => {
+                    Event::Clear => {
                         readline.clear()?;
                     },
-                    Response::Quit => {
+                    Event::Quit => {
                         readline.flush()?;
                         return Ok(());
                     },
@@ -92,20 +140,30 @@ async fn main() -> Result<(), AppError> {
                     ReadlineEvent::Line(line) => line,
                     _ => {readline.flush()?; return Ok(());}
                 };
-                let command = match commands::parse_command.parse(&line) {
-                    Ok(command) => command,
-                    Err(_e) => {
-                        writer.write_all(b"invalid command!\n").await?;
+                match submit(&mut commander, &line) {
+                    Ok(()) => {
+                        readline.add_history_entry(line);
+                    }
+                    Err(e @ SubmitError::Syntax { .. }) => {
+                        let underline = e.underline().unwrap_or_default();
+                        writer.write_all(format!("{line}\n{underline}\n{e}\n").as_bytes()).await?;
                         continue;
                     }
-                };
-                if let Err(e) = commander.dispatch(command) {
-                    writer.write_all(e.0.as_bytes()).await?;
-                    writer.write_all(b"\n").await?;
+                    Err(e) => {
+                        writer.write_all(format!("{e}\n").as_bytes()).await?;
+                        readline.add_history_entry(line);
+                    }
                 }
-                readline.add_history_entry(line);
             },
         }
-        readline.update_prompt(&prompt_string(commander.printer()))?;
+        // forget about prints which finished or were stopped
+        if progress
+            .as_ref()
+            .is_some_and(|p| p.is_finished() || !commander.tasks.contains_key(&*p.task))
+        {
+            progress = None;
+            writer.write_all(title("lin3d").as_bytes()).await?;
+        }
+        readline.update_prompt(&prompt_string(&commander, progress.as_ref()))?;
     }
 }
@@ -5,30 +5,240 @@ use serde::{
 
 use std::sync::{atomic::AtomicI32 as Ai32, atomic::Ordering, Arc};
 
+/// Derive `Serialize` for a type describing a gcode command, see `print3rs-derive` for attributes
+#[cfg(feature = "derive")]
+pub use print3rs_derive::Gcode;
+
 /// Default start point for new sequencers
 pub const SEQUENCE_START: i32 = 1;
 
+/// How values are written into a line
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Format {
+    /// Round floats to at most this many decimal places, dropping trailing zeros.
+    ///
+    /// By default floats are written with as many digits as it takes to represent them exactly,
+    /// so a value like `0.1 + 0.2` becomes `0.30000000000000004`.
+    pub precision: Option<u8>,
+    /// Longest a line may be, not counting the newline, for the `try_` serialize methods.
+    ///
+    /// Firmware silently truncates lines longer than its input buffer,
+    /// which is 96 bytes for Marlin.
+    pub max_line_length: Option<usize>,
+    /// How the checksum of sequenced lines is calculated
+    pub checksum: Checksum,
+    /// Remove `;` comments and surrounding whitespace from lines before they are checksummed,
+    /// so they don't take up bandwidth on the way to the device.
+    pub strip_comments: bool,
+    /// What each line is ended with
+    pub line_ending: LineEnding,
+}
+
+impl Format {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set `precision`
+    pub fn precision(mut self, precision: Option<u8>) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Set `max_line_length`
+    pub fn max_line_length(mut self, max_line_length: Option<usize>) -> Self {
+        self.max_line_length = max_line_length;
+        self
+    }
+
+    /// Set `checksum`
+    pub fn checksum(mut self, checksum: Checksum) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Set `strip_comments`
+    pub fn strip_comments(mut self, strip_comments: bool) -> Self {
+        self.strip_comments = strip_comments;
+        self
+    }
+
+    /// Set `line_ending`
+    pub fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+        self
+    }
+
+    fn check_length(&self, line: Box<[u8]>) -> Result<Box<[u8]>, LineTooLong> {
+        // the line ending isn't stored by the device
+        let length = line.len() - self.line_ending.as_bytes().len();
+        match self.max_line_length {
+            Some(max) if length > max => Err(LineTooLong { length, max }),
+            _ => Ok(line),
+        }
+    }
+}
+
+/// Characters written at the end of every line
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LineEnding {
+    /// `\n`, understood by nearly every firmware
+    #[default]
+    Lf,
+    /// `\r\n`, for devices which expect a carriage return as well
+    CrLf,
+}
+
+impl LineEnding {
+    pub fn as_bytes(self) -> &'static [u8] {
+        match self {
+            Self::Lf => b"\n",
+            Self::CrLf => b"\r\n",
+        }
+    }
+}
+
+/// Remove the `;` comment and surrounding whitespace from a single line
+fn strip_line(line: &[u8]) -> &[u8] {
+    let code = match line.iter().position(|&b| b == b';') {
+        Some(comment) => &line[..comment],
+        None => line,
+    };
+    let start = code
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(code.len());
+    let end = code
+        .iter()
+        .rposition(|b| !b.is_ascii_whitespace())
+        .map_or(start, |last| last + 1);
+    &code[start..end]
+}
+
+/// Remove `;` comments and surrounding whitespace from every line of raw gcode,
+/// dropping lines left with nothing to send
+pub fn strip_comments(raw: &[u8]) -> Vec<u8> {
+    let mut stripped = Vec::with_capacity(raw.len());
+    for code in raw.split(|&b| b == b'\n').map(strip_line) {
+        if !code.is_empty() {
+            stripped.extend_from_slice(code);
+            stripped.push(b'\n');
+        }
+    }
+    stripped
+}
+
+/// Algorithm for the `*<sum>` at the end of sequenced lines, always written in decimal
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Checksum {
+    /// Every byte XORed together, what Marlin and most firmwares expect
+    #[default]
+    Xor,
+    /// CRC-8 with polynomial 0x07 and no reflection, as used by some binary protocols
+    Crc8,
+    /// CRC-16/CCITT-FALSE, polynomial 0x1021 starting from 0xFFFF,
+    /// accepted by RepRapFirmware in place of the XOR checksum
+    Crc16Ccitt,
+    /// No checksum at all, for firmware like Grbl which rejects them
+    None,
+}
+
+impl Checksum {
+    fn initial(self) -> u16 {
+        match self {
+            Self::Xor | Self::Crc8 | Self::None => 0,
+            Self::Crc16Ccitt => 0xFFFF,
+        }
+    }
+
+    fn update(self, sum: u16, byte: u8) -> u16 {
+        match self {
+            Self::Xor => sum ^ byte as u16,
+            Self::None => sum,
+            Self::Crc8 => {
+                let mut crc = sum as u8 ^ byte;
+                for _ in 0..8 {
+                    crc = if crc & 0x80 != 0 {
+                        (crc << 1) ^ 0x07
+                    } else {
+                        crc << 1
+                    };
+                }
+                crc as u16
+            }
+            Self::Crc16Ccitt => {
+                let mut crc = sum ^ ((byte as u16) << 8);
+                for _ in 0..8 {
+                    crc = if crc & 0x8000 != 0 {
+                        (crc << 1) ^ 0x1021
+                    } else {
+                        crc << 1
+                    };
+                }
+                crc
+            }
+        }
+    }
+}
+
+/// A serialized line was longer than the `max_line_length` of its `Format`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineTooLong {
+    pub length: usize,
+    pub max: usize,
+}
+
+impl std::fmt::Display for LineTooLong {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "line is {} bytes long, more than the maximum of {}",
+            self.length, self.max
+        )
+    }
+}
+
+impl std::error::Error for LineTooLong {}
+
 /// An automatically sequenced serializer that can be cloned and sent between threads while guaranteeing strict sequence
 #[derive(Debug, Clone)]
 pub struct Sequenced {
     sequence: Arc<Ai32>,
+    format: Format,
 }
 
 impl Default for Sequenced {
     fn default() -> Self {
         Self {
             sequence: Arc::new(SEQUENCE_START.into()),
+            format: Format::default(),
         }
     }
 }
 
 /// Serialize anything, provides no sequencing, thus no traceability
 pub fn serialize_unsequenced(t: impl Serialize) -> Box<[u8]> {
-    let mut line = GcodeLine::new();
+    serialize_unsequenced_with(t, Format::default())
+}
+
+/// Serialize anything without sequencing, writing values according to `format`
+pub fn serialize_unsequenced_with(t: impl Serialize, format: Format) -> Box<[u8]> {
+    let mut line = GcodeLine::new(format);
     line.serialize(t);
     line.finish()
 }
 
+/// Like `serialize_unsequenced_with`, but fails if the line is longer than `format` allows
+pub fn try_serialize_unsequenced_with(
+    t: impl Serialize,
+    format: Format,
+) -> Result<Box<[u8]>, LineTooLong> {
+    format.check_length(serialize_unsequenced_with(t, format))
+}
+
 impl Sequenced {
     /// Format the given serializable into the internal buffer, then split
     /// off the bytes and return a handle to them.
@@ -37,18 +247,46 @@ impl Sequenced {
     /// the sequence number of the line is returned with the output for external tracking.
     pub fn serialize(&self, t: impl Serialize) -> (i32, Box<[u8]>) {
         let sequence = self.sequence.fetch_add(1, Ordering::SeqCst);
-        let mut line = GcodeLine::new();
+        let mut line = GcodeLine::new(self.format);
         line.serialize(('N', sequence, t));
         let bytes = line.finish_with_checksum();
         (sequence, bytes)
     }
 
+    /// Like `serialize`, but fails if the line is longer than the format's `max_line_length`.
+    ///
+    /// A line which is too long doesn't use up a sequence number, so the next line
+    /// still follows on from the last one actually sent.
+    pub fn try_serialize(&self, t: impl Serialize) -> Result<(i32, Box<[u8]>), LineTooLong> {
+        let mut sequence = self.sequence.load(Ordering::SeqCst);
+        loop {
+            let mut line = GcodeLine::new(self.format);
+            line.serialize(('N', sequence, &t));
+            let bytes = self.format.check_length(line.finish_with_checksum())?;
+            match self.sequence.compare_exchange(
+                sequence,
+                sequence + 1,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => return Ok((sequence, bytes)),
+                // another clone took this number first, try again with the next
+                Err(current) => sequence = current,
+            }
+        }
+    }
+
     /// Format the given serializable into the internal buffer, then split
     /// off the bytes and return the handle to them.
     ///
     /// No sequnce number or checksum are added, internal state does not change.
     pub fn serialize_unsequenced(&self, t: impl Serialize) -> Box<[u8]> {
-        serialize_unsequenced(t)
+        serialize_unsequenced_with(t, self.format)
+    }
+
+    /// Like `serialize_unsequenced`, but fails if the line is longer than the format's `max_line_length`
+    pub fn try_serialize_unsequenced(&self, t: impl Serialize) -> Result<Box<[u8]>, LineTooLong> {
+        try_serialize_unsequenced_with(t, self.format)
     }
 
     /// Crate a new serializer
@@ -56,6 +294,19 @@ impl Sequenced {
         Default::default()
     }
 
+    /// Crate a new serializer which writes values according to `format`
+    pub fn with_format(format: Format) -> Self {
+        Self {
+            format,
+            ..Default::default()
+        }
+    }
+
+    /// How values are written by this serializer
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
     /// Sets the internal sequence counter to the provided integer.
     /// This also affects all serializers cloned from this instance.
     ///
@@ -65,6 +316,7 @@ impl Sequenced {
     ///
     /// Note: Sometimes devices need to be told when sequence numbers don't change sequentially;
     /// for instance Marlin 3D printers require an `M110 N<seq>` to change line number.
+    /// print3rs-core's `Socket::reset_sequence` does both.
     pub fn set_sequence(&self, new_sequence: i32) {
         self.sequence.store(new_sequence, Ordering::SeqCst);
     }
@@ -73,40 +325,69 @@ impl Sequenced {
 #[derive(Debug, Default)]
 struct GcodeLine {
     buffer: Vec<u8>,
-    checksum: u8,
+    format: Format,
 }
 
 impl GcodeLine {
-    fn new() -> Self {
+    fn new(format: Format) -> Self {
         Self {
             buffer: Vec::new(),
-            checksum: 0,
-        }
-    }
-    fn checksum(&mut self, buf: &[u8]) {
-        for byte in buf {
-            self.checksum ^= byte;
+            format,
         }
     }
     fn write(&mut self, buf: &[u8]) {
         self.buffer.extend_from_slice(buf);
-        self.checksum(buf);
+    }
+    /// Write a float rounded to the precision of the format, if it has one
+    fn write_float(&mut self, v: f64, exact: &str) {
+        let Some(precision) = self.format.precision else {
+            self.write(exact.as_bytes());
+            return;
+        };
+        let rounded = format!("{v:.*}", precision as usize);
+        let rounded = if rounded.contains('.') {
+            rounded.trim_end_matches('0').trim_end_matches('.')
+        } else {
+            &rounded
+        };
+        let rounded = if rounded == "-0" { "0" } else { rounded };
+        self.write(rounded.as_bytes());
     }
     fn serialize(&mut self, t: impl Serialize) -> &mut Self {
         t.serialize(&mut *self).expect("Infallible");
         self
     }
 
+    /// Strip the line if the format asks for it
+    fn strip(&mut self) {
+        if self.format.strip_comments {
+            self.buffer = strip_line(&self.buffer).to_vec();
+        }
+    }
+
     fn finish_with_checksum(mut self) -> Box<[u8]> {
+        self.strip();
+        let checksum = self.format.checksum;
+        if checksum == Checksum::None {
+            return self.finish();
+        }
+        let sum = self
+            .buffer
+            .iter()
+            .fold(checksum.initial(), |sum, &byte| checksum.update(sum, byte));
         self.buffer.push(b'*');
         self.buffer
-            .extend_from_slice(itoa::Buffer::new().format(self.checksum).as_bytes());
-        self.finish()
+            .extend_from_slice(itoa::Buffer::new().format(sum).as_bytes());
+        self.buffer
+            .extend_from_slice(self.format.line_ending.as_bytes());
+        self.buffer.into_boxed_slice()
     }
 
     /// finish the current line and give the sequence number of it for tracking, 0 for unsequenced
     fn finish(mut self) -> Box<[u8]> {
-        self.buffer.push(b'\n');
+        self.strip();
+        self.buffer
+            .extend_from_slice(self.format.line_ending.as_bytes());
         self.buffer.into_boxed_slice()
     }
 }
@@ -195,15 +476,15 @@ impl ser::Serializer for &mut GcodeLine {
 
     fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
         let mut buf = ryu::Buffer::new();
-        let buf = buf.format(v).as_bytes();
-        self.write(buf);
+        let exact = buf.format(v);
+        self.write_float(v.into(), exact);
         Ok(())
     }
 
     fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
         let mut buf = ryu::Buffer::new();
-        let buf = buf.format(v).as_bytes();
-        self.write(buf);
+        let exact = buf.format(v);
+        self.write_float(v, exact);
         Ok(())
     }
 
@@ -568,4 +849,139 @@ mod test {
         assert_eq!(*b"0.0\n", *serialize_unsequenced(0.0));
         //assert_eq!(*b"test\n", *serialize_unsequenced(b"test"));
     }
+
+    #[test]
+    fn float_precision() {
+        #[derive(Serialize)]
+        struct G1 {
+            x: f64,
+            y: f32,
+            z: f64,
+        }
+        let line = G1 {
+            x: 0.1 + 0.2,
+            y: 10.0,
+            z: -0.0000001,
+        };
+        let format = Format::new().precision(Some(3));
+        assert_eq!(
+            *serialize_unsequenced_with(&line, format),
+            *b"G1X0.3Y10Z0\n"
+        );
+        assert_eq!(
+            *serialize_unsequenced(&line),
+            *b"G1X0.30000000000000004Y10.0Z-1e-7\n"
+        );
+        let sequenced = Sequenced::with_format(format);
+        assert_eq!(*sequenced.serialize(line).1, *b"N1G1X0.3Y10Z0*78\n");
+    }
+
+    #[test]
+    fn max_line_length() {
+        let format = Format::new().max_line_length(Some(12));
+        let sequenced = Sequenced::with_format(format);
+        assert_eq!(
+            sequenced.try_serialize_unsequenced("M117 Hello world"),
+            Err(LineTooLong {
+                length: 16,
+                max: 12
+            })
+        );
+        assert!(sequenced.try_serialize("M117 Hello world").is_err());
+        // the failed line didn't take a sequence number
+        let (sequence, bytes) = sequenced.try_serialize("M84").unwrap();
+        assert_eq!(sequence, 1);
+        assert_eq!(*bytes, *b"N1M84*62\n");
+        // limits only apply to the try_ methods
+        assert_eq!(
+            sequenced.serialize_unsequenced("M117 Hello world").len(),
+            17
+        );
+    }
+
+    #[test]
+    fn checksums() {
+        let line = |checksum| {
+            let sequenced = Sequenced::with_format(Format::new().checksum(checksum));
+            sequenced.serialize("M84").1
+        };
+        assert_eq!(*line(Checksum::Xor), *b"N1M84*62\n");
+        assert_eq!(*line(Checksum::Crc8), *b"N1M84*239\n");
+        assert_eq!(*line(Checksum::Crc16Ccitt), *b"N1M84*12422\n");
+        assert_eq!(*line(Checksum::None), *b"N1M84\n");
+    }
+
+    #[test]
+    fn comment_stripping() {
+        let format = Format::new().strip_comments(true);
+        let sequenced = Sequenced::with_format(format);
+        // the checksum only covers what is sent
+        assert_eq!(*sequenced.serialize("M84 ; motors off  ").1, *b"N1M84*62\n");
+        assert_eq!(
+            *sequenced.serialize_unsequenced("M84 ; motors off"),
+            *b"M84\n"
+        );
+        assert_eq!(
+            strip_comments(b"; header\nG28 \n\n  G1 X10 ;move\r\n"),
+            b"G28\nG1 X10\n"
+        );
+    }
+
+    #[test]
+    fn line_endings() {
+        let format = Format::new()
+            .line_ending(LineEnding::CrLf)
+            .max_line_length(Some(8));
+        let sequenced = Sequenced::with_format(format);
+        assert_eq!(*sequenced.serialize("M84").1, *b"N1M84*62\r\n");
+        assert_eq!(
+            *sequenced.try_serialize_unsequenced("M84").unwrap(),
+            *b"M84\r\n"
+        );
+        assert!(sequenced.try_serialize_unsequenced("M117 Hello").is_err());
+    }
+
+    #[test]
+    fn derived_gcode() {
+        use print3rs_derive::Gcode;
+
+        #[derive(Gcode)]
+        #[gcode(code = "G1")]
+        struct LinearMove {
+            x: Option<f32>,
+            y: Option<f32>,
+            #[gcode(precision = 2)]
+            e: Option<f32>,
+            #[gcode(rename = "F")]
+            speed: Option<u32>,
+        }
+        let line = LinearMove {
+            x: Some(1.5),
+            y: None,
+            e: Some(0.123),
+            speed: Some(1200),
+        };
+        assert_eq!(*serialize_unsequenced(line), *b"G1X1.5E0.12F1200\n");
+
+        #[derive(Gcode)]
+        enum Motors {
+            #[gcode(code = "G28")]
+            Home {
+                #[gcode(flag)]
+                x: bool,
+                #[gcode(flag)]
+                y: bool,
+                #[gcode(skip)]
+                _note: &'static str,
+            },
+            M84,
+        }
+        let home = Motors::Home {
+            x: true,
+            y: false,
+            _note: "ignored",
+        };
+        assert_eq!(*serialize_unsequenced(home), *b"G28X\n");
+        assert_eq!(*serialize_unsequenced(Motors::M84), *b"M84\n");
+    }
 }